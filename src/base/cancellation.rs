@@ -0,0 +1,92 @@
+use crate::base::error::Error;
+
+use std::future::Future;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::Arc;
+use std::time::Duration;
+
+/// Interval of checking for cancellation while awaiting a future, see `CancellationToken::run`
+const POLL_INTERVAL: Duration = Duration::from_millis(50);
+
+/// Token to cancel a long-running operation (e.g. key derivation) from another thread.
+/// Clones share the same state.
+#[derive(Clone, Debug, Default)]
+pub(crate) struct CancellationToken {
+    cancelled: Arc<AtomicBool>,
+}
+
+impl CancellationToken {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn cancel(&self) {
+        self.cancelled.store(true, Ordering::SeqCst);
+    }
+
+    pub fn is_cancelled(&self) -> bool {
+        self.cancelled.load(Ordering::SeqCst)
+    }
+
+    /// Return `Cancelled` error if cancelled
+    pub fn check(&self) -> Result<(), Error> {
+        if self.is_cancelled() {
+            Err(Error::Cancelled)
+        } else {
+            Ok(())
+        }
+    }
+
+    /// Await the future, unless cancelled: on cancellation the future is dropped
+    /// (aborting its network operations), and `Cancelled` is returned
+    pub async fn run<F: Future>(&self, future: F) -> Result<F::Output, Error> {
+        let cancelled = async {
+            while !self.is_cancelled() {
+                tokio::time::sleep(POLL_INTERVAL).await;
+            }
+        };
+        tokio::select! {
+            biased;
+            _ = cancelled => Err(Error::Cancelled),
+            output = future => Ok(output),
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn test_cancellation_token() {
+        let token = CancellationToken::new();
+        assert!(!token.is_cancelled());
+        assert!(token.check().is_ok());
+        let clone = token.clone();
+        clone.cancel();
+        assert!(token.is_cancelled());
+        assert!(matches!(token.check(), Err(Error::Cancelled)));
+    }
+
+    #[tokio::test]
+    async fn test_run() {
+        let token = CancellationToken::new();
+        assert!(matches!(token.run(async { 42 }).await, Ok(42)));
+
+        // cancelled while pending
+        let clone = token.clone();
+        let canceller = tokio::spawn(async move {
+            tokio::time::sleep(Duration::from_millis(10)).await;
+            clone.cancel();
+        });
+        let res = token.run(std::future::pending::<()>()).await;
+        assert!(matches!(res, Err(Error::Cancelled)));
+        canceller.await.unwrap();
+
+        // already cancelled
+        assert!(matches!(
+            token.run(async { 42 }).await,
+            Err(Error::Cancelled)
+        ));
+    }
+}
@@ -1,3 +1,4 @@
+use crate::base::cancellation::CancellationToken;
 use crate::base::error::Error;
 use chacha20poly1305::{
     aead::{Aead, AeadCore, KeyInit, Payload},
@@ -5,7 +6,30 @@ use chacha20poly1305::{
 };
 use nostr::prelude::SecretKey;
 use rand_core::{OsRng, RngCore};
-use zeroize::Zeroize;
+use zeroize::{Zeroize, Zeroizing};
+
+use std::sync::mpsc;
+use std::thread;
+use std::time::Duration;
+
+/// Optional progress callback for encryption/decryption, called with values from 0.0 to 1.0.
+/// Progress is coarse: scrypt does not report progress, so it jumps from 0.0 to the end of the KDF.
+pub(crate) type KdfProgress<'a> = Option<&'a dyn Fn(f32)>;
+
+/// Maximum accepted scrypt cost (log2 of N). Higher values from untrusted input
+/// would make the key derivation exhaust memory or run practically forever.
+const MAX_LOG2_ROUNDS: u8 = 22;
+/// Minimum accepted scrypt cost (log2 of N)
+const MIN_LOG2_ROUNDS: u8 = 1;
+
+/// Length of an encrypted key, as produced by `encrypt_key`: 1 + 1 + 16 + 24 + 1 + 48
+pub(crate) const ENCRYPTED_KEY_LEN: usize = 91;
+
+/// Progress reported once the key derivation (the slow part) is done
+const PROGRESS_KDF_DONE: f32 = 0.9;
+
+/// Interval of checking for cancellation while waiting for the key derivation
+const KDF_CANCEL_POLL_INTERVAL: Duration = Duration::from_millis(20);
 
 /// Two-way encryption, used for secret keys
 pub(crate) struct Encrypt {}
@@ -15,6 +39,14 @@ impl Encrypt {
         13
     }
 
+    /// Check that the scrypt cost (log2 of N) is in the accepted range (1-22)
+    pub(crate) fn check_log2_rounds(log2_rounds: u8) -> Result<(), Error> {
+        if !(MIN_LOG2_ROUNDS..=MAX_LOG2_ROUNDS).contains(&log2_rounds) {
+            return Err(Error::KeyInvalidScryptParams(log2_rounds));
+        }
+        Ok(())
+    }
+
     /// Encrypt a key.
     /// It is recommend to zeroize() the password after use.
     pub(crate) fn encrypt_key(
@@ -22,6 +54,42 @@ impl Encrypt {
         password: &str,
         log2_rounds: u8,
     ) -> Result<Vec<u8>, Error> {
+        Self::encrypt_key_with(key, password, log2_rounds, &CancellationToken::new(), None)
+    }
+
+    /// Encrypt a key, cancellable, with optional progress reporting.
+    /// On cancellation `Cancelled` is returned, and the partial result is discarded.
+    pub(crate) fn encrypt_key_with(
+        key: &SecretKey,
+        password: &str,
+        log2_rounds: u8,
+        cancel: &CancellationToken,
+        progress: KdfProgress,
+    ) -> Result<Vec<u8>, Error> {
+        let mut inner_secret: Vec<u8> = key.secret_bytes().to_vec();
+        let res = Self::encrypt_data_with(&inner_secret, password, log2_rounds, cancel, progress);
+        inner_secret.zeroize();
+        res
+    }
+
+    /// Encrypt arbitrary data, using the same scheme as for keys.
+    /// It is recommend to zeroize() the password after use.
+    pub(crate) fn encrypt_data(
+        data: &[u8],
+        password: &str,
+        log2_rounds: u8,
+    ) -> Result<Vec<u8>, Error> {
+        Self::encrypt_data_with(data, password, log2_rounds, &CancellationToken::new(), None)
+    }
+
+    fn encrypt_data_with(
+        data: &[u8],
+        password: &str,
+        log2_rounds: u8,
+        cancel: &CancellationToken,
+        progress: KdfProgress,
+    ) -> Result<Vec<u8>, Error> {
+        Self::check_log2_rounds(log2_rounds)?;
         // Generate a random 16-byte salt
         let salt = {
             let mut salt: [u8; 16] = [0; 16];
@@ -36,25 +104,19 @@ impl Encrypt {
 
         let ciphertext = {
             let cipher = {
-                let symmetric_key = Self::password_to_key(password, &salt, log2_rounds)?;
+                let symmetric_key =
+                    Self::password_to_key_with(password, &salt, log2_rounds, cancel, progress)?;
                 XChaCha20Poly1305::new((&symmetric_key).into())
             };
 
-            // The inner secret. We don't have to drop this because we are encrypting-in-place
-            let mut inner_secret: Vec<u8> = key.secret_bytes().to_vec();
-
             let payload = Payload {
-                msg: &inner_secret,
+                msg: data,
                 aad: &associated_data,
             };
 
-            let ciphertext = cipher
+            cipher
                 .encrypt(&nonce, payload)
-                .map_err(|_e| Error::KeyEncryption)?;
-
-            inner_secret.zeroize();
-
-            ciphertext
+                .map_err(|_e| Error::KeyEncryption)?
         };
 
         // Combine salt, IV and ciphertext
@@ -64,16 +126,50 @@ impl Encrypt {
         concat.extend(salt); // 16 bytes of salt
         concat.extend(nonce); // 24 bytes of nonce
         concat.extend(associated_data); // 1 byte of key security
-        concat.extend(ciphertext); // 48 bytes of ciphertext expected
-                                   // Total length is 91 = 1 + 1 + 16 + 24 + 1 + 32
+        concat.extend(ciphertext); // data length + 16 bytes of ciphertext, 48 for keys
+                                   // Total length for keys is 91 = 1 + 1 + 16 + 24 + 1 + 48
 
+        Self::report_progress(progress, 1.0);
         Ok(concat)
     }
 
     /// Decrypt a key encrypted using `encrypt_key`
     /// It is recommend to zeroize() the password after use.
     pub(crate) fn decrypt_key(encrypted: &Vec<u8>, password: &str) -> Result<SecretKey, Error> {
-        if encrypted.len() < 91 {
+        Self::decrypt_key_with(encrypted, password, &CancellationToken::new(), None)
+    }
+
+    /// Decrypt a key encrypted using `encrypt_key`, cancellable, with optional progress reporting.
+    /// On cancellation `Cancelled` is returned, and the partial result is discarded.
+    pub(crate) fn decrypt_key_with(
+        encrypted: &Vec<u8>,
+        password: &str,
+        cancel: &CancellationToken,
+        progress: KdfProgress,
+    ) -> Result<SecretKey, Error> {
+        if encrypted.len() < ENCRYPTED_KEY_LEN {
+            return Err(Error::KeyInvalidEncrypted);
+        }
+        let mut inner_secret = Self::decrypt_data_with(encrypted, password, cancel, progress)?;
+        let secret_key = SecretKey::from_slice(&inner_secret);
+        inner_secret.zeroize();
+        Ok(secret_key?)
+    }
+
+    /// Decrypt arbitrary data encrypted using `encrypt_data`
+    /// It is recommend to zeroize() the password after use.
+    pub(crate) fn decrypt_data(encrypted: &[u8], password: &str) -> Result<Vec<u8>, Error> {
+        Self::decrypt_data_with(encrypted, password, &CancellationToken::new(), None)
+    }
+
+    fn decrypt_data_with(
+        encrypted: &[u8],
+        password: &str,
+        cancel: &CancellationToken,
+        progress: KdfProgress,
+    ) -> Result<Vec<u8>, Error> {
+        // version, rounds, salt, nonce, key security, and at least the 16-byte tag
+        if encrypted.len() < 1 + 1 + 16 + 24 + 1 + 16 {
             return Err(Error::KeyInvalidEncrypted);
         }
 
@@ -83,6 +179,7 @@ impl Encrypt {
             return Err(Error::KeyInvalidEncryptionVersion);
         }
         let log2_rounds: u8 = encrypted[1];
+        Self::check_log2_rounds(log2_rounds).map_err(|_e| Error::KeyInvalidEncrypted)?;
         let salt: [u8; 16] = encrypted[2..2 + 16]
             .try_into()
             .map_err(|_e| Error::KeyInvalidEncrypted)?;
@@ -91,7 +188,8 @@ impl Encrypt {
         let ciphertext = &encrypted[2 + 16 + 24 + 1..];
 
         let cipher = {
-            let symmetric_key = Self::password_to_key(password, &salt, log2_rounds)?;
+            let symmetric_key =
+                Self::password_to_key_with(password, &salt, log2_rounds, cancel, progress)?;
             XChaCha20Poly1305::new((&symmetric_key).into())
         };
 
@@ -105,17 +203,62 @@ impl Encrypt {
             .map_err(|_e| Error::KeyEncryption)?;
 
         if associated_data.is_empty() {
+            inner_secret.zeroize();
             return Err(Error::KeyInvalidEncrypted);
         }
         let key_security = associated_data[0];
         if key_security != 1 {
+            inner_secret.zeroize();
             return Err(Error::KeyEncryption);
         }
 
-        let secret_key = SecretKey::from_slice(&inner_secret)?;
-        inner_secret.zeroize();
+        Self::report_progress(progress, 1.0);
+        Ok(inner_secret)
+    }
+
+    fn report_progress(progress: KdfProgress, value: f32) {
+        if let Some(callback) = progress {
+            callback(value);
+        }
+    }
 
-        Ok(secret_key)
+    /// Key derivation, cancellable: scrypt cannot be interrupted, so it runs on a worker thread,
+    /// and cancellation returns early, without waiting for it. The result of a cancelled
+    /// derivation is zeroized and discarded when the worker finishes.
+    fn password_to_key_with(
+        password: &str,
+        salt: &[u8; 16],
+        log_n: u8,
+        cancel: &CancellationToken,
+        progress: KdfProgress,
+    ) -> Result<[u8; 32], Error> {
+        cancel.check()?;
+        Self::report_progress(progress, 0.0);
+        let (sender, receiver) = mpsc::channel();
+        let worker_password = Zeroizing::new(password.to_string());
+        let worker_salt = *salt;
+        thread::spawn(move || {
+            let res = Self::password_to_key(&worker_password, &worker_salt, log_n);
+            if let Err(mpsc::SendError(Ok(mut key))) = sender.send(res) {
+                // cancelled, nobody is waiting for the key
+                key.zeroize();
+            }
+        });
+        loop {
+            match receiver.recv_timeout(KDF_CANCEL_POLL_INTERVAL) {
+                Ok(res) => {
+                    let mut key = res?;
+                    if cancel.is_cancelled() {
+                        key.zeroize();
+                        return Err(Error::Cancelled);
+                    }
+                    Self::report_progress(progress, PROGRESS_KDF_DONE);
+                    return Ok(key);
+                }
+                Err(mpsc::RecvTimeoutError::Timeout) => cancel.check()?,
+                Err(mpsc::RecvTimeoutError::Disconnected) => return Err(Error::KeyEncryption),
+            }
+        }
     }
 
     // Hash/Stretch password with scrypt into a 32-byte (256-bit) key
@@ -159,6 +302,97 @@ mod test {
         assert_eq!(hex::encode(encrypted)[0..4], "010d".to_string());
     }
 
+    #[test]
+    fn test_encrypt_and_decrypt_data() {
+        let data = "Some longer data, to be encrypted".as_bytes();
+        let password = "password".to_string();
+        let encrypted = Encrypt::encrypt_data(data, &password, 13).unwrap();
+        assert_eq!(encrypted.len(), 1 + 1 + 16 + 24 + 1 + data.len() + 16);
+
+        let decrypted = Encrypt::decrypt_data(&encrypted, &password).unwrap();
+        assert_eq!(decrypted, data);
+
+        assert!(Encrypt::decrypt_data(&encrypted, "wrong").is_err());
+        assert!(Encrypt::decrypt_data(&encrypted[0..40], &password).is_err());
+    }
+
+    #[test]
+    fn test_encrypt_progress_and_cancel() {
+        let sk = SecretKey::from_bech32(
+            "nsec1ktekw0hr5evjs0n9nyyquz4sue568snypy2rwk5mpv6hl2hq3vtsk0kpae",
+        )
+        .unwrap();
+        let cancel = CancellationToken::new();
+        let values = std::cell::RefCell::new(Vec::new());
+        let record = |p: f32| values.borrow_mut().push(p);
+        let encrypted =
+            Encrypt::encrypt_key_with(&sk, "password", 13, &cancel, Some(&record)).unwrap();
+        assert_eq!(*values.borrow(), vec![0.0, PROGRESS_KDF_DONE, 1.0]);
+
+        values.borrow_mut().clear();
+        let _decrypted =
+            Encrypt::decrypt_key_with(&encrypted, "password", &cancel, Some(&record)).unwrap();
+        assert_eq!(*values.borrow(), vec![0.0, PROGRESS_KDF_DONE, 1.0]);
+
+        // cancel during the KDF
+        let cancel_on_start = |p: f32| {
+            if p == 0.0 {
+                cancel.cancel();
+            }
+        };
+        assert!(matches!(
+            Encrypt::encrypt_key_with(&sk, "password", 13, &cancel, Some(&cancel_on_start)),
+            Err(Error::Cancelled)
+        ));
+        // already cancelled
+        assert!(matches!(
+            Encrypt::decrypt_key_with(&encrypted, "password", &cancel, None),
+            Err(Error::Cancelled)
+        ));
+    }
+
+    #[test]
+    fn test_decrypt_cancel_from_other_thread() {
+        let sk = SecretKey::from_bech32(
+            "nsec1ktekw0hr5evjs0n9nyyquz4sue568snypy2rwk5mpv6hl2hq3vtsk0kpae",
+        )
+        .unwrap();
+        // a slow KDF, cancelled while running
+        let encrypted = Encrypt::encrypt_key(&sk, "password", 17).unwrap();
+        let cancel = CancellationToken::new();
+        let canceller = {
+            let cancel = cancel.clone();
+            thread::spawn(move || {
+                thread::sleep(Duration::from_millis(10));
+                cancel.cancel();
+            })
+        };
+        assert!(matches!(
+            Encrypt::decrypt_key_with(&encrypted, "password", &cancel, None),
+            Err(Error::Cancelled)
+        ));
+        canceller.join().unwrap();
+    }
+
+    #[test]
+    fn test_decrypt_excessive_rounds() {
+        // regression: cost parameter from the input must be capped
+        let mut encrypted = hex::decode("010d6a32e0decd8553f02372df251c7f06dd0a54ba09bc0e8b2ea52e816c50f430fd0f051b2f7abcae05017f3c6f8a1ff7f3d694db4e624ef7dece7e3152b1ff536bc954eab1c85b3dbeb8e29140e84f0db5c473822e550d53a66e").unwrap();
+        encrypted[1] = 40;
+        assert!(matches!(
+            Encrypt::decrypt_key(&encrypted, "password"),
+            Err(Error::KeyInvalidEncrypted)
+        ));
+        encrypted[1] = 255;
+        assert!(Encrypt::decrypt_key(&encrypted, "password").is_err());
+        // and from below
+        encrypted[1] = 0;
+        assert!(matches!(
+            Encrypt::decrypt_key(&encrypted, "password"),
+            Err(Error::KeyInvalidEncrypted)
+        ));
+    }
+
     #[test]
     fn test_decrypt() {
         let encrypted = hex::decode("010d6a32e0decd8553f02372df251c7f06dd0a54ba09bc0e8b2ea52e816c50f430fd0f051b2f7abcae05017f3c6f8a1ff7f3d694db4e624ef7dece7e3152b1ff536bc954eab1c85b3dbeb8e29140e84f0db5c473822e550d53a66e").unwrap();
@@ -1,8 +1,58 @@
+use crate::base::key_utils::InputKind;
+
 #[derive(Debug, thiserror::Error)]
 pub enum Error {
+    /// Operation has been cancelled
+    #[error("Cancelled")]
+    Cancelled,
+    /// Event kind not allowed by the signing kind allowlist
+    #[error("Signing of event kind {0} is not allowed")]
+    KindNotAllowed(u64),
+    /// Invalid event kind (not a number)
+    #[error("Invalid event kind '{0}'")]
+    KindInvalid(String),
+    /// Mixed-case bech32 input, not allowed by the bech32 spec
+    #[error("Invalid key, bech32 must be all lowercase or all uppercase")]
+    MixedCaseBech32,
+    /// Input is not a key, but some other, recognized entity (e.g. lightning address, note)
+    #[error("That looks like {detected}, not a key")]
+    NotAKey { detected: InputKind },
+    /// Hex key input is ambiguous, it could be a public or a secret key
+    #[error("Hex key can be a public or a secret key, choose the format explicitly")]
+    KeyHexAmbiguous,
+    /// Both public and secret key inputs are filled, with different keys
+    #[error("Both public and secret key are entered, and they don't match. Clear one of them")]
+    KeyInputAmbiguous,
+    /// Input is an encrypted key (ncryptsec), a password is needed to import it
+    #[error("Encrypted key (ncryptsec), enter the password to decrypt it")]
+    KeyNeedsPassword,
+    /// Network operation did not complete in time, see the network timeout setting
+    #[error("Network timeout, the operation did not complete in time")]
+    Timeout,
+    /// Network access is disabled (offline mode)
+    #[error("Offline mode, network access is disabled")]
+    OfflineMode,
+    /// Clipboard is empty, or does not contain text
+    #[error("Clipboard is empty, or does not contain text")]
+    ClipboardEmpty,
+    /// Invalid base64-encoded secret key (it has to decode to exactly 32 bytes)
+    #[error("Invalid base64 secret key, expected 32 bytes")]
+    KeyInvalidBase64Secret,
+    /// Input is not recognized as a key in any supported format
+    #[error("Unrecognized key format")]
+    KeyUnrecognizedFormat,
     /// Key not set (secret key or public key)
     #[error("Key not set")]
     KeyNotSet,
+    /// Secret key is locked
+    #[error("Secret key is locked, unlock it first")]
+    KeyLocked,
+    /// Timed reveal of the secret key has expired, it has to be revealed again
+    #[error("Reveal has expired, reveal the secret key again")]
+    RevealExpired,
+    /// Key origin not allowed by key policy
+    #[error("Keys of origin '{0}' are not allowed by the key policy")]
+    KeyPolicyViolation(String),
     /// No unsaved changes to save
     #[error("No changes to save")]
     KeyNoChangeToSave,
@@ -18,12 +68,24 @@ pub enum Error {
     /// Secp256k1 key error
     #[error(transparent)]
     KeyErrorSecp256k1(#[from] nostr::secp256k1::Error),
+    /// Key does not match the expected identity (public key)
+    #[error("Key does not match the expected identity")]
+    KeyIdentityMismatch,
+    /// Self-test of the keys failed (signature does not verify with the public key)
+    #[error("Key self-test failed, the keys may be corrupted")]
+    KeySelfTestFailed,
     /// Invalid encrypted key
     #[error("Invalid encrypted key")]
     KeyInvalidEncrypted,
     /// Encryption error
     #[error("Encryption error")]
     KeyEncryption,
+    /// Decryption failed, most likely wrong password
+    #[error("Decryption failed, check the password")]
+    KeyDecryptionFailed,
+    /// Invalid scrypt cost parameter (log2 of N), out of the accepted range
+    #[error("Invalid scrypt parameter log_n {0}, accepted range is 1-22")]
+    KeyInvalidScryptParams(u8),
     /// Invalid encryption version
     #[error("Invalid encryption version")]
     KeyInvalidEncryptionVersion,
@@ -36,12 +98,36 @@ pub enum Error {
     /// Invalid BIP39 mnemonic
     #[error(transparent)]
     KeyMnemonic(#[from] bip39::Error),
+    /// Mandatory BIP39 passphrase missing
+    #[error("Mnemonic passphrase missing")]
+    KeyMnemonicPassphraseMissing,
+    /// BIP39 passphrases don't match
+    #[error("Mnemonic passphrases don't match")]
+    KeyMnemonicPassphraseMismatch,
+    /// Invalid BIP32 derivation path
+    #[error("Invalid derivation path '{0}'")]
+    KeyInvalidDerivationPath(String),
+    /// Invalid BIP32 extended key (encoding or checksum)
+    #[error("Invalid extended key, check encoding and checksum")]
+    KeyInvalidExtendedKey,
+    /// BIP32 extended key with unsupported prefix, only xprv and tprv are supported
+    #[error("Unsupported extended key prefix '{0}', expected xprv or tprv")]
+    KeyExtendedKeyUnsupportedPrefix(String),
     /// BIP32 key derivation error
     #[error(transparent)]
     KeyDerivation(#[from] bip32::Error),
     /// Nip19 error
     #[error(transparent)]
     SignatureError(#[from] nostr::nips::nip19::Error),
+    /// Bech32 encoding error
+    #[error(transparent)]
+    Bech32Error(#[from] bech32::Error),
+    /// Not a NIP-19 TLV entity
+    #[error("Not a NIP-19 TLV entity ({0}), only nprofile, nevent and naddr have relay hints")]
+    Nip19NotTlvEntity(String),
+    /// Invalid NIP-19 TLV data
+    #[error("Invalid NIP-19 TLV data")]
+    Nip19InvalidTlv,
     /// Nip26 error
     #[error(transparent)]
     Nip26Error(#[from] nostr::nips::nip26::Error),
@@ -51,22 +137,142 @@ pub enum Error {
     /// JSON serialization error
     #[error(transparent)]
     JsonError(#[from] serde_json::Error),
+    /// Encryption error (NIP-04)
+    #[error(transparent)]
+    Nip04Error(#[from] nostr::nips::nip04::Error),
+    /// Invalid encrypted payload (NIP-44)
+    #[error("Invalid encrypted payload")]
+    Nip44InvalidPayload,
+    /// Unsupported encrypted payload version (NIP-44)
+    #[error("Unsupported encrypted payload version")]
+    Nip44InvalidVersion,
+    /// Encrypted payload authentication failed, wrong key or tampered (NIP-44)
+    #[error("Decryption failed, invalid MAC")]
+    Nip44InvalidMac,
+    /// Message length out of the accepted range for encryption (NIP-44)
+    #[error("Invalid message length {0}, accepted range is 1-65535 bytes")]
+    Nip44InvalidLength(usize),
+    /// Invalid gift wrap or seal (NIP-59)
+    #[error("Invalid gift wrap, {0}")]
+    GiftWrapInvalid(String),
+    /// NIP-05 verification error
+    #[error(transparent)]
+    Nip05Error(#[from] nostr::nips::nip05::Error),
     /// Nostr connect error (NIP-46)
     #[error(transparent)]
     Nip46Error(#[from] nostr::nips::nip46::Error),
+    /// Invalid HTTP method for HTTP auth (NIP-98)
+    #[error("Invalid HTTP method '{0}'")]
+    HttpAuthInvalidMethod(String),
+    /// Invalid URL for HTTP auth (NIP-98), it must be an absolute http(s) URL
+    #[error("Invalid HTTP URL, it must be an absolute http or https URL")]
+    HttpAuthInvalidUrl,
+    /// Invalid payload hash for HTTP auth (NIP-98), it must be a SHA-256 hash in hex
+    #[error("Invalid payload hash, it must be a SHA-256 hash in hex")]
+    HttpAuthInvalidPayloadHash,
+    /// Invalid Nostr Wallet Connect URI (NIP-47)
+    #[error("Invalid wallet connect URI, {0}")]
+    NwcInvalidUri(String),
+    /// Invalid or unexpected response from the wallet service (NIP-47)
+    #[error("Invalid response from wallet")]
+    NwcInvalidResponse,
+    /// Error returned by the wallet service (NIP-47)
+    #[error("Wallet error: {0}")]
+    NwcWalletError(String),
+    /// No response from the wallet service in time (NIP-47)
+    #[error("Timeout waiting for wallet response")]
+    NwcTimeout,
+    /// No entry found in the OS keychain
+    #[error("No entry found in the keychain")]
+    KeychainEntryMissing,
+    /// OS keychain is locked or not accessible
+    #[error("Keychain is locked or not accessible: {0}")]
+    KeychainLocked(String),
+    /// Other OS keychain error
+    #[error("Keychain error: {0}")]
+    KeychainError(String),
     /// Relay client error
     #[error(transparent)]
     RelayClientError(#[from] nostr_sdk::client::Error),
+    /// Retyped secret key does not match, backup not confirmed
+    #[error("Secret key does not match, backup not confirmed")]
+    KeyBackupConfirmationMismatch,
+    /// Invalid delegation (signature or conditions)
+    #[error("Invalid delegation")]
+    DelegationInvalid,
+    /// Delegation chain is broken at the given link (index)
+    #[error("Delegation chain is broken at link {0}")]
+    DelegationChainBroken(usize),
+    /// No secret key found in the imported JSON
+    #[error("No secret key field found in JSON (expected e.g. 'nsec', 'sec', 'privkey')")]
+    ImportJsonNoSecretKey,
+    /// Invalid account label
+    #[error("Invalid account label, {0}")]
+    AccountLabelInvalid(String),
+    /// Invalid TOML config fragment
+    #[error("Invalid TOML config, {0}")]
+    TomlInvalid(String),
+    /// Invalid paper backup layout
+    #[error("Invalid paper backup, expected npub, ncryptsec and checksum lines")]
+    BackupInvalid,
+    /// Paper backup checksum does not match the keys, likely a typo
+    #[error("Paper backup checksum does not match, check for typos")]
+    BackupChecksumMismatch,
+    /// Invalid or corrupt account bundle
+    #[error("Invalid or corrupt account bundle")]
+    BundleInvalid,
+    /// Unsupported account bundle version
+    #[error("Unsupported account bundle version {0}")]
+    BundleUnsupportedVersion(u32),
+    /// Event is not a relay list (NIP-65, kind 10002)
+    #[error("Not a relay list event, kind {0}")]
+    RelayListWrongKind(u64),
+    /// Event content is longer than the relay accepts
+    #[error("Content is too long for the relay, {length} characters, maximum is {max}")]
+    RelayContentTooLong { length: usize, max: usize },
+    /// Invalid OK response from a relay (NIP-20)
+    #[error("Invalid OK response from relay")]
+    RelayInvalidOkResponse,
+    /// No OK response from the relay to a published event (NIP-20)
+    #[error("No OK response from relay")]
+    RelayNoOkResponse,
+    /// Invalid relay URL
+    #[error("Invalid relay URL")]
+    RelayInvalidUrl,
+    /// Relay information document (NIP-11) could not be fetched or parsed
+    #[error("Relay information document is not available")]
+    RelayInformationUnavailable,
+    /// Invalid air-gapped signing request/response
+    #[error("Invalid signing request/response data")]
+    AirgapInvalidBlob,
+    /// QR code payload not recognized (not a mnemonic or key)
+    #[error("Unrecognized QR code content")]
+    QrUnrecognizedPayload,
+    /// QR code encoding error
+    #[error("QR code encoding error {0}")]
+    QrEncodingError(#[from] qrcode::types::QrError),
+    /// Event ID does not match the event contents
+    #[error("Event ID does not match the event contents")]
+    EventIdMismatch,
     /// Signer is already connected, disconnect first
     #[error("Signer is already connected, disconnect first")]
     SignerAlreadyConnected,
     /// Internal event queue receive error
     #[error(transparent)]
     InternalEventQueueReceive(#[from] crossbeam::channel::RecvError),
+    /// Internal background task panicked
+    #[error("Internal error, a background task failed unexpectedly")]
+    InternalTaskPanicked,
     /// Internal event queue send error
     #[error("Internal event queue send error")]
     InternalEventQueueSend,
     /// Internal EventBuilder error
     #[error(transparent)]
     EventBuilderError(#[from] nostr::event::builder::Error),
+    /// Event error, e.g. invalid signature
+    #[error(transparent)]
+    EventError(#[from] nostr::event::Error),
+    /// Unsigned event error
+    #[error(transparent)]
+    UnsignedEventError(#[from] nostr::event::unsigned::Error),
 }
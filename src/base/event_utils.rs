@@ -0,0 +1,236 @@
+use nostr::hashes::sha256::Hash as Sha256Hash;
+use nostr::prelude::{Event, EventId, ToBech32};
+use rayon::prelude::*;
+use serde_json::json;
+
+use std::fmt;
+
+/// Above this number of events, verification is done in parallel
+const PARALLEL_VERIFY_THRESHOLD: usize = 64;
+
+/// Summary of an event, for display/debugging
+#[derive(Clone, Debug)]
+pub(crate) struct EventDescription {
+    pub kind: u64,
+    /// Replaceable event (kinds 0, 3, 10000-19999)
+    pub replaceable: bool,
+    /// Ephemeral event (kinds 20000-29999)
+    pub ephemeral: bool,
+    /// Addressable, or parameterized replaceable event (kinds 30000-39999)
+    pub addressable: bool,
+    /// NIPs detected as used by the event, in increasing order
+    pub nips: Vec<u16>,
+    pub author_npub: String,
+}
+
+impl fmt::Display for EventDescription {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "kind {}", self.kind)?;
+        if self.replaceable {
+            write!(f, ", replaceable")?;
+        }
+        if self.ephemeral {
+            write!(f, ", ephemeral")?;
+        }
+        if self.addressable {
+            write!(f, ", addressable")?;
+        }
+        if !self.nips.is_empty() {
+            let nips: Vec<String> = self.nips.iter().map(|n| format!("NIP-{n:02}")).collect();
+            write!(f, ", uses {}", nips.join(", "))?;
+        }
+        Ok(())
+    }
+}
+
+/// Analyze an event: kind properties, used NIPs (detected from tags), author.
+/// Read-only, the signature is not verified.
+pub(crate) fn describe_event(event: &Event) -> EventDescription {
+    let kind = event.kind.as_u64();
+    let mut nips = Vec::new();
+    for tag in &event.tags {
+        let nip = match tag.as_vec().first().map(|s| s.as_str()) {
+            Some("nonce") => Some(13),
+            Some("delegation") => Some(26),
+            Some("expiration") => Some(40),
+            _ => None,
+        };
+        if let Some(nip) = nip {
+            if !nips.contains(&nip) {
+                nips.push(nip);
+            }
+        }
+    }
+    nips.sort();
+    EventDescription {
+        kind,
+        replaceable: kind == 0 || kind == 3 || (10000..20000).contains(&kind),
+        ephemeral: (20000..30000).contains(&kind),
+        addressable: (30000..40000).contains(&kind),
+        nips,
+        author_npub: event.pubkey.to_bech32().unwrap_or_default(),
+    }
+}
+
+/// Canonical serialization of an event, as hashed for its id (NIP-01):
+/// `[0, <pubkey hex>, <created_at>, <kind>, <tags>, <content>]`, compact JSON
+pub(crate) fn canonical_event_json(event: &Event) -> String {
+    json!([
+        0,
+        event.pubkey.to_string(),
+        event.created_at.as_u64(),
+        event.kind.as_u64(),
+        event.tags,
+        event.content
+    ])
+    .to_string()
+}
+
+/// Recompute the id of an event from its contents (the SHA-256 of its canonical serialization),
+/// to verify that it matches `event.id`
+pub(crate) fn recompute_id(event: &Event) -> EventId {
+    EventId::from_hash(<Sha256Hash as nostr::hashes::Hash>::hash(
+        canonical_event_json(event).as_bytes(),
+    ))
+}
+
+/// Verify the id and the signature of an event
+fn verify_event(event: &Event) -> bool {
+    recompute_id(event) == event.id && event.verify().is_ok()
+}
+
+/// Verify the ids and signatures of many events, e.g. of a relay archive.
+/// Returns the result for each event, in the same order. Large inputs are verified in parallel
+/// (the secp256k1 backend has no batch schnorr verification).
+pub(crate) fn verify_events(events: &[Event]) -> Vec<(EventId, bool)> {
+    if events.len() < PARALLEL_VERIFY_THRESHOLD {
+        events.iter().map(|e| (e.id, verify_event(e))).collect()
+    } else {
+        events.par_iter().map(|e| (e.id, verify_event(e))).collect()
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use nostr::prelude::{Conditions, DelegationTag, EventBuilder, Keys, Kind, Metadata, Tag};
+    use std::str::FromStr;
+
+    #[test]
+    fn test_describe_event_text_note() {
+        let keys = Keys::generate();
+        let event = EventBuilder::new_text_note("Hello", &[])
+            .to_event(&keys)
+            .unwrap();
+        let desc = describe_event(&event);
+        assert_eq!(desc.kind, 1);
+        assert!(!desc.replaceable);
+        assert!(!desc.ephemeral);
+        assert!(!desc.addressable);
+        assert!(desc.nips.is_empty());
+        assert_eq!(desc.author_npub, keys.public_key().to_bech32().unwrap());
+        assert_eq!(desc.to_string(), "kind 1");
+    }
+
+    #[test]
+    fn test_canonical_event_json() {
+        let keys = Keys::generate();
+        let event = EventBuilder::new_text_note("Hello \"nostr\"", &[Tag::Hashtag("x".into())])
+            .to_event(&keys)
+            .unwrap();
+        assert_eq!(
+            canonical_event_json(&event),
+            format!(
+                "[0,\"{}\",{},1,[[\"t\",\"x\"]],\"Hello \\\"nostr\\\"\"]",
+                keys.public_key(),
+                event.created_at.as_u64()
+            )
+        );
+    }
+
+    #[test]
+    fn test_recompute_id() {
+        let keys = Keys::generate();
+        let event = EventBuilder::new_text_note("Hello", &[])
+            .to_event(&keys)
+            .unwrap();
+        assert_eq!(recompute_id(&event), event.id);
+
+        let mut modified = event.clone();
+        modified.content = "Hello!".to_string();
+        assert_ne!(recompute_id(&modified), event.id);
+    }
+
+    #[test]
+    fn test_verify_events() {
+        let keys = Keys::generate();
+        // enough events for parallel verification, too
+        for count in [4, PARALLEL_VERIFY_THRESHOLD + 6] {
+            let mut events: Vec<Event> = (0..count)
+                .map(|i| {
+                    EventBuilder::new_text_note(format!("note {i}"), &[])
+                        .to_event(&keys)
+                        .unwrap()
+                })
+                .collect();
+            // tampered content
+            events[1].content = "tampered".to_string();
+            // signature of another event
+            events[2].sig = events[3].sig;
+
+            let results = verify_events(&events);
+            assert_eq!(results.len(), count);
+            for (i, (id, valid)) in results.iter().enumerate() {
+                assert_eq!(*id, events[i].id);
+                assert_eq!(*valid, i != 1 && i != 2, "event {i}");
+            }
+        }
+        assert!(verify_events(&[]).is_empty());
+    }
+
+    #[test]
+    fn test_describe_event_metadata() {
+        let keys = Keys::generate();
+        let event = EventBuilder::set_metadata(Metadata::new().name("name"))
+            .to_event(&keys)
+            .unwrap();
+        assert!(describe_event(&event).replaceable);
+
+        let event = EventBuilder::new(Kind::Custom(30023), "Article", &[])
+            .to_event(&keys)
+            .unwrap();
+        assert!(describe_event(&event).addressable);
+    }
+
+    #[test]
+    fn test_describe_event_pow() {
+        let keys = Keys::generate();
+        let event = EventBuilder::new_text_note("Mined", &[])
+            .to_pow_event(&keys, 4)
+            .unwrap();
+        assert_eq!(describe_event(&event).nips, vec![13]);
+    }
+
+    #[test]
+    fn test_describe_event_delegated() {
+        let delegator_keys = Keys::generate();
+        let delegatee_keys = Keys::generate();
+        let dtag = DelegationTag::new(
+            &delegator_keys,
+            delegatee_keys.public_key(),
+            Conditions::from_str("kind=1").unwrap(),
+        )
+        .unwrap();
+        let tag = serde_json::from_str::<Tag>(&dtag.to_string()).unwrap();
+        let event = EventBuilder::new_text_note("Delegated", &[tag])
+            .to_pow_event(&delegatee_keys, 2)
+            .unwrap();
+        let desc = describe_event(&event);
+        assert_eq!(desc.nips, vec![13, 26]);
+        assert_eq!(
+            desc.author_npub,
+            delegatee_keys.public_key().to_bech32().unwrap()
+        );
+        assert_eq!(desc.to_string(), "kind 1, uses NIP-13, NIP-26");
+    }
+}
@@ -0,0 +1,574 @@
+use crate::base::error::Error;
+
+use nostr::hashes::sha256::Hash as Sha256Hash;
+use nostr::prelude::{FromBech32, SecretKey, ToBech32, XOnlyPublicKey};
+
+use std::fmt;
+use std::str::FromStr;
+
+/// Prefixes of bech32-encoded NIP-19 (and NIP-49) entities, including the separator
+const BECH32_PREFIXES: &[&str] = &[
+    "npub1",
+    "nsec1",
+    "note1",
+    "nprofile1",
+    "nevent1",
+    "naddr1",
+    "nrelay1",
+    "ncryptsec1",
+];
+/// Wrappers (opening, closing) around pasted keys, e.g. from chat clients or markdown
+const INPUT_WRAPPERS: &[(&str, &str)] = &[
+    ("```", "```"),
+    ("`", "`"),
+    ("<", ">"),
+    ("\"", "\""),
+    ("'", "'"),
+];
+/// URI prefix of NIP-21
+const NOSTR_URI_PREFIX: &str = "nostr:";
+/// URI prefix of lightning payment requests (BOLT-11 invoices, LNURL)
+const LIGHTNING_URI_PREFIX: &str = "lightning:";
+/// Prefixes of BOLT-11 lightning invoices (mainnet and regtest 'lnbc', testnet and signet 'lntb')
+const LIGHTNING_INVOICE_PREFIXES: &[&str] = &["lnbc", "lntb"];
+/// Prefixes of BIP32 extended keys (private and public)
+const EXTENDED_KEY_PREFIXES: &[&str] = &["xprv", "tprv", "xpub", "tpub"];
+/// Valid BIP39 mnemonic lengths, in words
+const MNEMONIC_WORD_COUNTS: &[usize] = &[12, 15, 18, 21, 24];
+
+/// Kind of a (pasted) input, as detected by its format.
+/// Besides keys, it recognizes some entities that are commonly pasted by mistake into a key field.
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub enum InputKind {
+    /// Public key, in 'npub' bech32 format
+    Npub,
+    /// Secret key, in 'nsec' bech32 format
+    Nsec,
+    /// 64-char hex key, public or secret
+    Hex,
+    /// BIP39 mnemonic words
+    Mnemonic,
+    /// BIP32 extended key (xprv, tprv, xpub, tpub)
+    ExtendedKey,
+    /// Encrypted secret key, in 'ncryptsec' bech32 format
+    EncryptedKey,
+    /// Profile reference, 'nprofile'
+    Profile,
+    /// Note (event) id, 'note'
+    NoteId,
+    /// Event reference, 'nevent'
+    Event,
+    /// Addressable event reference, 'naddr'
+    Address,
+    /// Relay reference, 'nrelay'
+    Relay,
+    /// Lightning address (user@domain), or a NIP-05 identifier, which has the same format
+    LightningAddress,
+    /// LNURL, bech32 'lnurl'
+    Lnurl,
+    /// BOLT-11 lightning invoice
+    LightningInvoice,
+    /// Not recognized
+    Unknown,
+}
+
+impl fmt::Display for InputKind {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let s = match self {
+            InputKind::Npub => "a public key (npub)",
+            InputKind::Nsec => "a secret key (nsec)",
+            InputKind::Hex => "a hex key",
+            InputKind::Mnemonic => "a mnemonic",
+            InputKind::ExtendedKey => "an extended key",
+            InputKind::EncryptedKey => "an encrypted key (ncryptsec), import it as encrypted key",
+            InputKind::Profile => "a profile reference (nprofile), use its npub instead",
+            InputKind::NoteId => "a note ID",
+            InputKind::Event => "an event reference (nevent)",
+            InputKind::Address => "an addressable event reference (naddr)",
+            InputKind::Relay => "a relay reference (nrelay)",
+            InputKind::LightningAddress => "a lightning address (or NIP-05 identifier)",
+            InputKind::Lnurl => "an LNURL",
+            InputKind::LightningInvoice => "a lightning invoice",
+            InputKind::Unknown => "unrecognized input",
+        };
+        write!(f, "{s}")
+    }
+}
+
+/// Sanitize a key input before parsing: trim whitespace, strip the `nostr:` URI prefix,
+/// and for bech32 inputs: reject mixed case, convert all-uppercase to lowercase (both are valid per spec).
+/// 64-character hex keys are converted to lowercase (some legacy tools emit uppercase hex).
+/// Other inputs are returned trimmed only.
+pub(crate) fn normalize_input(input: &str) -> Result<String, Error> {
+    let mut s = strip_wrappers(input.trim());
+    if s.len() >= NOSTR_URI_PREFIX.len()
+        && s.is_char_boundary(NOSTR_URI_PREFIX.len())
+        && s[..NOSTR_URI_PREFIX.len()].eq_ignore_ascii_case(NOSTR_URI_PREFIX)
+    {
+        s = &s[NOSTR_URI_PREFIX.len()..];
+    }
+    let lowercase = s.to_lowercase();
+    if s.len() == 64 && s.chars().all(|c| c.is_ascii_hexdigit()) {
+        return Ok(lowercase);
+    }
+    if !BECH32_PREFIXES.iter().any(|p| lowercase.starts_with(p)) {
+        return Ok(s.to_string());
+    }
+    let has_lower = s.chars().any(|c| c.is_ascii_lowercase());
+    let has_upper = s.chars().any(|c| c.is_ascii_uppercase());
+    if has_lower && has_upper {
+        return Err(Error::MixedCaseBech32);
+    }
+    Ok(lowercase)
+}
+
+/// Strip surrounding wrappers (angle brackets, backticks, matching quotes), also nested ones.
+/// Internal characters are left alone.
+fn strip_wrappers(input: &str) -> &str {
+    let mut s = input;
+    loop {
+        let stripped = INPUT_WRAPPERS.iter().find_map(|(open, close)| {
+            if s.len() >= open.len() + close.len() {
+                s.strip_prefix(open)?.strip_suffix(close)
+            } else {
+                None
+            }
+        });
+        match stripped {
+            Some(inner) => s = inner.trim(),
+            None => return s,
+        }
+    }
+}
+
+/// Normalize a pasted BIP39 mnemonic: collapse runs of whitespace (incl. line breaks)
+/// to single spaces, and trim the ends. The words themselves are not altered.
+pub(crate) fn normalize_mnemonic(input: &str) -> String {
+    input.split_whitespace().collect::<Vec<&str>>().join(" ")
+}
+
+/// Detect the kind of a (normalized, see `normalize_input`) input by its format.
+/// Only the format is checked (prefix, charset, length), not validity (e.g. checksum).
+pub(crate) fn detect_input_kind(input: &str) -> InputKind {
+    let mut lowercase = input.to_lowercase();
+    if let Some(rest) = lowercase.strip_prefix(LIGHTNING_URI_PREFIX) {
+        lowercase = rest.to_string();
+    }
+    let bech32_kinds = [
+        ("npub1", InputKind::Npub),
+        ("nsec1", InputKind::Nsec),
+        ("ncryptsec1", InputKind::EncryptedKey),
+        ("nprofile1", InputKind::Profile),
+        ("note1", InputKind::NoteId),
+        ("nevent1", InputKind::Event),
+        ("naddr1", InputKind::Address),
+        ("nrelay1", InputKind::Relay),
+        ("lnurl1", InputKind::Lnurl),
+    ];
+    for (prefix, kind) in bech32_kinds {
+        if lowercase.starts_with(prefix) {
+            return kind;
+        }
+    }
+    if LIGHTNING_INVOICE_PREFIXES
+        .iter()
+        .any(|p| lowercase.starts_with(p))
+    {
+        return InputKind::LightningInvoice;
+    }
+    if EXTENDED_KEY_PREFIXES.iter().any(|p| input.starts_with(p)) {
+        return InputKind::ExtendedKey;
+    }
+    if input.len() == 64 && input.chars().all(|c| c.is_ascii_hexdigit()) {
+        return InputKind::Hex;
+    }
+    if is_lightning_address(&lowercase) {
+        return InputKind::LightningAddress;
+    }
+    let words: Vec<&str> = lowercase.split_whitespace().collect();
+    if MNEMONIC_WORD_COUNTS.contains(&words.len())
+        && words
+            .iter()
+            .all(|w| w.chars().all(|c| c.is_ascii_lowercase()))
+    {
+        return InputKind::Mnemonic;
+    }
+    InputKind::Unknown
+}
+
+/// Check if the string has the format of a lightning address (`user@domain.tld`)
+fn is_lightning_address(s: &str) -> bool {
+    let (user, domain) = match s.split_once('@') {
+        Some(parts) => parts,
+        None => return false,
+    };
+    !user.is_empty()
+        && user
+            .chars()
+            .all(|c| c.is_ascii_alphanumeric() || c == '-' || c == '_' || c == '.')
+        && domain.contains('.')
+        && !domain.starts_with('.')
+        && !domain.ends_with('.')
+        && domain
+            .chars()
+            .all(|c| c.is_ascii_alphanumeric() || c == '-' || c == '.')
+}
+
+/// Check if the string is a valid public key in 'npub' bech32 format, after `normalize_input`
+/// (so the result matches what import accepts). No side effects.
+pub(crate) fn is_valid_npub(s: &str) -> bool {
+    normalize_input(s).map_or(false, |s| XOnlyPublicKey::from_bech32(s).is_ok())
+}
+
+/// Check if the string is a valid secret key in 'nsec' bech32 format, after `normalize_input`
+/// (so the result matches what import accepts). No side effects.
+pub(crate) fn is_valid_nsec(s: &str) -> bool {
+    normalize_input(s).map_or(false, |s| SecretKey::from_bech32(s).is_ok())
+}
+
+/// Warning: Security-sensitive method!
+/// Convert a secret key from 'nsec' bech32 to hex format. No side effects.
+pub(crate) fn nsec_to_hex(nsec: &str) -> Result<String, Error> {
+    Ok(hex::encode(SecretKey::from_bech32(nsec)?.secret_bytes()))
+}
+
+/// Warning: Security-sensitive method!
+/// Convert a secret key from hex to 'nsec' bech32 format. No side effects.
+pub(crate) fn hex_to_nsec(hex: &str) -> Result<String, Error> {
+    Ok(SecretKey::from_str(hex)?.to_bech32()?)
+}
+
+/// Convert a public key from 'npub' bech32 to hex format. No side effects.
+pub(crate) fn npub_to_hex(npub: &str) -> Result<String, Error> {
+    Ok(XOnlyPublicKey::from_bech32(npub)?.to_string())
+}
+
+/// Convert a public key from hex to 'npub' bech32 format. No side effects.
+pub(crate) fn hex_to_npub(hex: &str) -> Result<String, Error> {
+    Ok(XOnlyPublicKey::from_str(hex)?.to_bech32()?)
+}
+
+/// Similarity of two public keys ('npub' bech32): the Hamming distance (number of differing bits)
+/// of the decoded keys. 0 for identical keys, about 128 for unrelated ones. No side effects.
+pub(crate) fn npub_similarity(a: &str, b: &str) -> Result<usize, Error> {
+    let a = XOnlyPublicKey::from_bech32(normalize_input(a)?)?.serialize();
+    let b = XOnlyPublicKey::from_bech32(normalize_input(b)?)?.serialize();
+    Ok(a.iter()
+        .zip(b.iter())
+        .map(|(x, y)| (x ^ y).count_ones() as usize)
+        .sum())
+}
+
+/// Hex of a public key with a case checksum, similar to EIP-55: a letter is uppercase if the
+/// corresponding nibble of the SHA-256 hash of the lowercase hex is 8 or more.
+/// (EIP-55 uses Keccak-256, which is not available here.)
+pub(crate) fn checksummed_hex(public_key: &XOnlyPublicKey) -> String {
+    let hex = public_key.to_string();
+    let hash = <Sha256Hash as nostr::hashes::Hash>::hash(hex.as_bytes());
+    hex.chars()
+        .enumerate()
+        .map(|(i, c)| {
+            let nibble = (hash[i / 2] >> (if i % 2 == 0 { 4 } else { 0 })) & 0x0f;
+            if nibble >= 8 {
+                c.to_ascii_uppercase()
+            } else {
+                c
+            }
+        })
+        .collect()
+}
+
+/// Compare two byte strings in time independent of their content (only of their length),
+/// e.g. for MACs or retyped secrets
+pub(crate) fn constant_time_eq(a: &[u8], b: &[u8]) -> bool {
+    a.len() == b.len() && a.iter().zip(b).fold(0u8, |acc, (x, y)| acc | (x ^ y)) == 0
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use proptest::prelude::*;
+
+    #[test]
+    fn test_constant_time_eq() {
+        assert!(constant_time_eq(b"", b""));
+        assert!(constant_time_eq(b"abc", b"abc"));
+        assert!(!constant_time_eq(b"abc", b"abd"));
+        assert!(!constant_time_eq(b"abc", b"ab"));
+        assert!(!constant_time_eq(b"", b"a"));
+    }
+
+    #[test]
+    fn test_checksummed_hex() {
+        let pk = XOnlyPublicKey::from_str(
+            "1a459a8a6aa6441d480ba665fb8fb21a4cfe8bcacb7d87300f8046a558a3fce4",
+        )
+        .unwrap();
+        let checksummed = checksummed_hex(&pk);
+        assert_eq!(
+            checksummed,
+            "1a459a8A6aa6441d480Ba665fB8Fb21A4cFe8BCaCB7d87300f8046A558A3FCE4"
+        );
+        // case-insensitive hex parsers still accept it
+        assert_eq!(
+            XOnlyPublicKey::from_str(&checksummed.to_lowercase()).unwrap(),
+            pk
+        );
+    }
+
+    #[test]
+    fn test_is_valid_npub() {
+        assert!(is_valid_npub(
+            "npub1rfze4zn25ezp6jqt5ejlhrajrfx0az72ed7cwvq0spr22k9rlnjq93lmd4"
+        ));
+        assert!(!is_valid_npub(
+            "nsec1ktekw0hr5evjs0n9nyyquz4sue568snypy2rwk5mpv6hl2hq3vtsk0kpae"
+        ));
+        assert!(!is_valid_npub(
+            "npub1rfze4zn25ezp6jqt5ejlhrajrfx0az72ed7cwvq0spr22k9rlnjq93lmd5"
+        ));
+        assert!(!is_valid_npub(""));
+        assert!(!is_valid_npub("__NOT_A_VALID_KEY__"));
+        // normalized like on import
+        assert!(is_valid_npub(
+            " nostr:NPUB1RFZE4ZN25EZP6JQT5EJLHRAJRFX0AZ72ED7CWVQ0SPR22K9RLNJQ93LMD4\n"
+        ));
+        assert!(!is_valid_npub(
+            "npub1RFZE4ZN25EZP6JQT5EJLHRAJRFX0AZ72ED7CWVQ0SPR22K9RLNJQ93LMD4"
+        ));
+    }
+
+    #[test]
+    fn test_is_valid_nsec() {
+        assert!(is_valid_nsec(
+            "nsec1ktekw0hr5evjs0n9nyyquz4sue568snypy2rwk5mpv6hl2hq3vtsk0kpae"
+        ));
+        assert!(!is_valid_nsec(
+            "npub1rfze4zn25ezp6jqt5ejlhrajrfx0az72ed7cwvq0spr22k9rlnjq93lmd4"
+        ));
+        assert!(!is_valid_nsec(
+            "b2f3673ee3a659283e6599080e0ab0e669a3c2640914375a9b0b357faae08b17"
+        ));
+        assert!(!is_valid_nsec(""));
+        assert!(is_valid_nsec(
+            "`nsec1ktekw0hr5evjs0n9nyyquz4sue568snypy2rwk5mpv6hl2hq3vtsk0kpae` "
+        ));
+    }
+
+    #[test]
+    fn test_normalize_input() {
+        let npub = "npub1rfze4zn25ezp6jqt5ejlhrajrfx0az72ed7cwvq0spr22k9rlnjq93lmd4";
+        assert_eq!(normalize_input(npub).unwrap(), npub);
+        assert_eq!(normalize_input(&format!("  {npub}\n")).unwrap(), npub);
+        assert_eq!(normalize_input(&format!("nostr:{npub}")).unwrap(), npub);
+        // uppercase-only is valid
+        assert_eq!(normalize_input(&npub.to_uppercase()).unwrap(), npub);
+        assert_eq!(
+            normalize_input(&format!("NOSTR:{}", npub.to_uppercase())).unwrap(),
+            npub
+        );
+        // mixed case is not
+        assert!(matches!(
+            normalize_input("Npub1rfze4zn25ezp6jqt5ejlhrajrfx0az72ed7cwvq0spr22k9rlnjq93lmd4"),
+            Err(Error::MixedCaseBech32)
+        ));
+        assert!(matches!(
+            normalize_input("npub1rfze4zn25ezp6jqt5ejlhrajrfx0az72ed7cwvq0spr22k9rlnjq93LMD4"),
+            Err(Error::MixedCaseBech32)
+        ));
+        assert!(matches!(
+            normalize_input("NSEC1ktekw0hr5evjs0n9nyyquz4sue568snypy2rwk5mpv6hl2hq3vtsk0kpae"),
+            Err(Error::MixedCaseBech32)
+        ));
+        // hex key is lowercased, also mixed case (hex has no case rule)
+        assert_eq!(
+            normalize_input(" 1A459A8A6AA6441D480BA665FB8FB21A4CFE8BCACB7D87300F8046A558A3FCE4 ")
+                .unwrap(),
+            "1a459a8a6aa6441d480ba665fb8fb21a4cfe8bcacb7d87300f8046a558a3fce4"
+        );
+        assert_eq!(
+            normalize_input("1a459a8a6aa6441d480ba665fb8fb21a4cfe8bcacb7d87300f8046a558A3FCE4")
+                .unwrap(),
+            "1a459a8a6aa6441d480ba665fb8fb21a4cfe8bcacb7d87300f8046a558a3fce4"
+        );
+        // other input is only trimmed
+        assert_eq!(normalize_input(" Some Input ").unwrap(), "Some Input");
+        assert_eq!(normalize_input("").unwrap(), "");
+    }
+
+    #[test]
+    fn test_normalize_input_wrappers() {
+        let npub = "npub1rfze4zn25ezp6jqt5ejlhrajrfx0az72ed7cwvq0spr22k9rlnjq93lmd4";
+        for input in [
+            format!("<{npub}>"),
+            format!("`{npub}`"),
+            format!("```{npub}```"),
+            format!("```\n{npub}\n```"),
+            format!("\"{npub}\""),
+            format!("'{npub}'"),
+            format!(" `<nostr:{npub}>` "),
+            format!("<nostr:{}>", npub.to_uppercase()),
+        ] {
+            assert_eq!(normalize_input(&input).unwrap(), npub, "{input}");
+        }
+        // not matching, or internal: left alone
+        assert_eq!(
+            normalize_input(&format!("<{npub}")).unwrap(),
+            format!("<{npub}")
+        );
+        assert_eq!(
+            normalize_input(&format!("\"{npub}'")).unwrap(),
+            format!("\"{npub}'")
+        );
+        assert_eq!(normalize_input("a<b>c").unwrap(), "a<b>c");
+        assert_eq!(normalize_input("<>").unwrap(), "");
+        assert_eq!(normalize_input("`").unwrap(), "`");
+    }
+
+    #[test]
+    fn test_normalize_mnemonic() {
+        assert_eq!(normalize_mnemonic("oil oil oil"), "oil oil oil");
+        assert_eq!(
+            normalize_mnemonic("  oil   oil\noil\r\n\toil  "),
+            "oil oil oil oil"
+        );
+        // words are not altered
+        assert_eq!(normalize_mnemonic("Oil  OIL"), "Oil OIL");
+        assert_eq!(normalize_mnemonic(" \n "), "");
+    }
+
+    #[test]
+    fn test_detect_input_kind_keys() {
+        assert_eq!(
+            detect_input_kind("npub1rfze4zn25ezp6jqt5ejlhrajrfx0az72ed7cwvq0spr22k9rlnjq93lmd4"),
+            InputKind::Npub
+        );
+        assert_eq!(
+            detect_input_kind("nsec1ktekw0hr5evjs0n9nyyquz4sue568snypy2rwk5mpv6hl2hq3vtsk0kpae"),
+            InputKind::Nsec
+        );
+        assert_eq!(
+            detect_input_kind("1A459A8A6AA6441D480BA665FB8FB21A4CFE8BCACB7D87300F8046A558A3FCE4"),
+            InputKind::Hex
+        );
+        assert_eq!(
+            detect_input_kind("oil oil oil oil oil oil oil oil oil oil oil oil"),
+            InputKind::Mnemonic
+        );
+        assert_eq!(
+            detect_input_kind("xprv9s21ZrQH143K"),
+            InputKind::ExtendedKey
+        );
+        assert_eq!(
+            detect_input_kind("ncryptsec1qgg9947rlpvqu76pj5ecreduf9jxhselq2nae2kghhvd5g7dg"),
+            InputKind::EncryptedKey
+        );
+        assert_eq!(detect_input_kind(""), InputKind::Unknown);
+        assert_eq!(detect_input_kind("__NOT_A_VALID_KEY__"), InputKind::Unknown);
+        assert_eq!(detect_input_kind("oil oil oil"), InputKind::Unknown);
+    }
+
+    #[test]
+    fn test_detect_input_kind_misdirected() {
+        assert_eq!(
+            detect_input_kind("satoshi@getalby.com"),
+            InputKind::LightningAddress
+        );
+        assert_eq!(
+            detect_input_kind("_@nostr-domain.example.org"),
+            InputKind::LightningAddress
+        );
+        assert_eq!(detect_input_kind("satoshi@localhost"), InputKind::Unknown);
+        assert_eq!(detect_input_kind("@getalby.com"), InputKind::Unknown);
+        let lnurl =
+            "lnurl1dp68gurn8ghj7um9wfmxjcm99e3k7mf0v9cxj0m385ekvcenxc6r2c35xvukxefcv5ms47h9zs";
+        assert_eq!(detect_input_kind(lnurl), InputKind::Lnurl);
+        assert_eq!(detect_input_kind(&lnurl.to_uppercase()), InputKind::Lnurl);
+        assert_eq!(
+            detect_input_kind(&format!("lightning:{lnurl}")),
+            InputKind::Lnurl
+        );
+        assert_eq!(
+            detect_input_kind(
+                "lnbc2500u1pvjluezpp5qqqsyqcyq5rqwzqfqqqsyqcyq5rqwzqfqqqsyqcyq5rqwzqfqypq"
+            ),
+            InputKind::LightningInvoice
+        );
+        assert_eq!(
+            detect_input_kind("note1tjpa5aa0rhkx6u5fsdye3tt6477eugv389khtmpucfl45aezdumqkkhgwy"),
+            InputKind::NoteId
+        );
+        assert_eq!(
+            detect_input_kind(
+                "nevent1qqs9eq76w7h3mmrdw2ycxjvc44a2l0v7yxgnjmt4as7vyl66wu3x7dsrtwlqa"
+            ),
+            InputKind::Event
+        );
+        assert_eq!(
+            detect_input_kind("naddr1qqyk67fddehhxarj95pzqxj9n29x4fjyr4yqhfn9lw8myxjvl69u4jmasucqlqzx54v28l8yqvzqqqr4guzn4xpm"),
+            InputKind::Address
+        );
+        assert_eq!(
+            detect_input_kind("nprofile1qqsrhuxx8l9ex335q7he0f09aej04zpazpl0ne2cgukyawd24mayt8gpp4mhxue69uhhytnc9e3k7mgpz4mhxue69uhkg6nzv9ejuumpv34kytnrdaksjlyr9p"),
+            InputKind::Profile
+        );
+    }
+
+    #[test]
+    fn test_convert_secret_key() {
+        let nsec = "nsec1ktekw0hr5evjs0n9nyyquz4sue568snypy2rwk5mpv6hl2hq3vtsk0kpae";
+        let hex = "b2f3673ee3a659283e6599080e0ab0e669a3c2640914375a9b0b357faae08b17";
+        assert_eq!(nsec_to_hex(nsec).unwrap(), hex);
+        assert_eq!(hex_to_nsec(hex).unwrap(), nsec);
+        assert!(nsec_to_hex(hex).is_err());
+        assert!(hex_to_nsec(nsec).is_err());
+        assert!(
+            nsec_to_hex("npub1rfze4zn25ezp6jqt5ejlhrajrfx0az72ed7cwvq0spr22k9rlnjq93lmd4").is_err()
+        );
+    }
+
+    #[test]
+    fn test_convert_public_key() {
+        let npub = "npub1rfze4zn25ezp6jqt5ejlhrajrfx0az72ed7cwvq0spr22k9rlnjq93lmd4";
+        let hex = "1a459a8a6aa6441d480ba665fb8fb21a4cfe8bcacb7d87300f8046a558a3fce4";
+        assert_eq!(npub_to_hex(npub).unwrap(), hex);
+        assert_eq!(hex_to_npub(hex).unwrap(), npub);
+        assert!(npub_to_hex(hex).is_err());
+        assert!(hex_to_npub("__NOT_A_VALID_KEY__").is_err());
+    }
+
+    #[test]
+    fn test_npub_similarity() {
+        let npub = "npub1rfze4zn25ezp6jqt5ejlhrajrfx0az72ed7cwvq0spr22k9rlnjq93lmd4";
+        assert_eq!(npub_similarity(npub, npub).unwrap(), 0);
+        assert_eq!(
+            npub_similarity(npub, &format!("nostr:{}", npub.to_uppercase())).unwrap(),
+            0
+        );
+        // 1a45..fce4 vs 1a45..fce5: one bit differs
+        let similar =
+            hex_to_npub("1a459a8a6aa6441d480ba665fb8fb21a4cfe8bcacb7d87300f8046a558a3fce5")
+                .unwrap();
+        assert_eq!(npub_similarity(npub, &similar).unwrap(), 1);
+        // unrelated keys: about half of the 256 bits differ
+        let unrelated = "npub1tczgvlwvcdxp5f4mp8rqehramx6dqemq6v8egf3qdfzazn8cs7dqlhmwux";
+        assert_eq!(npub_similarity(npub, unrelated).unwrap(), 130);
+        assert!(npub_similarity(npub, "__NOT_A_VALID_KEY__").is_err());
+    }
+
+    proptest! {
+        #[test]
+        fn test_parse_no_panic(
+            input in "\\PC*|(?i)(nostr:)?(npub1|nsec1|ncryptsec1|lnurl1)[0-9a-z]{0,80}|[0-9a-fA-F]{60,68}|[`<'\"]{0,3}\\PC{0,70}[`>'\"]{0,3}"
+        ) {
+            let _ = normalize_input(&input);
+            let _ = detect_input_kind(&input);
+            let _ = is_valid_npub(&input);
+            let _ = is_valid_nsec(&input);
+            let _ = nsec_to_hex(&input);
+            let _ = hex_to_nsec(&input);
+            let _ = npub_to_hex(&input);
+            let _ = hex_to_npub(&input);
+        }
+    }
+}
@@ -1,4 +1,10 @@
+pub mod cancellation;
 pub mod encrypt;
 pub mod error;
+pub mod event_utils;
+pub mod key_utils;
+pub mod nip19_tlv;
+pub mod nip44;
+pub mod petname;
 // mod nostr_libs;
 pub mod storage;
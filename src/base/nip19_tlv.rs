@@ -0,0 +1,196 @@
+use crate::base::error::Error;
+
+use bech32::{FromBase32, ToBase32};
+use nostr::prelude::XOnlyPublicKey;
+
+/// TLV type for the special entry (pubkey, event id, or identifier)
+const TLV_TYPE_SPECIAL: u8 = 0;
+/// TLV type for relay hints
+const TLV_TYPE_RELAY: u8 = 1;
+/// Human-readable prefixes of NIP-19 entities that are TLV-encoded, and may have relay hints
+const TLV_RELAY_HINT_PREFIXES: &[&str] = &["nprofile", "nevent", "naddr"];
+
+/// A TLV (type-length-value) entry of a NIP-19 entity
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub(crate) struct TlvEntry {
+    pub tlv_type: u8,
+    pub value: Vec<u8>,
+}
+
+/// Decode a NIP-19 TLV-encoded entity, return its prefix and its TLV entries
+pub(crate) fn decode_tlv_entity(entity: &str) -> Result<(String, Vec<TlvEntry>), Error> {
+    let (hrp, data, _variant) = bech32::decode(entity)?;
+    if !TLV_RELAY_HINT_PREFIXES.contains(&hrp.as_str()) {
+        return Err(Error::Nip19NotTlvEntity(hrp));
+    }
+    let bytes = Vec::<u8>::from_base32(&data)?;
+
+    let mut entries = Vec::new();
+    let mut i = 0;
+    while i < bytes.len() {
+        if i + 2 > bytes.len() {
+            return Err(Error::Nip19InvalidTlv);
+        }
+        let tlv_type = bytes[i];
+        let len = bytes[i + 1] as usize;
+        let start = i + 2;
+        if start + len > bytes.len() {
+            return Err(Error::Nip19InvalidTlv);
+        }
+        entries.push(TlvEntry {
+            tlv_type,
+            value: bytes[start..start + len].to_vec(),
+        });
+        i = start + len;
+    }
+    Ok((hrp, entries))
+}
+
+/// Encode TLV entries into a NIP-19 entity with the given prefix
+pub(crate) fn encode_tlv_entity(hrp: &str, entries: &[TlvEntry]) -> Result<String, Error> {
+    let mut bytes = Vec::new();
+    for e in entries {
+        if e.value.len() > u8::MAX as usize {
+            return Err(Error::Nip19InvalidTlv);
+        }
+        bytes.push(e.tlv_type);
+        bytes.push(e.value.len() as u8);
+        bytes.extend(&e.value);
+    }
+    Ok(bech32::encode(
+        hrp,
+        bytes.to_base32(),
+        bech32::Variant::Bech32,
+    )?)
+}
+
+/// Replace the relay hints of a NIP-19 TLV entity (nprofile, nevent, naddr) with the new ones,
+/// without re-signing anything. All other entries (pubkey, event id, kind, identifier) are preserved.
+/// Non-TLV entities (e.g. plain npub) have no relay hints, they are rejected.
+pub(crate) fn update_relay_hints(entity: &str, new_relays: &[String]) -> Result<String, Error> {
+    let (hrp, entries) = decode_tlv_entity(entity)?;
+    let mut new_entries: Vec<TlvEntry> = entries
+        .into_iter()
+        .filter(|e| e.tlv_type != TLV_TYPE_RELAY)
+        .collect();
+    for relay in new_relays {
+        new_entries.push(TlvEntry {
+            tlv_type: TLV_TYPE_RELAY,
+            value: relay.as_bytes().to_vec(),
+        });
+    }
+    encode_tlv_entity(&hrp, &new_entries)
+}
+
+/// Encode a public key as 'nprofile', with the relays as relay hints
+pub(crate) fn nprofile(public_key: &XOnlyPublicKey, relays: &[String]) -> Result<String, Error> {
+    let entity = encode_tlv_entity(
+        "nprofile",
+        &[TlvEntry {
+            tlv_type: TLV_TYPE_SPECIAL,
+            value: public_key.serialize().to_vec(),
+        }],
+    )?;
+    update_relay_hints(&entity, relays)
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use proptest::prelude::*;
+    use std::str::FromStr;
+
+    // Example from NIP-19
+    const NPROFILE: &str = "nprofile1qqsrhuxx8l9ex335q7he0f09aej04zpazpl0ne2cgukyawd24mayt8gpp4mhxue69uhhytnc9e3k7mgpz4mhxue69uhkg6nzv9ejuumpv34kytnrdaksjlyr9p";
+
+    fn get_special(entity: &str) -> Vec<u8> {
+        let (_hrp, entries) = decode_tlv_entity(entity).unwrap();
+        entries
+            .into_iter()
+            .find(|e| e.tlv_type == TLV_TYPE_SPECIAL)
+            .unwrap()
+            .value
+    }
+
+    fn get_relay_hints(entity: &str) -> Vec<String> {
+        let (_hrp, entries) = decode_tlv_entity(entity).unwrap();
+        entries
+            .iter()
+            .filter(|e| e.tlv_type == TLV_TYPE_RELAY)
+            .map(|e| String::from_utf8_lossy(&e.value).to_string())
+            .collect()
+    }
+
+    #[test]
+    fn test_decode_tlv_entity() {
+        assert_eq!(
+            get_relay_hints(NPROFILE),
+            vec!["wss://r.x.com", "wss://djbas.sadkb.com"]
+        );
+        assert_eq!(
+            hex::encode(get_special(NPROFILE)),
+            "3bf0c63fcb93463407af97a5e5ee64fa883d107ef9e558472c4eb9aaaefa459d"
+        );
+    }
+
+    #[test]
+    fn test_update_relay_hints_nprofile() {
+        let updated = update_relay_hints(NPROFILE, &["wss://relay.damus.io".to_string()]).unwrap();
+        assert!(updated.starts_with("nprofile1"));
+        assert_ne!(updated, NPROFILE);
+        assert_eq!(get_relay_hints(&updated), vec!["wss://relay.damus.io"]);
+        // pubkey preserved
+        assert_eq!(get_special(&updated), get_special(NPROFILE));
+
+        // change back
+        let original = update_relay_hints(
+            &updated,
+            &[
+                "wss://r.x.com".to_string(),
+                "wss://djbas.sadkb.com".to_string(),
+            ],
+        )
+        .unwrap();
+        assert_eq!(original, NPROFILE);
+
+        // remove all
+        let no_relays = update_relay_hints(NPROFILE, &[]).unwrap();
+        assert!(get_relay_hints(&no_relays).is_empty());
+    }
+
+    #[test]
+    fn test_update_relay_hints_npub() {
+        let res = update_relay_hints(
+            "npub1rfze4zn25ezp6jqt5ejlhrajrfx0az72ed7cwvq0spr22k9rlnjq93lmd4",
+            &["wss://relay.damus.io".to_string()],
+        );
+        assert!(matches!(res, Err(Error::Nip19NotTlvEntity(_))));
+    }
+
+    #[test]
+    fn test_update_relay_hints_invalid() {
+        assert!(update_relay_hints("__NOT_AN_ENTITY__", &[]).is_err());
+    }
+
+    #[test]
+    fn test_nprofile() {
+        let public_key = XOnlyPublicKey::from_str(
+            "3bf0c63fcb93463407af97a5e5ee64fa883d107ef9e558472c4eb9aaaefa459d",
+        )
+        .unwrap();
+        let relays = [
+            "wss://r.x.com".to_string(),
+            "wss://djbas.sadkb.com".to_string(),
+        ];
+        assert_eq!(nprofile(&public_key, &relays).unwrap(), NPROFILE);
+        assert!(nprofile(&public_key, &["x".repeat(256)]).is_err());
+    }
+
+    proptest! {
+        #[test]
+        fn test_parse_no_panic(input in "\\PC*|(?i)n(profile|event|addr)1[0-9a-z]{0,120}") {
+            let _ = decode_tlv_entity(&input);
+            let _ = update_relay_hints(&input, &[input.clone()]);
+        }
+    }
+}
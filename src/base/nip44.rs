@@ -0,0 +1,315 @@
+use crate::base::error::Error;
+use crate::base::key_utils::constant_time_eq;
+
+use base64::engine::general_purpose::STANDARD;
+use base64::Engine;
+use chacha20::cipher::{KeyIvInit, StreamCipher};
+use chacha20::ChaCha20;
+use nostr::hashes::hmac::{Hmac, HmacEngine};
+use nostr::hashes::sha256::Hash as Sha256Hash;
+use nostr::hashes::HashEngine;
+use nostr::prelude::{SecretKey, XOnlyPublicKey};
+use rand_core::{OsRng, RngCore};
+use zeroize::Zeroize;
+
+/// Salt of the NIP-44 (v2) conversation key derivation
+const NIP44_SALT: &[u8] = b"nip44-v2";
+/// Version byte of NIP-44 v2 payloads
+const NIP44_VERSION: u8 = 2;
+const NONCE_LEN: usize = 32;
+const MAC_LEN: usize = 32;
+/// Maximum plaintext length, in bytes
+pub(crate) const MAX_PLAINTEXT_LEN: usize = 65535;
+/// Minimum length of a decoded payload: version, nonce, 2 length bytes + 32 padded bytes, mac
+const MIN_PAYLOAD_LEN: usize = 1 + NONCE_LEN + 2 + 32 + MAC_LEN;
+
+fn hmac_sha256(key: &[u8], parts: &[&[u8]]) -> [u8; 32] {
+    let mut engine = HmacEngine::<Sha256Hash>::new(key);
+    for part in parts {
+        engine.input(part);
+    }
+    let hmac = <Hmac<Sha256Hash> as nostr::hashes::Hash>::from_engine(engine);
+    let mut out = [0u8; 32];
+    out.copy_from_slice(&hmac[..]);
+    out
+}
+
+/// Warning: Security-sensitive method!
+/// NIP-44 (v2) conversation key between a secret key and a peer public key:
+/// HKDF-extract (HMAC-SHA256) with salt 'nip44-v2' of the unhashed x coordinate of the ECDH shared point.
+/// Symmetric: both sides derive the same key.
+pub(crate) fn conversation_key(secret_key: &SecretKey, peer: &XOnlyPublicKey) -> [u8; 32] {
+    let mut point = nostr::secp256k1::ecdh::shared_secret_point(
+        &peer.public_key(nostr::secp256k1::Parity::Even),
+        secret_key,
+    );
+    let key = hmac_sha256(NIP44_SALT, &[&point[..32]]);
+    point.zeroize();
+    key
+}
+
+/// Per-message keys, HKDF-expand of the conversation key with the nonce as info
+struct MessageKeys {
+    chacha_key: [u8; 32],
+    chacha_nonce: [u8; 12],
+    hmac_key: [u8; 32],
+}
+
+impl MessageKeys {
+    fn derive(conversation_key: &[u8; 32], nonce: &[u8; NONCE_LEN]) -> Self {
+        // 76 bytes are needed, 3 rounds of HKDF-expand
+        let mut okm = [0u8; 96];
+        let mut prev: Vec<u8> = Vec::new();
+        for i in 0..3u8 {
+            let block = hmac_sha256(conversation_key, &[&prev, nonce, &[i + 1]]);
+            okm[i as usize * 32..(i as usize + 1) * 32].copy_from_slice(&block);
+            prev.zeroize();
+            prev = block.to_vec();
+        }
+        prev.zeroize();
+        let mut keys = MessageKeys {
+            chacha_key: [0u8; 32],
+            chacha_nonce: [0u8; 12],
+            hmac_key: [0u8; 32],
+        };
+        keys.chacha_key.copy_from_slice(&okm[0..32]);
+        keys.chacha_nonce.copy_from_slice(&okm[32..44]);
+        keys.hmac_key.copy_from_slice(&okm[44..76]);
+        okm.zeroize();
+        keys
+    }
+}
+
+impl Drop for MessageKeys {
+    fn drop(&mut self) {
+        self.chacha_key.zeroize();
+        self.chacha_nonce.zeroize();
+        self.hmac_key.zeroize();
+    }
+}
+
+/// Length of the padded plaintext (without the 2 length bytes), as defined by NIP-44
+pub(crate) fn padded_len(len: usize) -> usize {
+    if len <= 32 {
+        return 32;
+    }
+    let next_power = 1usize << (usize::BITS - (len - 1).leading_zeros());
+    let chunk = if next_power <= 256 {
+        32
+    } else {
+        next_power / 8
+    };
+    chunk * ((len - 1) / chunk + 1)
+}
+
+fn pad(plaintext: &[u8]) -> Result<Vec<u8>, Error> {
+    let len = plaintext.len();
+    if len == 0 || len > MAX_PLAINTEXT_LEN {
+        return Err(Error::Nip44InvalidLength(len));
+    }
+    let mut padded = Vec::with_capacity(2 + padded_len(len));
+    padded.extend_from_slice(&(len as u16).to_be_bytes());
+    padded.extend_from_slice(plaintext);
+    padded.resize(2 + padded_len(len), 0);
+    Ok(padded)
+}
+
+fn unpad(padded: &[u8]) -> Result<Vec<u8>, Error> {
+    if padded.len() < 2 {
+        return Err(Error::Nip44InvalidPayload);
+    }
+    let len = u16::from_be_bytes([padded[0], padded[1]]) as usize;
+    if len == 0 || padded.len() != 2 + padded_len(len) {
+        return Err(Error::Nip44InvalidPayload);
+    }
+    Ok(padded[2..2 + len].to_vec())
+}
+
+/// Warning: Security-sensitive method!
+/// Encrypt a message with a conversation key, as NIP-44 (v2) base64 payload. Nonce is random.
+pub(crate) fn encrypt(conversation_key: &[u8; 32], plaintext: &str) -> Result<String, Error> {
+    let mut nonce = [0u8; NONCE_LEN];
+    OsRng.fill_bytes(&mut nonce);
+    encrypt_with_nonce(conversation_key, plaintext, &nonce)
+}
+
+fn encrypt_with_nonce(
+    conversation_key: &[u8; 32],
+    plaintext: &str,
+    nonce: &[u8; NONCE_LEN],
+) -> Result<String, Error> {
+    let keys = MessageKeys::derive(conversation_key, nonce);
+    let mut buffer = pad(plaintext.as_bytes())?;
+    ChaCha20::new((&keys.chacha_key).into(), (&keys.chacha_nonce).into())
+        .apply_keystream(&mut buffer);
+    let mac = hmac_sha256(&keys.hmac_key, &[nonce, &buffer]);
+
+    let mut payload = Vec::with_capacity(1 + NONCE_LEN + buffer.len() + MAC_LEN);
+    payload.push(NIP44_VERSION);
+    payload.extend_from_slice(nonce);
+    payload.extend_from_slice(&buffer);
+    payload.extend_from_slice(&mac);
+    Ok(STANDARD.encode(payload))
+}
+
+/// Warning: Security-sensitive method!
+/// Decrypt a NIP-44 (v2) base64 payload with a conversation key. The MAC is checked before decryption.
+pub(crate) fn decrypt(conversation_key: &[u8; 32], payload: &str) -> Result<String, Error> {
+    if payload.starts_with('#') {
+        // reserved for future versions
+        return Err(Error::Nip44InvalidVersion);
+    }
+    let data = STANDARD
+        .decode(payload)
+        .map_err(|_| Error::Nip44InvalidPayload)?;
+    if data.len() < MIN_PAYLOAD_LEN {
+        return Err(Error::Nip44InvalidPayload);
+    }
+    if data[0] != NIP44_VERSION {
+        return Err(Error::Nip44InvalidVersion);
+    }
+    let mut nonce = [0u8; NONCE_LEN];
+    nonce.copy_from_slice(&data[1..1 + NONCE_LEN]);
+    let ciphertext = &data[1 + NONCE_LEN..data.len() - MAC_LEN];
+    let mac = &data[data.len() - MAC_LEN..];
+
+    let keys = MessageKeys::derive(conversation_key, &nonce);
+    let expected_mac = hmac_sha256(&keys.hmac_key, &[&nonce, ciphertext]);
+    if !constant_time_eq(&expected_mac, mac) {
+        return Err(Error::Nip44InvalidMac);
+    }
+    let mut buffer = ciphertext.to_vec();
+    ChaCha20::new((&keys.chacha_key).into(), (&keys.chacha_nonce).into())
+        .apply_keystream(&mut buffer);
+    let plaintext = unpad(&buffer);
+    buffer.zeroize();
+    String::from_utf8(plaintext?).map_err(|_| Error::Nip44InvalidPayload)
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use nostr::prelude::Keys;
+    use std::str::FromStr;
+
+    fn test_sk(n: u8) -> SecretKey {
+        let mut bytes = [0u8; 32];
+        bytes[31] = n;
+        SecretKey::from_slice(&bytes).unwrap()
+    }
+
+    #[test]
+    fn test_conversation_key() {
+        // test vector of NIP-44: secret keys 1 and 2
+        let sk1 = test_sk(1);
+        let sk2 = test_sk(2);
+        let pk1 = Keys::new(sk1).public_key();
+        let pk2 = Keys::new(sk2).public_key();
+        assert_eq!(
+            hex::encode(conversation_key(&sk1, &pk2)),
+            "c41c775356fd92eadc63ff5a0dc1da211b268cbea22316767095b2871ea1412d"
+        );
+        // symmetric
+        assert_eq!(conversation_key(&sk2, &pk1), conversation_key(&sk1, &pk2));
+    }
+
+    #[test]
+    fn test_encrypt_vector() {
+        // test vector of NIP-44
+        let key = conversation_key(&test_sk(1), &Keys::new(test_sk(2)).public_key());
+        let mut nonce = [0u8; NONCE_LEN];
+        nonce[31] = 1;
+        let payload = encrypt_with_nonce(&key, "a", &nonce).unwrap();
+        assert_eq!(
+            payload,
+            "AgAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAABee0G5VSK0/9YypIObAtDKfYEAjD35uVkHyB0F4DwrcNaCXlCWZKaArsGrY6M9wnuTMxWfp1RTN9Xga8no+kF5Vsb"
+        );
+        assert_eq!(decrypt(&key, &payload).unwrap(), "a");
+    }
+
+    #[test]
+    fn test_padded_len() {
+        for (len, expected) in [
+            (1, 32),
+            (32, 32),
+            (33, 64),
+            (37, 64),
+            (100, 128),
+            (256, 256),
+            (257, 320),
+            (1000, 1024),
+            (65535, 65536),
+        ] {
+            assert_eq!(padded_len(len), expected, "len {len}");
+        }
+    }
+
+    #[test]
+    fn test_encrypt_decrypt_roundtrip() {
+        let sk1 =
+            SecretKey::from_str("b2f3673ee3a659283e6599080e0ab0e669a3c2640914375a9b0b357faae08b17")
+                .unwrap();
+        let peer = Keys::generate();
+        let key = conversation_key(&sk1, &peer.public_key());
+        let key_other_side =
+            conversation_key(&peer.secret_key().unwrap(), &Keys::new(sk1).public_key());
+        for text in ["x", "Hello, NIP-44! ⚡", &"z".repeat(MAX_PLAINTEXT_LEN)] {
+            let payload = encrypt(&key, text).unwrap();
+            assert_eq!(decrypt(&key_other_side, &payload).unwrap(), text);
+        }
+        // random nonce, different payloads
+        assert_ne!(encrypt(&key, "x").unwrap(), encrypt(&key, "x").unwrap());
+    }
+
+    #[test]
+    fn test_encrypt_invalid_length() {
+        let key = [7u8; 32];
+        assert!(matches!(
+            encrypt(&key, ""),
+            Err(Error::Nip44InvalidLength(0))
+        ));
+        assert!(matches!(
+            encrypt(&key, &"z".repeat(MAX_PLAINTEXT_LEN + 1)),
+            Err(Error::Nip44InvalidLength(_))
+        ));
+    }
+
+    #[test]
+    fn test_decrypt_invalid() {
+        let key = [7u8; 32];
+        let payload = encrypt(&key, "secret message").unwrap();
+
+        // tampered ciphertext
+        let mut data = STANDARD.decode(&payload).unwrap();
+        data[40] ^= 1;
+        assert!(matches!(
+            decrypt(&key, &STANDARD.encode(&data)),
+            Err(Error::Nip44InvalidMac)
+        ));
+        // wrong key
+        assert!(matches!(
+            decrypt(&[8u8; 32], &payload),
+            Err(Error::Nip44InvalidMac)
+        ));
+        // wrong version
+        let mut data = STANDARD.decode(&payload).unwrap();
+        data[0] = 1;
+        assert!(matches!(
+            decrypt(&key, &STANDARD.encode(&data)),
+            Err(Error::Nip44InvalidVersion)
+        ));
+        assert!(matches!(
+            decrypt(&key, "#future"),
+            Err(Error::Nip44InvalidVersion)
+        ));
+        // not base64, too short
+        assert!(matches!(
+            decrypt(&key, "not base64!"),
+            Err(Error::Nip44InvalidPayload)
+        ));
+        assert!(matches!(
+            decrypt(&key, &STANDARD.encode([2u8; 40])),
+            Err(Error::Nip44InvalidPayload)
+        ));
+    }
+}
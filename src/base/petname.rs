@@ -0,0 +1,115 @@
+/// Adjectives for petnames
+const ADJECTIVES: [&str; 64] = [
+    "amber",
+    "ancient",
+    "autumn",
+    "bold",
+    "brave",
+    "bright",
+    "calm",
+    "clever",
+    "cosmic",
+    "crimson",
+    "curious",
+    "dancing",
+    "daring",
+    "dusty",
+    "eager",
+    "electric",
+    "fancy",
+    "fearless",
+    "fierce",
+    "gentle",
+    "giant",
+    "golden",
+    "happy",
+    "hidden",
+    "humble",
+    "icy",
+    "jolly",
+    "kind",
+    "lively",
+    "lucky",
+    "lunar",
+    "mellow",
+    "misty",
+    "noble",
+    "patient",
+    "polar",
+    "proud",
+    "quiet",
+    "quick",
+    "rapid",
+    "rustic",
+    "silent",
+    "silver",
+    "sleepy",
+    "smooth",
+    "solar",
+    "spicy",
+    "steady",
+    "stormy",
+    "sunny",
+    "swift",
+    "tidy",
+    "tiny",
+    "velvet",
+    "vivid",
+    "wandering",
+    "warm",
+    "wild",
+    "wise",
+    "witty",
+    "woolly",
+    "young",
+    "zealous",
+    "zesty",
+];
+
+/// Nouns for petnames
+const NOUNS: [&str; 64] = [
+    "badger", "bear", "beaver", "bison", "camel", "cat", "cheetah", "condor", "coyote", "crane",
+    "crow", "deer", "dolphin", "dove", "eagle", "falcon", "ferret", "finch", "fox", "gecko",
+    "giraffe", "goat", "heron", "horse", "ibis", "jaguar", "koala", "lemur", "leopard", "lion",
+    "llama", "lynx", "marmot", "mole", "moose", "newt", "otter", "owl", "panda", "panther",
+    "parrot", "pelican", "penguin", "puffin", "rabbit", "raven", "robin", "salmon", "seal",
+    "shark", "sparrow", "squirrel", "swan", "tiger", "toucan", "turtle", "walrus", "weasel",
+    "whale", "wolf", "wombat", "yak", "zebra", "hawk",
+];
+
+/// FNV-1a 64-bit hash, used for its simplicity and stability
+fn fnv1a_64(data: &[u8]) -> u64 {
+    let mut hash: u64 = 0xcbf29ce484222325;
+    for b in data {
+        hash ^= u64::from(*b);
+        hash = hash.wrapping_mul(0x100000001b3);
+    }
+    hash
+}
+
+/// Derive a human-friendly, two-word name (adjective + noun) from some data (e.g. a public key).
+/// Deterministic, but not unique; purely cosmetic.
+pub(crate) fn petname(data: &[u8]) -> String {
+    let hash = fnv1a_64(data);
+    let adjective = ADJECTIVES[(hash % ADJECTIVES.len() as u64) as usize];
+    let noun = NOUNS[((hash >> 32) % NOUNS.len() as u64) as usize];
+    format!("{adjective} {noun}")
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn test_fnv1a_64() {
+        assert_eq!(fnv1a_64(b""), 0xcbf29ce484222325);
+        assert_eq!(fnv1a_64(b"a"), 0xaf63dc4c8601ec8c);
+    }
+
+    #[test]
+    fn test_petname() {
+        assert_eq!(petname(b"abc"), "dancing jaguar");
+        assert_eq!(petname(b"abc"), petname(b"abc"));
+        assert_ne!(petname(b"abc"), petname(b"abd"));
+    }
+}
@@ -1,6 +1,10 @@
 use crate::base::error::Error;
+
+use nostr::prelude::Event;
+
 use std::fs;
-use std::path::PathBuf;
+use std::io::Write;
+use std::path::{Path, PathBuf};
 
 pub(crate) struct Storage {}
 
@@ -10,8 +14,22 @@ const LOCAL_STORAGE_FOLDER: &str = "keystr";
 const PUBLIC_KEY_FILENAME: &str = "npub";
 /// Encrypted secret key storage file name, relative to data folder.
 const ENCRYPTED_SECRET_KEY_FILENAME: &str = ".ncrypt";
+/// Key note storage file name, relative to data folder.
+const NOTE_FILENAME: &str = "note";
+/// Key label storage file name, relative to data folder.
+const LABEL_FILENAME: &str = "label";
 /// Public key storage file name, relative to data folder.
 const SETTINGS_FILENAME: &str = "settings.json";
+/// Relay usage statistics file name, relative to data folder.
+const RELAY_STATS_FILENAME: &str = "relay_stats.json";
+/// Encrypted bundle of all accounts file name, relative to data folder.
+const ACCOUNTS_BUNDLE_FILENAME: &str = "accounts.bundle";
+/// Encrypted archive of all accounts file name, relative to data folder.
+const ACCOUNTS_ARCHIVE_FILENAME: &str = "accounts.archive";
+/// Plaintext secret key export file name, relative to data folder (readable only by the owner).
+const SECRET_KEY_EXPORT_FILENAME: &str = "nsec.txt";
+/// Folder of saved (signed) events, relative to data folder.
+const EVENTS_FOLDER: &str = "events";
 
 impl Storage {
     pub fn public_key_file() -> PathBuf {
@@ -22,10 +40,38 @@ impl Storage {
         Self::full_file_path(ENCRYPTED_SECRET_KEY_FILENAME)
     }
 
+    pub fn note_file() -> PathBuf {
+        Self::full_file_path(NOTE_FILENAME)
+    }
+
+    pub fn label_file() -> PathBuf {
+        Self::full_file_path(LABEL_FILENAME)
+    }
+
     pub fn settings_file() -> PathBuf {
         Self::full_file_path(SETTINGS_FILENAME)
     }
 
+    pub fn relay_stats_file() -> PathBuf {
+        Self::full_file_path(RELAY_STATS_FILENAME)
+    }
+
+    pub fn accounts_bundle_file() -> PathBuf {
+        Self::full_file_path(ACCOUNTS_BUNDLE_FILENAME)
+    }
+
+    pub fn accounts_archive_file() -> PathBuf {
+        Self::full_file_path(ACCOUNTS_ARCHIVE_FILENAME)
+    }
+
+    pub fn secret_key_export_file() -> PathBuf {
+        Self::full_file_path(SECRET_KEY_EXPORT_FILENAME)
+    }
+
+    pub fn events_folder() -> PathBuf {
+        Self::full_file_path(EVENTS_FOLDER)
+    }
+
     pub fn check_create_folder() -> Result<(), Error> {
         let p = Self::get_storage_folder();
         if p.is_dir() {
@@ -35,6 +81,26 @@ impl Storage {
         Ok(())
     }
 
+    /// Write a (signed) event in JSON to a file in the given folder, named `<event_id>.json`.
+    /// An existing file is overwritten only if `overwrite` is set, otherwise it is an error.
+    /// Returns the path of the file.
+    pub fn write_event_to_file(
+        event: &Event,
+        dir: &Path,
+        overwrite: bool,
+    ) -> Result<PathBuf, Error> {
+        let mut p = dir.to_path_buf();
+        p.push(format!("{}.json", event.id.to_hex()));
+        let mut file = fs::OpenOptions::new()
+            .write(true)
+            .create(true)
+            .truncate(true)
+            .create_new(!overwrite)
+            .open(p.as_path())?;
+        file.write_all(event.as_json().as_bytes())?;
+        Ok(p)
+    }
+
     fn get_storage_folder() -> PathBuf {
         let mut p = dirs::data_local_dir().unwrap_or(PathBuf::from("."));
         p.push(LOCAL_STORAGE_FOLDER);
@@ -47,3 +113,38 @@ impl Storage {
         p
     }
 }
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use nostr::prelude::{EventBuilder, Keys};
+
+    #[test]
+    fn test_write_event_to_file() {
+        let keys = Keys::generate();
+        let event = EventBuilder::new_text_note("Hello", &[])
+            .to_event(&keys)
+            .unwrap();
+        let mut dir = std::env::temp_dir();
+        dir.push(format!("keystr-test-{}", event.id.to_hex()));
+        fs::create_dir_all(&dir).unwrap();
+
+        let path = Storage::write_event_to_file(&event, &dir, false).unwrap();
+        assert_eq!(
+            path.file_name().unwrap().to_str().unwrap(),
+            format!("{}.json", event.id.to_hex())
+        );
+        let read_event = Event::from_json(fs::read_to_string(&path).unwrap()).unwrap();
+        assert_eq!(read_event.id, event.id);
+
+        // not overwritten without flag
+        assert!(Storage::write_event_to_file(&event, &dir, false).is_err());
+        // overwritten with flag
+        assert_eq!(
+            Storage::write_event_to_file(&event, &dir, true).unwrap(),
+            path
+        );
+
+        let _ = fs::remove_dir_all(&dir);
+    }
+}
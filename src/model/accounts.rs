@@ -0,0 +1,816 @@
+use crate::base::encrypt::Encrypt;
+use crate::base::error::Error;
+use crate::base::key_utils::npub_similarity;
+use crate::base::storage::Storage;
+use crate::model::keystore::Keystore;
+use crate::model::relays::parse_relay_urls;
+use crate::model::status_messages::StatusMessages;
+
+use bech32::{FromBase32, ToBase32};
+use nostr::prelude::{FromBech32, Keys, SecretKey, ToBech32, XOnlyPublicKey};
+use serde::{Deserialize, Serialize};
+use zeroize::Zeroize;
+
+use std::fs;
+use std::path::Path;
+
+/// Current version of the account bundle format
+const BUNDLE_VERSION: u32 = 1;
+/// Bech32 prefix for encrypted secret keys (NIP-49 layout)
+const NCRYPTSEC_HRP: &str = "ncryptsec";
+/// Different public keys with at most this many differing bits are suspiciously similar
+/// (unrelated keys differ in about 128 bits)
+const LOOKALIKE_MAX_DISTANCE: usize = 64;
+/// An account (identity): keys (with label), its relays, and optional color tag
+pub(crate) struct Account {
+    pub keystore: Keystore,
+    pub relays: Vec<String>,
+    /// Color tag (RGB), for the account switcher
+    color: Option<[u8; 3]>,
+}
+
+/// Manages multiple accounts
+pub(crate) struct AccountManager {
+    accounts: Vec<Account>,
+    /// Scrypt cost (log2 of N) for encrypting exported bundles and archives
+    scrypt_log_n: u8,
+    /// Input for the password of the bundle and archive files
+    pub password_input: String,
+}
+
+/// Outer, unencrypted part of an account bundle file
+#[derive(Serialize, Deserialize)]
+struct BundleFile {
+    version: u32,
+    /// Encrypted bundle content, hex
+    data: String,
+}
+
+/// An account in the (decrypted) bundle
+#[derive(Serialize, Deserialize)]
+struct BundleAccount {
+    label: String,
+    npub: String,
+    /// Secret key, if available (unlocked)
+    nsec: Option<String>,
+    /// Encrypted secret key as is (with its own password), if locked
+    #[serde(default)]
+    ncryptsec: Option<String>,
+    relays: Vec<String>,
+    #[serde(default)]
+    note: String,
+    #[serde(default)]
+    color: Option<[u8; 3]>,
+}
+
+/// Zeroize the secret key on drop, both after export and after import
+impl Drop for BundleAccount {
+    fn drop(&mut self) {
+        if let Some(nsec) = self.nsec.as_mut() {
+            nsec.zeroize();
+        }
+    }
+}
+
+/// An account in the (decrypted) archive: secret key is encrypted individually as well
+#[derive(Serialize, Deserialize)]
+struct ArchiveAccount {
+    label: String,
+    note: String,
+    npub: String,
+    /// Encrypted secret key, if set: with the archive password if it was unlocked,
+    /// or as is (with its own password) if it was locked
+    ncryptsec: Option<String>,
+    relays: Vec<String>,
+    color: Option<[u8; 3]>,
+}
+
+/// An account in the public (unencrypted) bundle, without secret key and note
+#[derive(Serialize)]
+struct PublicBundleAccount {
+    label: String,
+    npub: String,
+    relays: Vec<String>,
+    color: Option<[u8; 3]>,
+}
+
+/// Warning: Security-sensitive method!
+/// Encrypt data (JSON) with the password, and write it to a file (readable only by the owner),
+/// together with the format version
+fn write_encrypted_file(path: &Path, json: &str, password: &str, log_n: u8) -> Result<(), Error> {
+    let encrypted = Encrypt::encrypt_data(json.as_bytes(), password, log_n)?;
+    let file = BundleFile {
+        version: BUNDLE_VERSION,
+        data: hex::encode(encrypted),
+    };
+    // create empty file
+    fs::write(path, "")?;
+    #[cfg(unix)]
+    {
+        use std::os::unix::fs::PermissionsExt;
+        fs::set_permissions(path, fs::Permissions::from_mode(0o600))?;
+    }
+    fs::write(path, serde_json::to_string(&file)?)?;
+    Ok(())
+}
+
+/// Warning: Security-sensitive method!
+/// Read and decrypt data written by `write_encrypted_file`
+fn read_encrypted_file(path: &Path, password: &str) -> Result<Vec<u8>, Error> {
+    let file = serde_json::from_str::<BundleFile>(&fs::read_to_string(path)?)
+        .map_err(|_e| Error::BundleInvalid)?;
+    if file.version != BUNDLE_VERSION {
+        return Err(Error::BundleUnsupportedVersion(file.version));
+    }
+    let encrypted = hex::decode(&file.data).map_err(|_e| Error::BundleInvalid)?;
+    Encrypt::decrypt_data(&encrypted, password)
+}
+
+/// Encode an encrypted secret key as ncryptsec (bech32)
+fn encode_ncryptsec(encrypted: &[u8]) -> Result<String, Error> {
+    Ok(bech32::encode(
+        NCRYPTSEC_HRP,
+        encrypted.to_base32(),
+        bech32::Variant::Bech32,
+    )?)
+}
+
+/// Decode an ncryptsec (bech32) into the encrypted secret key
+fn decode_ncryptsec(ncryptsec: &str) -> Result<Vec<u8>, Error> {
+    let (hrp, data, _variant) = bech32::decode(ncryptsec)?;
+    if hrp != NCRYPTSEC_HRP {
+        return Err(Error::BundleInvalid);
+    }
+    Ok(Vec::<u8>::from_base32(&data)?)
+}
+
+impl Account {
+    pub fn new(keystore: Keystore, relays: Vec<String>) -> Self {
+        Self {
+            keystore,
+            relays,
+            color: None,
+        }
+    }
+
+    pub fn label(&self) -> &str {
+        &self.keystore.label
+    }
+
+    /// Warning: Security-sensitive method!
+    /// New account with a copy of the keys (and label, note) of the keystore;
+    /// a locked secret key is copied locked
+    pub fn from_keystore(keystore: &Keystore, relays: Vec<String>) -> Result<Self, Error> {
+        let keys = keystore.get_keys()?;
+        let mut copy = match (keys.secret_key(), keystore.get_encrypted_secret_key()) {
+            (Err(_), Some(encrypted)) => {
+                Keystore::from_locked(keys.public_key(), encrypted.clone())
+            }
+            _ => Keystore::from_keys(keys.clone()),
+        };
+        copy.label = keystore.label.clone();
+        copy.set_note(keystore.note());
+        Ok(Self::new(copy, relays))
+    }
+
+    /// Warning: Security-sensitive method!
+    /// Unlocked secret key goes in as nsec, the encrypted key of a locked account as ncryptsec
+    fn to_bundle(&self) -> Result<BundleAccount, Error> {
+        let keys = self.keystore.get_keys()?;
+        let (nsec, ncryptsec) = match keys.secret_key() {
+            Ok(sk) => (Some(sk.to_bech32()?), None),
+            Err(_) => match self.keystore.get_encrypted_secret_key() {
+                None => (None, None),
+                Some(e) => (None, Some(encode_ncryptsec(e)?)),
+            },
+        };
+        Ok(BundleAccount {
+            label: self.keystore.label.clone(),
+            npub: keys.public_key().to_bech32()?,
+            nsec,
+            ncryptsec,
+            relays: self.relays.clone(),
+            note: self.keystore.note().to_string(),
+            color: self.color,
+        })
+    }
+
+    /// Warning: Security-sensitive method!
+    fn to_archive(&self, password: &str, log_n: u8) -> Result<ArchiveAccount, Error> {
+        let keys = self.keystore.get_keys()?;
+        let encrypted = match keys.secret_key() {
+            Ok(sk) => Some(Encrypt::encrypt_key(&sk, password, log_n)?),
+            Err(_) => self.keystore.get_encrypted_secret_key().cloned(),
+        };
+        let ncryptsec = match encrypted {
+            None => None,
+            Some(e) => Some(encode_ncryptsec(&e)?),
+        };
+        Ok(ArchiveAccount {
+            label: self.keystore.label.clone(),
+            note: self.keystore.note().to_string(),
+            npub: keys.public_key().to_bech32()?,
+            ncryptsec,
+            relays: self.relays.clone(),
+            color: self.color,
+        })
+    }
+
+    /// Warning: Security-sensitive method!
+    /// Secret keys encrypted with the archive password are unlocked, others remain locked
+    fn from_archive(aa: &ArchiveAccount, password: &str) -> Result<Self, Error> {
+        let public_key = XOnlyPublicKey::from_bech32(&aa.npub)?;
+        let mut keystore = match &aa.ncryptsec {
+            None => Keystore::from_keys(Keys::from_public_key(public_key)),
+            Some(ncryptsec) => {
+                let mut keystore = Keystore::from_locked(public_key, decode_ncryptsec(ncryptsec)?);
+                // with another password it is not unlocked, but a key of another identity is an error
+                if let Err(Error::KeyIdentityMismatch) = keystore.decrypt_secret_key(password) {
+                    return Err(Error::KeyIdentityMismatch);
+                }
+                keystore
+            }
+        };
+        keystore.label = aa.label.clone();
+        keystore.set_note(&aa.note);
+        let mut account = Self::new(keystore, aa.relays.clone());
+        account.color = aa.color;
+        Ok(account)
+    }
+
+    fn to_public_bundle(&self) -> Result<PublicBundleAccount, Error> {
+        Ok(PublicBundleAccount {
+            label: self.keystore.label.clone(),
+            npub: self.keystore.get_public_key()?.to_bech32()?,
+            relays: self.relays.clone(),
+            color: self.color,
+        })
+    }
+
+    /// Warning: Security-sensitive method!
+    /// Accounts exported locked are imported locked, to be unlocked with their own password
+    fn from_bundle(ba: &BundleAccount) -> Result<Self, Error> {
+        let public_key = XOnlyPublicKey::from_bech32(&ba.npub)?;
+        let mut keystore = match (&ba.nsec, &ba.ncryptsec) {
+            (Some(nsec), _) => {
+                let keys = Keys::new(SecretKey::from_bech32(nsec)?);
+                if keys.public_key() != public_key {
+                    return Err(Error::KeyIdentityMismatch);
+                }
+                Keystore::from_keys(keys)
+            }
+            (None, Some(ncryptsec)) => {
+                Keystore::from_locked(public_key, decode_ncryptsec(ncryptsec)?)
+            }
+            (None, None) => Keystore::from_keys(Keys::from_public_key(public_key)),
+        };
+        keystore.label = ba.label.clone();
+        keystore.set_note(&ba.note);
+        let mut account = Self::new(keystore, ba.relays.clone());
+        account.color = ba.color;
+        Ok(account)
+    }
+}
+
+impl AccountManager {
+    pub fn new() -> Self {
+        Self {
+            accounts: Vec::new(),
+            scrypt_log_n: Encrypt::default_log2_rounds(),
+            password_input: String::new(),
+        }
+    }
+
+    /// Set the scrypt cost (log2 of N) for encrypting exports, accepted range is 1-22
+    pub fn set_scrypt_log_n(&mut self, log_n: u8) -> Result<(), Error> {
+        Encrypt::check_log2_rounds(log_n)?;
+        self.scrypt_log_n = log_n;
+        Ok(())
+    }
+
+    pub fn add(&mut self, account: Account) {
+        self.accounts.push(account);
+    }
+
+    pub fn accounts(&self) -> &Vec<Account> {
+        &self.accounts
+    }
+
+    /// Warning: Security-sensitive method!
+    /// Export all accounts (keys, labels, relays) into a single, password-encrypted bundle file.
+    /// Secret keys are included for unlocked accounts, locked accounts are exported with
+    /// their encrypted secret key (with their own password).
+    pub fn export_bundle(&self, path: &Path, password: &str) -> Result<(), Error> {
+        let bundle_accounts = self
+            .accounts
+            .iter()
+            .map(|a| a.to_bundle())
+            .collect::<Result<Vec<BundleAccount>, Error>>()?;
+        let mut json = serde_json::to_string(&bundle_accounts)?;
+        let res = write_encrypted_file(path, &json, password, self.scrypt_log_n);
+        json.zeroize();
+        res
+    }
+
+    /// Warning: Security-sensitive method!
+    /// Export all accounts (keys, labels, notes, relays) into a single encrypted archive file,
+    /// for whole-wallet backup. Secret keys are additionally encrypted individually (NIP-49 layout):
+    /// unlocked ones with the archive password, locked ones are kept with their own password.
+    pub fn export_archive(&self, path: &Path, password: &str) -> Result<(), Error> {
+        let archive_accounts = self
+            .accounts
+            .iter()
+            .map(|a| a.to_archive(password, self.scrypt_log_n))
+            .collect::<Result<Vec<ArchiveAccount>, Error>>()?;
+        write_encrypted_file(
+            path,
+            &serde_json::to_string(&archive_accounts)?,
+            password,
+            self.scrypt_log_n,
+        )
+    }
+
+    /// Warning: Security-sensitive method!
+    /// Import all accounts from an archive file, appending them to the existing ones.
+    /// Either all accounts are imported, or none (in case of wrong password or corrupt archive).
+    pub fn import_archive(&mut self, path: &Path, password: &str) -> Result<usize, Error> {
+        let json = read_encrypted_file(path, password)?;
+        let new_accounts = serde_json::from_slice::<Vec<ArchiveAccount>>(&json)
+            .map_err(|_e| Error::BundleInvalid)?
+            .iter()
+            .map(|aa| Account::from_archive(aa, password))
+            .collect::<Result<Vec<Account>, Error>>()?;
+        let count = new_accounts.len();
+        self.accounts.extend(new_accounts);
+        Ok(count)
+    }
+
+    /// Labels which are used by more than one account (labels need not be unique)
+    pub fn duplicate_labels(&self) -> Vec<String> {
+        let mut duplicates: Vec<String> = Vec::new();
+        for (i, a) in self.accounts.iter().enumerate() {
+            let label = a.label();
+            if !duplicates.iter().any(|d| d == label)
+                && self.accounts[i + 1..].iter().any(|b| b.label() == label)
+            {
+                duplicates.push(label.to_string());
+            }
+        }
+        duplicates
+    }
+
+    /// Pairs of labels of accounts with different, but suspiciously similar public keys
+    /// (see `npub_similarity`), e.g. a lookalike of a trusted identity
+    pub fn lookalike_accounts(&self) -> Vec<(String, String)> {
+        let mut lookalikes = Vec::new();
+        for (i, a) in self.accounts.iter().enumerate() {
+            for b in &self.accounts[i + 1..] {
+                if let Ok(distance) =
+                    npub_similarity(&a.keystore.get_npub(), &b.keystore.get_npub())
+                {
+                    if distance > 0 && distance <= LOOKALIKE_MAX_DISTANCE {
+                        lookalikes.push((a.label().to_string(), b.label().to_string()));
+                    }
+                }
+            }
+        }
+        lookalikes
+    }
+
+    /// Public bundle of all accounts in JSON: labels, public keys and relays.
+    /// Secret keys and notes are not included.
+    pub fn public_bundle(&self) -> Result<String, Error> {
+        let bundle_accounts = self
+            .accounts
+            .iter()
+            .map(|a| a.to_public_bundle())
+            .collect::<Result<Vec<PublicBundleAccount>, Error>>()?;
+        Ok(serde_json::to_string(&bundle_accounts)?)
+    }
+
+    /// Warning: Security-sensitive method!
+    /// Import all accounts from a bundle file, appending them to the existing ones.
+    /// Either all accounts are imported, or none (in case of wrong password or corrupt bundle).
+    pub fn import_bundle(&mut self, path: &Path, password: &str) -> Result<usize, Error> {
+        let mut json = read_encrypted_file(path, password)?;
+        let bundle_accounts = serde_json::from_slice::<Vec<BundleAccount>>(&json);
+        json.zeroize();
+        // the secret keys of the bundle accounts are zeroized on drop
+        let new_accounts = bundle_accounts
+            .map_err(|_e| Error::BundleInvalid)?
+            .iter()
+            .map(Account::from_bundle)
+            .collect::<Result<Vec<Account>, Error>>()?;
+        let count = new_accounts.len();
+        self.accounts.extend(new_accounts);
+        Ok(count)
+    }
+
+    /// Warning: Security-sensitive method!
+    /// Emergency wipe of all accounts (see `Keystore::panic_wipe`), and of the password input
+    pub fn panic_wipe(&mut self) {
+        for account in self.accounts.iter_mut() {
+            let _res = account.keystore.panic_wipe(None);
+        }
+        self.accounts.clear();
+        self.password_input.zeroize();
+    }
+
+    /// Warning: Security-sensitive method!
+    /// Action to add the current keys as an account, with the relays of the relay input
+    pub fn add_action(&mut self, keystore: &Keystore, status: &mut StatusMessages) {
+        match Account::from_keystore(keystore, parse_relay_urls(&keystore.relays_input)) {
+            Err(e) => status.set_error_err(&e),
+            Ok(account) => {
+                status.set(&format!("Account '{}' added", account.label()));
+                self.add(account);
+            }
+        }
+    }
+
+    /// Warning: Security-sensitive method!
+    /// Action to export all accounts to the bundle file, encrypted with the password input
+    pub fn export_bundle_action(&mut self, status: &mut StatusMessages) {
+        let res = self.with_password(|m, password| {
+            m.export_bundle(&Storage::accounts_bundle_file(), password)
+        });
+        match res {
+            Err(e) => status.set_error_err(&e),
+            Ok(_) => status.set(&format!(
+                "{} accounts exported to bundle",
+                self.accounts.len()
+            )),
+        }
+    }
+
+    /// Warning: Security-sensitive method!
+    /// Action to import accounts from the bundle file, decrypted with the password input
+    pub fn import_bundle_action(&mut self, status: &mut StatusMessages) {
+        let res = self.with_password(|m, password| {
+            m.import_bundle(&Storage::accounts_bundle_file(), password)
+        });
+        match res {
+            Err(e) => status.set_error_err(&e),
+            Ok(count) => status.set(&format!("{count} accounts imported from bundle")),
+        }
+    }
+
+    /// Warning: Security-sensitive method!
+    /// Action to export all accounts to the archive file, encrypted with the password input
+    pub fn export_archive_action(&mut self, status: &mut StatusMessages) {
+        let res = self.with_password(|m, password| {
+            m.export_archive(&Storage::accounts_archive_file(), password)
+        });
+        match res {
+            Err(e) => status.set_error_err(&e),
+            Ok(_) => status.set(&format!(
+                "{} accounts exported to archive",
+                self.accounts.len()
+            )),
+        }
+    }
+
+    /// Warning: Security-sensitive method!
+    /// Action to import accounts from the archive file, decrypted with the password input
+    pub fn import_archive_action(&mut self, status: &mut StatusMessages) {
+        let res = self.with_password(|m, password| {
+            m.import_archive(&Storage::accounts_archive_file(), password)
+        });
+        match res {
+            Err(e) => status.set_error_err(&e),
+            Ok(count) => status.set(&format!("{count} accounts imported from archive")),
+        }
+    }
+
+    /// Warning: Security-sensitive method!
+    /// Run a file operation with the password input (must not be empty), and clear the input
+    fn with_password<R>(
+        &mut self,
+        f: impl FnOnce(&mut Self, &str) -> Result<R, Error>,
+    ) -> Result<R, Error> {
+        let mut password = std::mem::take(&mut self.password_input);
+        let res = if password.is_empty() {
+            Err(Error::KeyEncryptionPasswordMissing)
+        } else {
+            Storage::check_create_folder().and_then(|_| f(self, &password))
+        };
+        password.zeroize();
+        res
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use std::path::PathBuf;
+
+    fn temp_file(name: &str) -> PathBuf {
+        let mut p = std::env::temp_dir();
+        p.push(format!(
+            "keystr-test-{}-{}",
+            name,
+            Keys::generate().public_key()
+        ));
+        p
+    }
+
+    fn test_manager() -> AccountManager {
+        let mut m = AccountManager::new();
+        let mut k1 = Keystore::new();
+        let _res = k1
+            .import_secret_key(
+                "nsec1ktekw0hr5evjs0n9nyyquz4sue568snypy2rwk5mpv6hl2hq3vtsk0kpae",
+                true,
+            )
+            .unwrap();
+        k1.label = "main".to_string();
+        k1.set_note("old key, rotated 2024");
+        let mut a1 = Account::new(k1, vec!["wss://relay.damus.io".to_string()]);
+        a1.color = Some([255, 128, 0]);
+        m.add(a1);
+        let mut k2 = Keystore::new();
+        let _res = k2
+            .import_public_key("npub1tczgvlwvcdxp5f4mp8rqehramx6dqemq6v8egf3qdfzazn8cs7dqlhmwux")
+            .unwrap();
+        k2.label = "watched".to_string();
+        m.add(Account::new(k2, Vec::new()));
+        m
+    }
+
+    #[test]
+    fn test_export_import_bundle() {
+        let path = temp_file("bundle");
+        let _res = test_manager().export_bundle(&path, "password").unwrap();
+
+        let mut m = AccountManager::new();
+        assert_eq!(m.import_bundle(&path, "password").unwrap(), 2);
+        let _ = fs::remove_file(&path);
+
+        let accounts = m.accounts();
+        assert_eq!(accounts.len(), 2);
+        assert_eq!(accounts[0].keystore.label, "main");
+        assert_eq!(accounts[0].keystore.note(), "old key, rotated 2024");
+        assert_eq!(
+            accounts[0].keystore.get_npub(),
+            "npub1rfze4zn25ezp6jqt5ejlhrajrfx0az72ed7cwvq0spr22k9rlnjq93lmd4"
+        );
+        assert!(accounts[0].keystore.is_secret_key_set());
+        assert_eq!(accounts[0].relays, vec!["wss://relay.damus.io"]);
+        assert_eq!(accounts[0].color, Some([255, 128, 0]));
+        assert_eq!(accounts[1].keystore.label, "watched");
+        assert_eq!(accounts[1].keystore.is_secret_key_set(), false);
+        assert!(accounts[1].relays.is_empty());
+        assert_eq!(accounts[1].color, None);
+    }
+
+    #[test]
+    fn test_export_bundle_scrypt_log_n() {
+        let mut m = test_manager();
+        assert!(matches!(
+            m.set_scrypt_log_n(0),
+            Err(Error::KeyInvalidScryptParams(0))
+        ));
+        for log_n in [8, 18] {
+            let _res = m.set_scrypt_log_n(log_n).unwrap();
+            let path = temp_file("bundle-log-n");
+            let _res = m.export_bundle(&path, "password").unwrap();
+            let file =
+                serde_json::from_str::<BundleFile>(&fs::read_to_string(&path).unwrap()).unwrap();
+            assert_eq!(hex::decode(&file.data).unwrap()[1], log_n);
+            let mut m2 = AccountManager::new();
+            assert_eq!(m2.import_bundle(&path, "password").unwrap(), 2);
+            let _ = fs::remove_file(&path);
+        }
+    }
+
+    #[test]
+    fn test_from_keystore() {
+        let mut k = Keystore::new();
+        let _res = k
+            .import_secret_key(
+                "nsec1ktekw0hr5evjs0n9nyyquz4sue568snypy2rwk5mpv6hl2hq3vtsk0kpae",
+                true,
+            )
+            .unwrap();
+        k.label = "main".to_string();
+        k.set_note("old key");
+        k.relays_input = "wss://relay.damus.io, wss://nos.lol".to_string();
+        let mut m = AccountManager::new();
+        let mut status = StatusMessages::new();
+        m.add_action(&k, &mut status);
+        let accounts = m.accounts();
+        assert_eq!(accounts.len(), 1);
+        assert_eq!(accounts[0].label(), "main");
+        assert_eq!(accounts[0].keystore.note(), "old key");
+        assert_eq!(accounts[0].keystore.get_npub(), k.get_npub());
+        assert!(accounts[0].keystore.is_secret_key_set());
+        assert_eq!(
+            accounts[0].relays,
+            vec!["wss://relay.damus.io", "wss://nos.lol"]
+        );
+
+        // locked keys are copied locked
+        let keys = Keys::generate();
+        let encrypted = Encrypt::encrypt_key(
+            &keys.secret_key().unwrap(),
+            "other",
+            Encrypt::default_log2_rounds(),
+        )
+        .unwrap();
+        let a = Account::from_keystore(
+            &Keystore::from_locked(keys.public_key(), encrypted),
+            Vec::new(),
+        )
+        .unwrap();
+        assert!(a.keystore.is_locked());
+
+        // no keys
+        m.add_action(&Keystore::new(), &mut status);
+        assert_eq!(m.accounts().len(), 1);
+    }
+
+    #[test]
+    fn test_duplicate_labels() {
+        let mut m = AccountManager::new();
+        for label in ["a", "b", "a", "c", "a", "b"] {
+            let mut a = Account::new(Keystore::new(), Vec::new());
+            a.keystore.label = label.to_string();
+            m.add(a);
+        }
+        assert_eq!(m.duplicate_labels(), vec!["a", "b"]);
+        assert!(test_manager().duplicate_labels().is_empty());
+    }
+
+    #[test]
+    fn test_lookalike_accounts() {
+        let mut m = test_manager();
+        assert!(m.lookalike_accounts().is_empty());
+
+        // one bit differs from the key of 'main'
+        let mut k = Keystore::new();
+        let _res = k
+            .import_public_key("1a459a8a6aa6441d480ba665fb8fb21a4cfe8bcacb7d87300f8046a558a3fce5")
+            .unwrap();
+        k.label = "lookalike".to_string();
+        m.add(Account::new(k, Vec::new()));
+        // same key is not a lookalike, and keys not set are skipped
+        let mut k = Keystore::new();
+        let _res = k
+            .import_public_key("npub1tczgvlwvcdxp5f4mp8rqehramx6dqemq6v8egf3qdfzazn8cs7dqlhmwux")
+            .unwrap();
+        m.add(Account::new(k, Vec::new()));
+        m.add(Account::new(Keystore::new(), Vec::new()));
+        assert_eq!(
+            m.lookalike_accounts(),
+            vec![("main".to_string(), "lookalike".to_string())]
+        );
+    }
+
+    #[test]
+    fn test_public_bundle() {
+        let json = test_manager().public_bundle().unwrap();
+        assert!(json.contains("npub1rfze4zn25ezp6jqt5ejlhrajrfx0az72ed7cwvq0spr22k9rlnjq93lmd4"));
+        assert!(json.contains("\"main\""));
+        assert!(!json.contains("nsec"));
+        assert!(!json.contains("note"));
+        assert!(!json.contains("rotated"));
+    }
+
+    fn archive_test_manager() -> AccountManager {
+        let mut m = test_manager();
+        // locked account, encrypted with a different password
+        let keys = Keys::generate();
+        let encrypted = Encrypt::encrypt_key(
+            &keys.secret_key().unwrap(),
+            "other",
+            Encrypt::default_log2_rounds(),
+        )
+        .unwrap();
+        let mut k3 = Keystore::from_locked(keys.public_key(), encrypted);
+        k3.label = "locked".to_string();
+        m.add(Account::new(k3, Vec::new()));
+        m
+    }
+
+    #[test]
+    fn test_export_import_archive() {
+        let path = temp_file("archive");
+        let _res = archive_test_manager()
+            .export_archive(&path, "password")
+            .unwrap();
+        let content = fs::read_to_string(&path).unwrap();
+        assert!(!content.contains("nsec"));
+        assert!(!content.contains("rotated"));
+
+        let mut m = AccountManager::new();
+        assert_eq!(m.import_archive(&path, "password").unwrap(), 3);
+        let _ = fs::remove_file(&path);
+
+        let accounts = m.accounts();
+        assert_eq!(accounts[0].label(), "main");
+        assert_eq!(accounts[0].keystore.note(), "old key, rotated 2024");
+        assert_eq!(
+            accounts[0].keystore.get_npub(),
+            "npub1rfze4zn25ezp6jqt5ejlhrajrfx0az72ed7cwvq0spr22k9rlnjq93lmd4"
+        );
+        assert!(accounts[0].keystore.is_secret_key_set());
+        assert_eq!(accounts[0].relays, vec!["wss://relay.damus.io"]);
+        assert_eq!(accounts[0].color, Some([255, 128, 0]));
+        assert_eq!(accounts[1].label(), "watched");
+        assert!(!accounts[1].keystore.is_secret_key_set());
+        assert!(!accounts[1].keystore.is_encrypted_secret_key_set());
+        // still locked, can be unlocked with its own password
+        assert_eq!(accounts[2].label(), "locked");
+        assert!(accounts[2].keystore.is_locked());
+        let mut k3 = Keystore::from_locked(
+            accounts[2].keystore.get_public_key().unwrap(),
+            accounts[2]
+                .keystore
+                .get_encrypted_secret_key()
+                .unwrap()
+                .clone(),
+        );
+        let _res = k3.decrypt_secret_key("other").unwrap();
+    }
+
+    #[test]
+    fn test_export_import_bundle_locked() {
+        let path = temp_file("bundle-locked");
+        let _res = archive_test_manager()
+            .export_bundle(&path, "password")
+            .unwrap();
+
+        let mut m = AccountManager::new();
+        assert_eq!(m.import_bundle(&path, "password").unwrap(), 3);
+        let _ = fs::remove_file(&path);
+
+        let accounts = m.accounts();
+        assert!(accounts[0].keystore.is_secret_key_set());
+        assert!(!accounts[1].keystore.is_encrypted_secret_key_set());
+        // still locked, encrypted key kept, can be unlocked with its own password
+        assert_eq!(accounts[2].label(), "locked");
+        assert!(accounts[2].keystore.is_locked());
+        let mut k3 = Keystore::from_locked(
+            accounts[2].keystore.get_public_key().unwrap(),
+            accounts[2]
+                .keystore
+                .get_encrypted_secret_key()
+                .unwrap()
+                .clone(),
+        );
+        let _res = k3.decrypt_secret_key("other").unwrap();
+    }
+
+    #[test]
+    fn test_import_archive_wrong_password_or_corrupt() {
+        let path = temp_file("archive-pw");
+        let _res = archive_test_manager()
+            .export_archive(&path, "password")
+            .unwrap();
+
+        let mut m = test_manager();
+        assert!(m.import_archive(&path, "wrong").is_err());
+        assert_eq!(m.accounts().len(), 2);
+
+        let content = fs::read_to_string(&path).unwrap();
+        fs::write(&path, content.replace("\"data\":\"01", "\"data\":\"02")).unwrap();
+        assert!(m.import_archive(&path, "password").is_err());
+        fs::write(&path, &content[0..content.len() / 2]).unwrap();
+        assert!(m.import_archive(&path, "password").is_err());
+        let _ = fs::remove_file(&path);
+        assert_eq!(m.accounts().len(), 2);
+    }
+
+    #[test]
+    fn test_import_bundle_wrong_password() {
+        let path = temp_file("bundle-pw");
+        let _res = test_manager().export_bundle(&path, "password").unwrap();
+
+        let mut m = AccountManager::new();
+        assert!(m.import_bundle(&path, "wrong").is_err());
+        let _ = fs::remove_file(&path);
+        assert!(m.accounts().is_empty());
+    }
+
+    #[test]
+    fn test_import_bundle_corrupt() {
+        let path = temp_file("bundle-corrupt");
+        let _res = test_manager().export_bundle(&path, "password").unwrap();
+        let content = fs::read_to_string(&path).unwrap();
+        let mut m = AccountManager::new();
+
+        // truncated
+        fs::write(&path, &content[0..content.len() - 10]).unwrap();
+        assert!(m.import_bundle(&path, "password").is_err());
+
+        // future version
+        fs::write(&path, content.replace("\"version\":1", "\"version\":99")).unwrap();
+        assert!(matches!(
+            m.import_bundle(&path, "password"),
+            Err(Error::BundleUnsupportedVersion(99))
+        ));
+
+        let _ = fs::remove_file(&path);
+        assert!(m.accounts().is_empty());
+    }
+}
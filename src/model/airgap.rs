@@ -0,0 +1,329 @@
+use crate::base::error::Error;
+
+use base64::engine::general_purpose::URL_SAFE_NO_PAD;
+use base64::Engine;
+use nostr::prelude::{Event, EventId, UnsignedEvent};
+use qrcode::render::svg;
+use qrcode::QrCode;
+
+/// Prefix of an encoded signing request
+const SIGN_REQUEST_PREFIX: &str = "keystr-signreq:";
+/// Prefix of an encoded signing response
+const SIGN_RESPONSE_PREFIX: &str = "keystr-signresp:";
+/// Prefix of a frame of a multi-frame payload
+const FRAME_PREFIX: &str = "keystr-frame:";
+/// Minimum size of rendered QR codes, in pixels
+const QR_MIN_DIMENSION: u32 = 256;
+/// Quiet zone (margin) around rendered QR codes, in modules
+const QR_QUIET_ZONE: usize = 4;
+/// Data capacity of QR code versions 1-40 in byte mode, at the default error correction level (M)
+const QR_BYTE_CAPACITY: [usize; 40] = [
+    14, 26, 42, 62, 84, 106, 122, 152, 180, 213, 251, 287, 331, 362, 412, 450, 504, 560, 624, 666,
+    711, 779, 857, 911, 997, 1059, 1125, 1190, 1264, 1370, 1452, 1538, 1628, 1722, 1809, 1911,
+    1989, 2099, 2213, 2331,
+];
+/// Length of the fixed parts of a rendered SVG (XML header, background, path element), without the sizes
+const QR_SVG_FIXED_LEN: usize = 236;
+
+fn encode_blob(prefix: &str, json: &str) -> String {
+    format!("{}{}", prefix, URL_SAFE_NO_PAD.encode(json.as_bytes()))
+}
+
+fn decode_blob(prefix: &str, blob: &str) -> Result<String, Error> {
+    let data = blob
+        .trim()
+        .strip_prefix(prefix)
+        .ok_or(Error::AirgapInvalidBlob)?;
+    let bytes = URL_SAFE_NO_PAD
+        .decode(data)
+        .map_err(|_e| Error::AirgapInvalidBlob)?;
+    String::from_utf8(bytes).map_err(|_e| Error::AirgapInvalidBlob)
+}
+
+/// Check that the ID of the unsigned event matches its contents
+fn check_unsigned_event_id(unsigned: &UnsignedEvent) -> Result<(), Error> {
+    let id = EventId::new(
+        &unsigned.pubkey,
+        unsigned.created_at,
+        &unsigned.kind,
+        &unsigned.tags,
+        &unsigned.content,
+    );
+    if id != unsigned.id {
+        return Err(Error::EventIdMismatch);
+    }
+    Ok(())
+}
+
+/// Encode an unsigned event into a compact text blob, to be moved (via QR, USB, ...)
+/// to an offline (air-gapped) machine for signing
+pub(crate) fn sign_request_encode(unsigned: &UnsignedEvent) -> Result<String, Error> {
+    Ok(encode_blob(
+        SIGN_REQUEST_PREFIX,
+        &serde_json::to_string(unsigned)?,
+    ))
+}
+
+/// Decode a signing request blob, on the offline side. The event ID is checked against the contents.
+pub(crate) fn sign_request_decode(blob: &str) -> Result<UnsignedEvent, Error> {
+    let json = decode_blob(SIGN_REQUEST_PREFIX, blob)?;
+    let unsigned = serde_json::from_str::<UnsignedEvent>(&json)?;
+    check_unsigned_event_id(&unsigned)?;
+    Ok(unsigned)
+}
+
+/// Encode a signed event into a compact text blob, to be moved back from the offline machine
+pub(crate) fn sign_response_encode(event: &Event) -> Result<String, Error> {
+    Ok(encode_blob(SIGN_RESPONSE_PREFIX, &event.as_json()))
+}
+
+/// Decode a signing response blob, on the online side. The signature is verified.
+pub(crate) fn sign_response_decode(blob: &str) -> Result<Event, Error> {
+    let json = decode_blob(SIGN_RESPONSE_PREFIX, blob)?;
+    let event = serde_json::from_str::<Event>(&json)?;
+    event.verify()?;
+    Ok(event)
+}
+
+/// Warning: Security-sensitive method!
+/// Render a BIP39 mnemonic phrase as a QR code (SVG), e.g. for transfer to a hardware signer.
+/// WARNING: the QR code contains the mnemonic in PLAINTEXT. Anyone who can see, photograph,
+/// or screen-capture it gains full control of the keys. Show it only briefly, in private.
+pub(crate) fn mnemonic_qr_svg(phrase: &str) -> Result<String, Error> {
+    let mnemonic = bip39::Mnemonic::parse(phrase.trim())?;
+    qr_svg(&mnemonic.to_string())
+}
+
+/// Warning: Security-sensitive method!
+/// Recognize a BIP39 mnemonic in the text payload of a scanned QR code: either the phrase itself,
+/// or the SeedQR digit format (4-digit word indices, 12 or 24 words). None if not a valid mnemonic.
+pub(crate) fn mnemonic_from_qr_payload(payload: &str) -> Option<String> {
+    let payload = payload.trim();
+    let phrase = if !payload.is_empty() && payload.chars().all(|c| c.is_ascii_digit()) {
+        if payload.len() != 12 * 4 && payload.len() != 24 * 4 {
+            return None;
+        }
+        let word_list = bip39::Language::English.word_list();
+        let mut words = Vec::new();
+        for i in (0..payload.len()).step_by(4) {
+            let index = payload[i..i + 4].parse::<usize>().ok()?;
+            words.push(*word_list.get(index)?);
+        }
+        words.join(" ")
+    } else {
+        payload.to_string()
+    };
+    bip39::Mnemonic::parse(&phrase).ok().map(|m| m.to_string())
+}
+
+/// Split data into frame payloads of at most `chunk_size` characters of data each,
+/// for devices reading multi-frame (animated) QR codes.
+/// Each frame carries its (1-based) index and the total count: `keystr-frame:<i>/<n>:<chunk>`
+pub(crate) fn split_frames(data: &str, chunk_size: usize) -> Vec<String> {
+    let chars: Vec<char> = data.chars().collect();
+    let chunks: Vec<String> = chars
+        .chunks(chunk_size.max(1))
+        .map(|c| c.iter().collect())
+        .collect();
+    let total = chunks.len();
+    chunks
+        .iter()
+        .enumerate()
+        .map(|(i, c)| format!("{}{}/{}:{}", FRAME_PREFIX, i + 1, total, c))
+        .collect()
+}
+
+/// Lengths of the frame payloads `split_frames` creates from data of the given length (in characters)
+pub(crate) fn frame_lengths(data_len: usize, chunk_size: usize) -> Vec<usize> {
+    let chunk_size = chunk_size.max(1);
+    let total = (data_len + chunk_size - 1) / chunk_size;
+    (0..total)
+        .map(|i| {
+            let chunk_len = chunk_size.min(data_len - i * chunk_size);
+            FRAME_PREFIX.len() + format!("{}/{}:", i + 1, total).len() + chunk_len
+        })
+        .collect()
+}
+
+/// Render a payload as a QR code, in SVG format
+pub(crate) fn qr_svg(payload: &str) -> Result<String, Error> {
+    let code = QrCode::new(payload.as_bytes())?;
+    Ok(code
+        .render::<svg::Color>()
+        .min_dimensions(QR_MIN_DIMENSION, QR_MIN_DIMENSION)
+        .build())
+}
+
+/// Estimated length of the SVG `qr_svg` renders for a payload of the given length (in bytes),
+/// without rendering it. The QR version follows from the length, and about half of the modules are dark,
+/// each drawn as a path segment; the actual length varies slightly with the content.
+pub(crate) fn estimated_qr_svg_len(payload_len: usize) -> usize {
+    let version = QR_BYTE_CAPACITY
+        .iter()
+        .position(|capacity| payload_len <= *capacity)
+        .unwrap_or(QR_BYTE_CAPACITY.len() - 1)
+        + 1;
+    let modules = 17 + 4 * version;
+    let width_in_modules = modules + 2 * QR_QUIET_ZONE;
+    let module_size = (QR_MIN_DIMENSION as usize + width_in_modules - 1) / width_in_modules;
+    let digits = |n: usize| n.to_string().len();
+    // the image size appears 6 times in the header
+    let fixed_len = QR_SVG_FIXED_LEN + 6 * digits(width_in_modules * module_size);
+    // a dark module is "M{x} {y}h{size}v{size}H{x}V{y}"
+    let coordinates_len: usize = (QR_QUIET_ZONE..QR_QUIET_ZONE + modules)
+        .map(|i| digits(i * module_size))
+        .sum();
+    let dark_modules_len = 6 * modules * modules
+        + 4 * modules * coordinates_len
+        + 2 * modules * modules * digits(module_size);
+    fixed_len + dark_modules_len / 2
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::model::keystore::Keystore;
+    use nostr::prelude::{EventBuilder, Keys};
+    use proptest::prelude::*;
+
+    #[test]
+    fn test_mnemonic_qr() {
+        let phrase = "oil oil oil oil oil oil oil oil oil oil oil oil";
+        assert!(mnemonic_qr_svg(phrase).unwrap().contains("<svg"));
+        assert!(mnemonic_qr_svg("oil oil oil").is_err());
+
+        assert_eq!(mnemonic_from_qr_payload(phrase).unwrap(), phrase);
+        assert_eq!(
+            mnemonic_from_qr_payload(&format!(" {phrase}\n")).unwrap(),
+            phrase
+        );
+        // SeedQR digits
+        let index = bip39::Language::English
+            .word_list()
+            .iter()
+            .position(|w| *w == "oil")
+            .unwrap();
+        let digits = format!("{:04}", index).repeat(12);
+        assert_eq!(mnemonic_from_qr_payload(&digits).unwrap(), phrase);
+
+        assert!(mnemonic_from_qr_payload(&format!("{:04}", index).repeat(11)).is_none());
+        assert!(mnemonic_from_qr_payload(&"9999".repeat(12)).is_none());
+        assert!(mnemonic_from_qr_payload("oil oil oil").is_none());
+        assert!(mnemonic_from_qr_payload(
+            "npub1rfze4zn25ezp6jqt5ejlhrajrfx0az72ed7cwvq0spr22k9rlnjq93lmd4"
+        )
+        .is_none());
+    }
+
+    #[test]
+    fn test_split_frames() {
+        let frames = split_frames(&"q".repeat(160), 50);
+        assert_eq!(frames.len(), 4);
+        assert_eq!(frames[0], format!("keystr-frame:1/4:{}", "q".repeat(50)));
+        assert_eq!(frames[3], "keystr-frame:4/4:qqqqqqqqqq");
+        assert!(split_frames("", 50).is_empty());
+    }
+
+    #[test]
+    fn test_frame_lengths() {
+        for (len, chunk_size) in [(0, 50), (1, 50), (50, 50), (160, 50), (1200, 7)] {
+            let frames = split_frames(&"q".repeat(len), chunk_size);
+            let lengths: Vec<usize> = frames.iter().map(|f| f.len()).collect();
+            assert_eq!(frame_lengths(len, chunk_size), lengths);
+        }
+    }
+
+    #[test]
+    fn test_qr_svg() {
+        let svg = qr_svg("hello").unwrap();
+        assert!(svg.contains("<svg"));
+    }
+
+    #[test]
+    fn test_estimated_qr_svg_len() {
+        for len in [1, 20, 67, 100, 300, 1000] {
+            let payload: String = (0..len)
+                .map(|i| (b'a' + (i * 7 % 26) as u8) as char)
+                .collect();
+            let actual = qr_svg(&payload).unwrap().len();
+            let estimate = estimated_qr_svg_len(len);
+            assert!(
+                estimate.abs_diff(actual) * 10 < actual,
+                "{len} {estimate} {actual}"
+            );
+        }
+    }
+
+    #[test]
+    fn test_sign_request_roundtrip() {
+        let keys = Keys::generate();
+        let unsigned =
+            EventBuilder::new_text_note("Signed offline", &[]).to_unsigned_event(keys.public_key());
+
+        let request = sign_request_encode(&unsigned).unwrap();
+        assert!(request.starts_with(SIGN_REQUEST_PREFIX));
+        let decoded = sign_request_decode(&request).unwrap();
+        assert_eq!(decoded.id, unsigned.id);
+        assert_eq!(decoded.content, "Signed offline");
+
+        // offline side
+        let offline_keystore = Keystore::from_keys(keys.clone());
+        let response = offline_keystore.sign_offline_request(&request).unwrap();
+        assert!(response.starts_with(SIGN_RESPONSE_PREFIX));
+
+        // online side
+        let event = sign_response_decode(&response).unwrap();
+        assert_eq!(event.id, unsigned.id);
+        assert_eq!(event.pubkey, keys.public_key());
+        assert!(event.verify().is_ok());
+
+        // online keystore, with the public key only
+        let online_keystore = Keystore::from_keys(Keys::from_public_key(keys.public_key()));
+        let request = online_keystore
+            .offline_sign_request("Signed offline")
+            .unwrap();
+        let response = offline_keystore.sign_offline_request(&request).unwrap();
+        let event = online_keystore.check_offline_response(&response).unwrap();
+        assert_eq!(event.content, "Signed offline");
+        assert!(Keystore::from_keys(Keys::generate())
+            .check_offline_response(&response)
+            .is_err());
+    }
+
+    #[test]
+    fn test_sign_request_other_key() {
+        let unsigned = EventBuilder::new_text_note("Signed offline", &[])
+            .to_unsigned_event(Keys::generate().public_key());
+        let request = sign_request_encode(&unsigned).unwrap();
+        let offline_keystore = Keystore::from_keys(Keys::generate());
+        assert!(matches!(
+            offline_keystore.sign_offline_request(&request),
+            Err(Error::KeyIdentityMismatch)
+        ));
+    }
+
+    #[test]
+    fn test_sign_request_decode_invalid() {
+        assert!(sign_request_decode("").is_err());
+        assert!(sign_request_decode("keystr-signreq:!!!").is_err());
+        assert!(sign_request_decode("keystr-signresp:e30").is_err());
+        assert!(sign_response_decode("keystr-signreq:e30").is_err());
+    }
+
+    proptest! {
+        #[test]
+        fn test_decode_no_panic(input in "\\PC*") {
+            let _ = sign_request_decode(&input);
+            let _ = sign_response_decode(&input);
+            let _ = sign_request_decode(&format!("{SIGN_REQUEST_PREFIX}{input}"));
+            let _ = sign_response_decode(&format!("{SIGN_RESPONSE_PREFIX}{input}"));
+        }
+
+        #[test]
+        fn test_mnemonic_from_qr_payload_no_panic(
+            input in "\\PC*|[0-9]{44,100}|([a-z]{2,8} ){11,24}[a-z]{2,8}"
+        ) {
+            let _ = mnemonic_from_qr_payload(&input);
+        }
+    }
+}
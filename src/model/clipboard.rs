@@ -0,0 +1,79 @@
+use zeroize::Zeroize;
+
+use std::time::Duration;
+
+/// Default time after which a copied secret is cleared from the clipboard
+pub(crate) const CLIPBOARD_CLEAR_TIMEOUT: Duration = Duration::from_secs(30);
+
+/// Warning to show to the user after copying a secret to the clipboard
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub(crate) enum ClipboardWarning {
+    /// Clipboard will be cleared, but clipboard history/managers or sync may have retained a copy
+    HistoryMayRetain,
+    /// Clipboard cannot be cleared automatically on this platform, it has to be cleared manually
+    CannotAutoClear,
+}
+
+impl ClipboardWarning {
+    pub fn message(&self) -> &'static str {
+        match self {
+            Self::HistoryMayRetain => "The clipboard will be cleared shortly, but clipboard history, clipboard managers or clipboard sync may keep a copy of the secret key. Clear them if used.",
+            Self::CannotAutoClear => "The clipboard cannot be cleared automatically on this platform, clear it manually! Clipboard history or managers may also keep a copy of the secret key.",
+        }
+    }
+}
+
+/// A secret to be copied to the clipboard, with information on clearing it
+pub(crate) struct ClipboardCopy {
+    /// Content to copy (secret); zeroized on drop
+    pub content: String,
+    /// If the clipboard can be cleared automatically on this platform
+    pub auto_clear_supported: bool,
+    /// Time after which the clipboard should be cleared (if supported)
+    pub clear_after: Option<Duration>,
+    pub warning: ClipboardWarning,
+}
+
+impl ClipboardCopy {
+    pub fn new(content: String, timeout: Duration) -> Self {
+        let auto_clear_supported = auto_clear_supported();
+        Self {
+            content,
+            auto_clear_supported,
+            clear_after: if auto_clear_supported {
+                Some(timeout)
+            } else {
+                None
+            },
+            warning: if auto_clear_supported {
+                ClipboardWarning::HistoryMayRetain
+            } else {
+                ClipboardWarning::CannotAutoClear
+            },
+        }
+    }
+}
+
+impl Drop for ClipboardCopy {
+    fn drop(&mut self) {
+        self.content.zeroize();
+    }
+}
+
+/// Warning: Security-sensitive method!
+/// Read the text content of the system clipboard, if any. It is recommended to zeroize() it after use.
+#[cfg(feature = "clipboard")]
+pub(crate) fn read_clipboard_text() -> Option<String> {
+    arboard::Clipboard::new().ok()?.get_text().ok()
+}
+
+/// If the clipboard can be cleared (overwritten) by the app on the current platform
+pub(crate) fn auto_clear_supported() -> bool {
+    cfg!(any(
+        target_os = "linux",
+        target_os = "macos",
+        target_os = "windows",
+        target_os = "freebsd",
+        target_os = "openbsd"
+    ))
+}
@@ -1,7 +1,10 @@
 use crate::base::error::Error;
+use crate::base::event_utils::describe_event;
+use crate::model::status_messages::StatusMessages;
 
 use nostr::prelude::{
-    Conditions, DelegationTag, DelegationToken, FromBech32, Keys, ToBech32, XOnlyPublicKey,
+    Conditions, DelegationTag, DelegationToken, Event, EventProperties, FromBech32, Keys, ToBech32,
+    XOnlyPublicKey,
 };
 
 use std::str::FromStr;
@@ -27,6 +30,8 @@ pub(crate) struct Delegator {
     pub signature: String,
     // Compiled delegation tag (contains pubkey, conditions, signature)
     pub delegation_tag: String,
+    // Input for an event (JSON) to verify, possibly signed under a delegation
+    pub verify_event_input: String,
 }
 
 impl Delegator {
@@ -41,6 +46,7 @@ impl Delegator {
             delegation_string: String::new(),
             signature: String::new(),
             delegation_tag: String::new(),
+            verify_event_input: String::new(),
         };
         let _r = d.validate_and_update();
         d
@@ -120,12 +126,113 @@ impl Delegator {
         self.signature = tag.signature().to_string();
         Ok(())
     }
+
+    /// Action to verify the event in the input, see `verify_event_with_delegation`
+    pub fn verify_event_action(&self, status: &mut StatusMessages) {
+        let res = serde_json::from_str::<Event>(self.verify_event_input.trim())
+            .map_err(Error::from)
+            .and_then(|event| {
+                Ok((
+                    verify_event_with_delegation(&event)?,
+                    describe_event(&event),
+                ))
+            });
+        match res {
+            Err(e) => status.set_error(&format!("Event is not valid, {}", e.to_string())),
+            Ok((DelegationVerdict::Author(_), desc)) => status.set(&format!(
+                "Event is valid ({}), signed by its author {}",
+                desc, desc.author_npub
+            )),
+            Ok((
+                DelegationVerdict::Delegated {
+                    delegator,
+                    delegatee,
+                },
+                desc,
+            )) => status.set(&format!(
+                "Event is valid ({}), signed by {} on behalf of delegator {}",
+                desc,
+                delegatee.to_bech32().unwrap_or_default(),
+                delegator.to_bech32().unwrap_or_default()
+            )),
+        }
+    }
+}
+
+/// Result of verifying an event with respect to NIP-26 delegation:
+/// who is effectively responsible for the event
+#[derive(Clone, Debug, PartialEq)]
+pub(crate) enum DelegationVerdict {
+    /// Not delegated, the signer is the author
+    Author(XOnlyPublicKey),
+    /// Signed by the delegatee on behalf of the delegator, the delegator is responsible
+    Delegated {
+        delegator: XOnlyPublicKey,
+        delegatee: XOnlyPublicKey,
+    },
+}
+
+/// Verify an event, possibly signed under a NIP-26 delegation:
+/// the event signature, and if a delegation tag is present, the delegation signature
+/// and the delegation conditions against the event.
+pub(crate) fn verify_event_with_delegation(event: &Event) -> Result<DelegationVerdict, Error> {
+    event.verify()?;
+    let delegation_tag = event
+        .tags
+        .iter()
+        .find(|t| t.as_vec().first().map(|s| s.as_str()) == Some("delegation"));
+    match delegation_tag {
+        None => Ok(DelegationVerdict::Author(event.pubkey)),
+        Some(tag) => {
+            let tag_str = serde_json::to_string(tag)?;
+            let dtag = DelegationTag::from_str(&tag_str).map_err(|_e| Error::DelegationInvalid)?;
+            let kind = u16::try_from(event.kind.as_u64()).map_err(|_e| Error::DelegationInvalid)?;
+            // a single-link chain, the delegatee signed the event
+            verify_delegation_chain(
+                std::slice::from_ref(&dtag),
+                &event.pubkey,
+                kind,
+                event.created_at.as_u64(),
+            )
+            .map_err(|_e| Error::DelegationInvalid)?;
+            Ok(DelegationVerdict::Delegated {
+                delegator: dtag.delegator_pubkey(),
+                delegatee: event.pubkey,
+            })
+        }
+    }
+}
+
+/// Verify a chain of NIP-26 delegations (A->B, B->C, ...).
+/// The delegatee of each link is the delegator of the next link, the delegatee of the last link
+/// must be `final_signer`. The signature and the conditions (for the given event kind
+/// and creation time) of each link are validated.
+/// Returns false for an empty chain, and `DelegationChainBroken` with the index of the first invalid link.
+pub(crate) fn verify_delegation_chain(
+    chain: &[DelegationTag],
+    final_signer: &XOnlyPublicKey,
+    kind: u16,
+    created_at: u64,
+) -> Result<bool, Error> {
+    if chain.is_empty() {
+        return Ok(false);
+    }
+    let event_properties = EventProperties::new(u64::from(kind), created_at);
+    for (i, link) in chain.iter().enumerate() {
+        let delegatee = match chain.get(i + 1) {
+            Some(next) => next.delegator_pubkey(),
+            None => *final_signer,
+        };
+        link.validate(delegatee, &event_properties)
+            .map_err(|_e| Error::DelegationChainBroken(i))?;
+    }
+    Ok(true)
 }
 
 #[cfg(test)]
 mod test {
     use super::*;
-    use nostr::prelude::{DelegationTag, EventProperties, SecretKey};
+    use nostr::prelude::{EventBuilder, SecretKey, Tag};
 
     #[test]
     fn test_create_delegation() {
@@ -201,6 +308,172 @@ mod test {
         }
     }
 
+    fn delegate(delegator: &Keys, delegatee: &Keys) -> DelegationTag {
+        DelegationTag::new(
+            delegator,
+            delegatee.public_key(),
+            Conditions::from_str("kind=1&created_at>1676067553&created_at<1678659553").unwrap(),
+        )
+        .unwrap()
+    }
+
+    #[test]
+    fn test_verify_delegation_chain() {
+        let a = Keys::generate();
+        let b = Keys::generate();
+        let c = Keys::generate();
+        let chain = vec![delegate(&a, &b), delegate(&b, &c)];
+
+        assert_eq!(
+            verify_delegation_chain(&chain, &c.public_key(), 1, 1677000000).unwrap(),
+            true
+        );
+        // final signer mismatch
+        assert!(matches!(
+            verify_delegation_chain(&chain, &b.public_key(), 1, 1677000000),
+            Err(Error::DelegationChainBroken(1))
+        ));
+        // conditions not met
+        assert!(matches!(
+            verify_delegation_chain(&chain, &c.public_key(), 4, 1677000000),
+            Err(Error::DelegationChainBroken(0))
+        ));
+        assert_eq!(
+            verify_delegation_chain(&[], &c.public_key(), 1, 1677000000).unwrap(),
+            false
+        );
+    }
+
+    #[test]
+    fn test_verify_delegation_chain_tampered() {
+        let a = Keys::generate();
+        let b = Keys::generate();
+        let c = Keys::generate();
+        let d = Keys::generate();
+        let middle = delegate(&b, &c);
+        // widen the conditions of the middle link, keeping its signature
+        let tampered = DelegationTag::from_str(&format!(
+            "[\"delegation\",\"{}\",\"kind=1\",\"{}\"]",
+            b.public_key(),
+            middle.signature()
+        ))
+        .unwrap();
+        let chain = vec![delegate(&a, &b), tampered, delegate(&c, &d)];
+
+        assert!(matches!(
+            verify_delegation_chain(&chain, &d.public_key(), 1, 1677000000),
+            Err(Error::DelegationChainBroken(1))
+        ));
+    }
+
+    fn delegated_note(delegatee: &Keys, delegation_tag_str: &str) -> Event {
+        let tag = serde_json::from_str::<Tag>(delegation_tag_str).unwrap();
+        EventBuilder::new_text_note("Delegated", &[tag])
+            .to_event(delegatee)
+            .unwrap()
+    }
+
+    #[test]
+    fn test_verify_event_with_delegation() {
+        let delegator = Keys::generate();
+        let delegatee = Keys::generate();
+        let dtag = DelegationTag::new(
+            &delegator,
+            delegatee.public_key(),
+            Conditions::from_str("kind=1").unwrap(),
+        )
+        .unwrap();
+        let event = delegated_note(&delegatee, &dtag.to_string());
+        assert_eq!(
+            verify_event_with_delegation(&event).unwrap(),
+            DelegationVerdict::Delegated {
+                delegator: delegator.public_key(),
+                delegatee: delegatee.public_key(),
+            }
+        );
+
+        // not delegated
+        let event = EventBuilder::new_text_note("Hello", &[])
+            .to_event(&delegatee)
+            .unwrap();
+        assert_eq!(
+            verify_event_with_delegation(&event).unwrap(),
+            DelegationVerdict::Author(delegatee.public_key())
+        );
+    }
+
+    #[test]
+    fn test_verify_event_with_delegation_invalid() {
+        let delegator = Keys::generate();
+        let delegatee = Keys::generate();
+        let dtag = DelegationTag::new(
+            &delegator,
+            delegatee.public_key(),
+            Conditions::from_str("kind=4").unwrap(),
+        )
+        .unwrap();
+
+        // conditions not met (kind 1 event)
+        let event = delegated_note(&delegatee, &dtag.to_string());
+        assert!(matches!(
+            verify_event_with_delegation(&event),
+            Err(Error::DelegationInvalid)
+        ));
+
+        // tampered conditions, signature does not match
+        let tampered = format!(
+            "[\"delegation\",\"{}\",\"kind=1\",\"{}\"]",
+            delegator.public_key(),
+            dtag.signature()
+        );
+        let event = delegated_note(&delegatee, &tampered);
+        assert!(matches!(
+            verify_event_with_delegation(&event),
+            Err(Error::DelegationInvalid)
+        ));
+
+        // tampered event content, with a delegation whose conditions are met:
+        // only the event signature does not match
+        let dtag_kind1 = DelegationTag::new(
+            &delegator,
+            delegatee.public_key(),
+            Conditions::from_str("kind=1").unwrap(),
+        )
+        .unwrap();
+        let mut event = delegated_note(&delegatee, &dtag_kind1.to_string());
+        assert!(verify_event_with_delegation(&event).is_ok());
+        event.content = "Tampered".to_string();
+        assert!(matches!(
+            verify_event_with_delegation(&event),
+            Err(Error::EventError(nostr::event::Error::InvalidSignature))
+        ));
+    }
+
+    #[test]
+    fn test_verify_event_action() {
+        let delegator = Keys::generate();
+        let delegatee = Keys::generate();
+        let dtag = DelegationTag::new(
+            &delegator,
+            delegatee.public_key(),
+            Conditions::from_str("kind=1").unwrap(),
+        )
+        .unwrap();
+        let event = delegated_note(&delegatee, &dtag.to_string());
+        let mut d = Delegator::new();
+        let mut status = StatusMessages::new();
+        d.verify_event_input = serde_json::to_string(&event).unwrap();
+        d.verify_event_action(&mut status);
+        assert!(status
+            .get_last()
+            .contains(&delegator.public_key().to_bech32().unwrap()));
+        assert!(status.get_last().contains("uses NIP-26"));
+
+        d.verify_event_input = "not an event".to_string();
+        d.verify_event_action(&mut status);
+        assert!(status.get_last().contains("not valid"));
+    }
+
     #[test]
     fn test_time_set_start() {
         let mut d = Delegator::new();
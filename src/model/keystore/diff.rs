@@ -0,0 +1,179 @@
+use crate::model::keystore::{KeysSetState, Keystore};
+use crate::model::relays::normalize_relay_url;
+
+use zeroize::Zeroize;
+
+use std::collections::HashSet;
+use std::fmt;
+
+/// Changes between two keystore states, e.g. for a confirmation screen.
+/// Contains no secrets, only whether the secret key has changed.
+#[derive(Clone, Debug, Default, Eq, PartialEq)]
+pub(crate) struct KeystoreDiff {
+    /// Set state change (from, to), if changed
+    pub set_state: Option<(KeysSetState, KeysSetState)>,
+    pub public_key_changed: bool,
+    /// The secret key (plain or encrypted) has changed
+    pub secret_key_changed: bool,
+    pub origin_changed: bool,
+    pub label_changed: bool,
+    pub note_changed: bool,
+    pub auto_lock_changed: bool,
+    pub allowed_kinds_changed: bool,
+    /// Relays present only in the new state (compared normalized)
+    pub relays_added: Vec<String>,
+    /// Relays present only in the old state (compared normalized)
+    pub relays_removed: Vec<String>,
+}
+
+impl KeystoreDiff {
+    /// True if there is no change
+    pub fn is_empty(&self) -> bool {
+        *self == Self::default()
+    }
+}
+
+/// Short summary of the changes, e.g. "public key, secret key, label, relays +1 -2"
+impl fmt::Display for KeystoreDiff {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        if self.is_empty() {
+            return write!(f, "no changes");
+        }
+        let mut changes = Vec::new();
+        if let Some((from, to)) = &self.set_state {
+            changes.push(format!("state {:?} -> {:?}", from, to));
+        }
+        for (changed, name) in [
+            (self.public_key_changed, "public key"),
+            (self.secret_key_changed, "secret key"),
+            (self.origin_changed, "origin"),
+            (self.label_changed, "label"),
+            (self.note_changed, "note"),
+            (self.auto_lock_changed, "auto-lock"),
+            (self.allowed_kinds_changed, "allowed kinds"),
+        ] {
+            if changed {
+                changes.push(name.to_string());
+            }
+        }
+        if !self.relays_added.is_empty() || !self.relays_removed.is_empty() {
+            changes.push(format!(
+                "relays +{} -{}",
+                self.relays_added.len(),
+                self.relays_removed.len()
+            ));
+        }
+        write!(f, "{}", changes.join(", "))
+    }
+}
+
+impl Keystore {
+    /// Describe the changes from this state (with its relays) to `other` (with its relays).
+    /// Secrets are only compared, not exposed. Relays are kept outside of the keystore,
+    /// hence they are passed in.
+    pub fn diff(
+        &self,
+        relays: &[String],
+        other: &Keystore,
+        other_relays: &[String],
+    ) -> KeystoreDiff {
+        let (from, to) = (self.get_set_state(), other.get_set_state());
+        let mut secret = self.get_secret_key().map(|sk| sk.secret_bytes()).ok();
+        let mut other_secret = other.get_secret_key().map(|sk| sk.secret_bytes()).ok();
+        let secret_key_changed =
+            secret != other_secret || self.encrypted_secret_key != other.encrypted_secret_key;
+        secret.zeroize();
+        other_secret.zeroize();
+        let normalized = |list: &[String]| {
+            list.iter()
+                .map(|r| normalize_relay_url(r))
+                .collect::<HashSet<String>>()
+        };
+        let (old_relays, new_relays) = (normalized(relays), normalized(other_relays));
+        let mut relays_added: Vec<String> = new_relays.difference(&old_relays).cloned().collect();
+        let mut relays_removed: Vec<String> = old_relays.difference(&new_relays).cloned().collect();
+        relays_added.sort();
+        relays_removed.sort();
+        KeystoreDiff {
+            set_state: if from != to { Some((from, to)) } else { None },
+            public_key_changed: self.get_public_key().ok() != other.get_public_key().ok(),
+            secret_key_changed,
+            origin_changed: self.origin != other.origin,
+            label_changed: self.label != other.label,
+            note_changed: self.note != other.note,
+            auto_lock_changed: self.auto_lock_after != other.auto_lock_after,
+            allowed_kinds_changed: self.allowed_kinds != other.allowed_kinds,
+            relays_added,
+            relays_removed,
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::model::keystore::test::keystore_with_state;
+    use nostr::prelude::Keys;
+
+    #[test]
+    fn test_diff() {
+        let keys = Keys::generate();
+        let k1 = Keystore::from_keys(keys.clone());
+        let mut k2 = Keystore::from_keys(keys.clone());
+        assert!(k1.diff(&[], &k2, &[]).is_empty());
+        assert_eq!(k1.diff(&[], &k2, &[]).to_string(), "no changes");
+
+        k2.label = "label2".to_string();
+        let changes = k1.diff(&[], &k2, &[]);
+        assert!(!changes.is_empty());
+        assert!(changes.label_changed);
+        assert!(!changes.public_key_changed);
+        assert!(!changes.secret_key_changed);
+        assert_eq!(changes.set_state, None);
+        assert_eq!(changes.to_string(), "label");
+
+        // public only: secret changed, but public key not
+        let k3 = keystore_with_state(keys.clone(), KeysSetState::PublicOnly);
+        let changes = k1.diff(&[], &k3, &[]);
+        assert_eq!(
+            changes.set_state,
+            Some((KeysSetState::SecretAndPublic, KeysSetState::PublicOnly))
+        );
+        assert!(!changes.public_key_changed);
+        assert!(changes.secret_key_changed);
+
+        // different keys
+        let k4 = Keystore::from_keys(Keys::generate());
+        let changes = k1.diff(&[], &k4, &[]);
+        assert_eq!(changes.set_state, None);
+        assert!(changes.public_key_changed);
+        assert!(changes.secret_key_changed);
+        assert_eq!(changes.to_string(), "public key, secret key");
+        // no secrets in the diff
+        let diff_str = format!("{:?}", changes);
+        assert!(!diff_str.contains(&keys.secret_key().unwrap().display_secret().to_string()));
+
+        let changes = Keystore::new().diff(&[], &k1, &[]);
+        assert_eq!(
+            changes.set_state,
+            Some((KeysSetState::NotSet, KeysSetState::SecretAndPublic))
+        );
+        assert!(changes.public_key_changed);
+
+        // relays, compared normalized
+        let relays = vec![
+            "wss://relay.damus.io".to_string(),
+            "wss://nos.lol".to_string(),
+        ];
+        let other_relays = vec![
+            "WSS://Relay.Damus.io/".to_string(),
+            "wss://relay.nostr.band".to_string(),
+        ];
+        assert!(k1.diff(&relays, &k1, &relays).is_empty());
+        let changes = k1.diff(&relays, &k1, &other_relays);
+        assert!(!changes.is_empty());
+        assert_eq!(changes.relays_added, vec!["wss://relay.nostr.band"]);
+        assert_eq!(changes.relays_removed, vec!["wss://nos.lol"]);
+        assert_eq!(changes.to_string(), "relays +1 -1");
+    }
+}
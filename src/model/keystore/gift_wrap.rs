@@ -0,0 +1,187 @@
+use crate::base::error::Error;
+use crate::base::key_utils::normalize_input;
+use crate::base::nip44;
+use crate::model::keystore::{unsigned_at, Keystore};
+
+use nostr::prelude::{
+    Event, EventBuilder, EventId, FromPkStr, Keys, Kind, SecretKey, Tag, UnsignedEvent, SECP256K1,
+};
+use rand_core::{OsRng, RngCore};
+use zeroize::Zeroize;
+
+use std::time::Duration;
+
+/// Event kind of the seal inside a gift wrap (NIP-59)
+const GIFT_WRAP_SEAL_KIND: u64 = 13;
+/// Event kind of gift wraps (NIP-59)
+const GIFT_WRAP_KIND: u64 = 1059;
+/// Maximum random shift of gift wrap and seal timestamps into the past (NIP-59)
+const GIFT_WRAP_MAX_TIME_SHIFT: Duration = Duration::from_secs(2 * 24 * 3600);
+
+/// Warning: Security-sensitive method!
+/// Create a gift wrap (NIP-59) of a rumor (unsigned event) for a recipient (npub or hex):
+/// the rumor is sealed (kind 13, signed by us, NIP-44 encrypted to the recipient),
+/// and the seal is wrapped (kind 1059, NIP-44 encrypted, signed by a fresh one-time key).
+/// Timestamps of the seal and the wrap are randomized up to 2 days into the past.
+pub(crate) fn create_gift_wrap(
+    keystore: &Keystore,
+    recipient: &str,
+    rumor: UnsignedEvent,
+) -> Result<Event, Error> {
+    let recipient = Keys::from_pk_str(&normalize_input(recipient)?)?.public_key();
+    if rumor.pubkey != keystore.get_public_key()? {
+        return Err(Error::KeyIdentityMismatch);
+    }
+    let rumor = unsigned_at(
+        EventBuilder::new(rumor.kind, rumor.content, &rumor.tags),
+        rumor.pubkey,
+        rumor.created_at.as_u64(),
+    );
+
+    let mut conversation_key = nip44::conversation_key(&keystore.get_secret_key()?, &recipient);
+    let sealed_rumor = nip44::encrypt(&conversation_key, &serde_json::to_string(&rumor)?);
+    conversation_key.zeroize();
+    let seal = keystore.sign(unsigned_at(
+        EventBuilder::new(Kind::Custom(GIFT_WRAP_SEAL_KIND), sealed_rumor?, &[]),
+        rumor.pubkey,
+        randomized_past_time(),
+    ))?;
+
+    // one-time key, used only for this wrap
+    let mut ephemeral_bytes = [0u8; 32];
+    OsRng.fill_bytes(&mut ephemeral_bytes);
+    let ephemeral_key = SecretKey::from_slice(&ephemeral_bytes);
+    ephemeral_bytes.zeroize();
+    let mut ephemeral_key = ephemeral_key?;
+    let mut key_pair = nostr::secp256k1::KeyPair::from_secret_key(&SECP256K1, &ephemeral_key);
+    let mut conversation_key = nip44::conversation_key(&ephemeral_key, &recipient);
+    ephemeral_key.non_secure_erase();
+    let wrapped_seal = nip44::encrypt(&conversation_key, &serde_json::to_string(&seal)?);
+    conversation_key.zeroize();
+    let wrap = unsigned_at(
+        EventBuilder::new(
+            Kind::Custom(GIFT_WRAP_KIND),
+            wrapped_seal?,
+            &[Tag::PubKey(recipient, None)],
+        ),
+        key_pair.x_only_public_key().0,
+        randomized_past_time(),
+    );
+    let signature = nostr::secp256k1::Message::from_slice(wrap.id.as_bytes())
+        .map(|message| SECP256K1.sign_schnorr(&message, &key_pair));
+    key_pair.non_secure_erase();
+    Ok(wrap.add_signature(signature?)?)
+}
+
+/// Warning: Security-sensitive method!
+/// Open a gift wrap (NIP-59) addressed to us, and return the rumor (unsigned event) in it.
+/// Signature of the wrap and the seal are checked, and the rumor author must match the seal signer.
+pub(crate) fn unwrap_gift(keystore: &Keystore, wrap: &Event) -> Result<UnsignedEvent, Error> {
+    if wrap.kind.as_u64() != GIFT_WRAP_KIND {
+        return Err(Error::GiftWrapInvalid(format!(
+            "wrong kind {}",
+            wrap.kind.as_u64()
+        )));
+    }
+    wrap.verify()?;
+    let secret_key = keystore.get_secret_key()?;
+
+    let mut conversation_key = nip44::conversation_key(&secret_key, &wrap.pubkey);
+    let seal_json = nip44::decrypt(&conversation_key, &wrap.content);
+    conversation_key.zeroize();
+    let seal: Event = serde_json::from_str(&seal_json?)
+        .map_err(|_| Error::GiftWrapInvalid("invalid seal".to_string()))?;
+    if seal.kind.as_u64() != GIFT_WRAP_SEAL_KIND {
+        return Err(Error::GiftWrapInvalid(format!(
+            "wrong seal kind {}",
+            seal.kind.as_u64()
+        )));
+    }
+    seal.verify()?;
+
+    let mut conversation_key = nip44::conversation_key(&secret_key, &seal.pubkey);
+    let rumor_json = nip44::decrypt(&conversation_key, &seal.content);
+    conversation_key.zeroize();
+    let rumor: UnsignedEvent = serde_json::from_str(&rumor_json?)
+        .map_err(|_| Error::GiftWrapInvalid("invalid rumor".to_string()))?;
+    if rumor.pubkey != seal.pubkey {
+        return Err(Error::GiftWrapInvalid(
+            "rumor author does not match the seal".to_string(),
+        ));
+    }
+    let id = EventId::new(
+        &rumor.pubkey,
+        rumor.created_at,
+        &rumor.kind,
+        &rumor.tags,
+        &rumor.content,
+    );
+    if rumor.id != id {
+        return Err(Error::EventIdMismatch);
+    }
+    Ok(rumor)
+}
+
+/// Random time in the past (within `GIFT_WRAP_MAX_TIME_SHIFT`), to hide the real time of gift wraps (NIP-59)
+fn randomized_past_time() -> u64 {
+    let shift = OsRng.next_u64() % GIFT_WRAP_MAX_TIME_SHIFT.as_secs();
+    Keystore::current_time().saturating_sub(shift)
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use nostr::prelude::ToBech32;
+
+    #[test]
+    fn test_gift_wrap() {
+        let alice = Keystore::from_keys(Keys::generate());
+        let bob_keys = Keys::generate();
+        let bob = Keystore::from_keys(bob_keys.clone());
+        let bob_npub = bob_keys.public_key().to_bech32().unwrap();
+        let rumor = EventBuilder::new_text_note("Psst, secret note", &[])
+            .to_unsigned_event(alice.get_public_key().unwrap());
+
+        let wrap = create_gift_wrap(&alice, &bob_npub, rumor.clone()).unwrap();
+        assert_eq!(wrap.kind.as_u64(), GIFT_WRAP_KIND);
+        assert!(wrap.verify().is_ok());
+        // signed by a one-time key, not by the sender
+        assert_ne!(wrap.pubkey, alice.get_public_key().unwrap());
+        assert!(wrap
+            .tags
+            .contains(&Tag::PubKey(bob_keys.public_key(), None)));
+        assert!(!wrap.content.contains("Psst"));
+        assert!(wrap.created_at.as_u64() <= Keystore::current_time());
+
+        let unwrapped = unwrap_gift(&bob, &wrap).unwrap();
+        assert_eq!(unwrapped.content, "Psst, secret note");
+        assert_eq!(unwrapped.pubkey, alice.get_public_key().unwrap());
+        assert_eq!(unwrapped.id, rumor.id);
+
+        // a fresh one-time key for each wrap
+        let wrap2 = create_gift_wrap(&alice, &bob_npub, rumor.clone()).unwrap();
+        assert_ne!(wrap2.pubkey, wrap.pubkey);
+
+        // only the recipient can open it
+        let carol = Keystore::from_keys(Keys::generate());
+        assert!(matches!(
+            unwrap_gift(&carol, &wrap),
+            Err(Error::Nip44InvalidMac)
+        ));
+        assert!(unwrap_gift(&alice, &wrap).is_err());
+
+        // rumor has to be authored by us
+        let foreign_rumor =
+            EventBuilder::new_text_note("x", &[]).to_unsigned_event(bob_keys.public_key());
+        assert!(matches!(
+            create_gift_wrap(&alice, &bob_npub, foreign_rumor),
+            Err(Error::KeyIdentityMismatch)
+        ));
+        // not a gift wrap
+        let note = alice.sign_text_note("hi").unwrap();
+        assert!(matches!(
+            unwrap_gift(&bob, &note),
+            Err(Error::GiftWrapInvalid(_))
+        ));
+    }
+}
@@ -0,0 +1,120 @@
+use crate::base::error::Error;
+use crate::model::keystore::Keystore;
+use crate::model::security_settings::SecuritySettings;
+use crate::model::status_messages::StatusMessages;
+
+use nostr::prelude::ToBech32;
+use zeroize::Zeroize;
+
+/// Service name of the own secret key in the OS keychain
+const KEYCHAIN_SERVICE: &str = "keystr";
+/// Account name of the own secret key in the OS keychain
+const KEYCHAIN_ACCOUNT: &str = "secret_key";
+
+impl Keystore {
+    /// Warning: Security-sensitive method!
+    /// Save the secret key to the OS keychain (macOS Keychain, Windows Credential Manager, Linux Secret Service),
+    /// under the given service and account names. There is no fallback to a plaintext file.
+    pub fn save_to_keychain(&self, service: &str, account: &str) -> Result<(), Error> {
+        let mut nsec = self.get_secret_key()?.to_bech32()?;
+        let res = keyring::Entry::new(service, account)
+            .and_then(|entry| entry.set_password(&nsec))
+            .map_err(keychain_error);
+        nsec.zeroize();
+        res
+    }
+
+    /// Warning: Security-sensitive method!
+    /// Load the secret key from the OS keychain, saved by `save_to_keychain`
+    pub fn load_from_keychain(&mut self, service: &str, account: &str) -> Result<(), Error> {
+        let mut nsec = keyring::Entry::new(service, account)
+            .and_then(|entry| entry.get_password())
+            .map_err(keychain_error)?;
+        let res = self.import_secret_key(&nsec, false);
+        nsec.zeroize();
+        res
+    }
+
+    /// Action to save the secret key to the OS keychain, if persisting is allowed
+    pub fn save_to_keychain_action(
+        &self,
+        security_settings: &SecuritySettings,
+        status: &mut StatusMessages,
+    ) {
+        let res = if !security_settings.allows_persist() {
+            Err(Error::KeySaveNotAllowed)
+        } else {
+            self.save_to_keychain(KEYCHAIN_SERVICE, KEYCHAIN_ACCOUNT)
+        };
+        match res {
+            Err(e) => status.set_error_err(&e),
+            Ok(_) => status.set("Secret key saved to the OS keychain"),
+        }
+    }
+
+    /// Action to load the secret key from the OS keychain, if persisting is allowed
+    pub fn load_from_keychain_action(
+        &mut self,
+        security_settings: &SecuritySettings,
+        status: &mut StatusMessages,
+    ) {
+        let res = if !security_settings.allows_persist() {
+            Err(Error::KeyLoadNotAllowed)
+        } else {
+            self.load_from_keychain(KEYCHAIN_SERVICE, KEYCHAIN_ACCOUNT)
+        };
+        match res {
+            Err(e) => status.set_error_err(&e),
+            Ok(_) => status.set("Secret key loaded from the OS keychain"),
+        }
+    }
+}
+
+/// Map an OS keychain error to a descriptive error
+fn keychain_error(e: keyring::Error) -> Error {
+    match e {
+        keyring::Error::NoEntry => Error::KeychainEntryMissing,
+        keyring::Error::NoStorageAccess(e) => Error::KeychainLocked(e.to_string()),
+        e => Error::KeychainError(e.to_string()),
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::base::encrypt::Encrypt;
+    use nostr::prelude::Keys;
+
+    #[test]
+    fn test_save_to_keychain_no_secret() {
+        let keys = Keys::generate();
+        let k = Keystore::from_keys(Keys::from_public_key(keys.public_key()));
+        assert!(k.save_to_keychain("keystr-test", "test").is_err());
+        let encrypted = Encrypt::encrypt_key(
+            &keys.secret_key().unwrap(),
+            "password",
+            Encrypt::default_log2_rounds(),
+        )
+        .unwrap();
+        let k = Keystore::from_locked(keys.public_key(), encrypted);
+        assert!(k.save_to_keychain("keystr-test", "test").is_err());
+    }
+
+    #[test]
+    fn test_keychain_error() {
+        assert!(matches!(
+            keychain_error(keyring::Error::NoEntry),
+            Error::KeychainEntryMissing
+        ));
+        assert!(matches!(
+            keychain_error(keyring::Error::NoStorageAccess(Box::new(
+                std::io::Error::new(std::io::ErrorKind::PermissionDenied, "locked")
+            ))),
+            Error::KeychainLocked(_)
+        ));
+        assert!(matches!(
+            keychain_error(keyring::Error::TooLong("service".into(), 64)),
+            Error::KeychainError(_)
+        ));
+    }
+}
@@ -0,0 +1,5645 @@
+pub mod diff;
+pub mod gift_wrap;
+pub mod keychain;
+pub mod reveal;
+pub mod signing_stats;
+pub mod unlock;
+
+use crate::base::cancellation::CancellationToken;
+use crate::base::encrypt::{Encrypt, KdfProgress, ENCRYPTED_KEY_LEN};
+use crate::base::error::Error;
+use crate::base::key_utils::{
+    checksummed_hex, constant_time_eq, detect_input_kind, is_valid_nsec, normalize_input,
+    normalize_mnemonic, InputKind,
+};
+use crate::base::nip44;
+use crate::base::petname::petname;
+use crate::base::storage::Storage;
+use crate::model::airgap::{
+    estimated_qr_svg_len, frame_lengths, mnemonic_from_qr_payload, qr_svg, sign_request_decode,
+    sign_request_encode, sign_response_decode, sign_response_encode, split_frames,
+};
+use crate::model::relays::{
+    fetch_metadata, fetch_notes, has_published_events, publish_and_confirm, publish_event,
+    verify_nip05, ConfirmedPublish, NetworkConfig, PublishResult, RelayStat, RelayUsageStats,
+};
+use crate::model::security_settings::{KeyOrigin, KeyPolicy, SecurityLevel, SecuritySettings};
+use crate::model::status_messages::StatusMessages;
+use signing_stats::{record_signature, SigningStats};
+
+use base64::engine::general_purpose::{STANDARD, STANDARD_NO_PAD};
+use base64::Engine;
+use bech32::{FromBase32, ToBase32};
+use nostr::hashes::sha256::Hash as Sha256Hash;
+use nostr::prelude::{
+    Event, EventBuilder, EventId, FromBech32, FromPkStr, FromSkStr, Keys, Kind, Metadata,
+    SecretKey, Tag, TagKind, Timestamp, ToBech32, UnsignedEvent, Url, XOnlyPublicKey, SECP256K1,
+};
+use nostr::secp256k1::schnorr::Signature;
+use rand_core::{OsRng, RngCore};
+use zeroize::{Zeroize, Zeroizing};
+
+use std::cell::Cell;
+use std::collections::HashSet;
+use std::fmt;
+use std::fs;
+use std::future::Future;
+use std::hash::{Hash, Hasher};
+use std::io::Write;
+use std::path::Path;
+use std::str::FromStr;
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant, SystemTime, UNIX_EPOCH};
+
+/// State of the keys: which keys are set/available
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub(crate) enum KeysSetState {
+    /// No keys set
+    NotSet,
+    /// Only public key is set, signing is not possible
+    PublicOnly,
+    /// Secret key is set (public key is derived from it)
+    SecretAndPublic,
+    /// Encrypted secret key is set, but it is not decrypted (yet). Public key may be visible.
+    Locked,
+}
+
+/// What to import on submit, based on the public and secret key inputs, see `submit_strategy`
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub(crate) enum SubmitAction {
+    /// Both inputs are empty
+    Nothing,
+    ImportPublicKey,
+    /// Import the secret key (preferred, as it allows signing)
+    ImportSecretKey,
+}
+
+/// Explicit format of a key import input, to avoid ambiguity of auto-detection
+/// (e.g. a 64-char hex string can be a public or a secret key)
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub(crate) enum ImportFormat {
+    /// Public key, in hex format
+    HexPublic,
+    /// Secret key, in hex format
+    HexSecret,
+    /// Public key, in 'npub' bech32 format
+    Npub,
+    /// Secret key, in 'nsec' bech32 format
+    Nsec,
+    /// BIP39 mnemonic, secret key is derived from it
+    Mnemonic,
+    /// Secret key, the raw 32 bytes in base64 (44 chars, or 43 without padding)
+    Base64Secret,
+    /// Paper backup (npub, ncryptsec and checksum), see `export_paper_backup`
+    PaperBackup,
+    /// Public config as TOML (npub and label), see `to_toml_fragment`
+    TomlConfig,
+}
+
+/// The import formats, for explicit selection
+pub(crate) static IMPORT_FORMATS: &[ImportFormat] = &[
+    ImportFormat::Npub,
+    ImportFormat::Nsec,
+    ImportFormat::HexPublic,
+    ImportFormat::HexSecret,
+    ImportFormat::Mnemonic,
+    ImportFormat::Base64Secret,
+    ImportFormat::PaperBackup,
+    ImportFormat::TomlConfig,
+];
+
+impl fmt::Display for ImportFormat {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let desc = match self {
+            Self::HexPublic => "public key, hex",
+            Self::HexSecret => "secret key, hex",
+            Self::Npub => "npub",
+            Self::Nsec => "nsec",
+            Self::Mnemonic => "mnemonic",
+            Self::Base64Secret => "secret key, base64",
+            Self::PaperBackup => "paper backup",
+            Self::TomlConfig => "TOML config",
+        };
+        write!(f, "{}", desc)
+    }
+}
+
+/// Format of an export of the keys, for size estimation
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub(crate) enum ExportFormat {
+    /// Public key, in 'npub' bech32 format (public key file)
+    Npub,
+    /// Encrypted secret key, in hex (secret key file, see `save_encrypted_secret_key`)
+    EncryptedSecretKey,
+    /// Encrypted secret key, in 'ncryptsec' bech32 format
+    Ncryptsec,
+    /// Enrollment QR codes, as SVG, all frames together (see `enrollment_qr`)
+    EnrollmentQr,
+}
+
+/// Age of the keys, in coarse buckets, for display
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub(crate) enum AgeBucket {
+    /// Creation time is not known (e.g. imported keys)
+    Unknown,
+    /// Less than a minute
+    JustNow,
+    /// Less than an hour
+    Minutes,
+    /// Less than a day
+    Hours,
+    /// Less than 30 days
+    Days,
+    Older,
+}
+
+impl fmt::Display for AgeBucket {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let desc = match self {
+            Self::Unknown => "creation time unknown",
+            Self::JustNow => "created just now",
+            Self::Minutes => "created minutes ago",
+            Self::Hours => "created hours ago",
+            Self::Days => "created days ago",
+            Self::Older => "created over a month ago",
+        };
+        write!(f, "{}", desc)
+    }
+}
+
+/// Overall security warning level of the keys, for a color-coded badge
+#[derive(Clone, Copy, Debug, Eq, Ord, PartialEq, PartialOrd)]
+pub(crate) enum WarningLevel {
+    Good,
+    Caution,
+    Danger,
+}
+
+/// A reason for a security warning
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub(crate) enum SecurityWarning {
+    /// The secret key is publicly known (e.g. a test vector), anyone can sign with it
+    KnownCompromised,
+    /// The secret key has suspiciously low entropy (e.g. a small number or repeated bytes)
+    LowEntropy,
+    /// The secret key is stored encrypted with an empty password, which is practically plaintext
+    EmptyPassword,
+}
+
+impl SecurityWarning {
+    /// Warning level of this reason
+    pub fn level(&self) -> WarningLevel {
+        match self {
+            Self::KnownCompromised | Self::LowEntropy => WarningLevel::Danger,
+            Self::EmptyPassword => WarningLevel::Caution,
+        }
+    }
+
+    pub fn message(&self) -> &'static str {
+        match self {
+            Self::KnownCompromised => "The secret key is publicly known, anyone can sign with it!",
+            Self::LowEntropy => "The secret key has low entropy, it may be guessed",
+            Self::EmptyPassword => "The secret key is encrypted with an empty password",
+        }
+    }
+}
+
+/// Security assessment of the keys: overall level, and the reasons for it
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub(crate) struct SecurityAssessment {
+    pub level: WarningLevel,
+    reasons: Vec<SecurityWarning>,
+}
+
+impl SecurityAssessment {
+    fn from_reasons(reasons: Vec<SecurityWarning>) -> Self {
+        Self {
+            level: reasons
+                .iter()
+                .map(|r| r.level())
+                .max()
+                .unwrap_or(WarningLevel::Good),
+            reasons,
+        }
+    }
+
+    /// Reasons of the warnings, empty if `Good`
+    pub fn reasons(&self) -> &[SecurityWarning] {
+        &self.reasons
+    }
+}
+
+/// Result of re-validating the NIP-05 identity of the keys
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub(crate) enum IdentityStatus {
+    /// NIP-05 identifier verifies, and it matches the published profile (if found)
+    Verified,
+    /// NIP-05 identifier does not verify (does not point to this public key, or not reachable)
+    Nip05Failed(String),
+    /// NIP-05 identifier verifies, but the published profile (kind 0) contains a different one
+    ProfileMismatch(Option<String>),
+    /// NIP-05 identifier verifies, but the published profile could not be fetched, so it is unknown
+    /// if it matches (not verified)
+    ProfileUnknown(String),
+    /// Public key is not set
+    KeyNotSet,
+    /// Offline mode, could not be checked
+    Offline,
+    /// The check was cancelled
+    Cancelled,
+}
+
+impl fmt::Display for IdentityStatus {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Self::Verified => write!(f, "NIP-05 identity verified"),
+            Self::Nip05Failed(e) => write!(f, "NIP-05 identity does not verify: {e}"),
+            Self::ProfileMismatch(Some(other)) => write!(
+                f,
+                "NIP-05 identity verifies, but the published profile has '{other}'"
+            ),
+            Self::ProfileMismatch(None) => write!(
+                f,
+                "NIP-05 identity verifies, but the published profile has none"
+            ),
+            Self::ProfileUnknown(e) => write!(
+                f,
+                "NIP-05 identity verifies, but the published profile could not be checked: {e}"
+            ),
+            Self::KeyNotSet => write!(f, "NIP-05 identity not checked, no keys"),
+            Self::Offline => write!(f, "NIP-05 identity not checked, offline mode"),
+            Self::Cancelled => write!(f, "NIP-05 identity check cancelled"),
+        }
+    }
+}
+
+/// An event signed with an explicit timestamp, see `Keystore::sign_event_at`
+#[derive(Clone, Debug)]
+pub(crate) struct TimestampedEvent {
+    pub event: Event,
+    /// The timestamp is too far in the future, relays will likely reject the event
+    pub far_future: bool,
+}
+
+/// A signed HTTP auth event (NIP-98), with the corresponding `Authorization` header value
+#[derive(Clone, Debug)]
+pub(crate) struct HttpAuth {
+    pub event: Event,
+    /// Value of the `Authorization` header: `Nostr <base64-encoded event JSON>`
+    pub authorization_header: String,
+}
+
+/// Maximum length of the label of the keys (characters)
+pub(crate) const MAX_LABEL_LEN: usize = 64;
+/// Consecutive unused accounts after which account discovery stops, see `discover_used_accounts`
+pub(crate) const DISCOVERY_GAP_LIMIT: u32 = 5;
+/// Bech32 prefix for the encrypted secret key (NIP-49 layout)
+const NCRYPTSEC_HRP: &str = "ncryptsec";
+/// Name of the TOML table of the public config, see `to_toml_fragment`
+const TOML_TABLE: &str = "keystr";
+/// Timestamps later than this ahead of the current time are flagged as far future
+const MAX_FUTURE_TIMESTAMP_DRIFT: Duration = Duration::from_secs(15 * 60);
+/// Event kind of HTTP auth events (NIP-98)
+const HTTP_AUTH_KIND: u64 = 27235;
+/// HTTP methods accepted for HTTP auth (NIP-98)
+const HTTP_AUTH_METHODS: &[&str] = &["GET", "HEAD", "POST", "PUT", "PATCH", "DELETE", "OPTIONS"];
+/// Number of characters of data in one frame of the enrollment QR
+const ENROLLMENT_FRAME_CHUNK_SIZE: usize = 50;
+/// Exports estimated larger than this (bytes) are better saved to a file than shown as QR codes
+pub(crate) const MAX_QR_EXPORT_SIZE: usize = 256 * 1024;
+/// Fixed message signed by the self-test
+const SELF_TEST_MESSAGE: &[u8] = b"keystr self-test";
+/// First line of paper backups
+const PAPER_BACKUP_HEADER: &str = "keystr paper backup v1";
+/// Field names of paper backups, in the order of the lines
+const PAPER_BACKUP_FIELDS: [&str; 3] = ["npub", "ncryptsec", "checksum"];
+/// DER prefix of a SubjectPublicKeyInfo with a compressed secp256k1 public key:
+/// algorithm id-ecPublicKey (1.2.840.10045.2.1), curve secp256k1 (1.3.132.0.10), bit string of 33 bytes
+const SPKI_SECP256K1_COMPRESSED_PREFIX: [u8; 23] = [
+    0x30, 0x36, 0x30, 0x10, 0x06, 0x07, 0x2a, 0x86, 0x48, 0xce, 0x3d, 0x02, 0x01, 0x06, 0x05, 0x2b,
+    0x81, 0x04, 0x00, 0x0a, 0x03, 0x22, 0x00,
+];
+/// Public keys (hex) of publicly known secret keys: test vectors of this crate and of NIP-06
+const KNOWN_COMPROMISED_PUBKEYS: &[&str] = &[
+    "1a459a8a6aa6441d480ba665fb8fb21a4cfe8bcacb7d87300f8046a558a3fce4",
+    "5e04867dccc34c1a26bb09c60cdc7dd9b4d06760d30f9426206a45d14cf8879a",
+    "17162c921dc4d2518f9a101db33695df1afb56ab82f5ff3e5da6eec3ca5cd917",
+    "d41b22899549e1f3d335a31002cfd382174006e166d3e658e3a5eecdb6463573",
+];
+/// Field names used for the secret key in JSON exports of other tools (compared case-insensitively)
+const GENERIC_JSON_SECRET_FIELDS: [&str; 7] = [
+    "nsec",
+    "sec",
+    "privkey",
+    "private_key",
+    "privatekey",
+    "secret_key",
+    "secretkey",
+];
+/// Field names used for the relays in JSON exports of other tools (compared case-insensitively)
+const GENERIC_JSON_RELAY_FIELDS: [&str; 3] = ["relays", "relay_list", "relaylist"];
+/// Placeholder for secrets in debug output
+const REDACTED: &str = "[redacted]";
+/// Secret keys with fewer distinct byte values than this are considered low-entropy
+const MIN_DISTINCT_SECRET_BYTES: usize = 8;
+
+/// Displayable, public-only state of the keystore, e.g. for a render thread.
+/// Holds no secret (not even encrypted), so it can be freely cloned and shared across threads.
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub(crate) struct PublicSnapshot {
+    /// Public key, in 'npub' bech32 format, if set
+    pub npub: Option<String>,
+    pub set_state: KeysSetState,
+    pub origin: Option<KeyOrigin>,
+}
+
+/// Model for KeyStore part
+#[readonly::make]
+pub(crate) struct Keystore {
+    #[readonly]
+    has_unsaved_change: bool,
+    /// The keys, if set; when not set, no key material is held at all (no placeholder keys)
+    keys: Option<Keys>,
+    encrypted_secret_key: Option<Vec<u8>>,
+    /// Origin of the keys (how they were created or imported)
+    #[readonly]
+    origin: Option<KeyOrigin>,
+    /// Creation time of the keys (unix seconds), known only if generated here
+    created_at: Option<u64>,
+    /// Policy on allowed key origins, checked on import
+    pub key_policy: KeyPolicy,
+    /// Auto-lock: inactivity period after which the secret key should be locked, if set
+    #[readonly]
+    auto_lock_after: Option<Duration>,
+    /// Time of the last unlock or secret key use, for auto-lock
+    last_activity: Option<Instant>,
+    /// If set, revealing the secret key is refused once the auto-lock period has expired
+    pub require_unlock_for_reveal: bool,
+    /// If set, only events of these kinds are signed
+    #[readonly]
+    allowed_kinds: Option<HashSet<u16>>,
+    pub hide_secret_key: bool,
+    /// User-given label of the keys, e.g. for identifying accounts
+    pub label: String,
+    /// User-given freeform note about the keys; may be sensitive, not included in public exports
+    note: String,
+    /// Usage statistics of relays used for publishing and fetching
+    relay_stats: RelayUsageStats,
+    /// Signing activity since the keys were set or unlocked; shared with the signers from `get_signer`,
+    /// so that their signatures (e.g. NIP-46 requests) are counted too. Replaced (not reset) on key change,
+    /// so that signers of previous keys do not count into it.
+    signing_stats: Arc<Mutex<SigningStats>>,
+    /// If the secret key was last saved or decrypted with an empty password (updated on saving, which takes `&self`)
+    empty_password: Cell<bool>,
+    /// Expiry of the last timed reveal of the secret key, see `reveal_nsec_timed`
+    reveal_expires_at: Option<Instant>,
+    /// Offline mode: if set, network operations fail with `OfflineMode`, without opening connections
+    #[readonly]
+    offline: bool,
+    /// Settings of network operations (timeout), e.g. raised timeout for slow connections
+    pub network_config: NetworkConfig,
+    /// Scrypt cost (log2 of N) for encrypting the secret key (save, ncryptsec export).
+    /// Decryption reads it from the encrypted key.
+    #[readonly]
+    scrypt_log_n: u8,
+    /// Input for the retyped secret key, for confirming its backup
+    pub backup_confirm_input: String,
+    /// Input for the label
+    pub label_input: String,
+    /// Input for the allowed event kinds, comma-separated; empty allows all
+    pub allowed_kinds_input: String,
+    /// Input for relay URLs, for network operations; comma- or whitespace-separated
+    pub relays_input: String,
+    /// Input for the NIP-05 identifier, for verifying it against the keys
+    pub nip05_input: String,
+    /// Input for a text note to publish, signed with the keys
+    pub publish_note_input: String,
+    /// Input for the authentication challenge of a relay (NIP-42), see `sign_auth_challenge`
+    pub auth_challenge_input: String,
+    /// Input for the recipient (npub or hex) of a gift wrap (NIP-59), see `create_gift_wrap`
+    pub gift_wrap_recipient_input: String,
+    /// Input for a received gift wrap event (JSON) to open, see `unwrap_gift`
+    pub gift_wrap_input: String,
+    /// Input for the creation time (unix seconds) of a backdated note, see `sign_event_at`
+    pub created_at_input: String,
+    /// Input for a Nostr Wallet Connect URI (NIP-47); contains the secret of the wallet connection
+    pub nwc_uri_input: String,
+    /// Input for a lightning invoice to pay through the wallet connection
+    pub invoice_input: String,
+    /// Input for the HTTP method of a request to authenticate (NIP-98), see `create_http_auth`
+    pub http_auth_method_input: String,
+    /// Input for the URL of a request to authenticate (NIP-98)
+    pub http_auth_url_input: String,
+    /// Input for the SHA-256 hash (hex) of the body of a request to authenticate (NIP-98), optional
+    pub http_auth_payload_input: String,
+    /// Input for the peers (comma-separated npub or hex) to derive NIP-44 conversation keys with
+    pub conversation_peers_input: String,
+    /// Input for an 'ncryptsec' backup to re-encrypt with a new password, see `reencrypt_ncryptsec_action`
+    pub reencrypt_input: String,
+    /// Input for the current password of the backup to re-encrypt
+    pub reencrypt_old_password_input: String,
+    /// Input for the new password of the backup to re-encrypt
+    pub reencrypt_new_password_input: String,
+    /// Input for repeating the new password of the backup to re-encrypt
+    pub reencrypt_repeat_password_input: String,
+    /// Input for an air-gapped signing request or response, see `sign_offline_request`
+    pub offline_signing_input: String,
+    /// Input for converting a key between formats, without importing it, see `convert_key_action`
+    pub convert_input: String,
+    /// Input for a vanity prefix of the npub, see `npub_prefix_match`
+    pub vanity_prefix_input: String,
+    /// Input for the text payload of a scanned QR code, see `import_from_qr_payload`
+    pub qr_payload_input: String,
+    /// Input for the challenge of in-person key verification, see `verification_qr`
+    pub verification_challenge_input: String,
+    /// Input for a verification payload scanned from someone else, checked against the challenge
+    pub verification_payload_input: String,
+    /// Input for public key import
+    pub public_key_input: String,
+    /// Input for secret key import
+    pub secret_key_input: String,
+    /// Input for import with an explicitly selected format
+    pub format_import_input: String,
+    /// The selected format, for import with explicit format
+    pub import_format: ImportFormat,
+    /// The encrypted key (ncryptsec) pasted for import, waiting for its password, see `import_ncryptsec_action`
+    pub ncryptsec_input: String,
+    /// If set, the secret key input is shown unmasked
+    pub reveal_input: bool,
+    /// Input for BIP39 mnemonic
+    pub mnemonic_input: String,
+    /// Input for optional BIP39 passphrase (kept separate from the mnemonic words)
+    pub mnemonic_passphrase_input: String,
+    /// Input for the repeated BIP39 passphrase, for generating keys from a new mnemonic
+    pub mnemonic_passphrase_repeat_input: String,
+    /// Input for optional custom BIP32 derivation path, for mnemonic import; NIP-06 path if empty
+    pub derivation_path_input: String,
+    /// Input for encryption password, for decrypt
+    pub decrypt_password_input: String,
+    /// Input for encryption password, for save
+    pub save_password_input: String,
+    /// Input for repeat encryption password, for save
+    pub save_repeat_password_input: String,
+}
+
+/// Debug output with the set state and the public key; secrets and inputs are never included
+impl fmt::Debug for Keystore {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let npub = self
+            .get_public_key()
+            .ok()
+            .and_then(|pk| pk.to_bech32().ok());
+        let secret = if self.is_secret_key_set() || self.encrypted_secret_key.is_some() {
+            Some(REDACTED)
+        } else {
+            None
+        };
+        f.debug_struct("Keystore")
+            .field("set_state", &self.get_set_state())
+            .field("npub", &npub)
+            .field("secret", &secret)
+            .field("origin", &self.origin)
+            .field("has_unsaved_change", &self.has_unsaved_change)
+            .field("offline", &self.offline)
+            .finish_non_exhaustive()
+    }
+}
+
+impl Keystore {
+    pub fn new() -> Self {
+        Keystore {
+            has_unsaved_change: false,
+            keys: None,
+            encrypted_secret_key: None,
+            origin: None,
+            created_at: None,
+            key_policy: KeyPolicy::default(),
+            auto_lock_after: None,
+            last_activity: None,
+            require_unlock_for_reveal: false,
+            allowed_kinds: None,
+            hide_secret_key: true,
+            label: String::new(),
+            note: String::new(),
+            relay_stats: RelayUsageStats::default(),
+            signing_stats: Arc::default(),
+            empty_password: Cell::new(false),
+            reveal_expires_at: None,
+            offline: false,
+            network_config: NetworkConfig::default(),
+            scrypt_log_n: Encrypt::default_log2_rounds(),
+            backup_confirm_input: String::new(),
+            label_input: String::new(),
+            allowed_kinds_input: String::new(),
+            relays_input: String::new(),
+            nip05_input: String::new(),
+            publish_note_input: String::new(),
+            auth_challenge_input: String::new(),
+            gift_wrap_recipient_input: String::new(),
+            gift_wrap_input: String::new(),
+            created_at_input: String::new(),
+            nwc_uri_input: String::new(),
+            invoice_input: String::new(),
+            http_auth_method_input: String::new(),
+            http_auth_url_input: String::new(),
+            http_auth_payload_input: String::new(),
+            conversation_peers_input: String::new(),
+            reencrypt_input: String::new(),
+            reencrypt_old_password_input: String::new(),
+            reencrypt_new_password_input: String::new(),
+            reencrypt_repeat_password_input: String::new(),
+            offline_signing_input: String::new(),
+            convert_input: String::new(),
+            vanity_prefix_input: String::new(),
+            qr_payload_input: String::new(),
+            verification_challenge_input: String::new(),
+            verification_payload_input: String::new(),
+            public_key_input: String::new(),
+            secret_key_input: String::new(),
+            format_import_input: String::new(),
+            import_format: ImportFormat::Nsec,
+            ncryptsec_input: String::new(),
+            reveal_input: false,
+            mnemonic_input: String::new(),
+            mnemonic_passphrase_input: String::new(),
+            mnemonic_passphrase_repeat_input: String::new(),
+            derivation_path_input: String::new(),
+            decrypt_password_input: String::new(),
+            save_password_input: String::new(),
+            save_repeat_password_input: String::new(),
+        }
+    }
+
+    /// Create with already existing keys (public only, or secret and public)
+    pub fn from_keys(keys: Keys) -> Self {
+        let mut k = Self::new();
+        k.keys = Some(keys);
+        k
+    }
+
+    /// Create with a locked (encrypted) secret key, and its public key
+    pub(crate) fn from_locked(public_key: XOnlyPublicKey, encrypted_secret_key: Vec<u8>) -> Self {
+        let mut k = Self::from_keys(Keys::from_public_key(public_key));
+        k.encrypted_secret_key = Some(encrypted_secret_key);
+        k
+    }
+
+    /// Action to clear existing keys
+    pub fn clear(&mut self) {
+        self.keys = None;
+        self.encrypted_secret_key = None;
+        self.origin = None;
+        self.created_at = None;
+        self.has_unsaved_change = false;
+        self.signing_stats = Arc::default();
+        self.empty_password.set(false);
+        self.reveal_expires_at = None;
+    }
+
+    /// Warning: Security-sensitive method!
+    /// Emergency wipe: zeroize and clear all secrets and inputs, reset to not set (also label and note),
+    /// and optionally overwrite and delete the keystore file. Idempotent: a missing file is not an error.
+    pub fn panic_wipe(&mut self, also_delete_file: Option<&Path>) -> Result<(), Error> {
+        if let Some(encrypted) = self.encrypted_secret_key.as_mut() {
+            encrypted.zeroize();
+        }
+        self.clear();
+        for input in [
+            &mut self.public_key_input,
+            &mut self.secret_key_input,
+            &mut self.format_import_input,
+            &mut self.ncryptsec_input,
+            &mut self.mnemonic_input,
+            &mut self.mnemonic_passphrase_input,
+            &mut self.mnemonic_passphrase_repeat_input,
+            &mut self.derivation_path_input,
+            &mut self.decrypt_password_input,
+            &mut self.save_password_input,
+            &mut self.save_repeat_password_input,
+            &mut self.label,
+            &mut self.label_input,
+            &mut self.backup_confirm_input,
+            &mut self.relays_input,
+            &mut self.nip05_input,
+            &mut self.publish_note_input,
+            &mut self.auth_challenge_input,
+            &mut self.gift_wrap_recipient_input,
+            &mut self.gift_wrap_input,
+            &mut self.created_at_input,
+            &mut self.nwc_uri_input,
+            &mut self.invoice_input,
+            &mut self.http_auth_method_input,
+            &mut self.http_auth_url_input,
+            &mut self.http_auth_payload_input,
+            &mut self.conversation_peers_input,
+            &mut self.reencrypt_input,
+            &mut self.reencrypt_old_password_input,
+            &mut self.reencrypt_new_password_input,
+            &mut self.reencrypt_repeat_password_input,
+            &mut self.offline_signing_input,
+            &mut self.convert_input,
+            &mut self.vanity_prefix_input,
+            &mut self.qr_payload_input,
+            &mut self.verification_challenge_input,
+            &mut self.verification_payload_input,
+            &mut self.note,
+        ] {
+            input.zeroize();
+        }
+        self.relay_stats = RelayUsageStats::default();
+        self.reveal_input = false;
+        self.last_activity = None;
+        match also_delete_file {
+            None => Ok(()),
+            Some(path) => Self::overwrite_and_delete(path),
+        }
+    }
+
+    /// Overwrite the file contents with zeroes, and delete it. A missing file is not an error.
+    fn overwrite_and_delete(path: &Path) -> Result<(), Error> {
+        let len = match fs::metadata(path) {
+            Ok(m) => m.len(),
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => return Ok(()),
+            Err(e) => return Err(e.into()),
+        };
+        let mut file = fs::OpenOptions::new().write(true).open(path)?;
+        file.write_all(&vec![0u8; len as usize])?;
+        file.sync_all()?;
+        drop(file);
+        match fs::remove_file(path) {
+            Err(e) if e.kind() != std::io::ErrorKind::NotFound => Err(e.into()),
+            _ => Ok(()),
+        }
+    }
+
+    /// Set new keys (replacing existing ones), if their origin is allowed by the key policy
+    fn set_keys(&mut self, keys: Keys, origin: KeyOrigin, is_changed: bool) -> Result<(), Error> {
+        self.key_policy.check(origin)?;
+        self.replace_keys(keys, origin, is_changed);
+        Ok(())
+    }
+
+    /// Restore own stored keys (replacing existing ones). The key policy is not checked:
+    /// it applies to imports, and stored keys were checked when they were imported.
+    fn restore_keys(&mut self, keys: Keys, is_changed: bool) {
+        self.replace_keys(keys, KeyOrigin::Storage, is_changed);
+    }
+
+    fn replace_keys(&mut self, keys: Keys, origin: KeyOrigin, is_changed: bool) {
+        self.clear();
+        log_key_event(
+            match origin {
+                KeyOrigin::Generated => "key.generated",
+                KeyOrigin::Storage => "key.loaded",
+                _ => "key.imported",
+            },
+            &keys.public_key(),
+        );
+        self.keys = Some(keys);
+        self.signing_stats = Arc::default();
+        self.origin = Some(origin);
+        self.has_unsaved_change = is_changed;
+    }
+
+    fn current_time() -> u64 {
+        SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .unwrap()
+            .as_secs()
+    }
+
+    /// Generate new random keys
+    pub fn generate(&mut self) -> Result<(), Error> {
+        self.set_keys(Keys::generate(), KeyOrigin::Generated, true)?;
+        self.created_at = Some(Self::current_time());
+        Ok(())
+    }
+
+    /// Generate new random keys, and an introduction text note (kind 1) signed with them, ready to publish
+    pub fn generate_with_intro(&mut self, intro_text: &str) -> Result<Event, Error> {
+        // check before changing the keys
+        check_kind_allowed(&self.allowed_kinds, &Kind::TextNote)?;
+        self.generate()?;
+        self.sign_text_note(intro_text)
+    }
+
+    /// Import public key only, in 'npub' bech32 or hex format. Signing will not be possible.
+    pub fn import_public_key(&mut self, public_key_str: &str) -> Result<(), Error> {
+        self.set_keys(
+            Keys::from_pk_str(&normalize_input(public_key_str)?)?,
+            KeyOrigin::PublicKey,
+            true,
+        )
+    }
+
+    /// Warning: Security-sensitive method!
+    /// Import secret key, in 'nsec' bech32 or hex format (pubkey is derived from it)
+    pub fn import_secret_key(
+        &mut self,
+        secret_key_str: &str,
+        is_changed: bool,
+    ) -> Result<(), Error> {
+        let keys = Self::parse_secret_key(secret_key_str)?;
+        self.set_keys(keys, KeyOrigin::SecretKey, is_changed)
+    }
+
+    /// Warning: Security-sensitive method!
+    /// Parse a secret key, in 'nsec' bech32 or hex format, without changing the state
+    fn parse_secret_key(secret_key_str: &str) -> Result<Keys, Error> {
+        let mut input = normalize_input(secret_key_str)?;
+        let keys = Keys::from_sk_str(&input);
+        input.zeroize();
+        Ok(keys?)
+    }
+
+    /// Warning: Security-sensitive method!
+    /// Confirm that the user has backed up the secret key, by comparing the retyped nsec
+    /// to the stored one (in constant time).
+    pub fn confirm_backup(&self, retyped_nsec: &str) -> Result<(), Error> {
+        let mut nsec = self.get_secret_key()?.to_bech32()?;
+        let matches = constant_time_eq(nsec.as_bytes(), retyped_nsec.trim().as_bytes());
+        nsec.zeroize();
+        if matches {
+            Ok(())
+        } else {
+            Err(Error::KeyBackupConfirmationMismatch)
+        }
+    }
+
+    /// Warning: Security-sensitive method!
+    /// Import from the text payload of a scanned QR code: a BIP39 mnemonic (phrase or SeedQR),
+    /// an nsec, or an npub.
+    pub fn import_from_qr_payload(&mut self, payload: &str) -> Result<(), Error> {
+        if let Some(mut mnemonic) = mnemonic_from_qr_payload(payload) {
+            let res = self.import_mnemonic(&mnemonic, None, true);
+            mnemonic.zeroize();
+            return res;
+        }
+        let mut input = normalize_input(payload)?;
+        let res = if input.starts_with("nsec1") {
+            self.import_secret_key(&input, true)
+        } else if input.starts_with("npub1") {
+            self.import_public_key(&input)
+        } else {
+            Err(Error::QrUnrecognizedPayload)
+        };
+        input.zeroize();
+        res
+    }
+
+    /// Warning: Security-sensitive method!
+    /// Import only the public key derived from a secret key ('nsec' bech32 or hex), watch-only.
+    /// The secret key is not retained, the parsed keys are dropped right after deriving the public key.
+    pub fn import_public_from_secret(&mut self, secret_key_str: &str) -> Result<(), Error> {
+        let public_key = Self::parse_secret_key(secret_key_str)?.public_key();
+        self.set_keys(
+            Keys::from_public_key(public_key),
+            KeyOrigin::PublicKey,
+            true,
+        )
+    }
+
+    /// Warning: Security-sensitive method!
+    /// Upgrade a public-only (or locked) keystore in place with the matching secret key ('nsec' bech32 or hex),
+    /// instead of importing it as new keys. Label, note and creation time are preserved.
+    /// The secret key has to belong to the current public key.
+    pub fn upgrade_with_secret(&mut self, nsec: &str) -> Result<(), Error> {
+        let public_key = self.get_public_key()?;
+        let keys = Self::parse_secret_key(nsec)?;
+        if keys.public_key() != public_key {
+            return Err(Error::KeyIdentityMismatch);
+        }
+        // same identity, keep its creation time
+        let created_at = self.created_at;
+        self.set_keys(keys, KeyOrigin::SecretKey, true)?;
+        self.created_at = created_at;
+        Ok(())
+    }
+
+    /// Warning: Security-sensitive method!
+    /// Import a public or secret key, in the explicitly given format (no auto-detection)
+    pub fn import_with_format(&mut self, input: &str, format: ImportFormat) -> Result<(), Error> {
+        // the normalized copy of a key is zeroized on drop
+        let normalized = || normalize_input(input).map(Zeroizing::new);
+        let (keys, origin) = match format {
+            ImportFormat::HexPublic => (
+                Keys::from_public_key(XOnlyPublicKey::from_str(normalized()?.as_str())?),
+                KeyOrigin::PublicKey,
+            ),
+            ImportFormat::HexSecret => (
+                Keys::new(SecretKey::from_str(normalized()?.as_str())?),
+                KeyOrigin::SecretKey,
+            ),
+            ImportFormat::Npub => (
+                Keys::from_public_key(XOnlyPublicKey::from_bech32(normalized()?.as_str())?),
+                KeyOrigin::PublicKey,
+            ),
+            ImportFormat::Nsec => (
+                Keys::new(SecretKey::from_bech32(normalized()?.as_str())?),
+                KeyOrigin::SecretKey,
+            ),
+            ImportFormat::Mnemonic => return self.import_mnemonic(input, None, true),
+            // base64 is case-sensitive, only trimmed
+            ImportFormat::Base64Secret => return self.import_base64_secret(input.trim()),
+            ImportFormat::PaperBackup => return self.import_from_paper_backup(input),
+            ImportFormat::TomlConfig => {
+                let loaded = Self::from_toml_fragment(input)?;
+                self.set_keys(loaded.get_keys()?.clone(), KeyOrigin::PublicKey, true)?;
+                if !loaded.label.is_empty() {
+                    self.set_label(&loaded.label)?;
+                }
+                return Ok(());
+            }
+        };
+        self.set_keys(keys, origin, true)
+    }
+
+    /// Warning: Security-sensitive method!
+    /// Import a secret key given as its raw 32 bytes in base64 (with or without padding)
+    fn import_base64_secret(&mut self, input: &str) -> Result<(), Error> {
+        let decoded = match input.len() {
+            44 => STANDARD.decode(input),
+            43 => STANDARD_NO_PAD.decode(input),
+            _ => return Err(Error::KeyInvalidBase64Secret),
+        };
+        let mut bytes = decoded.map_err(|_e| Error::KeyInvalidBase64Secret)?;
+        let secret_key = if bytes.len() == 32 {
+            SecretKey::from_slice(&bytes).map_err(|_e| Error::KeyInvalidBase64Secret)
+        } else {
+            Err(Error::KeyInvalidBase64Secret)
+        };
+        bytes.zeroize();
+        self.set_keys(Keys::new(secret_key?), KeyOrigin::SecretKey, true)
+    }
+
+    /// Warning: Security-sensitive method!
+    /// Import secret key and relays from a JSON exported by another tool, e.g.
+    /// `{ "nsec": "...", "relays": [...] }`. Field names are matched leniently
+    /// (see `GENERIC_JSON_SECRET_FIELDS`), the secret key is validated (nsec or hex).
+    /// Relays can be an array of URLs, or an object keyed by URL; invalid URLs are skipped,
+    /// the valid ones are put in the relays input.
+    /// On error the keystore is not changed.
+    pub fn import_generic_json(&mut self, json: &str) -> Result<(), Error> {
+        let value = serde_json::from_str::<serde_json::Value>(json)?;
+        let obj = match value.as_object() {
+            Some(o) => o,
+            None => return Err(Error::ImportJsonNoSecretKey),
+        };
+        let find_field = |names: &[&str]| {
+            obj.iter()
+                .find(|(k, _v)| names.contains(&k.to_lowercase().as_str()))
+                .map(|(_k, v)| v)
+        };
+        let secret_key = find_field(&GENERIC_JSON_SECRET_FIELDS)
+            .and_then(|v| v.as_str())
+            .ok_or(Error::ImportJsonNoSecretKey)?;
+        let relays: Vec<String> = match find_field(&GENERIC_JSON_RELAY_FIELDS) {
+            Some(serde_json::Value::Array(a)) => a
+                .iter()
+                .filter_map(|r| r.as_str().map(|s| s.to_string()))
+                .collect(),
+            Some(serde_json::Value::Object(o)) => o.keys().cloned().collect(),
+            _ => Vec::new(),
+        };
+        self.import_secret_key(secret_key.trim(), true)?;
+        let relays: Vec<String> = relays
+            .into_iter()
+            .filter(|r| Url::parse(r).is_ok())
+            .collect();
+        if !relays.is_empty() {
+            self.relays_input = relays.join(", ");
+        }
+        Ok(())
+    }
+
+    /// Warning: Security-sensitive method!
+    /// Import a key, with the format auto-detected: npub, nsec, BIP39 mnemonic, or BIP32 extended key.
+    /// A JSON object is imported with `import_generic_json`.
+    /// Hex keys are ambiguous (public or secret), they have to be imported with `import_with_format`.
+    /// Inputs recognized as something else (e.g. lightning address, note) are rejected with `Error::NotAKey`,
+    /// so that the user can be told what was pasted.
+    /// For an encrypted key (ncryptsec) `Error::KeyNeedsPassword` is returned, so that the password
+    /// can be asked for, and the key imported with `import_ncryptsec`.
+    pub fn import_auto(&mut self, input: &str) -> Result<(), Error> {
+        let mut normalized = normalize_input(input)?;
+        if normalized.starts_with('{') {
+            let res = self.import_generic_json(&normalized);
+            normalized.zeroize();
+            return res;
+        }
+        let res = match detect_input_kind(&normalized) {
+            InputKind::Npub => self.import_public_key(&normalized),
+            InputKind::Nsec => self.import_secret_key(&normalized, true),
+            InputKind::Mnemonic => self.import_mnemonic(&normalized, None, true),
+            InputKind::ExtendedKey => self.import_xprv(&normalized, None),
+            InputKind::Hex => Err(Error::KeyHexAmbiguous),
+            InputKind::EncryptedKey => Err(Error::KeyNeedsPassword),
+            InputKind::Unknown => Err(Error::KeyUnrecognizedFormat),
+            detected => Err(Error::NotAKey { detected }),
+        };
+        normalized.zeroize();
+        res
+    }
+
+    /// Warning: Security-sensitive method!
+    /// Import a key from the clipboard, with the format auto-detected (see `import_auto`).
+    /// Returns the resulting set state.
+    #[cfg(feature = "clipboard")]
+    pub fn import_from_clipboard(&mut self) -> Result<KeysSetState, Error> {
+        self.import_from_clipboard_content(crate::model::clipboard::read_clipboard_text())
+    }
+
+    /// Warning: Security-sensitive method!
+    /// Import from the content read from the clipboard; `ClipboardEmpty` if there is no text.
+    /// A pasted encrypted key (ncryptsec) is kept in `ncryptsec_input`, until its password is entered.
+    fn import_from_clipboard_content(
+        &mut self,
+        content: Option<String>,
+    ) -> Result<KeysSetState, Error> {
+        let mut content = match content {
+            Some(c) if !c.trim().is_empty() => c,
+            _ => return Err(Error::ClipboardEmpty),
+        };
+        let res = self.import_auto(&content);
+        if let Err(Error::KeyNeedsPassword) = res {
+            self.ncryptsec_input = normalize_input(&content).unwrap_or_default();
+        }
+        content.zeroize();
+        res.map(|_| self.get_set_state())
+    }
+
+    /// Warning: Security-sensitive method!
+    pub fn import_encrypted_secret_key(
+        &mut self,
+        encrypted_key_str: &str,
+        is_changed: bool,
+    ) -> Result<(), Error> {
+        self.clear();
+        self.encrypted_secret_key =
+            Some(hex::decode(encrypted_key_str).map_err(|_e| Error::KeyInvalidEncrypted)?);
+        self.has_unsaved_change = is_changed;
+        Ok(())
+    }
+
+    /// Try to decrypt the already loaded encrypted key using the decryption password
+    /// It is recommend to zeroize() the password after use.
+    pub fn decrypt_secret_key(&mut self, password: &str) -> Result<(), Error> {
+        self.decrypt_secret_key_with(password, &CancellationToken::new(), None)
+    }
+
+    /// Warning: Security-sensitive method!
+    /// Decrypt the secret key, cancellable, with optional progress reporting.
+    /// On cancellation the state is not changed.
+    pub fn decrypt_secret_key_with(
+        &mut self,
+        password: &str,
+        cancel: &CancellationToken,
+        progress: KdfProgress,
+    ) -> Result<(), Error> {
+        let sk_bytes = match &self.encrypted_secret_key {
+            None => return Err(Error::KeyNotSet),
+            Some(d) => d,
+        };
+        let encrypted = sk_bytes.clone();
+        let sk = Encrypt::decrypt_key_with(&sk_bytes, &password, cancel, progress)?;
+        self.set_decrypted_keys(sk, encrypted)?;
+        self.empty_password.set(password.is_empty());
+        Ok(())
+    }
+
+    /// Warning: Security-sensitive method!
+    /// Import a secret key encrypted with a password, in 'ncryptsec' bech32 format.
+    /// Wrong password results in `KeyDecryptionFailed`.
+    pub fn import_ncryptsec(&mut self, ncryptsec: &str, password: &str) -> Result<(), Error> {
+        let encrypted = decode_ncryptsec(ncryptsec)?;
+        let sk = match Encrypt::decrypt_key(&encrypted, password) {
+            Ok(sk) => sk,
+            Err(Error::KeyEncryption) => return Err(Error::KeyDecryptionFailed),
+            Err(e) => return Err(e),
+        };
+        self.set_keys(Keys::new(sk), KeyOrigin::SecretKey, true)
+    }
+
+    /// Set keys from the decrypted secret key.
+    /// If a public key is already set (e.g. imported as public key only), the decrypted key has to match it.
+    fn set_decrypted_keys(&mut self, sk: SecretKey, encrypted: Vec<u8>) -> Result<(), Error> {
+        let keys = Keys::new(sk);
+        if let Some(existing) = &self.keys {
+            if existing.public_key() != keys.public_key() {
+                return Err(Error::KeyIdentityMismatch);
+            }
+        }
+        let is_changed = self.has_unsaved_change;
+        self.restore_keys(keys, is_changed);
+        // keep the encrypted key, so that it can be locked again
+        self.encrypted_secret_key = Some(encrypted);
+        self.last_activity = Some(Instant::now());
+        Ok(())
+    }
+
+    /// Set (or unset) auto-lock, the inactivity period after which the secret key should be locked.
+    /// The timer is (re)started.
+    pub fn set_auto_lock(&mut self, auto_lock_after: Option<Duration>) {
+        self.auto_lock_after = auto_lock_after;
+        self.last_activity = Some(Instant::now());
+    }
+
+    /// Check if auto-lock is set and its period has expired, relative to the given time
+    pub fn is_auto_lock_expired_at(&self, now: Instant) -> bool {
+        match (self.auto_lock_after, self.last_activity) {
+            (Some(period), Some(last)) => now.saturating_duration_since(last) >= period,
+            _ => false,
+        }
+    }
+
+    /// Lock the secret key: drop the decrypted secret key, keep only the encrypted secret key
+    /// and the public key. Possible only if encrypted secret key is set. Ends a timed reveal.
+    pub fn lock(&mut self) -> Result<(), Error> {
+        if self.encrypted_secret_key.is_none() {
+            return Err(Error::KeyNotSet);
+        }
+        if let Ok(pubkey) = self.get_public_key() {
+            self.keys = Some(Keys::from_public_key(pubkey));
+        }
+        self.signing_stats = Arc::default();
+        self.reveal_expires_at = None;
+        Ok(())
+    }
+
+    /// Warning: Security-sensitive method!
+    /// Import secret key from BIP39 mnemonic, with optional passphrase
+    pub fn import_mnemonic(
+        &mut self,
+        mnemonic: &str,
+        passphrase: Option<&str>,
+        is_changed: bool,
+    ) -> Result<(), Error> {
+        let secret_key = Self::derive_from_mnemonic(mnemonic, passphrase, 0)?;
+        self.set_keys(Keys::new(secret_key), KeyOrigin::Mnemonic, is_changed)
+    }
+
+    /// Warning: Security-sensitive method!
+    /// Generate a new random BIP39 mnemonic, and derive the keys from it using the passphrase.
+    /// The passphrase is mandatory, and has to be confirmed (repeated).
+    /// Returns the mnemonic; note that both the mnemonic and the passphrase are needed to restore the keys.
+    pub fn generate_with_mnemonic_and_passphrase(
+        &mut self,
+        passphrase: &str,
+        repeat_passphrase: &str,
+    ) -> Result<String, Error> {
+        if passphrase.is_empty() {
+            return Err(Error::KeyMnemonicPassphraseMissing);
+        }
+        if passphrase != repeat_passphrase {
+            return Err(Error::KeyMnemonicPassphraseMismatch);
+        }
+        let mut entropy: [u8; 16] = [0; 16];
+        OsRng.fill_bytes(&mut entropy);
+        let mnemonic = bip39::Mnemonic::from_entropy(&entropy)?.to_string();
+        entropy.zeroize();
+        self.import_mnemonic(&mnemonic, Some(passphrase), true)?;
+        self.created_at = Some(Self::current_time());
+        Ok(mnemonic)
+    }
+
+    /// NIP-06 standard derivation path, for the given account
+    pub(crate) fn nip06_derivation_path(account: u32) -> String {
+        format!("m/44'/1237'/{account}'/0/0")
+    }
+
+    /// Parse and validate a BIP32 derivation path, such as `m/44'/1237'/0'/0/0`
+    fn parse_derivation_path(path: &str) -> Result<bip32::DerivationPath, Error> {
+        bip32::DerivationPath::from_str(path)
+            .map_err(|_e| Error::KeyInvalidDerivationPath(path.to_string()))
+    }
+
+    /// Warning: Security-sensitive method!
+    /// Derive secret key from BIP39 mnemonic, optional passphrase, and account, using NIP-06 derivation path
+    fn derive_from_mnemonic(
+        mnemonic: &str,
+        passphrase: Option<&str>,
+        account: u32,
+    ) -> Result<SecretKey, Error> {
+        Self::derive_from_mnemonic_path(mnemonic, passphrase, &Self::nip06_derivation_path(account))
+    }
+
+    /// Warning: Security-sensitive method!
+    /// Derive secret key from BIP39 mnemonic, optional passphrase, using an arbitrary BIP32 derivation path
+    fn derive_from_mnemonic_path(
+        mnemonic: &str,
+        passphrase: Option<&str>,
+        derivation_path: &str,
+    ) -> Result<SecretKey, Error> {
+        let path = Self::parse_derivation_path(derivation_path)?;
+        let mut normalized = normalize_mnemonic(mnemonic);
+        let mnemonic = bip39::Mnemonic::parse(&normalized);
+        normalized.zeroize();
+        let mnemonic = mnemonic?;
+        let seed = mnemonic.to_seed(passphrase.unwrap_or_default());
+        let child_xprv = bip32::XPrv::derive_from_path(&seed, &path)?;
+        let private_key = child_xprv.private_key();
+        Ok(SecretKey::from_slice(&private_key.to_bytes())?)
+    }
+
+    /// Warning: Security-sensitive method!
+    /// Import secret key from a BIP32 extended private key ('xprv' or 'tprv'), deriving along the given path,
+    /// or the NIP-06 standard path if not given. The path is relative to the extended key.
+    pub fn import_xprv(&mut self, xprv: &str, derivation_path: Option<&str>) -> Result<(), Error> {
+        let path = Self::parse_derivation_path(
+            &derivation_path
+                .map(|p| p.to_string())
+                .unwrap_or(Self::nip06_derivation_path(0)),
+        )?;
+        let extended_key =
+            bip32::ExtendedKey::from_str(xprv).map_err(|_e| Error::KeyInvalidExtendedKey)?;
+        if extended_key.prefix != bip32::Prefix::XPRV && extended_key.prefix != bip32::Prefix::TPRV
+        {
+            return Err(Error::KeyExtendedKeyUnsupportedPrefix(
+                extended_key.prefix.as_str().to_string(),
+            ));
+        }
+        let mut child_xprv = bip32::XPrv::try_from(extended_key)?;
+        for child_number in path.iter() {
+            child_xprv = child_xprv.derive_child(child_number)?;
+        }
+        let secret_key = SecretKey::from_slice(&child_xprv.private_key().to_bytes())?;
+        self.set_keys(Keys::new(secret_key), KeyOrigin::ExtendedKey, true)
+    }
+
+    /// Warning: Security-sensitive method!
+    /// Import secret key from BIP39 mnemonic, with optional passphrase, using a custom BIP32 derivation path
+    /// (e.g. `m/44'/1237'/0'/0/3`). For keys derived by other tools with non-default paths.
+    pub fn import_mnemonic_path(
+        &mut self,
+        mnemonic: &str,
+        passphrase: Option<&str>,
+        derivation_path: &str,
+    ) -> Result<(), Error> {
+        let secret_key = Self::derive_from_mnemonic_path(mnemonic, passphrase, derivation_path)?;
+        self.set_keys(Keys::new(secret_key), KeyOrigin::Mnemonic, true)
+    }
+
+    /// Preview the public key (npub) that would be imported from a BIP39 mnemonic, without setting anything
+    pub fn preview_mnemonic_npub(
+        mnemonic: &str,
+        passphrase: Option<&str>,
+        account: u32,
+    ) -> Result<String, Error> {
+        Ok(Self::derive_public_key_from_mnemonic(mnemonic, passphrase, account)?.to_bech32()?)
+    }
+
+    /// Public key of an account derived from a BIP39 mnemonic (NIP-06), the secret key is not kept
+    fn derive_public_key_from_mnemonic(
+        mnemonic: &str,
+        passphrase: Option<&str>,
+        account: u32,
+    ) -> Result<XOnlyPublicKey, Error> {
+        let mut secret_key = Self::derive_from_mnemonic(mnemonic, passphrase, account)?;
+        let public_key = Keys::new(secret_key).public_key();
+        secret_key.non_secure_erase();
+        Ok(public_key)
+    }
+
+    /// Scan the accounts of a BIP39 mnemonic (without passphrase) for published events of any kind
+    /// on the relays, stopping after `gap_limit` consecutive unused accounts, see `discover_used_accounts`.
+    /// Returns the used accounts with their npubs.
+    /// The returned future does not borrow the keystore, so it can be run in the background.
+    pub fn scan_used_accounts_from_mnemonic(
+        &self,
+        mnemonic: &str,
+        relays: &[String],
+        gap_limit: u32,
+    ) -> Result<impl Future<Output = Result<Vec<(u32, String)>, Error>> + Send + 'static, Error>
+    {
+        let discover = self.discover_used_accounts(mnemonic, None, relays, gap_limit, &[])?;
+        Ok(async move {
+            discover
+                .await?
+                .into_iter()
+                .map(|(account, pubkey)| Ok((account, pubkey.to_bech32()?)))
+                .collect()
+        })
+    }
+
+    /// Discover the used accounts of a BIP39 mnemonic (NIP-06 derivation), for recovery from a seed:
+    /// accounts are derived sequentially, and checked for published events of the given kinds
+    /// (of any kind if empty) on the relays. The scan stops after `gap_limit` consecutive unused accounts
+    /// (as in BIP-44 wallet recovery). Relay checks of the accounts are done concurrently.
+    /// The returned future does not borrow the keystore, so it can be run in the background.
+    pub fn discover_used_accounts(
+        &self,
+        mnemonic: &str,
+        passphrase: Option<&str>,
+        relays: &[String],
+        gap_limit: u32,
+        kinds: &[Kind],
+    ) -> Result<
+        impl Future<Output = Result<Vec<(u32, XOnlyPublicKey)>, Error>> + Send + 'static,
+        Error,
+    > {
+        self.check_online()?;
+        Ok(Self::scan_used_accounts(
+            Zeroizing::new(mnemonic.to_string()),
+            passphrase.map(|p| Zeroizing::new(p.to_string())),
+            relays.to_vec(),
+            gap_limit,
+            kinds.to_vec(),
+            self.network_config.clone(),
+        ))
+    }
+
+    /// Scan the accounts of a mnemonic for published events of the given kinds (any kind if empty),
+    /// see `discover_used_accounts`
+    async fn scan_used_accounts(
+        mnemonic: Zeroizing<String>,
+        passphrase: Option<Zeroizing<String>>,
+        relays: Vec<String>,
+        gap_limit: u32,
+        kinds: Vec<Kind>,
+        config: NetworkConfig,
+    ) -> Result<Vec<(u32, XOnlyPublicKey)>, Error> {
+        let mut used = Vec::new();
+        let mut next_account = 0;
+        let mut gap = 0;
+        while gap < gap_limit {
+            // just enough accounts to reach the gap limit if all of them are unused
+            let batch = (next_account..next_account + (gap_limit - gap))
+                .map(|account| {
+                    Self::derive_public_key_from_mnemonic(
+                        &mnemonic,
+                        passphrase.as_ref().map(|p| p.as_str()),
+                        account,
+                    )
+                    .map(|pubkey| (account, pubkey))
+                })
+                .collect::<Result<Vec<_>, Error>>()?;
+            next_account += batch.len() as u32;
+            let checks: Vec<_> = batch
+                .iter()
+                .map(|(_account, pubkey)| {
+                    let (pubkey, kinds, relays, config) =
+                        (*pubkey, kinds.clone(), relays.clone(), config.clone());
+                    tokio::spawn(async move {
+                        has_published_events(pubkey, &kinds, &relays, &config).await
+                    })
+                })
+                .collect();
+            for ((account, pubkey), check) in batch.into_iter().zip(checks) {
+                let is_used = check.await.map_err(|e| {
+                    if e.is_panic() {
+                        Error::InternalTaskPanicked
+                    } else {
+                        Error::Cancelled
+                    }
+                })??;
+                if is_used {
+                    used.push((account, pubkey));
+                    gap = 0;
+                } else {
+                    gap += 1;
+                }
+            }
+        }
+        Ok(used)
+    }
+
+    /// The most recently used relays (for publishing or fetching), at most `limit`
+    pub fn recent_relays(&self, limit: usize) -> Vec<RelayStat> {
+        self.relay_stats.recent_relays(limit)
+    }
+
+    /// Sign a relay authentication challenge (NIP-42): create a signed kind 22242 event,
+    /// with the relay and challenge tags. Secret key is needed.
+    pub fn sign_auth_challenge(&self, relay_url: &str, challenge: &str) -> Result<Event, Error> {
+        let relay = Url::parse(relay_url).map_err(|_e| Error::RelayInvalidUrl)?;
+        check_kind_allowed(&self.allowed_kinds, &Kind::Authentication)?;
+        let keys = self.get_keys()?;
+        Ok(EventBuilder::auth(challenge, relay).to_event(keys)?)
+    }
+
+    /// Restrict the event kinds that are signed; `None` allows all
+    pub fn set_allowed_kinds(&mut self, allowed_kinds: Option<HashSet<u16>>) {
+        self.allowed_kinds = allowed_kinds;
+    }
+
+    /// Warning: Security-sensitive method!
+    /// Sign an unsigned event, if its kind is allowed and it is for these keys
+    pub fn sign(&self, unsigned: UnsignedEvent) -> Result<Event, Error> {
+        check_kind_allowed(&self.allowed_kinds, &unsigned.kind)?;
+        let keys = self.get_keys()?;
+        if unsigned.pubkey != keys.public_key() {
+            return Err(Error::KeyIdentityMismatch);
+        }
+        let event = unsigned.sign(keys)?;
+        record_signature(&self.signing_stats);
+        log_key_event("event.signed", &event.pubkey);
+        Ok(event)
+    }
+
+    /// Compute the ID of an unsigned event from its contents (SHA-256 of the canonical serialization),
+    /// e.g. for showing it before signing. No secret key is needed.
+    pub fn compute_event_id(&self, unsigned: &UnsignedEvent) -> [u8; 32] {
+        let id = EventId::new(
+            &unsigned.pubkey,
+            unsigned.created_at,
+            &unsigned.kind,
+            &unsigned.tags,
+            &unsigned.content,
+        );
+        let mut bytes = [0u8; 32];
+        bytes.copy_from_slice(id.as_bytes());
+        bytes
+    }
+
+    /// Warning: Security-sensitive method!
+    /// Create and sign a text note (kind 1)
+    pub fn sign_text_note(&self, content: &str) -> Result<Event, Error> {
+        let pubkey = self.get_public_key()?;
+        self.sign(EventBuilder::new_text_note(content, &[]).to_unsigned_event(pubkey))
+    }
+
+    /// Warning: Security-sensitive method!
+    /// Create and sign an event with the given `created_at` timestamp (unix seconds), instead of the current time.
+    /// Future timestamps are allowed, but more than 15 minutes ahead are flagged (`far_future`).
+    pub fn sign_event_at(
+        &self,
+        builder: EventBuilder,
+        created_at: u64,
+    ) -> Result<TimestampedEvent, Error> {
+        let unsigned = unsigned_at(builder, self.get_public_key()?, created_at);
+        let event = self.sign(unsigned)?;
+        Ok(TimestampedEvent {
+            event,
+            far_future: created_at > Self::current_time() + MAX_FUTURE_TIMESTAMP_DRIFT.as_secs(),
+        })
+    }
+
+    /// Warning: Security-sensitive method!
+    /// Create and sign an HTTP auth event (NIP-98, kind 27235) for a request to an HTTP endpoint,
+    /// with the `u` (URL), `method`, and optional `payload` (SHA-256 hash of the body, hex) tags.
+    /// Returns the event, and the `Authorization` header containing it.
+    pub fn create_http_auth(
+        &self,
+        method: &str,
+        url: &str,
+        payload_hash: Option<&str>,
+    ) -> Result<HttpAuth, Error> {
+        let method = method.trim().to_uppercase();
+        if !HTTP_AUTH_METHODS.contains(&method.as_str()) {
+            return Err(Error::HttpAuthInvalidMethod(method));
+        }
+        let parsed_url = Url::parse(url.trim()).map_err(|_e| Error::HttpAuthInvalidUrl)?;
+        if parsed_url.scheme() != "http" && parsed_url.scheme() != "https" {
+            return Err(Error::HttpAuthInvalidUrl);
+        }
+        let mut tags = vec![
+            Tag::Generic(TagKind::Custom("u".into()), vec![url.trim().to_string()]),
+            Tag::Generic(TagKind::Custom("method".into()), vec![method]),
+        ];
+        if let Some(hash) = payload_hash {
+            if hash.len() != 64 || !hash.chars().all(|c| c.is_ascii_hexdigit()) {
+                return Err(Error::HttpAuthInvalidPayloadHash);
+            }
+            tags.push(Tag::Generic(
+                TagKind::Custom("payload".into()),
+                vec![hash.to_lowercase()],
+            ));
+        }
+        let pubkey = self.get_public_key()?;
+        let event = self.sign(
+            EventBuilder::new(Kind::Custom(HTTP_AUTH_KIND), "", &tags).to_unsigned_event(pubkey),
+        )?;
+        let authorization_header = format!("Nostr {}", STANDARD.encode(event.as_json()));
+        Ok(HttpAuth {
+            event,
+            authorization_header,
+        })
+    }
+
+    /// Create a signing request for a text note, to be signed on an offline (air-gapped) machine
+    /// holding the secret key (see `airgap`). Only the public key is needed.
+    pub fn offline_sign_request(&self, content: &str) -> Result<String, Error> {
+        let pubkey = self.get_public_key()?;
+        sign_request_encode(&EventBuilder::new_text_note(content, &[]).to_unsigned_event(pubkey))
+    }
+
+    /// Warning: Security-sensitive method!
+    /// Sign an unsigned event with a deterministic nonce: same keys and event always
+    /// give the same signature. Default signing is randomized.
+    pub fn sign_event_deterministic(&self, unsigned: UnsignedEvent) -> Result<Event, Error> {
+        check_kind_allowed(&self.allowed_kinds, &unsigned.kind)?;
+        let signature = self.with_keys(|keys| {
+            if unsigned.pubkey != keys.public_key() {
+                return Err(Error::KeyIdentityMismatch);
+            }
+            sign_hash_deterministic(keys, unsigned.id.as_bytes())
+        })??;
+        record_signature(&self.signing_stats);
+        log_key_event("event.signed", &unsigned.pubkey);
+        Ok(unsigned.add_signature(signature)?)
+    }
+
+    /// Warning: Security-sensitive method!
+    /// Sign an air-gapped signing request blob (see `airgap`), producing the response blob.
+    /// The request has to be for the public key of this keystore.
+    /// Signing is deterministic, so a request scanned again gives the same response.
+    pub fn sign_offline_request(&self, request: &str) -> Result<String, Error> {
+        let unsigned = sign_request_decode(request)?;
+        let event = self.sign_event_deterministic(unsigned)?;
+        sign_response_encode(&event)
+    }
+
+    /// Decode a signing response blob coming back from the offline machine.
+    /// The signature is verified, and the event has to be for the public key of this keystore.
+    pub fn check_offline_response(&self, response: &str) -> Result<Event, Error> {
+        let event = sign_response_decode(response)?;
+        if event.pubkey != self.get_public_key()? {
+            return Err(Error::KeyIdentityMismatch);
+        }
+        Ok(event)
+    }
+
+    /// Warning: Security-sensitive method!
+    /// Sign a text note (kind 1), and publish it to the relays; if `confirm_timeout` is given, it is also
+    /// confirmed that the accepting relays stored it (see `publish_and_confirm`).
+    /// The note is signed right away; the returned future does not borrow the keystore,
+    /// so it can be run in the background. Record its results with `record_publish`.
+    pub fn publish_text_note(
+        &self,
+        content: &str,
+        relays: &[String],
+        confirm_timeout: Option<Duration>,
+    ) -> Result<impl Future<Output = Vec<ConfirmedPublish>> + Send + 'static, Error> {
+        self.check_online()?;
+        let event = self.sign_text_note(content)?;
+        // the note is signed, publishing needs no secret
+        let keys = Keys::from_public_key(event.pubkey);
+        let relays = relays.to_vec();
+        let config = self.network_config.clone();
+        Ok(
+            async move { publish_and_confirm(&keys, &event, &relays, confirm_timeout, &config).await },
+        )
+    }
+
+    /// Warning: Security-sensitive method!
+    /// Import secret key, and publish the profile metadata (kind 0 event) to each of the relays.
+    /// A publish failure to one relay does not abort the others, see the per-relay results.
+    /// The keys are only changed if the profile event could be signed. The keys are imported right away;
+    /// the returned future does not borrow the keystore, so it can be run in the background.
+    /// Record its results with `record_publish`.
+    pub fn import_and_announce(
+        &mut self,
+        secret_key_str: &str,
+        profile: &Metadata,
+        relays: &[String],
+    ) -> Result<impl Future<Output = Vec<PublishResult>> + Send + 'static, Error> {
+        self.check_online()?;
+        let keys = Self::parse_secret_key(secret_key_str)?;
+        self.key_policy.check(KeyOrigin::SecretKey)?;
+        check_kind_allowed(&self.allowed_kinds, &Kind::Metadata)?;
+        let event = EventBuilder::set_metadata(profile.clone()).to_event(&keys)?;
+        // the event is signed, publishing needs no secret
+        let public_keys = Keys::from_public_key(keys.public_key());
+        self.set_keys(keys, KeyOrigin::SecretKey, true)?;
+        let relays = relays.to_vec();
+        let config = self.network_config.clone();
+        Ok(async move { publish_event(&public_keys, &event, &relays, &config).await })
+    }
+
+    /// Record the results of a publish in the relay usage statistics
+    pub fn record_publish(&mut self, results: &[PublishResult]) {
+        self.relay_stats
+            .record_publish(results, Self::current_time());
+    }
+
+    /// Integrity self-test of the keys, e.g. after import or load: sign a fixed message with the secret key,
+    /// and verify the signature with the public key. If it passes, the secret key and the public key
+    /// belong together, and signing works. Secret key is needed.
+    pub fn self_test(&self) -> Result<(), Error> {
+        let hash = <Sha256Hash as nostr::hashes::Hash>::hash(SELF_TEST_MESSAGE);
+        let signature = self.with_keys(|keys| sign_hash_deterministic(keys, hash.as_ref()))??;
+        SECP256K1
+            .verify_schnorr(
+                &signature,
+                &nostr::secp256k1::Message::from_slice(hash.as_ref())?,
+                &self.get_public_key()?,
+            )
+            .map_err(|_e| Error::KeySelfTestFailed)
+    }
+
+    /// Warning: Security-sensitive method!
+    /// Sign a challenge (e.g. a login nonce) to prove possession of the secret key.
+    /// The signed message is the SHA-256 hash of the challenge with a domain prefix,
+    /// so that a challenge can never be a valid event ID. Returns the schnorr signature, in hex.
+    pub fn sign_challenge(&self, challenge: &[u8]) -> Result<String, Error> {
+        let kp = self.get_keys()?.key_pair()?;
+        let signature = SECP256K1.sign_schnorr(&challenge_message(challenge)?, &kp);
+        Ok(signature.to_string())
+    }
+
+    /// Warning: Security-sensitive method!
+    /// Payload of a verification QR, for in-person key verification: JSON with the npub,
+    /// the challenge, and the challenge signature (see `sign_challenge`). Secret key is needed.
+    pub fn verification_payload(&self, challenge: &str) -> Result<String, Error> {
+        let sig = self.sign_challenge(challenge.as_bytes())?;
+        let npub = self.get_public_key()?.to_bech32()?;
+        Ok(serde_json::json!({
+            "npub": npub,
+            "challenge": challenge,
+            "sig": sig,
+        })
+        .to_string())
+    }
+
+    /// Warning: Security-sensitive method!
+    /// Verification QR (SVG) for in-person key verification, see `verification_payload`.
+    /// A scanner can check it with `verify_verification_payload`.
+    pub fn verification_qr(&self, challenge: &str) -> Result<String, Error> {
+        qr_svg(&self.verification_payload(challenge)?)
+    }
+
+    /// Action to check a scanned verification payload (`verification_payload_input`) against
+    /// the challenge (`verification_challenge_input`)
+    pub fn check_verification_action(&self, status: &mut StatusMessages) {
+        match verify_verification_payload(
+            self.verification_payload_input.trim(),
+            self.verification_challenge_input.trim(),
+        ) {
+            None => {
+                status.set_error("Verification failed, payload is not signed for this challenge")
+            }
+            Some(pubkey) => status.set(&format!(
+                "Verified, the presenter controls {}",
+                pubkey.to_bech32().unwrap_or_default()
+            )),
+        }
+    }
+
+    /// Re-verify a NIP-05 identifier against the public key, and if relays are given,
+    /// cross-check it with the published profile metadata (kind 0).
+    /// Useful to notice if a saved identity has been changed or compromised.
+    /// If the profile cannot be fetched, the result is `ProfileUnknown`, not `Verified`.
+    /// Cancellable with the token, network operations are aborted on cancellation.
+    /// The returned future does not borrow the keystore, so it can be run in the background.
+    pub fn revalidate_identity(
+        &self,
+        nip05: &str,
+        relays: &[String],
+        cancel: &CancellationToken,
+    ) -> impl Future<Output = IdentityStatus> + Send + 'static {
+        let pubkey = self.get_public_key().ok();
+        let offline = self.offline;
+        let config = self.network_config.clone();
+        let nip05 = nip05.to_string();
+        let relays = relays.to_vec();
+        let cancel = cancel.clone();
+        async move {
+            let pubkey = match pubkey {
+                None => return IdentityStatus::KeyNotSet,
+                Some(pk) => pk,
+            };
+            if offline {
+                return IdentityStatus::Offline;
+            }
+            match cancel.run(verify_nip05(pubkey, &nip05, &config)).await {
+                Err(_) => return IdentityStatus::Cancelled,
+                Ok(Err(e)) => return IdentityStatus::Nip05Failed(e.to_string()),
+                Ok(Ok(())) => {}
+            }
+            if !relays.is_empty() {
+                match cancel.run(fetch_metadata(pubkey, &relays, &config)).await {
+                    Err(_) => return IdentityStatus::Cancelled,
+                    Ok(Err(e)) => return IdentityStatus::ProfileUnknown(e.to_string()),
+                    Ok(Ok(Some(metadata))) => {
+                        if metadata.nip05.as_deref() != Some(nip05.as_str()) {
+                            return IdentityStatus::ProfileMismatch(metadata.nip05);
+                        }
+                    }
+                    Ok(Ok(None)) => {}
+                }
+            }
+            IdentityStatus::Verified
+        }
+    }
+
+    /// Set (or unset) offline mode, in which network operations (publish, fetch, NIP-05 verification)
+    /// are refused, for guaranteed no network access (e.g. air-gapped use)
+    pub fn set_offline(&mut self, offline: bool) {
+        self.offline = offline;
+    }
+
+    fn check_online(&self) -> Result<(), Error> {
+        if self.offline {
+            return Err(Error::OfflineMode);
+        }
+        Ok(())
+    }
+
+    /// Watch mode: fetch the most recent text notes (kind 1) of the public key from the relays,
+    /// newest first. No secret key is needed, so it works for watched (public only) identities.
+    /// The returned future does not borrow the keystore, so it can be run in the background.
+    /// Record its outcome with `record_relay_use`.
+    pub fn fetch_recent_notes(
+        &self,
+        relays: &[String],
+        limit: usize,
+    ) -> Result<impl Future<Output = Result<Vec<Event>, Error>> + Send + 'static, Error> {
+        let pubkey = self.get_public_key()?;
+        self.check_online()?;
+        let relays = relays.to_vec();
+        let config = self.network_config.clone();
+        Ok(async move { fetch_notes(pubkey, &relays, limit, &config).await })
+    }
+
+    /// Record a use of the relays (e.g. a fetch) in the relay usage statistics
+    pub fn record_relay_use(&mut self, relays: &[String], success: bool) {
+        let now = Self::current_time();
+        for relay in relays {
+            self.relay_stats.record(relay, success, now);
+        }
+    }
+
+    /// Warning: Security-sensitive method!
+    /// Save secret key to file.
+    pub fn save_encrypted_secret_key(&self) -> Result<(), Error> {
+        Storage::check_create_folder()?;
+        self.save_encrypted_secret_key_to(
+            &Storage::encrypted_secret_key_file(),
+            &CancellationToken::new(),
+            None,
+        )
+    }
+
+    /// Warning: Security-sensitive method!
+    /// Save secret key to the given file, cancellable, with optional progress reporting.
+    /// On cancellation no file is written.
+    pub fn save_encrypted_secret_key_to(
+        &self,
+        path: &Path,
+        cancel: &CancellationToken,
+        progress: KdfProgress,
+    ) -> Result<(), Error> {
+        let sk = self.get_secret_key()?;
+
+        if self.save_password_input != self.save_repeat_password_input {
+            return Err(Error::KeyEncryptionPasswordMismatch);
+        }
+        let password = &self.save_password_input;
+
+        let data = Encrypt::encrypt_key_with(&sk, &password, self.scrypt_log_n, cancel, progress)?;
+        let hex_string = hex::encode(data);
+        // create empty file
+        fs::write(path, "")?;
+        // set permissions, TODO make it on non-unix as well
+        #[cfg(unix)]
+        {
+            use std::os::unix::fs::PermissionsExt;
+            fs::set_permissions(path, fs::Permissions::from_mode(0o600))?;
+        }
+        // write contents
+        fs::write(path, hex_string.to_string())?;
+        self.empty_password.set(password.is_empty());
+
+        Ok(())
+    }
+
+    /// Set the scrypt cost (log2 of N) for encrypting the secret key: lower is faster (for weak devices),
+    /// higher is stronger (for high-value keys). Accepted range is 1-22.
+    pub fn set_scrypt_log_n(&mut self, log_n: u8) -> Result<(), Error> {
+        Encrypt::check_log2_rounds(log_n)?;
+        self.scrypt_log_n = log_n;
+        Ok(())
+    }
+
+    /// Warning: Security-sensitive method!
+    /// Secret key encrypted with the password, in 'ncryptsec' bech32 format
+    pub fn encrypted_secret_key_ncryptsec(&self, password: &str) -> Result<String, Error> {
+        let sk = self.get_secret_key()?;
+        let data = Encrypt::encrypt_key(&sk, password, self.scrypt_log_n)?;
+        Ok(bech32::encode(
+            NCRYPTSEC_HRP,
+            data.to_base32(),
+            bech32::Variant::Bech32,
+        )?)
+    }
+
+    /// Warning: Security-sensitive method!
+    /// Export a paper backup (for printing): the npub, the secret key encrypted with the password ('ncryptsec'),
+    /// and a human-readable checksum (two words) over both, for detecting typos when retyping.
+    pub fn export_paper_backup(&self, password: &str) -> Result<String, Error> {
+        let npub = self.get_public_key()?.to_bech32()?;
+        let ncryptsec = self.encrypted_secret_key_ncryptsec(password)?;
+        let checksum = Self::paper_backup_checksum(&npub, &ncryptsec);
+        Ok(format!(
+            "{PAPER_BACKUP_HEADER}\nnpub: {npub}\nncryptsec: {ncryptsec}\nchecksum: {checksum}\n"
+        ))
+    }
+
+    /// Warning: Security-sensitive method!
+    /// Import a paper backup created by `export_paper_backup` (possibly retyped, lines may also be joined).
+    /// The checksum is validated; the keys are imported locked, the password is needed to unlock them.
+    /// The checksum only catches typos; the match of the secret and public keys is checked on unlock.
+    pub fn import_from_paper_backup(&mut self, text: &str) -> Result<(), Error> {
+        // words following a field name belong to that field, words before any field (header) are ignored
+        let mut values: [Vec<&str>; 3] = Default::default();
+        let mut field = None;
+        for word in text.split_whitespace() {
+            let mut value = word;
+            if let Some((name, rest)) = word.split_once(':') {
+                if let Some(i) = PAPER_BACKUP_FIELDS
+                    .iter()
+                    .position(|f| f.eq_ignore_ascii_case(name))
+                {
+                    field = Some(i);
+                    value = rest;
+                }
+            }
+            if let (Some(i), false) = (field, value.is_empty()) {
+                values[i].push(value);
+            }
+        }
+        let [npub, ncryptsec, checksum] = values;
+        let (npub, ncryptsec) = match (npub.as_slice(), ncryptsec.as_slice()) {
+            ([npub], [ncryptsec]) if !checksum.is_empty() => {
+                (normalize_input(npub)?, normalize_input(ncryptsec)?)
+            }
+            _ => return Err(Error::BackupInvalid),
+        };
+        if Self::paper_backup_checksum(&npub, &ncryptsec) != checksum.join(" ").to_lowercase() {
+            return Err(Error::BackupChecksumMismatch);
+        }
+        let public_key = XOnlyPublicKey::from_bech32(&npub)?;
+        let (hrp, data, _variant) = bech32::decode(&ncryptsec)?;
+        if hrp != NCRYPTSEC_HRP {
+            return Err(Error::BackupInvalid);
+        }
+        let encrypted = Vec::<u8>::from_base32(&data)?;
+        if encrypted.len() != ENCRYPTED_KEY_LEN {
+            return Err(Error::KeyInvalidEncrypted);
+        }
+        // restored from an own backup, same as loading from own storage
+        self.set_keys(Keys::from_public_key(public_key), KeyOrigin::Storage, true)?;
+        self.encrypted_secret_key = Some(encrypted);
+        Ok(())
+    }
+
+    /// Human-readable checksum of a paper backup: a petname over the npub and ncryptsec
+    fn paper_backup_checksum(npub: &str, ncryptsec: &str) -> String {
+        petname(format!("{npub}\n{ncryptsec}").as_bytes())
+    }
+
+    /// Warning: Security-sensitive method!
+    /// Frame payloads for enrolling the keys into a hardware signer reading multi-frame QR codes:
+    /// the encrypted secret key ('ncryptsec'), split into frames. Plaintext is never included.
+    pub fn enrollment_frames(&self, password: &str) -> Result<Vec<String>, Error> {
+        let ncryptsec = self.encrypted_secret_key_ncryptsec(password)?;
+        Ok(split_frames(&ncryptsec, ENROLLMENT_FRAME_CHUNK_SIZE))
+    }
+
+    /// Warning: Security-sensitive method!
+    /// Enrollment QR for hardware signers: the frames of `enrollment_frames`, as SVG QR codes
+    pub fn enrollment_qr(&self, password: &str) -> Result<Vec<String>, Error> {
+        self.enrollment_frames(password)?
+            .iter()
+            .map(|f| qr_svg(f))
+            .collect()
+    }
+
+    /// Approximate size (in bytes) of the export in the given format, without doing the export
+    /// (no password needed). Bech32 and hex lengths are exact, QR SVG sizes vary slightly with content.
+    pub fn estimated_export_size(&self, format: ExportFormat) -> usize {
+        let ncryptsec_len = Self::bech32_len(NCRYPTSEC_HRP, ENCRYPTED_KEY_LEN);
+        match format {
+            ExportFormat::Npub => Self::bech32_len("npub", 32),
+            ExportFormat::EncryptedSecretKey => 2 * ENCRYPTED_KEY_LEN,
+            ExportFormat::Ncryptsec => ncryptsec_len,
+            ExportFormat::EnrollmentQr => frame_lengths(ncryptsec_len, ENROLLMENT_FRAME_CHUNK_SIZE)
+                .into_iter()
+                .map(estimated_qr_svg_len)
+                .sum(),
+        }
+    }
+
+    /// Length of the bech32 encoding of `data_len` bytes: prefix, separator, data, 6 checksum characters
+    fn bech32_len(hrp: &str, data_len: usize) -> usize {
+        hrp.len() + 1 + (data_len * 8 + 4) / 5 + 6
+    }
+
+    /// Save public key to file.
+    pub fn save_public_key(&self) -> Result<(), Error> {
+        let pubkey = self.get_public_key()?;
+        Storage::check_create_folder()?;
+        let npub_string = pubkey.to_bech32()?;
+        fs::write(Storage::public_key_file(), npub_string)?;
+        Ok(())
+    }
+
+    /// Set the label; it must be non-empty (after trimming), and at most `MAX_LABEL_LEN` characters
+    pub fn set_label(&mut self, label: &str) -> Result<(), Error> {
+        let label = label.trim();
+        if label.is_empty() {
+            return Err(Error::AccountLabelInvalid("empty".to_string()));
+        }
+        if label.chars().count() > MAX_LABEL_LEN {
+            return Err(Error::AccountLabelInvalid(format!(
+                "longer than {MAX_LABEL_LEN} characters"
+            )));
+        }
+        if self.label != label {
+            self.label = label.to_string();
+            self.has_unsaved_change = true;
+        }
+        Ok(())
+    }
+
+    pub fn note(&self) -> &str {
+        &self.note
+    }
+
+    pub fn set_note(&mut self, note: &str) {
+        if self.note != note {
+            self.note = note.to_string();
+            self.has_unsaved_change = true;
+        }
+    }
+
+    /// Save note to file; if the note is empty, the file is removed.
+    fn save_note_to(&self, path: &Path) -> Result<(), Error> {
+        Self::save_optional_text_to(path, &self.note)
+    }
+
+    /// Load note from file; a missing file means no note.
+    fn load_note_from(&mut self, path: &Path) -> Result<(), Error> {
+        self.note = Self::load_optional_text_from(path)?;
+        Ok(())
+    }
+
+    /// Save label to file; if the label is empty, the file is removed.
+    fn save_label_to(&self, path: &Path) -> Result<(), Error> {
+        Self::save_optional_text_to(path, &self.label)
+    }
+
+    /// Load label from file; a missing file means no label.
+    fn load_label_from(&mut self, path: &Path) -> Result<(), Error> {
+        self.label = Self::load_optional_text_from(path)?;
+        Ok(())
+    }
+
+    /// Save text to file, readable only by the user; if the text is empty, the file is removed.
+    fn save_optional_text_to(path: &Path, text: &str) -> Result<(), Error> {
+        if text.is_empty() {
+            if path.is_file() {
+                fs::remove_file(path)?;
+            }
+            return Ok(());
+        }
+        // create empty file
+        fs::write(path, "")?;
+        #[cfg(unix)]
+        {
+            use std::os::unix::fs::PermissionsExt;
+            fs::set_permissions(path, fs::Permissions::from_mode(0o600))?;
+        }
+        fs::write(path, text)?;
+        Ok(())
+    }
+
+    /// Load text from file; a missing file means empty text.
+    fn load_optional_text_from(path: &Path) -> Result<String, Error> {
+        if path.is_file() {
+            Ok(fs::read_to_string(path)?)
+        } else {
+            Ok(String::new())
+        }
+    }
+
+    /// Warning: Security-sensitive method!
+    /// Save public/secret key to file(s).
+    /// Returns if secret key has been saved
+    pub fn save_keys(&self) -> Result<bool, Error> {
+        if !self.has_unsaved_change {
+            return Err(Error::KeyNoChangeToSave);
+        }
+        // save public key
+        self.save_public_key()?;
+        self.save_note_to(&Storage::note_file())?;
+        self.save_label_to(&Storage::label_file())?;
+        self.relay_stats.save_to(&Storage::relay_stats_file())?;
+        // save secret key if set
+        if self.is_secret_key_set() {
+            self.save_encrypted_secret_key()?;
+            Ok(true)
+        } else {
+            Ok(false)
+        }
+    }
+
+    /// Warning: Security-sensitive method!
+    /// Load secret key from file
+    pub fn load_secret_key(&mut self) -> Result<(), Error> {
+        self.load_secret_key_from(
+            &Storage::encrypted_secret_key_file(),
+            &CancellationToken::new(),
+            None,
+        )?;
+        // Also load public key if available, so that it is visible while locked
+        if !self.is_secret_key_set() {
+            if let Ok(pk_string) = fs::read_to_string(Storage::public_key_file()) {
+                self.keys = Some(Keys::from_pk_str(&pk_string)?);
+            }
+        }
+        Ok(())
+    }
+
+    /// Warning: Security-sensitive method!
+    /// Load secret key from the given file, cancellable, with optional progress reporting.
+    /// Decryption with empty password is tried, the key remains locked if it fails.
+    /// On cancellation the state is not changed.
+    pub fn load_secret_key_from(
+        &mut self,
+        path: &Path,
+        cancel: &CancellationToken,
+        progress: KdfProgress,
+    ) -> Result<(), Error> {
+        let sk_hex = fs::read_to_string(path)?;
+        let encrypted = hex::decode(&sk_hex).map_err(|_e| Error::KeyInvalidEncrypted)?;
+        // Try to decrypt with empty password, before changing the state
+        let decrypted = match Encrypt::decrypt_key_with(&encrypted, "", cancel, progress) {
+            Err(Error::Cancelled) => return Err(Error::Cancelled),
+            res => res.ok(),
+        };
+        self.import_encrypted_secret_key(&sk_hex, false)?;
+        if let Some(sk) = decrypted {
+            self.set_decrypted_keys(sk, encrypted)?;
+            self.empty_password.set(true);
+        }
+        Ok(())
+    }
+
+    /// Load public key from file
+    pub fn load_public_key(&mut self) -> Result<(), Error> {
+        let pk_string = fs::read_to_string(Storage::public_key_file())?;
+        self.restore_keys(Keys::from_pk_str(&pk_string)?, false);
+        Ok(())
+    }
+
+    /// Warning: Security-sensitive method!
+    /// Load public/secret key from file
+    pub fn load_keys(&mut self) -> Result<(), Error> {
+        let secret_path = Storage::encrypted_secret_key_file();
+        if secret_path.as_path().is_file() {
+            // secret key file exists, load secret key
+            self.load_secret_key()?;
+        } else {
+            // load public key
+            self.load_public_key()?;
+        }
+        self.load_relay_stats_from(&Storage::relay_stats_file());
+        self.load_note_from(&Storage::note_file())?;
+        self.load_label_from(&Storage::label_file())?;
+        self.label_input = self.label.clone();
+        Ok(())
+    }
+
+    /// Load relay usage stats from file. The stats are informational only, so an unreadable
+    /// or corrupt file does not fail the load: the stats are reset, and a warning is logged.
+    fn load_relay_stats_from(&mut self, path: &Path) {
+        self.relay_stats = RelayUsageStats::load_from(path).unwrap_or_else(|e| {
+            log_warning("relay_stats.reset", &e);
+            RelayUsageStats::default()
+        });
+    }
+
+    /// Warning: Security-sensitive method!
+    ///.Action to save secret key to file
+    pub fn save_action(
+        &mut self,
+        security_settings: &SecuritySettings,
+        status: &mut StatusMessages,
+    ) {
+        let res = if !security_settings.allows_persist() {
+            Err(Error::KeySaveNotAllowed)
+        } else {
+            if security_settings.security_level == SecurityLevel::PersistMandatoryPassword
+                && self.save_password_input.is_empty()
+            {
+                Err(Error::KeyEncryptionPasswordMissing)
+            } else {
+                self.save_keys()
+            }
+        };
+        match res {
+            Err(e) => status.set_error_err(&e),
+            Ok(ss) => {
+                if ss {
+                    // Clear password input
+                    self.save_password_input = "".to_string();
+                    self.save_repeat_password_input = "".to_string();
+                    status.set(&format!(
+                        "Secret key persisted to storage ({} bytes)",
+                        self.estimated_export_size(ExportFormat::EncryptedSecretKey)
+                    ));
+                } else {
+                    status.set(&format!(
+                        "Public key persisted to storage ({} bytes)",
+                        self.estimated_export_size(ExportFormat::Npub)
+                    ));
+                }
+            }
+        }
+    }
+
+    ///.Action to load secret key from file
+    pub fn load_action(
+        &mut self,
+        security_settings: &SecuritySettings,
+        status: &mut StatusMessages,
+    ) {
+        // TODO confirmation
+        let res = if !security_settings.allows_persist() {
+            Err(Error::KeyLoadNotAllowed)
+        } else {
+            self.load_keys()
+        };
+        if let Err(e) = res {
+            status.set_error_err(&e);
+        } else {
+            status.set("Keys loaded from storage (may need decryption with password)");
+        }
+    }
+
+    /// Warning: Security-sensitive method!
+    /// Import from the scanned QR code payload input, see `import_from_qr_payload`
+    pub fn import_from_qr_payload_action(&mut self, status: &mut StatusMessages) {
+        let payload = Zeroizing::new(std::mem::take(&mut self.qr_payload_input));
+        match self.import_from_qr_payload(&payload) {
+            Err(e) => status.set_error(&format!("Error importing, {}", e.to_string())),
+            Ok(_) => status.set("Key imported from QR code"),
+        };
+    }
+
+    /// Warning: Security-sensitive method!
+    /// Import a key from the format import input, in the selected format
+    pub fn import_with_format_action(&mut self, status: &mut StatusMessages) {
+        // the input is zeroized on drop
+        let input = Zeroizing::new(std::mem::take(&mut self.format_import_input));
+        match self.import_with_format(&input, self.import_format) {
+            Err(e) => status.set_error(&format!("Error importing, {}", e.to_string())),
+            Ok(_) => status.set(&format!("Key imported ({})", self.import_format)),
+        };
+    }
+
+    /// Warning: Security-sensitive method!
+    /// Convert the key in the convert input to another format with `convert` (see `key_utils`),
+    /// in place. The keys are not affected.
+    pub fn convert_key_action(
+        &mut self,
+        convert: fn(&str) -> Result<String, Error>,
+        status: &mut StatusMessages,
+    ) {
+        match convert(self.convert_input.trim()) {
+            Err(e) => status.set_error(&format!("Error converting, {}", e.to_string())),
+            Ok(converted) => {
+                self.convert_input.zeroize();
+                self.convert_input = converted;
+                status.set("Key converted");
+            }
+        };
+    }
+
+    /// Warning: Security-sensitive method!
+    /// Import secret key, in 'nsec' bech32 or hex format (pubkey is derived from it)
+    pub fn import_secret_key_action(&mut self, status: &mut StatusMessages) {
+        let input = Zeroizing::new(std::mem::take(&mut self.secret_key_input));
+        // the secret key of the current public key is added to it, instead of replacing the keys
+        let upgraded = if self.keys_is_set() && !self.is_secret_key_set() {
+            match self.upgrade_with_secret(&input) {
+                Err(Error::KeyIdentityMismatch) => None,
+                res => Some(res.map(|_| "Secret key added to the public key")),
+            }
+        } else {
+            None
+        };
+        let res = upgraded.unwrap_or_else(|| {
+            self.import_secret_key(&input, true)
+                .map(|_| "Secret key imported")
+        });
+        match res {
+            Err(e) => status.set_error(&format!("Error importing, {}", e.to_string())),
+            Ok(message) => status.set(message),
+        };
+    }
+
+    /// Warning: Security-sensitive method!
+    /// Import only the public key of the secret key input (watch-only)
+    pub fn import_public_from_secret_action(&mut self, status: &mut StatusMessages) {
+        let input = Zeroizing::new(std::mem::take(&mut self.secret_key_input));
+        match self.import_public_from_secret(&input) {
+            Err(e) => status.set_error(&format!("Error importing, {}", e.to_string())),
+            Ok(_) => status.set("Public key imported from secret key (watch-only)"),
+        };
+    }
+
+    /// Warning: Security-sensitive method!
+    /// Import a key from the content read from the clipboard, with the format auto-detected
+    pub fn import_from_clipboard_action(
+        &mut self,
+        content: Option<String>,
+        status: &mut StatusMessages,
+    ) {
+        match self.import_from_clipboard_content(content) {
+            Err(Error::KeyNeedsPassword) => {
+                status.set("Encrypted key (ncryptsec) pasted, enter its password to import it")
+            }
+            Err(e) => status.set_error(&format!("Error importing, {}", e.to_string())),
+            Ok(_) => status.set("Key imported from clipboard"),
+        };
+    }
+
+    /// Warning: Security-sensitive method!
+    /// Import the pasted encrypted key (`ncryptsec_input`), with the password from `decrypt_password_input`.
+    /// On error (e.g. wrong password) the encrypted key is kept, so that it can be retried.
+    pub fn import_ncryptsec_action(&mut self, status: &mut StatusMessages) {
+        let mut password = std::mem::take(&mut self.decrypt_password_input);
+        let ncryptsec = self.ncryptsec_input.clone();
+        match self.import_ncryptsec(&ncryptsec, &password) {
+            Err(e) => status.set_error(&format!("Error importing, {}", e.to_string())),
+            Ok(_) => {
+                self.ncryptsec_input = String::new();
+                status.set("Encrypted secret key imported");
+            }
+        };
+        password.zeroize();
+    }
+
+    /// Warning: Security-sensitive method!
+    /// Import on submit (Enter), from the public or secret key input, as decided by `submit_strategy`
+    pub fn import_submit_action(&mut self, status: &mut StatusMessages) {
+        match self.submit_strategy() {
+            Err(e) => status.set_error(&format!("Error importing, {}", e.to_string())),
+            Ok(SubmitAction::Nothing) => {}
+            Ok(SubmitAction::ImportPublicKey) => {
+                match self.import_public_key(&self.public_key_input.clone()) {
+                    Err(e) => status.set_error(&e.to_string()),
+                    Ok(_) => status.set("Public key imported"),
+                };
+                // cleanup
+                self.public_key_input = String::new();
+            }
+            Ok(SubmitAction::ImportSecretKey) => {
+                self.import_secret_key_action(status);
+                self.public_key_input = String::new();
+            }
+        }
+    }
+
+    /// Warning: Security-sensitive method!
+    /// Decide what to import on submit, based on the public and secret key inputs.
+    /// The secret key is preferred, as it allows signing. If both are entered, they have to
+    /// match (same public key), otherwise it's ambiguous, and the user should clear one of them.
+    pub fn submit_strategy(&self) -> Result<SubmitAction, Error> {
+        let has_public = !self.public_key_input.trim().is_empty();
+        let has_secret = !self.secret_key_input.trim().is_empty();
+        match (has_public, has_secret) {
+            (false, false) => Ok(SubmitAction::Nothing),
+            (true, false) => Ok(SubmitAction::ImportPublicKey),
+            (false, true) => Ok(SubmitAction::ImportSecretKey),
+            (true, true) => {
+                let mut secret_input = normalize_input(&self.secret_key_input)?;
+                let keys = Keys::from_sk_str(&secret_input);
+                secret_input.zeroize();
+                let public_key = Keys::from_pk_str(&normalize_input(&self.public_key_input)?)
+                    .map(|k| k.public_key());
+                match (keys, public_key) {
+                    (Ok(keys), Ok(public_key)) if keys.public_key() == public_key => {
+                        Ok(SubmitAction::ImportSecretKey)
+                    }
+                    _ => Err(Error::KeyInputAmbiguous),
+                }
+            }
+        }
+    }
+
+    /// Warning: Security-sensitive method!
+    /// Set the secret key input, the previous value is zeroized
+    pub fn set_secret_input(&mut self, input: String) {
+        self.secret_key_input.zeroize();
+        self.secret_key_input = input;
+    }
+
+    /// Clear the secret key input, zeroizing it
+    pub fn clear_secret_input(&mut self) {
+        self.secret_key_input.zeroize();
+        self.secret_key_input = String::new();
+    }
+
+    /// Warning: Security-sensitive method!
+    /// Action to confirm the backup of the secret key, with the retyped secret key input
+    pub fn confirm_backup_action(&mut self, status: &mut StatusMessages) {
+        if !is_valid_nsec(&self.backup_confirm_input) {
+            // the checksum catches typos, tell them apart from a different key
+            status.set_error("The retyped secret key is not a valid nsec, check it for typos");
+        } else {
+            match self.confirm_backup(&self.backup_confirm_input) {
+                Err(e) => status.set_error_err(&e),
+                Ok(_) => status.set("Backup of the secret key confirmed"),
+            }
+        }
+        // cleanup
+        self.backup_confirm_input.zeroize();
+    }
+
+    /// Warning: Security-sensitive method!
+    /// Action to change the password of an 'ncryptsec' backup in the re-encrypt input (see `reencrypt_ncryptsec`),
+    /// with the old and new password inputs. The input is replaced by the re-encrypted backup.
+    pub fn reencrypt_ncryptsec_action(&mut self, status: &mut StatusMessages) {
+        if self.reencrypt_new_password_input != self.reencrypt_repeat_password_input {
+            status.set_error_err(&Error::KeyEncryptionPasswordMismatch);
+            return;
+        }
+        match reencrypt_ncryptsec(
+            &self.reencrypt_input,
+            &self.reencrypt_old_password_input,
+            &self.reencrypt_new_password_input,
+            self.scrypt_log_n,
+        ) {
+            Err(e) => status.set_error_err(&e),
+            Ok(reencrypted) => {
+                self.reencrypt_input = reencrypted;
+                status.set("Backup re-encrypted with the new password, copy it from the input");
+            }
+        }
+        // cleanup
+        self.reencrypt_old_password_input.zeroize();
+        self.reencrypt_new_password_input.zeroize();
+        self.reencrypt_repeat_password_input.zeroize();
+    }
+
+    pub fn set_label_action(&mut self, status: &mut StatusMessages) {
+        match self.set_label(&self.label_input.clone()) {
+            Err(e) => status.set_error_err(&e),
+            Ok(_) => status.set(&format!("Label set to '{}'", self.label)),
+        }
+    }
+
+    pub fn set_allowed_kinds_action(&mut self, status: &mut StatusMessages) {
+        match parse_kinds(&self.allowed_kinds_input) {
+            Err(e) => status.set_error_err(&e),
+            Ok(None) => {
+                self.set_allowed_kinds(None);
+                status.set("All event kinds are allowed for signing");
+            }
+            Ok(Some(kinds)) => {
+                let mut sorted = kinds.iter().copied().collect::<Vec<u16>>();
+                sorted.sort_unstable();
+                self.set_allowed_kinds(Some(kinds));
+                status.set(&format!(
+                    "Only event kinds {:?} are allowed for signing",
+                    sorted
+                ));
+            }
+        }
+    }
+
+    /// Warning: Security-sensitive method!
+    /// Import secret key from BIP39 mnemonic
+    pub fn import_mnemonic_action(&mut self, status: &mut StatusMessages) {
+        // the inputs are zeroized on drop
+        let passphrase = Zeroizing::new(std::mem::take(&mut self.mnemonic_passphrase_input));
+        let passphrase_opt = if passphrase.is_empty() {
+            None
+        } else {
+            Some(passphrase.as_str())
+        };
+        let mnemonic = Zeroizing::new(std::mem::take(&mut self.mnemonic_input));
+        let derivation_path = std::mem::take(&mut self.derivation_path_input);
+        let derivation_path = derivation_path.trim();
+        let res = if derivation_path.is_empty() {
+            self.import_mnemonic(&mnemonic, passphrase_opt, true)
+        } else {
+            self.import_mnemonic_path(&mnemonic, passphrase_opt, derivation_path)
+        };
+        match res {
+            Err(e) => {
+                status.set_error(&format!("Error importing from mnemonic, {}", e.to_string()))
+            }
+            Ok(_) => status.set("Secret key imported from mnemonic"),
+        };
+    }
+
+    /// Warning: Security-sensitive method!
+    /// Generate new keys from a new mnemonic and the passphrase inputs (see `generate_with_mnemonic_and_passphrase`).
+    /// The mnemonic is put into the mnemonic input, so that it can be backed up.
+    pub fn generate_with_mnemonic_action(&mut self, status: &mut StatusMessages) {
+        let passphrase = Zeroizing::new(self.mnemonic_passphrase_input.clone());
+        let repeat_passphrase = Zeroizing::new(self.mnemonic_passphrase_repeat_input.clone());
+        match self.generate_with_mnemonic_and_passphrase(&passphrase, &repeat_passphrase) {
+            Err(e) => status.set_error_err(&e),
+            Ok(mnemonic) => {
+                self.mnemonic_input.zeroize();
+                self.mnemonic_input = mnemonic;
+                self.derivation_path_input = String::new();
+                status.set(
+                    "New keys generated from a new mnemonic and the passphrase, back up both!",
+                );
+            }
+        }
+        // cleanup
+        self.mnemonic_passphrase_repeat_input.zeroize();
+    }
+
+    pub fn get_signer(&self) -> Result<KeySigner, Error> {
+        Ok(KeySigner {
+            keys: self.get_keys()?.clone(),
+            allowed_kinds: self.allowed_kinds.clone(),
+            signing_stats: self.signing_stats.clone(),
+        })
+    }
+
+    pub fn keys_is_set(&self) -> bool {
+        self.keys.is_some()
+    }
+
+    #[cfg(test)]
+    pub fn is_public_key_set(&self) -> bool {
+        self.get_public_key().is_ok()
+    }
+
+    pub fn is_secret_key_set(&self) -> bool {
+        self.get_secret_key().is_ok()
+    }
+
+    pub(crate) fn get_encrypted_secret_key(&self) -> Option<&Vec<u8>> {
+        self.encrypted_secret_key.as_ref()
+    }
+
+    pub fn is_encrypted_secret_key_set(&self) -> bool {
+        self.encrypted_secret_key.is_some()
+    }
+
+    /// Encrypted secret key is set, but not decrypted
+    pub fn is_locked(&self) -> bool {
+        self.get_set_state() == KeysSetState::Locked
+    }
+
+    pub fn get_set_state(&self) -> KeysSetState {
+        if self.is_secret_key_set() {
+            KeysSetState::SecretAndPublic
+        } else if self.is_encrypted_secret_key_set() {
+            KeysSetState::Locked
+        } else if self.keys_is_set() {
+            KeysSetState::PublicOnly
+        } else {
+            KeysSetState::NotSet
+        }
+    }
+
+    /// Warning: Security-sensitive method!
+    pub(crate) fn get_keys(&self) -> Result<&Keys, Error> {
+        match &self.keys {
+            None => Err(Error::KeyNotSet),
+            Some(kk) => Ok(kk),
+        }
+    }
+
+    /// Warning: Security-sensitive method!
+    /// Lend the keys to the closure, without copying them (unlike `get_signer`).
+    /// The closure should return only non-secret results (e.g. a signature).
+    pub(crate) fn with_keys<R>(&self, f: impl FnOnce(&Keys) -> R) -> Result<R, Error> {
+        Ok(f(self.get_keys()?))
+    }
+
+    pub fn get_public_key(&self) -> Result<XOnlyPublicKey, Error> {
+        Ok(self.get_keys()?.public_key())
+    }
+
+    /// Warning: Security-sensitive method!
+    fn get_secret_key(&self) -> Result<SecretKey, Error> {
+        Ok(self.get_keys()?.secret_key()?)
+    }
+
+    /// Public key in hex with an EIP-55-like case checksum (see `checksummed_hex`),
+    /// for catching transcription errors when the key is passed through non-Nostr systems
+    pub fn public_key_checksummed_hex(&self) -> Option<String> {
+        self.get_public_key().ok().map(|pk| checksummed_hex(&pk))
+    }
+
+    /// Public key as raw 32-byte x-only (BIP340) bytes, as expected by schnorr libraries
+    pub fn xonly_public_key_bytes(&self) -> Option<[u8; 32]> {
+        self.get_public_key().ok().map(|pk| pk.serialize())
+    }
+
+    /// Warning: Security-sensitive method!
+    /// Derive the NIP-44 conversation keys with each of the peers, in one call (e.g. for pre-warming
+    /// a session cache). Secret key is needed. It is recommended to zeroize() the keys after use.
+    pub fn conversation_keys(&self, peers: &[XOnlyPublicKey]) -> Result<Vec<[u8; 32]>, Error> {
+        let mut secret_key = self.get_secret_key()?;
+        let keys = peers
+            .iter()
+            .map(|peer| nip44::conversation_key(&secret_key, peer))
+            .collect();
+        secret_key.non_secure_erase();
+        Ok(keys)
+    }
+
+    /// Warning: Security-sensitive method!
+    /// Conversation keys (see `conversation_keys`) with the peers of the conversation peers input
+    /// (comma-separated npub or hex), as lines of 'peer_hex conversation_key_hex'
+    pub fn conversation_keys_export(&self) -> Result<Zeroizing<String>, Error> {
+        let peers = self
+            .conversation_peers_input
+            .split(',')
+            .map(|p| p.trim())
+            .filter(|p| !p.is_empty())
+            .map(|p| Ok(Keys::from_pk_str(&normalize_input(p)?)?.public_key()))
+            .collect::<Result<Vec<XOnlyPublicKey>, Error>>()?;
+        let mut keys = self.conversation_keys(&peers)?;
+        let mut export = Zeroizing::new(String::new());
+        for (peer, key) in peers.iter().zip(keys.iter()) {
+            export.push_str(&format!("{} {}\n", peer, hex::encode(key)));
+        }
+        keys.zeroize();
+        Ok(export)
+    }
+
+    /// Public-only snapshot of the displayable state, see `PublicSnapshot`
+    pub fn snapshot(&self) -> PublicSnapshot {
+        PublicSnapshot {
+            npub: self
+                .get_public_key()
+                .ok()
+                .and_then(|pk| pk.to_bech32().ok()),
+            set_state: self.get_set_state(),
+            origin: self.origin,
+        }
+    }
+
+    /// Diagnostics string for bug reports, with no identifying data: the public key is
+    /// only included as a (truncated) hash, so reports can be correlated, but not linked to the identity.
+    /// Contains no secrets, no label or note.
+    pub fn redacted_debug(&self) -> String {
+        let pubkey_hash = match self.get_public_key() {
+            Ok(pk) => {
+                let hash = <Sha256Hash as nostr::hashes::Hash>::hash(&pk.serialize());
+                hash.to_string()[..16].to_string()
+            }
+            Err(_) => "-".to_string(),
+        };
+        format!(
+            "set_level: {:?}, origin: {:?}, pubkey_hash: {}, encrypted: {}, hide_secret_key: {}, unsaved_change: {}, auto_lock: {}, allowed_kinds: {}",
+            self.get_set_state(),
+            self.origin,
+            pubkey_hash,
+            self.encrypted_secret_key.is_some(),
+            self.hide_secret_key,
+            self.has_unsaved_change,
+            self.auto_lock_after.is_some(),
+            self.allowed_kinds.as_ref().map(|k| k.len()).unwrap_or_default(),
+        )
+    }
+
+    /// Public config as a TOML table (`[keystr]`), for embedding into the config of an integrating app:
+    /// `npub` (if set), `label` and `set_level`. The secret key is never included.
+    pub fn to_toml_fragment(&self) -> String {
+        let mut table = toml::value::Table::new();
+        if let Some(npub) = self
+            .get_public_key()
+            .ok()
+            .and_then(|pk| pk.to_bech32().ok())
+        {
+            table.insert("npub".to_string(), toml::Value::String(npub));
+        }
+        table.insert("label".to_string(), toml::Value::String(self.label.clone()));
+        table.insert(
+            "set_level".to_string(),
+            toml::Value::String(format!("{:?}", self.get_set_state())),
+        );
+        let mut config = toml::value::Table::new();
+        config.insert(TOML_TABLE.to_string(), toml::Value::Table(table));
+        toml::Value::Table(config).to_string()
+    }
+
+    /// Load the public config from a TOML fragment, see `to_toml_fragment`.
+    /// Only the public key and the label are loaded (`set_level` is informative),
+    /// the result is public-only. Other tables and unknown fields are ignored.
+    pub fn from_toml_fragment(fragment: &str) -> Result<Keystore, Error> {
+        let config = fragment
+            .parse::<toml::Value>()
+            .map_err(|e| Error::TomlInvalid(e.to_string()))?;
+        let mut keystore = Keystore::new();
+        let table = match config.get(TOML_TABLE) {
+            None => return Ok(keystore),
+            Some(table) => table,
+        };
+        let field = |name: &str| match table.get(name) {
+            None => Ok(None),
+            Some(value) => value
+                .as_str()
+                .map(Some)
+                .ok_or_else(|| Error::TomlInvalid(format!("'{name}' is not a string"))),
+        };
+        if let Some(npub) = field("npub")? {
+            keystore.import_public_key(npub)?;
+        }
+        if let Some(label) = field("label")? {
+            keystore.label = label.to_string();
+        }
+        Ok(keystore)
+    }
+
+    /// Assess the security of the keys, combining several signals: publicly known key,
+    /// low-entropy secret key, empty encryption password. Read-only and cheap (e.g. for a UI badge):
+    /// the empty password is known from the last save or decryption, no key derivation is done.
+    pub fn security_warning_level(&self) -> SecurityAssessment {
+        let mut reasons = Vec::new();
+        if let Ok(pk) = self.get_public_key() {
+            if KNOWN_COMPROMISED_PUBKEYS.contains(&pk.to_string().as_str()) {
+                reasons.push(SecurityWarning::KnownCompromised);
+            }
+        }
+        if let Ok(sk) = self.get_secret_key() {
+            let mut bytes = sk.secret_bytes();
+            let mut distinct = bytes.to_vec();
+            distinct.sort_unstable();
+            distinct.dedup();
+            if distinct.len() < MIN_DISTINCT_SECRET_BYTES || bytes[..16].iter().all(|b| *b == 0) {
+                reasons.push(SecurityWarning::LowEntropy);
+            }
+            distinct.zeroize();
+            bytes.zeroize();
+        }
+        if self.empty_password.get() {
+            reasons.push(SecurityWarning::EmptyPassword);
+        }
+        SecurityAssessment::from_reasons(reasons)
+    }
+
+    /// Public key in PEM format (SubjectPublicKeyInfo), for non-Nostr tools.
+    /// The x-only key is encoded as a compressed point with even Y coordinate, as implied by BIP340.
+    pub fn public_key_pem(&self) -> Result<String, Error> {
+        let mut der = SPKI_SECP256K1_COMPRESSED_PREFIX.to_vec();
+        der.push(0x02);
+        der.extend_from_slice(&self.get_public_key()?.serialize());
+        let encoded = STANDARD.encode(der);
+        let mut pem = "-----BEGIN PUBLIC KEY-----\n".to_string();
+        for line in encoded.as_bytes().chunks(64) {
+            pem.push_str(&String::from_utf8_lossy(line));
+            pem.push('\n');
+        }
+        pem.push_str("-----END PUBLIC KEY-----\n");
+        Ok(pem)
+    }
+
+    /// Number of leading characters of the npub data (after `npub1`) matching the target
+    /// (vanity prefix), case-insensitive. A leading `npub1` in the target is ignored.
+    /// Returns None if no public key is set.
+    pub fn npub_prefix_match(&self, target: &str) -> Option<usize> {
+        let npub = self.get_public_key().ok()?.to_bech32().ok()?;
+        let data = npub.strip_prefix("npub1").unwrap_or(&npub);
+        let target = target.trim().to_lowercase();
+        let target = target.strip_prefix("npub1").unwrap_or(&target);
+        Some(
+            data.chars()
+                .zip(target.chars())
+                .take_while(|(a, b)| a == b)
+                .count(),
+        )
+    }
+
+    /// Human-friendly two-word name derived from the public key, stable across runs.
+    /// Purely cosmetic, to help distinguish identities at a glance.
+    pub fn petname(&self) -> Option<String> {
+        self.xonly_public_key_bytes().map(|b| petname(&b))
+    }
+
+    /// Age of the keys, in coarse buckets
+    pub fn age_bucket(&self) -> AgeBucket {
+        self.age_bucket_at(Self::current_time())
+    }
+
+    /// Age of the keys, in coarse buckets, relative to the given time
+    pub fn age_bucket_at(&self, now: u64) -> AgeBucket {
+        match self.created_at {
+            None => AgeBucket::Unknown,
+            Some(created_at) => {
+                let age = now.saturating_sub(created_at);
+                if age < 60 {
+                    AgeBucket::JustNow
+                } else if age < 3600 {
+                    AgeBucket::Minutes
+                } else if age < 24 * 3600 {
+                    AgeBucket::Hours
+                } else if age < 30 * 24 * 3600 {
+                    AgeBucket::Days
+                } else {
+                    AgeBucket::Older
+                }
+            }
+        }
+    }
+
+    pub fn get_npub(&self) -> String {
+        match self.get_public_key() {
+            Err(_e) => "(not set)".to_string(),
+            Ok(pk) => match pk.to_bech32() {
+                Err(_) => "(conversion error)".to_string(),
+                Ok(s) => s,
+            },
+        }
+    }
+
+    /// Warning: Security-sensitive method!
+    /// Return secret key as nsec string, if set, and if Hide option is not active.
+    /// Deprecated: the returned plain string can easily end up in a terminal or log,
+    /// use `reveal::reveal_secret_into` with a `SecretSink` instead.
+    #[deprecated(note = "use reveal_secret_into with a SecretSink")]
+    pub fn get_nsec(&self) -> String {
+        match self.get_secret_key() {
+            Err(_) => "(not set)".to_string(),
+            Ok(key) => {
+                if self.hide_secret_key {
+                    "".to_string()
+                } else {
+                    match key.to_bech32() {
+                        Err(_) => "(conversion error)".to_string(),
+                        Ok(s) => s,
+                    }
+                }
+            }
+        }
+    }
+}
+
+/// Equality is identity-based, not state-based: two keystores are equal if they have the same
+/// public key (or both have none), regardless of secret key presence and inputs.
+impl PartialEq for Keystore {
+    fn eq(&self, other: &Self) -> bool {
+        self.get_public_key().ok() == other.get_public_key().ok()
+    }
+}
+
+impl Eq for Keystore {}
+
+/// Hash is identity-based, consistent with `PartialEq`: only the public key is hashed.
+impl Hash for Keystore {
+    fn hash<H: Hasher>(&self, state: &mut H) {
+        self.get_public_key().ok().hash(state);
+    }
+}
+
+/// Emit a structured log event (with the `tracing` feature) for a key operation.
+/// Only the public key (npub) is logged, never anything secret.
+fn log_key_event(name: &'static str, public_key: &XOnlyPublicKey) {
+    #[cfg(feature = "tracing")]
+    tracing::info!(event = name, npub = %public_key.to_bech32().unwrap_or_default());
+    #[cfg(not(feature = "tracing"))]
+    let _ = (name, public_key);
+}
+
+/// Emit a structured warning log event (with the `tracing` feature) for a recovered error
+fn log_warning(name: &'static str, error: &Error) {
+    #[cfg(feature = "tracing")]
+    tracing::warn!(event = name, error = %error);
+    #[cfg(not(feature = "tracing"))]
+    let _ = (name, error);
+}
+
+/// Unsigned event from a builder, with the given creation time (and the ID recomputed accordingly)
+fn unsigned_at(builder: EventBuilder, pubkey: XOnlyPublicKey, created_at: u64) -> UnsignedEvent {
+    let mut unsigned = builder.to_unsigned_event(pubkey);
+    unsigned.created_at = Timestamp::from(created_at);
+    unsigned.id = EventId::new(
+        &unsigned.pubkey,
+        unsigned.created_at,
+        &unsigned.kind,
+        &unsigned.tags,
+        &unsigned.content,
+    );
+    unsigned
+}
+
+/// Parse a comma-separated list of event kinds; an empty list means no restriction (`None`)
+fn parse_kinds(input: &str) -> Result<Option<HashSet<u16>>, Error> {
+    let kinds = input
+        .split(',')
+        .map(|k| k.trim())
+        .filter(|k| !k.is_empty())
+        .map(|k| {
+            k.parse::<u16>()
+                .map_err(|_e| Error::KindInvalid(k.to_string()))
+        })
+        .collect::<Result<HashSet<u16>, Error>>()?;
+    if kinds.is_empty() {
+        Ok(None)
+    } else {
+        Ok(Some(kinds))
+    }
+}
+
+/// Check the event kind against an optional allowlist (`None` allows all)
+fn check_kind_allowed(allowed_kinds: &Option<HashSet<u16>>, kind: &Kind) -> Result<(), Error> {
+    match allowed_kinds {
+        None => Ok(()),
+        Some(allowed) => match u16::try_from(kind.as_u64()) {
+            Ok(k) if allowed.contains(&k) => Ok(()),
+            _ => Err(Error::KindNotAllowed(kind.as_u64())),
+        },
+    }
+}
+
+/// Sign a hash with deterministic nonce (BIP340 without auxiliary randomness)
+fn sign_hash_deterministic(keys: &Keys, hash: &[u8]) -> Result<Signature, Error> {
+    let kp = keys.key_pair()?;
+    Ok(SECP256K1.sign_schnorr_no_aux_rand(&nostr::secp256k1::Message::from_slice(hash)?, &kp))
+}
+
+/// Prefix of signed challenges, for domain separation
+const CHALLENGE_DOMAIN_PREFIX: &[u8] = b"keystr-challenge:";
+
+/// Message (hash) signed for a challenge, see `Keystore::sign_challenge`
+fn challenge_message(challenge: &[u8]) -> Result<nostr::secp256k1::Message, Error> {
+    let mut data = CHALLENGE_DOMAIN_PREFIX.to_vec();
+    data.extend_from_slice(challenge);
+    let hash = <Sha256Hash as nostr::hashes::Hash>::hash(&data);
+    Ok(nostr::secp256k1::Message::from_slice(hash.as_ref())?)
+}
+
+/// Verify a challenge signature (hex) created by `Keystore::sign_challenge`. No side effects.
+pub(crate) fn verify_challenge(pubkey: &XOnlyPublicKey, challenge: &[u8], sig: &str) -> bool {
+    match (Signature::from_str(sig), challenge_message(challenge)) {
+        (Ok(signature), Ok(message)) => SECP256K1
+            .verify_schnorr(&signature, &message, pubkey)
+            .is_ok(),
+        _ => false,
+    }
+}
+
+/// Decode an encrypted key from 'ncryptsec' bech32 format (not decrypted)
+fn decode_ncryptsec(ncryptsec: &str) -> Result<Vec<u8>, Error> {
+    let (hrp, data, _variant) = bech32::decode(&normalize_input(ncryptsec)?)?;
+    if hrp != NCRYPTSEC_HRP {
+        return Err(Error::KeyInvalidEncrypted);
+    }
+    let encrypted = Vec::<u8>::from_base32(&data)?;
+    if encrypted.len() != ENCRYPTED_KEY_LEN {
+        return Err(Error::KeyInvalidEncrypted);
+    }
+    Ok(encrypted)
+}
+
+/// Warning: Security-sensitive method!
+/// Re-encrypt an 'ncryptsec' with a new password, e.g. for changing the password of a backup.
+/// The secret key is decrypted only in memory, and zeroized after use.
+/// Wrong old password results in `KeyDecryptionFailed`.
+pub(crate) fn reencrypt_ncryptsec(
+    ncryptsec: &str,
+    old_password: &str,
+    new_password: &str,
+    log_n: u8,
+) -> Result<String, Error> {
+    let encrypted = decode_ncryptsec(ncryptsec)?;
+    let mut sk = match Encrypt::decrypt_key(&encrypted, old_password) {
+        Ok(sk) => sk,
+        Err(Error::KeyEncryption) => return Err(Error::KeyDecryptionFailed),
+        Err(e) => return Err(e),
+    };
+    let reencrypted = Encrypt::encrypt_key(&sk, new_password, log_n);
+    sk.non_secure_erase();
+    Ok(bech32::encode(
+        NCRYPTSEC_HRP,
+        reencrypted?.to_base32(),
+        bech32::Variant::Bech32,
+    )?)
+}
+
+/// Verify a scanned verification payload (see `Keystore::verification_payload`) against the
+/// expected challenge. Returns the public key, if the signature is valid; no side effects.
+pub(crate) fn verify_verification_payload(
+    payload: &str,
+    expected_challenge: &str,
+) -> Option<XOnlyPublicKey> {
+    let v: serde_json::Value = serde_json::from_str(payload).ok()?;
+    let npub = v.get("npub")?.as_str()?;
+    let challenge = v.get("challenge")?.as_str()?;
+    let sig = v.get("sig")?.as_str()?;
+    if challenge != expected_challenge {
+        return None;
+    }
+    let pubkey = XOnlyPublicKey::from_bech32(npub).ok()?;
+    if !verify_challenge(&pubkey, challenge.as_bytes(), sig) {
+        return None;
+    }
+    Some(pubkey)
+}
+
+/// Can sign a hash with its own secret key
+#[derive(Clone)]
+pub(crate) struct KeySigner {
+    keys: Keys,
+    allowed_kinds: Option<HashSet<u16>>,
+    /// Signing stats of the keystore it was created from
+    signing_stats: Arc<Mutex<SigningStats>>,
+}
+
+impl KeySigner {
+    pub fn get_public_key(&self) -> XOnlyPublicKey {
+        self.keys.public_key()
+    }
+
+    /// Perform signing of a hash using loaded secret key
+    pub fn sign(&self, hash: Vec<u8>) -> Result<Signature, Error> {
+        let kp = self.keys.key_pair()?;
+        let signature = SECP256K1.sign_schnorr(&nostr::secp256k1::Message::from_slice(&hash)?, &kp);
+        record_signature(&self.signing_stats);
+        Ok(signature)
+    }
+
+    /// Sign an unsigned event (its ID), if its kind is allowed
+    pub fn sign_event(&self, unsigned: &UnsignedEvent) -> Result<Signature, Error> {
+        check_kind_allowed(&self.allowed_kinds, &unsigned.kind)?;
+        let signature = self.sign(unsigned.id.as_bytes().to_vec())?;
+        log_key_event("event.signed", &self.keys.public_key());
+        Ok(signature)
+    }
+}
+
+#[cfg(test)]
+#[allow(deprecated)]
+mod test {
+    use super::*;
+    use crate::base::key_utils::{hex_to_nsec, npub_to_hex, nsec_to_hex};
+    use crate::model::relays::mock_relay::start_mock_relay;
+    use proptest::prelude::*;
+    use std::collections::hash_map::DefaultHasher;
+
+    /// Password of the keys created by `keystore_with_state` in `Locked` state
+    const TEST_LOCK_PASSWORD: &str = "test-password";
+
+    /// Create a keystore with the keys in the given state, without parsing key strings.
+    /// For `Locked`, the secret key is encrypted with `TEST_LOCK_PASSWORD`.
+    pub(super) fn keystore_with_state(keys: Keys, state: KeysSetState) -> Keystore {
+        match state {
+            KeysSetState::NotSet => Keystore::new(),
+            KeysSetState::PublicOnly => {
+                Keystore::from_keys(Keys::from_public_key(keys.public_key()))
+            }
+            KeysSetState::SecretAndPublic => Keystore::from_keys(keys),
+            KeysSetState::Locked => {
+                let encrypted = Encrypt::encrypt_key(
+                    &keys.secret_key().unwrap(),
+                    TEST_LOCK_PASSWORD,
+                    Encrypt::default_log2_rounds(),
+                )
+                .unwrap();
+                Keystore::from_locked(keys.public_key(), encrypted)
+            }
+        }
+    }
+
+    #[test]
+    fn test_new() {
+        let k = Keystore::new();
+        assert_eq!(k.is_public_key_set(), false);
+        assert_eq!(k.is_secret_key_set(), false);
+        assert_eq!(k.get_npub(), "(not set)");
+        assert_eq!(k.get_nsec(), "(not set)");
+        assert!(k.get_keys().is_err());
+    }
+
+    #[test]
+    fn test_keystore_with_state() {
+        let keys = Keys::generate();
+        for state in [
+            KeysSetState::NotSet,
+            KeysSetState::PublicOnly,
+            KeysSetState::SecretAndPublic,
+            KeysSetState::Locked,
+        ] {
+            let k = keystore_with_state(keys.clone(), state);
+            assert_eq!(k.get_set_state(), state);
+            if state != KeysSetState::NotSet {
+                assert_eq!(k.get_public_key().unwrap(), keys.public_key());
+            }
+        }
+
+        let mut k = keystore_with_state(keys.clone(), KeysSetState::Locked);
+        let _res = k.decrypt_secret_key(TEST_LOCK_PASSWORD).unwrap();
+        assert_eq!(k.get_set_state(), KeysSetState::SecretAndPublic);
+        assert_eq!(
+            k.get_keys().unwrap().secret_key().unwrap(),
+            keys.secret_key().unwrap()
+        );
+    }
+
+    #[test]
+    fn test_paper_backup_round_trip() {
+        let keys = Keys::generate();
+        let k = keystore_with_state(keys.clone(), KeysSetState::SecretAndPublic);
+        let backup = k.export_paper_backup("password").unwrap();
+        let lines: Vec<&str> = backup.lines().collect();
+        assert_eq!(lines.len(), 4);
+        assert_eq!(lines[0], "keystr paper backup v1");
+        assert_eq!(lines[1], format!("npub: {}", k.get_npub()));
+        assert!(lines[2].starts_with("ncryptsec: ncryptsec1"));
+        assert!(lines[3].starts_with("checksum: "));
+
+        let mut k2 = Keystore::new();
+        let _res = k2.import_from_paper_backup(&backup).unwrap();
+        assert_eq!(k2.get_set_state(), KeysSetState::Locked);
+        assert_eq!(k2.get_public_key().unwrap(), keys.public_key());
+        assert_eq!(k2.origin, Some(KeyOrigin::Storage));
+        let _res = k2.decrypt_secret_key("password").unwrap();
+        assert!(k2.has_unsaved_change);
+        assert_eq!(
+            k2.get_keys().unwrap().secret_key().unwrap(),
+            keys.secret_key().unwrap()
+        );
+
+        // retyped: extra whitespace, uppercase checksum
+        let retyped = backup.replace("checksum: ", "Checksum:   ").to_uppercase();
+        let retyped = retyped.replace("KEYSTR PAPER BACKUP V1", "");
+        let mut k3 = Keystore::new();
+        let _res = k3.import_from_paper_backup(&retyped).unwrap();
+        assert_eq!(k3.get_public_key().unwrap(), keys.public_key());
+
+        // pasted into a single-line input, through the import format
+        let mut k4 = Keystore::new();
+        let _res = k4
+            .import_with_format(&backup.replace('\n', " "), ImportFormat::PaperBackup)
+            .unwrap();
+        assert_eq!(k4.get_public_key().unwrap(), keys.public_key());
+    }
+
+    #[test]
+    fn test_paper_backup_invalid() {
+        let k = keystore_with_state(Keys::generate(), KeysSetState::SecretAndPublic);
+        let backup = k.export_paper_backup("password").unwrap();
+        let mut k2 = Keystore::new();
+
+        // typo in the npub: rejected, by the checksum (or, if the two-word checksum collides, by bech32)
+        let npub = k.get_npub();
+        let last = npub.chars().last().unwrap();
+        let typo = format!(
+            "{}{}",
+            &npub[..npub.len() - 1],
+            if last == 'q' { 'p' } else { 'q' }
+        );
+        assert!(k2
+            .import_from_paper_backup(&backup.replace(&npub, &typo))
+            .is_err());
+        // wrong checksum words
+        let checksum_line = backup.lines().last().unwrap();
+        assert!(matches!(
+            k2.import_from_paper_backup(&backup.replace(checksum_line, "checksum: no words")),
+            Err(Error::BackupChecksumMismatch)
+        ));
+        // missing line
+        assert!(matches!(
+            k2.import_from_paper_backup(&backup.replace(checksum_line, "")),
+            Err(Error::BackupInvalid)
+        ));
+        assert!(matches!(
+            k2.import_from_paper_backup(""),
+            Err(Error::BackupInvalid)
+        ));
+        assert_eq!(k2.get_set_state(), KeysSetState::NotSet);
+
+        // npub and ncryptsec of different keys (with a matching checksum): rejected on unlock
+        let other_npub = Keys::generate().public_key().to_bech32().unwrap();
+        let ncryptsec = backup.lines().nth(2).unwrap()["ncryptsec: ".len()..].to_string();
+        let forged = format!(
+            "npub: {other_npub}\nncryptsec: {ncryptsec}\nchecksum: {}\n",
+            Keystore::paper_backup_checksum(&other_npub, &ncryptsec)
+        );
+        let _res = k2.import_from_paper_backup(&forged).unwrap();
+        assert!(matches!(
+            k2.decrypt_secret_key("password"),
+            Err(Error::KeyIdentityMismatch)
+        ));
+        assert_eq!(k2.get_set_state(), KeysSetState::Locked);
+        assert_eq!(k2.get_npub(), other_npub);
+
+        // secret key is needed for export
+        let k = keystore_with_state(Keys::generate(), KeysSetState::PublicOnly);
+        assert!(k.export_paper_backup("password").is_err());
+    }
+
+    #[cfg(feature = "tracing")]
+    #[tracing_test::traced_test]
+    #[test]
+    fn test_log_import_no_secret() {
+        let mut k = Keystore::new();
+        let _res = k
+            .import_secret_key(
+                "nsec1ktekw0hr5evjs0n9nyyquz4sue568snypy2rwk5mpv6hl2hq3vtsk0kpae",
+                true,
+            )
+            .unwrap();
+        let _res = k.sign_text_note("Hello").unwrap();
+        assert!(logs_contain("key.imported"));
+        assert!(logs_contain("event.signed"));
+        assert!(logs_contain(
+            "npub1rfze4zn25ezp6jqt5ejlhrajrfx0az72ed7cwvq0spr22k9rlnjq93lmd4"
+        ));
+        assert!(!logs_contain("nsec1"));
+        assert!(!logs_contain(
+            "b2f3673ee3a659283e6599080e0ab0e669a3c2640914375a9b0b357faae08b17"
+        ));
+    }
+
+    #[cfg(feature = "tracing")]
+    #[tracing_test::traced_test]
+    #[test]
+    fn test_log_load_and_signer() {
+        let keys = Keys::generate();
+        let npub = keys.public_key().to_bech32().unwrap();
+        let mut k = keystore_with_state(keys.clone(), KeysSetState::Locked);
+        let _res = k.decrypt_secret_key(TEST_LOCK_PASSWORD).unwrap();
+        assert!(logs_contain("key.loaded"));
+        assert!(!logs_contain("key.imported"));
+        assert!(!logs_contain("event.signed"));
+
+        let unsigned =
+            EventBuilder::new_text_note("note", &[]).to_unsigned_event(keys.public_key());
+        let _signature = k.get_signer().unwrap().sign_event(&unsigned).unwrap();
+        assert!(logs_contain("event.signed"));
+        assert!(logs_contain(&npub));
+    }
+
+    #[test]
+    fn test_submit_strategy() {
+        let npub = "npub1rfze4zn25ezp6jqt5ejlhrajrfx0az72ed7cwvq0spr22k9rlnjq93lmd4";
+        let nsec = "nsec1ktekw0hr5evjs0n9nyyquz4sue568snypy2rwk5mpv6hl2hq3vtsk0kpae";
+        let mut k = Keystore::new();
+        assert_eq!(k.submit_strategy().unwrap(), SubmitAction::Nothing);
+
+        k.public_key_input = npub.to_string();
+        assert_eq!(k.submit_strategy().unwrap(), SubmitAction::ImportPublicKey);
+
+        k.public_key_input = String::new();
+        k.set_secret_input(nsec.to_string());
+        assert_eq!(k.submit_strategy().unwrap(), SubmitAction::ImportSecretKey);
+
+        // both, matching: secret is preferred
+        k.public_key_input = npub.to_string();
+        assert_eq!(k.submit_strategy().unwrap(), SubmitAction::ImportSecretKey);
+
+        // both, different keys
+        k.public_key_input = Keys::generate().public_key().to_bech32().unwrap();
+        assert!(matches!(k.submit_strategy(), Err(Error::KeyInputAmbiguous)));
+        // both, invalid
+        k.public_key_input = "not a key".to_string();
+        assert!(matches!(k.submit_strategy(), Err(Error::KeyInputAmbiguous)));
+        // whitespace only counts as empty
+        k.public_key_input = "  ".to_string();
+        assert_eq!(k.submit_strategy().unwrap(), SubmitAction::ImportSecretKey);
+        // no side effects
+        assert!(!k.keys_is_set());
+    }
+
+    #[test]
+    fn test_scrypt_log_n() {
+        let keys = Keys::generate();
+        let mut k = Keystore::from_keys(keys.clone());
+        assert_eq!(k.scrypt_log_n, Encrypt::default_log2_rounds());
+        for log_n in [8, 18] {
+            let _res = k.set_scrypt_log_n(log_n).unwrap();
+            let ncryptsec = k.encrypted_secret_key_ncryptsec("password").unwrap();
+            let encrypted = decode_ncryptsec(&ncryptsec).unwrap();
+            assert_eq!(encrypted[1], log_n);
+            // decryption reads the parameter
+            let mut k2 = Keystore::new();
+            let _res = k2.import_ncryptsec(&ncryptsec, "password").unwrap();
+            assert_eq!(k2.get_secret_key().unwrap(), keys.secret_key().unwrap());
+        }
+        for log_n in [0, 23, 255] {
+            assert!(matches!(
+                k.set_scrypt_log_n(log_n),
+                Err(Error::KeyInvalidScryptParams(_))
+            ));
+        }
+        assert_eq!(k.scrypt_log_n, 18);
+        assert!(matches!(
+            Encrypt::encrypt_key(&keys.secret_key().unwrap(), "password", 23),
+            Err(Error::KeyInvalidScryptParams(23))
+        ));
+    }
+
+    #[test]
+    fn test_reencrypt_ncryptsec() {
+        let k = Keystore::from_keys(Keys::generate());
+        let ncryptsec = k.encrypted_secret_key_ncryptsec("oldpassword").unwrap();
+        let reencrypted =
+            reencrypt_ncryptsec(&ncryptsec, "oldpassword", "newpassword", 10).unwrap();
+        assert!(reencrypted.starts_with("ncryptsec1"));
+        assert_ne!(reencrypted, ncryptsec);
+
+        let encrypted = decode_ncryptsec(&reencrypted).unwrap();
+        assert_eq!(encrypted[1], 10);
+        let sk = Encrypt::decrypt_key(&encrypted, "newpassword").unwrap();
+        assert_eq!(sk, k.get_secret_key().unwrap());
+        assert!(Encrypt::decrypt_key(&encrypted, "oldpassword").is_err());
+
+        // wrong old password
+        assert!(matches!(
+            reencrypt_ncryptsec(&ncryptsec, "wrongpassword", "newpassword", 10),
+            Err(Error::KeyDecryptionFailed)
+        ));
+        // not an ncryptsec
+        assert!(reencrypt_ncryptsec(&k.get_npub(), "oldpassword", "newpassword", 10).is_err());
+    }
+
+    #[test]
+    fn test_reencrypt_ncryptsec_action() {
+        let k = Keystore::from_keys(Keys::generate());
+        let ncryptsec = k.encrypted_secret_key_ncryptsec("oldpassword").unwrap();
+        let mut k2 = Keystore::new();
+        let mut status = StatusMessages::new();
+        k2.reencrypt_input = ncryptsec.clone();
+        k2.reencrypt_old_password_input = "oldpassword".to_string();
+        k2.reencrypt_new_password_input = "newpassword".to_string();
+        k2.reencrypt_repeat_password_input = "newpassword".to_string();
+        k2.reencrypt_ncryptsec_action(&mut status);
+        assert_ne!(k2.reencrypt_input, ncryptsec);
+        assert!(k2.reencrypt_old_password_input.is_empty());
+        assert!(k2.reencrypt_new_password_input.is_empty());
+        let mut k3 = Keystore::new();
+        let _res = k3
+            .import_ncryptsec(&k2.reencrypt_input, "newpassword")
+            .unwrap();
+        assert_eq!(
+            k3.get_keys().unwrap().public_key(),
+            k.get_keys().unwrap().public_key()
+        );
+    }
+
+    #[test]
+    fn test_conversation_keys() {
+        let keys = Keys::generate();
+        let k = Keystore::from_keys(keys.clone());
+        let peers: Vec<Keys> = (0..3).map(|_| Keys::generate()).collect();
+        let peer_pubkeys: Vec<XOnlyPublicKey> = peers.iter().map(|p| p.public_key()).collect();
+
+        let conversation_keys = k.conversation_keys(&peer_pubkeys).unwrap();
+        assert_eq!(conversation_keys.len(), 3);
+        for (i, peer) in peers.iter().enumerate() {
+            // matches the single-peer derivation, from both sides
+            assert_eq!(
+                conversation_keys[i],
+                nip44::conversation_key(&keys.secret_key().unwrap(), &peer.public_key())
+            );
+            assert_eq!(
+                conversation_keys[i],
+                nip44::conversation_key(&peer.secret_key().unwrap(), &keys.public_key())
+            );
+        }
+        assert_ne!(conversation_keys[0], conversation_keys[1]);
+        assert!(k.conversation_keys(&[]).unwrap().is_empty());
+
+        // secret key is needed
+        let k = keystore_with_state(keys, KeysSetState::PublicOnly);
+        assert!(k.conversation_keys(&peer_pubkeys).is_err());
+    }
+
+    #[test]
+    fn test_conversation_keys_export() {
+        let keys = Keys::generate();
+        let mut k = Keystore::from_keys(keys.clone());
+        let peer = Keys::generate();
+        k.conversation_peers_input = format!(" {}, ", peer.public_key().to_bech32().unwrap());
+        let export = k.conversation_keys_export().unwrap();
+        assert_eq!(
+            export.as_str(),
+            format!(
+                "{} {}\n",
+                peer.public_key(),
+                hex::encode(nip44::conversation_key(
+                    &keys.secret_key().unwrap(),
+                    &peer.public_key()
+                ))
+            )
+        );
+        k.conversation_peers_input = "npub1invalid".to_string();
+        assert!(k.conversation_keys_export().is_err());
+    }
+
+    #[test]
+    fn test_redacted_debug() {
+        let mut k = Keystore::new();
+        assert!(k.redacted_debug().contains("set_level: NotSet"));
+        let _res = k
+            .import_secret_key(
+                "nsec1ktekw0hr5evjs0n9nyyquz4sue568snypy2rwk5mpv6hl2hq3vtsk0kpae",
+                true,
+            )
+            .unwrap();
+        let debug = k.redacted_debug();
+        assert!(debug.contains("set_level: SecretAndPublic"));
+        assert!(!debug.contains("npub1rfze4zn25ezp6jqt5ejlhrajrfx0az72ed7cwvq0spr22k9rlnjq93lmd4"));
+        assert!(!debug.contains("1a459a8a6aa6441d480ba665fb8fb21a4cfe8bcacb7d87300f8046a558a3fce4"));
+        assert!(!debug.contains("nsec1ktekw0hr5evjs0n9nyyquz4sue568snypy2rwk5mpv6hl2hq3vtsk0kpae"));
+        assert!(!debug.contains("b2f3673e"));
+        // stable, for correlation
+        assert_eq!(debug, k.redacted_debug());
+
+        let _res = k
+            .import_public_key("npub1rfze4zn25ezp6jqt5ejlhrajrfx0az72ed7cwvq0spr22k9rlnjq93lmd4")
+            .unwrap();
+        let debug2 = k.redacted_debug();
+        assert!(debug2.contains("set_level: PublicOnly"));
+        assert!(!debug2.contains("npub1rfze4zn25ezp6jqt5ejlhrajrfx0az72ed7cwvq0spr22k9rlnjq93lmd4"));
+    }
+
+    #[test]
+    fn test_security_warning_level() {
+        let k = keystore_with_state(Keys::generate(), KeysSetState::SecretAndPublic);
+        let assessment = k.security_warning_level();
+        assert_eq!(assessment.level, WarningLevel::Good);
+        assert!(assessment.reasons().is_empty());
+        assert_eq!(
+            Keystore::new().security_warning_level().level,
+            WarningLevel::Good
+        );
+
+        // the test key of this crate is publicly known
+        let mut k = Keystore::new();
+        let _res = k
+            .import_secret_key(
+                "nsec1ktekw0hr5evjs0n9nyyquz4sue568snypy2rwk5mpv6hl2hq3vtsk0kpae",
+                true,
+            )
+            .unwrap();
+        let assessment = k.security_warning_level();
+        assert_eq!(assessment.level, WarningLevel::Danger);
+        assert_eq!(assessment.reasons(), &[SecurityWarning::KnownCompromised]);
+        // also for public key only
+        let _res = k
+            .import_public_key("npub1rfze4zn25ezp6jqt5ejlhrajrfx0az72ed7cwvq0spr22k9rlnjq93lmd4")
+            .unwrap();
+        assert_eq!(k.security_warning_level().level, WarningLevel::Danger);
+
+        // low entropy
+        let _res = k
+            .import_secret_key(
+                "0000000000000000000000000000000000000000000000000000000000000007",
+                true,
+            )
+            .unwrap();
+        let assessment = k.security_warning_level();
+        assert_eq!(assessment.level, WarningLevel::Danger);
+        assert_eq!(assessment.reasons(), &[SecurityWarning::LowEntropy]);
+
+        // empty password
+        let keys = Keys::generate();
+        let encrypted = Encrypt::encrypt_key(
+            &keys.secret_key().unwrap(),
+            "",
+            Encrypt::default_log2_rounds(),
+        )
+        .unwrap();
+        let mut k = Keystore::from_locked(keys.public_key(), encrypted);
+        // not known before decryption
+        assert_eq!(k.security_warning_level().level, WarningLevel::Good);
+        k.decrypt_secret_key("").unwrap();
+        let assessment = k.security_warning_level();
+        assert_eq!(assessment.level, WarningLevel::Caution);
+        assert_eq!(assessment.reasons(), &[SecurityWarning::EmptyPassword]);
+        k.clear();
+        assert_eq!(k.security_warning_level().level, WarningLevel::Good);
+        let mut k = keystore_with_state(keys, KeysSetState::Locked);
+        k.decrypt_secret_key(TEST_LOCK_PASSWORD).unwrap();
+        assert_eq!(k.security_warning_level().level, WarningLevel::Good);
+    }
+
+    #[test]
+    fn test_public_key_pem() {
+        let mut k = Keystore::new();
+        assert!(matches!(k.public_key_pem(), Err(Error::KeyNotSet)));
+
+        let _res = k
+            .import_public_key("npub1rfze4zn25ezp6jqt5ejlhrajrfx0az72ed7cwvq0spr22k9rlnjq93lmd4")
+            .unwrap();
+        let pem = k.public_key_pem().unwrap();
+        // verified with `openssl pkey -pubin -text`
+        assert_eq!(
+            pem,
+            "-----BEGIN PUBLIC KEY-----\nMDYwEAYHKoZIzj0CAQYFK4EEAAoDIgACGkWaimqmRB1IC6Zl+4+yGkz+i8rLfYcw\nD4BGpVij/OQ=\n-----END PUBLIC KEY-----\n"
+        );
+
+        // parse back
+        let body: String = pem.lines().filter(|l| !l.starts_with("-----")).collect();
+        let der = STANDARD.decode(body).unwrap();
+        assert_eq!(der.len(), 56);
+        assert_eq!(der[..23], SPKI_SECP256K1_COMPRESSED_PREFIX);
+        assert_eq!(der[23], 0x02);
+        assert_eq!(der[24..], k.xonly_public_key_bytes().unwrap());
+    }
+
+    #[test]
+    fn test_panic_wipe() {
+        let mut k = keystore_with_state(Keys::generate(), KeysSetState::SecretAndPublic);
+        k.label = "Main".to_string();
+        k.set_note("note");
+        k.set_secret_input("nsec1...".to_string());
+        k.mnemonic_input = "oil oil oil".to_string();
+        k.save_password_input = "password".to_string();
+        let path = temp_file("panic-wipe");
+        fs::write(&path, "encrypted key data").unwrap();
+
+        let _res = k.panic_wipe(Some(&path)).unwrap();
+        assert_eq!(k.get_set_state(), KeysSetState::NotSet);
+        assert!(k.secret_key_input.is_empty());
+        assert!(k.mnemonic_input.is_empty());
+        assert!(k.save_password_input.is_empty());
+        assert!(k.label.is_empty());
+        assert!(k.note().is_empty());
+        assert!(!path.exists());
+
+        // idempotent, also when the file is already gone
+        let _res = k.panic_wipe(Some(&path)).unwrap();
+        let _res = k.panic_wipe(None).unwrap();
+        assert_eq!(k.get_set_state(), KeysSetState::NotSet);
+    }
+
+    #[test]
+    fn test_panic_wipe_locked() {
+        let mut k = keystore_with_state(Keys::generate(), KeysSetState::Locked);
+        let _res = k.panic_wipe(None).unwrap();
+        assert_eq!(k.get_set_state(), KeysSetState::NotSet);
+        assert!(k.get_encrypted_secret_key().is_none());
+    }
+
+    #[test]
+    fn test_new_and_clear_hold_no_keys() {
+        let mut k = Keystore::new();
+        assert_eq!(k.get_set_state(), KeysSetState::NotSet);
+        assert!(matches!(k.with_keys(|_keys| ()), Err(Error::KeyNotSet)));
+        assert!(k.xonly_public_key_bytes().is_none());
+
+        let _res = k.generate().unwrap();
+        assert!(k.with_keys(|_keys| ()).is_ok());
+        k.clear();
+        assert_eq!(k.get_set_state(), KeysSetState::NotSet);
+        assert!(matches!(k.with_keys(|_keys| ()), Err(Error::KeyNotSet)));
+        assert!(k.get_signer().is_err());
+    }
+
+    #[test]
+    fn test_from_keys() {
+        let keys = Keys::generate();
+        let k = Keystore::from_keys(keys.clone());
+        assert_eq!(k.get_set_state(), KeysSetState::SecretAndPublic);
+        assert!(k.is_secret_key_set());
+        assert_eq!(k.get_public_key().unwrap(), keys.public_key());
+        assert_eq!(k.has_unsaved_change, false);
+        assert!(k.secret_key_input.is_empty());
+
+        let k = Keystore::from_keys(Keys::from_public_key(keys.public_key()));
+        assert_eq!(k.get_set_state(), KeysSetState::PublicOnly);
+        assert_eq!(k.is_secret_key_set(), false);
+        assert_eq!(k.get_public_key().unwrap(), keys.public_key());
+    }
+
+    #[test]
+    fn test_eq_hash_identity() {
+        let keys = Keys::generate();
+        let k_secret = Keystore::from_keys(keys.clone());
+        let mut k_public = Keystore::from_keys(Keys::from_public_key(keys.public_key()));
+        k_public.secret_key_input = "some input".to_string();
+        assert!(k_secret == k_public);
+
+        let hash = |k: &Keystore| {
+            let mut hasher = DefaultHasher::new();
+            k.hash(&mut hasher);
+            hasher.finish()
+        };
+        assert_eq!(hash(&k_secret), hash(&k_public));
+
+        let k_other = Keystore::from_keys(Keys::generate());
+        assert!(k_secret != k_other);
+
+        let mut set = HashSet::new();
+        set.insert(k_secret);
+        set.insert(k_public);
+        set.insert(k_other);
+        assert_eq!(set.len(), 2);
+    }
+
+    #[test]
+    fn test_generate() {
+        let mut k = Keystore::new();
+        k.generate().unwrap();
+        assert!(k.is_public_key_set());
+        assert!(k.is_secret_key_set());
+        assert!(k.get_npub().len() > 60);
+        k.hide_secret_key = false;
+        assert!(k.get_nsec().len() > 60);
+        assert!(k.get_keys().is_ok());
+        assert_eq!(
+            k.get_keys().unwrap().public_key().to_bech32().unwrap(),
+            k.get_npub()
+        );
+        assert_eq!(
+            k.get_keys()
+                .unwrap()
+                .secret_key()
+                .unwrap()
+                .to_bech32()
+                .unwrap(),
+            k.get_nsec()
+        );
+
+        // test hide option
+        k.hide_secret_key = true;
+        assert_eq!(k.get_nsec(), "".to_string());
+    }
+
+    #[test]
+    fn test_import_secret_key() {
+        let mut k = Keystore::new();
+        let _res = k
+            .import_secret_key(
+                "nsec1ktekw0hr5evjs0n9nyyquz4sue568snypy2rwk5mpv6hl2hq3vtsk0kpae",
+                true,
+            )
+            .unwrap();
+        assert!(k.is_public_key_set());
+        assert!(k.is_secret_key_set());
+        assert_eq!(
+            k.get_npub(),
+            "npub1rfze4zn25ezp6jqt5ejlhrajrfx0az72ed7cwvq0spr22k9rlnjq93lmd4"
+        );
+        assert_eq!(
+            k.get_secret_key().unwrap().to_bech32().unwrap(),
+            "nsec1ktekw0hr5evjs0n9nyyquz4sue568snypy2rwk5mpv6hl2hq3vtsk0kpae"
+        );
+    }
+
+    #[test]
+    fn test_import_secret_key_hex() {
+        let mut k = Keystore::new();
+        let _res = k
+            .import_secret_key(
+                "b2f3673ee3a659283e6599080e0ab0e669a3c2640914375a9b0b357faae08b17",
+                true,
+            )
+            .unwrap();
+        assert_eq!(
+            k.get_secret_key().unwrap().to_bech32().unwrap(),
+            "nsec1ktekw0hr5evjs0n9nyyquz4sue568snypy2rwk5mpv6hl2hq3vtsk0kpae"
+        );
+    }
+
+    #[test]
+    fn test_import_secret_key_hex_uppercase() {
+        let mut k = Keystore::new();
+        let _res = k
+            .import_secret_key(
+                "B2F3673EE3A659283E6599080E0AB0E669A3C2640914375A9B0B357FAAE08B17",
+                true,
+            )
+            .unwrap();
+        assert_eq!(
+            k.get_npub(),
+            "npub1rfze4zn25ezp6jqt5ejlhrajrfx0az72ed7cwvq0spr22k9rlnjq93lmd4"
+        );
+
+        let mut k = Keystore::new();
+        let _res = k
+            .import_public_key("1A459A8A6AA6441D480BA665FB8FB21A4CFE8BCACB7D87300F8046A558A3FCE4")
+            .unwrap();
+        assert_eq!(
+            k.get_npub(),
+            "npub1rfze4zn25ezp6jqt5ejlhrajrfx0az72ed7cwvq0spr22k9rlnjq93lmd4"
+        );
+
+        // bech32 with mixed case is still rejected
+        let mut k = Keystore::new();
+        assert!(matches!(
+            k.import_secret_key(
+                "NSEC1ktekw0hr5evjs0n9nyyquz4sue568snypy2rwk5mpv6hl2hq3vtsk0kpae",
+                true
+            ),
+            Err(Error::MixedCaseBech32)
+        ));
+    }
+
+    #[test]
+    fn test_import_secret_key_hex_invalid() {
+        let mut k = Keystore::new();
+        let res = k.import_secret_key("__NOT_A_VALID_KEY__", true);
+        assert!(res.is_err());
+        assert_eq!(k.is_public_key_set(), false);
+        assert_eq!(k.is_secret_key_set(), false);
+    }
+
+    #[test]
+    fn test_import_public_key() {
+        let mut k = Keystore::new();
+        let _res = k
+            .import_public_key("npub1rfze4zn25ezp6jqt5ejlhrajrfx0az72ed7cwvq0spr22k9rlnjq93lmd4")
+            .unwrap();
+        assert!(k.is_public_key_set());
+        assert_eq!(k.is_secret_key_set(), false);
+        assert_eq!(
+            k.get_npub(),
+            "npub1rfze4zn25ezp6jqt5ejlhrajrfx0az72ed7cwvq0spr22k9rlnjq93lmd4"
+        );
+    }
+
+    #[test]
+    fn test_import_public_key_hex() {
+        let mut k = Keystore::new();
+        let _res = k
+            .import_public_key("1a459a8a6aa6441d480ba665fb8fb21a4cfe8bcacb7d87300f8046a558a3fce4")
+            .unwrap();
+        assert_eq!(
+            k.get_npub(),
+            "npub1rfze4zn25ezp6jqt5ejlhrajrfx0az72ed7cwvq0spr22k9rlnjq93lmd4"
+        );
+    }
+
+    #[test]
+    fn test_import_public_key_invalid() {
+        let mut k = Keystore::new();
+        let res = k.import_public_key("__NOT_A_VALID_KEY__");
+        assert!(res.is_err());
+        assert_eq!(k.is_public_key_set(), false);
+        assert_eq!(k.is_secret_key_set(), false);
+    }
+
+    #[test]
+    fn test_set_state_locked() {
+        let mut k = Keystore::new();
+        assert_eq!(k.get_set_state(), KeysSetState::NotSet);
+        assert_eq!(k.is_locked(), false);
+
+        let _res = k.import_encrypted_secret_key("010d6a32e0decd8553f02372df251c7f06dd0a54ba09bc0e8b2ea52e816c50f430fd0f051b2f7abcae05017f3c6f8a1ff7f3d694db4e624ef7dece7e3152b1ff536bc954eab1c85b3dbeb8e29140e84f0db5c473822e550d53a66e", false).unwrap();
+        assert_eq!(k.get_set_state(), KeysSetState::Locked);
+        assert!(k.is_locked());
+        assert_eq!(k.is_secret_key_set(), false);
+
+        // unlock
+        let _res = k.decrypt_secret_key("password").unwrap();
+        assert_eq!(k.get_set_state(), KeysSetState::SecretAndPublic);
+        assert_eq!(k.is_locked(), false);
+        assert!(k.is_secret_key_set());
+
+        // lock again, npub stays visible
+        let _res = k.lock().unwrap();
+        assert_eq!(k.get_set_state(), KeysSetState::Locked);
+        assert!(k.is_locked());
+        assert_eq!(k.is_secret_key_set(), false);
+        assert_eq!(
+            k.get_npub(),
+            "npub1rfze4zn25ezp6jqt5ejlhrajrfx0az72ed7cwvq0spr22k9rlnjq93lmd4"
+        );
+        assert!(k.get_secret_key().is_err());
+    }
+
+    #[test]
+    fn test_import_public_from_secret() {
+        let mut k = Keystore::new();
+        let _res = k
+            .import_public_from_secret(
+                "nsec1ktekw0hr5evjs0n9nyyquz4sue568snypy2rwk5mpv6hl2hq3vtsk0kpae",
+            )
+            .unwrap();
+        assert_eq!(k.get_set_state(), KeysSetState::PublicOnly);
+        assert_eq!(k.is_secret_key_set(), false);
+        assert_eq!(
+            k.get_npub(),
+            "npub1rfze4zn25ezp6jqt5ejlhrajrfx0az72ed7cwvq0spr22k9rlnjq93lmd4"
+        );
+        assert_eq!(k.origin, Some(KeyOrigin::PublicKey));
+
+        // hex
+        let mut k = Keystore::new();
+        let _res = k
+            .import_public_from_secret(
+                "b2f3673ee3a659283e6599080e0ab0e669a3c2640914375a9b0b357faae08b17",
+            )
+            .unwrap();
+        assert_eq!(k.is_secret_key_set(), false);
+        assert_eq!(
+            k.get_npub(),
+            "npub1rfze4zn25ezp6jqt5ejlhrajrfx0az72ed7cwvq0spr22k9rlnjq93lmd4"
+        );
+
+        assert!(k.import_public_from_secret("__INVALID__").is_err());
+    }
+
+    fn temp_file(name: &str) -> std::path::PathBuf {
+        let mut path = std::env::temp_dir();
+        path.push(format!(
+            "keystr-test-{}-{}",
+            name,
+            Keys::generate().public_key()
+        ));
+        path
+    }
+
+    #[test]
+    fn test_save_load_encrypted_with_progress() {
+        let path = temp_file("ncrypt");
+        let mut k = Keystore::new();
+        let _res = k
+            .import_secret_key(
+                "nsec1ktekw0hr5evjs0n9nyyquz4sue568snypy2rwk5mpv6hl2hq3vtsk0kpae",
+                true,
+            )
+            .unwrap();
+        let cancel = CancellationToken::new();
+        let last_progress = std::cell::Cell::new(0.0);
+        let record = |p: f32| last_progress.set(p);
+        let _res = k
+            .save_encrypted_secret_key_to(&path, &cancel, Some(&record))
+            .unwrap();
+        assert_eq!(last_progress.get(), 1.0);
+
+        // saved with empty password, loads unlocked
+        let mut k2 = Keystore::new();
+        let _res = k2.load_secret_key_from(&path, &cancel, None).unwrap();
+        let _ = fs::remove_file(&path);
+        assert_eq!(k2.get_set_state(), KeysSetState::SecretAndPublic);
+        assert_eq!(
+            k2.get_npub(),
+            "npub1rfze4zn25ezp6jqt5ejlhrajrfx0az72ed7cwvq0spr22k9rlnjq93lmd4"
+        );
+    }
+
+    #[test]
+    fn test_save_load_encrypted_cancel() {
+        let path = temp_file("ncrypt-cancel");
+        let mut k = Keystore::new();
+        let _res = k
+            .import_secret_key(
+                "nsec1ktekw0hr5evjs0n9nyyquz4sue568snypy2rwk5mpv6hl2hq3vtsk0kpae",
+                true,
+            )
+            .unwrap();
+        let cancel = CancellationToken::new();
+        let cancel_on_start = |p: f32| {
+            if p == 0.0 {
+                cancel.cancel();
+            }
+        };
+        assert!(matches!(
+            k.save_encrypted_secret_key_to(&path, &cancel, Some(&cancel_on_start)),
+            Err(Error::Cancelled)
+        ));
+        assert!(!path.exists());
+
+        // load, cancelled mid-KDF
+        let _res = k
+            .save_encrypted_secret_key_to(&path, &CancellationToken::new(), None)
+            .unwrap();
+        let cancel = CancellationToken::new();
+        let cancel_on_start = |p: f32| {
+            if p == 0.0 {
+                cancel.cancel();
+            }
+        };
+        let mut k2 = Keystore::new();
+        assert!(matches!(
+            k2.load_secret_key_from(&path, &cancel, Some(&cancel_on_start)),
+            Err(Error::Cancelled)
+        ));
+        let _ = fs::remove_file(&path);
+        assert_eq!(k2.get_set_state(), KeysSetState::NotSet);
+        assert!(!k2.is_encrypted_secret_key_set());
+    }
+
+    #[test]
+    fn test_npub_prefix_match() {
+        let mut k = Keystore::new();
+        assert_eq!(k.npub_prefix_match("rfz"), None);
+        let _res = k
+            .import_public_key("npub1rfze4zn25ezp6jqt5ejlhrajrfx0az72ed7cwvq0spr22k9rlnjq93lmd4")
+            .unwrap();
+        // full
+        assert_eq!(k.npub_prefix_match("rfze4"), Some(5));
+        assert_eq!(k.npub_prefix_match("npub1rfze4"), Some(5));
+        assert_eq!(k.npub_prefix_match("RFZE4"), Some(5));
+        // partial
+        assert_eq!(k.npub_prefix_match("rfzxyz"), Some(3));
+        // none
+        assert_eq!(k.npub_prefix_match("abc"), Some(0));
+        assert_eq!(k.npub_prefix_match(""), Some(0));
+    }
+
+    #[test]
+    fn test_petname() {
+        let mut k = Keystore::new();
+        assert_eq!(k.petname(), None);
+        let _res = k
+            .import_public_key("npub1rfze4zn25ezp6jqt5ejlhrajrfx0az72ed7cwvq0spr22k9rlnjq93lmd4")
+            .unwrap();
+        assert_eq!(k.petname(), Some("calm dove".to_string()));
+
+        let mut k2 = Keystore::new();
+        let _res = k2
+            .import_public_key("npub1tczgvlwvcdxp5f4mp8rqehramx6dqemq6v8egf3qdfzazn8cs7dqlhmwux")
+            .unwrap();
+        assert_eq!(k2.petname(), Some("ancient crow".to_string()));
+    }
+
+    #[test]
+    fn test_confirm_backup() {
+        let mut k = Keystore::new();
+        let _res = k
+            .import_secret_key(
+                "nsec1ktekw0hr5evjs0n9nyyquz4sue568snypy2rwk5mpv6hl2hq3vtsk0kpae",
+                true,
+            )
+            .unwrap();
+        let _res = k
+            .confirm_backup("nsec1ktekw0hr5evjs0n9nyyquz4sue568snypy2rwk5mpv6hl2hq3vtsk0kpae")
+            .unwrap();
+        // surrounding whitespace is tolerated
+        let _res = k
+            .confirm_backup(" nsec1ktekw0hr5evjs0n9nyyquz4sue568snypy2rwk5mpv6hl2hq3vtsk0kpae\n")
+            .unwrap();
+        assert!(matches!(
+            k.confirm_backup("nsec1ktekw0hr5evjs0n9nyyquz4sue568snypy2rwk5mpv6hl2hq3vtsk0kpaf"),
+            Err(Error::KeyBackupConfirmationMismatch)
+        ));
+        assert!(matches!(
+            k.confirm_backup("nsec1ktekw0hr5evjs0n9"),
+            Err(Error::KeyBackupConfirmationMismatch)
+        ));
+
+        // no secret key
+        let mut k = Keystore::new();
+        let _res = k
+            .import_public_key("npub1rfze4zn25ezp6jqt5ejlhrajrfx0az72ed7cwvq0spr22k9rlnjq93lmd4")
+            .unwrap();
+        assert!(k
+            .confirm_backup("nsec1ktekw0hr5evjs0n9nyyquz4sue568snypy2rwk5mpv6hl2hq3vtsk0kpae")
+            .is_err());
+    }
+
+    proptest! {
+        #[test]
+        fn test_imports_no_panic(
+            input in "\\PC*|(nostr:)?(npub1|nsec1|ncryptsec1|xprv)[0-9a-zA-Z]{0,110}|[0-9a-fA-F]{60,68}|([a-z]{2,8} ){11,24}[a-z]{2,8}"
+        ) {
+            check_imports_no_panic(&input);
+        }
+    }
+
+    fn check_imports_no_panic(input: &str) {
+        let formats = [
+            ImportFormat::HexPublic,
+            ImportFormat::HexSecret,
+            ImportFormat::Npub,
+            ImportFormat::Nsec,
+            ImportFormat::Mnemonic,
+            ImportFormat::Base64Secret,
+            ImportFormat::PaperBackup,
+            ImportFormat::TomlConfig,
+        ];
+        let mut k = Keystore::new();
+        let _ = k.import_public_key(input);
+        let _ = k.import_secret_key(input, true);
+        let _ = k.import_public_from_secret(input);
+        for format in formats {
+            let _ = k.import_with_format(input, format);
+        }
+        let _ = k.import_mnemonic(input, Some(input), true);
+        let _ = k.import_xprv(input, None);
+        let _ = k.import_xprv(
+            "xprv9s21ZrQH143K3QTDL4LXw2F7HEK3wJUD2nW2nRk4stbPy6cq3jPPqjiChkVvvNKmPGJxWUtg6LnF5kejMRNNU3TGtRBeJgk33yuGBxrMPHi",
+            Some(input),
+        );
+        let _ = k.import_encrypted_secret_key(input, false);
+        let _ = k.confirm_backup(input);
+        let _ = k.import_generic_json(input);
+        let _ = k.import_generic_json(&format!(
+            "{{\"nsec\":{:?},\"relays\":[{:?}]}}",
+            input, input
+        ));
+    }
+
+    #[test]
+    fn test_set_state_public_only() {
+        let mut k = Keystore::new();
+        let _res = k
+            .import_public_key("npub1rfze4zn25ezp6jqt5ejlhrajrfx0az72ed7cwvq0spr22k9rlnjq93lmd4")
+            .unwrap();
+        assert_eq!(k.get_set_state(), KeysSetState::PublicOnly);
+        // cannot lock without encrypted key
+        assert!(k.lock().is_err());
+    }
+
+    #[test]
+    fn test_import_mnemonic() {
+        let mut k = Keystore::new();
+        let _res = k
+            .import_mnemonic(
+                "oil oil oil oil oil oil oil oil oil oil oil oil",
+                None,
+                true,
+            )
+            .unwrap();
+        assert!(k.is_public_key_set());
+        assert!(k.is_secret_key_set());
+        assert_eq!(
+            k.get_npub(),
+            "npub1tczgvlwvcdxp5f4mp8rqehramx6dqemq6v8egf3qdfzazn8cs7dqlhmwux"
+        );
+        assert_eq!(
+            k.get_secret_key().unwrap().to_bech32().unwrap(),
+            "nsec16awa8nftexjs4nk8zfl5wrrtc6a7hhycj7p8ztlf4dfy5xa9dcnslmfkz5"
+        );
+    }
+
+    #[test]
+    fn test_import_mnemonic_messy_whitespace() {
+        let mut k = Keystore::new();
+        let _res = k
+            .import_mnemonic(
+                "  oil oil   oil oil\noil oil\r\noil\toil oil  oil oil\n\noil \n",
+                None,
+                true,
+            )
+            .unwrap();
+        assert_eq!(
+            k.get_npub(),
+            "npub1tczgvlwvcdxp5f4mp8rqehramx6dqemq6v8egf3qdfzazn8cs7dqlhmwux"
+        );
+    }
+
+    #[test]
+    fn test_import_from_qr_payload() {
+        let mut k = Keystore::new();
+        let _res = k
+            .import_from_qr_payload("oil oil oil oil oil oil oil oil oil oil oil oil")
+            .unwrap();
+        assert_eq!(
+            k.get_npub(),
+            "npub1tczgvlwvcdxp5f4mp8rqehramx6dqemq6v8egf3qdfzazn8cs7dqlhmwux"
+        );
+        assert_eq!(k.origin, Some(KeyOrigin::Mnemonic));
+
+        let _res = k
+            .import_from_qr_payload(
+                "nostr:nsec1ktekw0hr5evjs0n9nyyquz4sue568snypy2rwk5mpv6hl2hq3vtsk0kpae",
+            )
+            .unwrap();
+        assert_eq!(
+            k.get_npub(),
+            "npub1rfze4zn25ezp6jqt5ejlhrajrfx0az72ed7cwvq0spr22k9rlnjq93lmd4"
+        );
+        assert!(k.is_secret_key_set());
+
+        let _res = k
+            .import_from_qr_payload(
+                "npub1tczgvlwvcdxp5f4mp8rqehramx6dqemq6v8egf3qdfzazn8cs7dqlhmwux",
+            )
+            .unwrap();
+        assert_eq!(k.get_set_state(), KeysSetState::PublicOnly);
+
+        assert!(matches!(
+            k.import_from_qr_payload("https://example.com"),
+            Err(Error::QrUnrecognizedPayload)
+        ));
+
+        // action, the input is cleared
+        let mut status = StatusMessages::new();
+        k.qr_payload_input = "oil oil oil oil oil oil oil oil oil oil oil oil".to_string();
+        k.import_from_qr_payload_action(&mut status);
+        assert_eq!(k.origin, Some(KeyOrigin::Mnemonic));
+        assert!(k.qr_payload_input.is_empty());
+    }
+
+    #[test]
+    fn test_preview_mnemonic_npub() {
+        let npub = Keystore::preview_mnemonic_npub(
+            "oil oil oil oil oil oil oil oil oil oil oil oil",
+            None,
+            0,
+        )
+        .unwrap();
+        assert_eq!(
+            npub,
+            "npub1tczgvlwvcdxp5f4mp8rqehramx6dqemq6v8egf3qdfzazn8cs7dqlhmwux"
+        );
+        // different account yields different key
+        let npub1 = Keystore::preview_mnemonic_npub(
+            "oil oil oil oil oil oil oil oil oil oil oil oil",
+            None,
+            1,
+        )
+        .unwrap();
+        assert_ne!(npub1, npub);
+        // same validation error as import
+        assert!(Keystore::preview_mnemonic_npub("oil oil oil", None, 0).is_err());
+    }
+
+    #[tokio::test]
+    async fn test_discover_used_accounts() {
+        let mnemonic = "oil oil oil oil oil oil oil oil oil oil oil oil";
+        let keys_of =
+            |account| Keys::new(Keystore::derive_from_mnemonic(mnemonic, None, account).unwrap());
+        // accounts 0 and 2 are used; account 1 has only an event of another kind
+        let events = vec![
+            EventBuilder::new_text_note("note", &[])
+                .to_event(&keys_of(0))
+                .unwrap(),
+            EventBuilder::new(Kind::Custom(30023), "article", &[])
+                .to_event(&keys_of(1))
+                .unwrap(),
+            EventBuilder::new(Kind::Metadata, "{}", &[])
+                .to_event(&keys_of(2))
+                .unwrap(),
+        ];
+        let relays = vec![start_mock_relay(events)];
+        let mut k = Keystore::new();
+        k.network_config = NetworkConfig::with_timeout(Duration::from_secs(3));
+
+        let kinds = [Kind::Metadata, Kind::TextNote];
+        let used = k
+            .discover_used_accounts(mnemonic, None, &relays, 2, &kinds)
+            .unwrap()
+            .await
+            .unwrap();
+        assert_eq!(
+            used,
+            vec![(0, keys_of(0).public_key()), (2, keys_of(2).public_key())]
+        );
+        // gap of 1 is too short to find account 2
+        let used = k
+            .discover_used_accounts(mnemonic, None, &relays, 1, &kinds)
+            .unwrap()
+            .await
+            .unwrap();
+        assert_eq!(used, vec![(0, keys_of(0).public_key())]);
+        // any kind counts when no kinds are given
+        let used = k
+            .discover_used_accounts(mnemonic, None, &relays, 2, &[])
+            .unwrap()
+            .await
+            .unwrap();
+        assert_eq!(
+            used.iter().map(|(account, _)| *account).collect::<Vec<_>>(),
+            vec![0, 1, 2]
+        );
+    }
+
+    #[tokio::test]
+    async fn test_scan_used_accounts_from_mnemonic() {
+        let mnemonic = "oil oil oil oil oil oil oil oil oil oil oil oil";
+        let keys_of =
+            |account| Keys::new(Keystore::derive_from_mnemonic(mnemonic, None, account).unwrap());
+        // accounts 0 and 2 are used, with different kinds of events
+        let events = vec![
+            EventBuilder::new_text_note("note", &[])
+                .to_event(&keys_of(0))
+                .unwrap(),
+            EventBuilder::new(Kind::Custom(30023), "article", &[])
+                .to_event(&keys_of(2))
+                .unwrap(),
+        ];
+        let relays = vec![start_mock_relay(events)];
+        let mut k = Keystore::new();
+        k.network_config = NetworkConfig::with_timeout(Duration::from_secs(3));
+
+        let used = k
+            .scan_used_accounts_from_mnemonic(mnemonic, &relays, 2)
+            .unwrap()
+            .await
+            .unwrap();
+        assert_eq!(
+            used,
+            vec![
+                (
+                    0,
+                    "npub1tczgvlwvcdxp5f4mp8rqehramx6dqemq6v8egf3qdfzazn8cs7dqlhmwux".to_string()
+                ),
+                (2, keys_of(2).public_key().to_bech32().unwrap()),
+            ]
+        );
+        // gap of 1 is too short to find account 2
+        let used = k
+            .scan_used_accounts_from_mnemonic(mnemonic, &relays, 1)
+            .unwrap()
+            .await
+            .unwrap();
+        assert_eq!(used.len(), 1);
+        assert_eq!(used[0].0, 0);
+    }
+
+    #[tokio::test]
+    async fn test_discover_used_accounts_no_network() {
+        let mnemonic = "oil oil oil oil oil oil oil oil oil oil oil oil";
+        let relays = vec!["wss://relay.example.com".to_string()];
+        let mut k = Keystore::new();
+        // nothing to scan
+        assert!(k
+            .discover_used_accounts(mnemonic, None, &relays, 0, &[])
+            .unwrap()
+            .await
+            .unwrap()
+            .is_empty());
+        // invalid mnemonic is an error, before any network access
+        assert!(matches!(
+            k.discover_used_accounts("oil oil oil", None, &relays, 3, &[])
+                .unwrap()
+                .await,
+            Err(Error::KeyMnemonic(_))
+        ));
+        k.set_offline(true);
+        assert!(matches!(
+            k.discover_used_accounts(mnemonic, None, &relays, 3, &[]),
+            Err(Error::OfflineMode)
+        ));
+    }
+
+    #[test]
+    fn test_sign_auth_challenge() {
+        let k = Keystore::from_keys(Keys::generate());
+        let event = k
+            .sign_auth_challenge("wss://relay.example.com", "challenge-123")
+            .unwrap();
+        assert_eq!(event.kind.as_u64(), 22242);
+        assert_eq!(event.pubkey, k.get_public_key().unwrap());
+        let tags: Vec<Vec<String>> = event.tags.iter().map(|t| t.as_vec()).collect();
+        assert!(tags.contains(&vec![
+            "relay".to_string(),
+            Url::parse("wss://relay.example.com").unwrap().to_string()
+        ]));
+        assert!(tags.contains(&vec!["challenge".to_string(), "challenge-123".to_string()]));
+        assert!(event.verify().is_ok());
+    }
+
+    #[test]
+    fn test_sign_auth_challenge_no_secret() {
+        let k = Keystore::from_keys(Keys::from_public_key(Keys::generate().public_key()));
+        assert!(k
+            .sign_auth_challenge("wss://relay.example.com", "challenge-123")
+            .is_err());
+        assert!(k
+            .sign_auth_challenge("__NOT_A_URL__", "challenge-123")
+            .is_err());
+    }
+
+    #[test]
+    fn test_upgrade_with_secret() {
+        let mut k = Keystore::new();
+        let _res = k
+            .import_public_key("npub1rfze4zn25ezp6jqt5ejlhrajrfx0az72ed7cwvq0spr22k9rlnjq93lmd4")
+            .unwrap();
+        k.label = "Main".to_string();
+        k.set_note("my note");
+        assert_eq!(k.get_set_state(), KeysSetState::PublicOnly);
+
+        let _res = k
+            .upgrade_with_secret("nsec1ktekw0hr5evjs0n9nyyquz4sue568snypy2rwk5mpv6hl2hq3vtsk0kpae")
+            .unwrap();
+        assert_eq!(k.get_set_state(), KeysSetState::SecretAndPublic);
+        assert_eq!(
+            k.get_npub(),
+            "npub1rfze4zn25ezp6jqt5ejlhrajrfx0az72ed7cwvq0spr22k9rlnjq93lmd4"
+        );
+        assert_eq!(k.label, "Main");
+        assert_eq!(k.note(), "my note");
+        assert_eq!(k.origin, Some(KeyOrigin::SecretKey));
+        assert!(k.has_unsaved_change);
+    }
+
+    #[test]
+    fn test_import_secret_key_action_upgrade() {
+        let mut k = Keystore::new();
+        let _res = k
+            .import_public_key("npub1rfze4zn25ezp6jqt5ejlhrajrfx0az72ed7cwvq0spr22k9rlnjq93lmd4")
+            .unwrap();
+        k.label = "Main".to_string();
+        let mut status = StatusMessages::new();
+        k.secret_key_input =
+            "nsec1ktekw0hr5evjs0n9nyyquz4sue568snypy2rwk5mpv6hl2hq3vtsk0kpae".to_string();
+        k.import_secret_key_action(&mut status);
+        assert_eq!(k.get_set_state(), KeysSetState::SecretAndPublic);
+        assert_eq!(k.label, "Main");
+        assert!(status.get_last().contains("added to the public key"));
+        assert!(k.secret_key_input.is_empty());
+
+        // a secret key of other keys replaces them
+        k.secret_key_input = Keys::generate().secret_key().unwrap().to_bech32().unwrap();
+        k.import_secret_key_action(&mut status);
+        assert_eq!(status.get_last(), "Secret key imported");
+        assert_ne!(
+            k.get_npub(),
+            "npub1rfze4zn25ezp6jqt5ejlhrajrfx0az72ed7cwvq0spr22k9rlnjq93lmd4"
+        );
+    }
+
+    #[test]
+    fn test_upgrade_with_secret_mismatch() {
+        let mut k = Keystore::new();
+        assert!(matches!(
+            k.upgrade_with_secret(
+                "nsec1ktekw0hr5evjs0n9nyyquz4sue568snypy2rwk5mpv6hl2hq3vtsk0kpae"
+            ),
+            Err(Error::KeyNotSet)
+        ));
+
+        let _res = k
+            .import_public_key("npub1rfze4zn25ezp6jqt5ejlhrajrfx0az72ed7cwvq0spr22k9rlnjq93lmd4")
+            .unwrap();
+        let other_nsec = Keys::generate().secret_key().unwrap().to_bech32().unwrap();
+        assert!(matches!(
+            k.upgrade_with_secret(&other_nsec),
+            Err(Error::KeyIdentityMismatch)
+        ));
+        assert!(k.upgrade_with_secret("__NOT_A_KEY__").is_err());
+        assert_eq!(k.get_set_state(), KeysSetState::PublicOnly);
+    }
+
+    #[test]
+    fn test_import_auto() {
+        let npub = "npub1rfze4zn25ezp6jqt5ejlhrajrfx0az72ed7cwvq0spr22k9rlnjq93lmd4";
+        let mut k = Keystore::new();
+
+        let _res = k.import_auto(&format!(" nostr:{npub} ")).unwrap();
+        assert_eq!(k.get_set_state(), KeysSetState::PublicOnly);
+        assert_eq!(k.get_npub(), npub);
+
+        let _res = k
+            .import_auto("nsec1ktekw0hr5evjs0n9nyyquz4sue568snypy2rwk5mpv6hl2hq3vtsk0kpae")
+            .unwrap();
+        assert_eq!(k.get_set_state(), KeysSetState::SecretAndPublic);
+        assert_eq!(k.get_npub(), npub);
+
+        let _res = k
+            .import_auto("oil oil oil oil oil oil oil oil oil oil oil oil")
+            .unwrap();
+        assert_eq!(
+            k.get_npub(),
+            "npub1tczgvlwvcdxp5f4mp8rqehramx6dqemq6v8egf3qdfzazn8cs7dqlhmwux"
+        );
+
+        assert!(matches!(
+            k.import_auto("1a459a8a6aa6441d480ba665fb8fb21a4cfe8bcacb7d87300f8046a558a3fce4"),
+            Err(Error::KeyHexAmbiguous)
+        ));
+        assert!(matches!(
+            k.import_auto("__NOT_A_VALID_KEY__"),
+            Err(Error::KeyUnrecognizedFormat)
+        ));
+
+        let mut k = Keystore::new();
+        let _res = k
+            .import_auto(r#" {"nsec":"nsec1ktekw0hr5evjs0n9nyyquz4sue568snypy2rwk5mpv6hl2hq3vtsk0kpae","relays":["wss://nos.lol"]} "#)
+            .unwrap();
+        assert_eq!(k.get_set_state(), KeysSetState::SecretAndPublic);
+        assert_eq!(k.get_npub(), npub);
+        assert_eq!(k.relays_input, "wss://nos.lol");
+    }
+
+    #[test]
+    fn test_enrollment_frames() {
+        let mut k = Keystore::new();
+        let _res = k
+            .import_secret_key(
+                "nsec1ktekw0hr5evjs0n9nyyquz4sue568snypy2rwk5mpv6hl2hq3vtsk0kpae",
+                true,
+            )
+            .unwrap();
+        let frames = k.enrollment_frames("password").unwrap();
+        assert!(frames.len() > 1);
+        for f in &frames {
+            assert!(!f.contains("nsec1"));
+            assert!(!f.contains("b2f3673ee3a659283e6599080e0ab0e669a3c2640914375a9b0b357faae08b17"));
+        }
+
+        // frames are in order, reassemble by stripping the headers
+        let ncryptsec: String = frames
+            .iter()
+            .map(|f| f.splitn(3, ':').nth(2).unwrap())
+            .collect();
+        let (hrp, data, _variant) = bech32::decode(&ncryptsec).unwrap();
+        assert_eq!(hrp, "ncryptsec");
+        let encrypted = Vec::<u8>::from_base32(&data).unwrap();
+        let sk = Encrypt::decrypt_key(&encrypted, "password").unwrap();
+        assert_eq!(
+            sk.to_bech32().unwrap(),
+            "nsec1ktekw0hr5evjs0n9nyyquz4sue568snypy2rwk5mpv6hl2hq3vtsk0kpae"
+        );
+
+        let svgs = k.enrollment_qr("password").unwrap();
+        assert_eq!(svgs.len(), frames.len());
+        assert!(svgs[0].contains("<svg"));
+    }
+
+    #[test]
+    fn test_estimated_export_size() {
+        let mut k = Keystore::new();
+        let _res = k.generate().unwrap();
+        let password = "password";
+
+        assert_eq!(
+            k.estimated_export_size(ExportFormat::Npub),
+            k.get_npub().len()
+        );
+        assert_eq!(
+            k.estimated_export_size(ExportFormat::Ncryptsec),
+            k.encrypted_secret_key_ncryptsec(password).unwrap().len()
+        );
+
+        k.save_password_input = password.to_string();
+        k.save_repeat_password_input = password.to_string();
+        let path = temp_file("estimated-size");
+        let _res = k
+            .save_encrypted_secret_key_to(&path, &CancellationToken::new(), None)
+            .unwrap();
+        assert_eq!(
+            k.estimated_export_size(ExportFormat::EncryptedSecretKey),
+            fs::read_to_string(&path).unwrap().len()
+        );
+        let _res = fs::remove_file(&path);
+
+        // QR size depends on the content, estimate is within 10%
+        let estimate = k.estimated_export_size(ExportFormat::EnrollmentQr);
+        let actual: usize = k
+            .enrollment_qr(password)
+            .unwrap()
+            .iter()
+            .map(|svg| svg.len())
+            .sum();
+        assert!(
+            estimate.abs_diff(actual) * 10 < actual,
+            "{estimate} {actual}"
+        );
+        assert!(estimate < MAX_QR_EXPORT_SIZE);
+    }
+
+    #[test]
+    fn test_enrollment_frames_no_secret() {
+        let mut k = Keystore::new();
+        let _res = k
+            .import_public_key("npub1rfze4zn25ezp6jqt5ejlhrajrfx0az72ed7cwvq0spr22k9rlnjq93lmd4")
+            .unwrap();
+        assert!(k.enrollment_qr("password").is_err());
+    }
+
+    #[test]
+    fn test_import_generic_json() {
+        let mut k = Keystore::new();
+        let _res = k
+            .import_generic_json(
+                r#"{"nsec":"nsec1ktekw0hr5evjs0n9nyyquz4sue568snypy2rwk5mpv6hl2hq3vtsk0kpae","relays":["wss://relay.damus.io","__NOT_A_RELAY__"]}"#,
+            )
+            .unwrap();
+        assert_eq!(
+            k.get_npub(),
+            "npub1rfze4zn25ezp6jqt5ejlhrajrfx0az72ed7cwvq0spr22k9rlnjq93lmd4"
+        );
+        assert!(k.is_secret_key_set());
+        assert_eq!(k.relays_input, "wss://relay.damus.io");
+
+        // other field names, hex key, relays as object
+        let mut k = Keystore::new();
+        let _res = k
+            .import_generic_json(
+                r#"{"privateKey":"b2f3673ee3a659283e6599080e0ab0e669a3c2640914375a9b0b357faae08b17","relayList":{"wss://nos.lol":{"read":true,"write":true}}}"#,
+            )
+            .unwrap();
+        assert_eq!(
+            k.get_npub(),
+            "npub1rfze4zn25ezp6jqt5ejlhrajrfx0az72ed7cwvq0spr22k9rlnjq93lmd4"
+        );
+        assert_eq!(k.relays_input, "wss://nos.lol");
+
+        // no relays, the relays input is kept
+        let mut k = Keystore::new();
+        k.relays_input = "wss://relay.damus.io".to_string();
+        let _res = k
+            .import_generic_json(
+                r#"{"sec":"nsec1ktekw0hr5evjs0n9nyyquz4sue568snypy2rwk5mpv6hl2hq3vtsk0kpae"}"#,
+            )
+            .unwrap();
+        assert!(k.is_secret_key_set());
+        assert_eq!(k.relays_input, "wss://relay.damus.io");
+    }
+
+    #[test]
+    fn test_import_generic_json_invalid() {
+        let mut k = Keystore::new();
+        k.relays_input = "wss://relay.damus.io".to_string();
+        assert!(k.import_generic_json("not json").is_err());
+        assert!(matches!(
+            k.import_generic_json(
+                r#"{"npub":"npub1rfze4zn25ezp6jqt5ejlhrajrfx0az72ed7cwvq0spr22k9rlnjq93lmd4"}"#
+            ),
+            Err(Error::ImportJsonNoSecretKey)
+        ));
+        assert!(k
+            .import_generic_json(r#"{"nsec":"nsec1invalid","relays":["wss://nos.lol"]}"#)
+            .is_err());
+        // unchanged
+        assert_eq!(k.keys_is_set(), false);
+        assert_eq!(k.relays_input, "wss://relay.damus.io");
+    }
+
+    #[test]
+    fn test_generate_with_intro() {
+        let mut k = Keystore::new();
+        let event = k.generate_with_intro("Hello Nostr, I'm new here!").unwrap();
+        assert_eq!(k.get_set_state(), KeysSetState::SecretAndPublic);
+        assert_eq!(k.origin, Some(KeyOrigin::Generated));
+        assert_eq!(event.pubkey, k.get_public_key().unwrap());
+        assert_eq!(event.kind, Kind::TextNote);
+        assert_eq!(event.content, "Hello Nostr, I'm new here!");
+        assert!(event.verify().is_ok());
+
+        // a new key each time
+        let event2 = k.generate_with_intro("Hello again").unwrap();
+        assert_ne!(event2.pubkey, event.pubkey);
+
+        // text notes not allowed: keys are not changed
+        let pubkey = k.get_public_key().unwrap();
+        k.set_allowed_kinds(Some([0u16].into_iter().collect()));
+        assert!(matches!(
+            k.generate_with_intro("Hello"),
+            Err(Error::KindNotAllowed(1))
+        ));
+        assert_eq!(k.get_public_key().unwrap(), pubkey);
+    }
+
+    #[test]
+    fn test_compute_event_id() {
+        let npub = "npub1rfze4zn25ezp6jqt5ejlhrajrfx0az72ed7cwvq0spr22k9rlnjq93lmd4";
+        let mut k = Keystore::new();
+        let _res = k.import_public_key(npub).unwrap();
+        assert_eq!(k.get_set_state(), KeysSetState::PublicOnly);
+
+        let mut unsigned =
+            EventBuilder::new_text_note("Hello", &[Tag::Hashtag("nostr".to_string())])
+                .to_unsigned_event(k.get_public_key().unwrap());
+        assert_eq!(k.compute_event_id(&unsigned), *unsigned.id.as_bytes());
+
+        // known id, computed independently
+        unsigned.created_at = Timestamp::from(1680000000);
+        assert_eq!(
+            hex::encode(k.compute_event_id(&unsigned)),
+            "459007beb360ff5e3ff5ab7ff72411251a03bedaced3281b2963b8b426eddc24"
+        );
+    }
+
+    #[test]
+    fn test_toml_fragment() {
+        let nsec = "nsec1ktekw0hr5evjs0n9nyyquz4sue568snypy2rwk5mpv6hl2hq3vtsk0kpae";
+        let mut k = Keystore::new();
+        k.import_secret_key(nsec, true).unwrap();
+        k.label = "Main \"work\" key\tA\\B".to_string();
+        let toml = k.to_toml_fragment();
+        assert!(toml.starts_with("[keystr]\n"));
+        assert!(toml.contains(
+            "npub = \"npub1rfze4zn25ezp6jqt5ejlhrajrfx0az72ed7cwvq0spr22k9rlnjq93lmd4\"\n"
+        ));
+        assert!(toml.contains("set_level = \"SecretAndPublic\"\n"));
+        // no secret in any form
+        assert!(!toml.contains("nsec"));
+        assert!(!toml.contains(nsec));
+        assert!(!toml.contains("b2f3673ee3a659283e6599080e0ab0e669a3c2640914375a9b0b357faae08b17"));
+
+        let loaded = Keystore::from_toml_fragment(&toml).unwrap();
+        assert_eq!(
+            loaded.get_public_key().unwrap(),
+            k.get_public_key().unwrap()
+        );
+        assert_eq!(loaded.label, k.label);
+        assert_eq!(loaded.get_set_state(), KeysSetState::PublicOnly);
+        assert_eq!(
+            loaded.to_toml_fragment(),
+            toml.replace("SecretAndPublic", "PublicOnly")
+        );
+
+        // embedded in a larger config, other tables are ignored
+        let config =
+            format!("title = \"app\"\n\n[other]\nlabel = \"x\"\n\n{toml}\n[more]\nnpub = 1\n");
+        let loaded = Keystore::from_toml_fragment(&config).unwrap();
+        assert_eq!(loaded.label, k.label);
+
+        // as import format, into the current keystore
+        let mut k2 = Keystore::new();
+        k2.import_with_format(&toml, ImportFormat::TomlConfig)
+            .unwrap();
+        assert_eq!(k2.get_set_state(), KeysSetState::PublicOnly);
+        assert_eq!(k2.get_public_key().unwrap(), k.get_public_key().unwrap());
+        assert_eq!(k2.label, k.label);
+
+        // not set
+        let toml = Keystore::new().to_toml_fragment();
+        assert!(toml.contains("set_level = \"NotSet\"\n"));
+        assert!(!toml.contains("npub"));
+        assert_eq!(
+            Keystore::from_toml_fragment(&toml).unwrap().get_set_state(),
+            KeysSetState::NotSet
+        );
+
+        assert!(matches!(
+            Keystore::from_toml_fragment("[keystr]\nlabel = unquoted"),
+            Err(Error::TomlInvalid(_))
+        ));
+        assert!(matches!(
+            Keystore::from_toml_fragment("[keystr]\nlabel"),
+            Err(Error::TomlInvalid(_))
+        ));
+        assert!(matches!(
+            Keystore::from_toml_fragment("[keystr]\nlabel = 1"),
+            Err(Error::TomlInvalid(_))
+        ));
+    }
+
+    #[test]
+    fn test_snapshot() {
+        fn assert_clone_send_sync<T: Clone + Send + Sync + 'static>(_t: &T) {}
+
+        let snapshot = Keystore::new().snapshot();
+        assert_clone_send_sync(&snapshot);
+        assert_eq!(snapshot.npub, None);
+        assert_eq!(snapshot.set_state, KeysSetState::NotSet);
+
+        let nsec = "nsec1ktekw0hr5evjs0n9nyyquz4sue568snypy2rwk5mpv6hl2hq3vtsk0kpae";
+        let mut k = Keystore::new();
+        let _res = k.import_secret_key(nsec, true).unwrap();
+        let snapshot = k.snapshot();
+        assert_eq!(
+            snapshot.npub.as_deref(),
+            Some("npub1rfze4zn25ezp6jqt5ejlhrajrfx0az72ed7cwvq0spr22k9rlnjq93lmd4")
+        );
+        assert_eq!(snapshot.set_state, KeysSetState::SecretAndPublic);
+        assert_eq!(snapshot.origin, Some(KeyOrigin::SecretKey));
+        // exhaustive destructuring: adding a field (e.g. a secret) breaks this test, to be reviewed
+        let PublicSnapshot {
+            npub: _,
+            set_state: _,
+            origin: _,
+        } = snapshot.clone();
+        assert!(!format!("{:?}", snapshot).contains(nsec));
+
+        // usable from another thread
+        let handle = std::thread::spawn(move || snapshot.npub.is_some());
+        assert!(handle.join().unwrap());
+    }
+
+    #[test]
+    fn test_debug_redacted() {
+        let nsec = "nsec1ktekw0hr5evjs0n9nyyquz4sue568snypy2rwk5mpv6hl2hq3vtsk0kpae";
+        let mut k = Keystore::new();
+        let _res = k.import_secret_key(nsec, true).unwrap();
+        k.set_secret_input(nsec.to_string());
+        let debug = format!("{:?}", k);
+        assert!(debug.contains("SecretAndPublic"));
+        assert!(debug.contains("npub1rfze4zn25ezp6jqt5ejlhrajrfx0az72ed7cwvq0spr22k9rlnjq93lmd4"));
+        assert!(debug.contains("[redacted]"));
+        assert!(!debug.contains(nsec));
+        assert!(!debug.contains("b2f3673ee3a659283e6599080e0ab0e669a3c2640914375a9b0b357faae08b17"));
+        let debug = format!("{:#?}", k);
+        assert!(!debug.contains(nsec));
+
+        let guard = k.reveal_nsec_timed(Duration::from_secs(5)).unwrap();
+        let debug = format!("{:?}", guard);
+        assert!(debug.contains("[redacted]"));
+        assert!(!debug.contains(nsec));
+
+        assert!(!format!("{:?}", Keystore::new()).contains("[redacted]"));
+    }
+
+    #[test]
+    fn test_import_from_clipboard_content() {
+        let mut k = Keystore::new();
+        assert!(matches!(
+            k.import_from_clipboard_content(None),
+            Err(Error::ClipboardEmpty)
+        ));
+        assert!(matches!(
+            k.import_from_clipboard_content(Some(" \n".to_string())),
+            Err(Error::ClipboardEmpty)
+        ));
+        assert_eq!(
+            k.import_from_clipboard_content(Some(
+                "nostr:npub1rfze4zn25ezp6jqt5ejlhrajrfx0az72ed7cwvq0spr22k9rlnjq93lmd4\n"
+                    .to_string()
+            ))
+            .unwrap(),
+            KeysSetState::PublicOnly
+        );
+        assert_eq!(
+            k.import_from_clipboard_content(Some(
+                "nsec1ktekw0hr5evjs0n9nyyquz4sue568snypy2rwk5mpv6hl2hq3vtsk0kpae".to_string()
+            ))
+            .unwrap(),
+            KeysSetState::SecretAndPublic
+        );
+    }
+
+    #[cfg(feature = "clipboard")]
+    #[test]
+    fn test_import_from_clipboard() {
+        let npub = "npub1rfze4zn25ezp6jqt5ejlhrajrfx0az72ed7cwvq0spr22k9rlnjq93lmd4";
+        let _res = arboard::Clipboard::new().unwrap().set_text(npub).unwrap();
+        let mut k = Keystore::new();
+        assert_eq!(k.import_from_clipboard().unwrap(), KeysSetState::PublicOnly);
+        assert_eq!(k.get_npub(), npub);
+    }
+
+    #[test]
+    fn test_import_auto_ncryptsec() {
+        let keys = Keys::generate();
+        let ncryptsec = Keystore::from_keys(keys.clone())
+            .encrypted_secret_key_ncryptsec("password")
+            .unwrap();
+        let mut k = Keystore::new();
+        assert!(matches!(
+            k.import_auto(&format!(" {ncryptsec}\n")),
+            Err(Error::KeyNeedsPassword)
+        ));
+        assert_eq!(k.get_set_state(), KeysSetState::NotSet);
+
+        // then import with the password
+        assert!(matches!(
+            k.import_ncryptsec(&ncryptsec, "wrongpassword"),
+            Err(Error::KeyDecryptionFailed)
+        ));
+        assert_eq!(k.get_set_state(), KeysSetState::NotSet);
+        let _res = k.import_ncryptsec(&ncryptsec, "password").unwrap();
+        assert_eq!(k.get_set_state(), KeysSetState::SecretAndPublic);
+        assert_eq!(k.get_public_key().unwrap(), keys.public_key());
+    }
+
+    #[test]
+    fn test_import_ncryptsec_action_from_clipboard() {
+        let keys = Keys::generate();
+        let ncryptsec = Keystore::from_keys(keys.clone())
+            .encrypted_secret_key_ncryptsec("password")
+            .unwrap();
+        let mut k = Keystore::new();
+        let mut status = StatusMessages::new();
+        k.import_from_clipboard_action(Some(format!("{ncryptsec}\n")), &mut status);
+        assert_eq!(k.get_set_state(), KeysSetState::NotSet);
+        assert_eq!(k.ncryptsec_input, ncryptsec);
+
+        // wrong password, the pasted key is kept for retry
+        k.decrypt_password_input = "wrongpassword".to_string();
+        k.import_ncryptsec_action(&mut status);
+        assert_eq!(k.get_set_state(), KeysSetState::NotSet);
+        assert_eq!(k.ncryptsec_input, ncryptsec);
+        assert!(k.decrypt_password_input.is_empty());
+
+        k.decrypt_password_input = "password".to_string();
+        k.import_ncryptsec_action(&mut status);
+        assert_eq!(k.get_set_state(), KeysSetState::SecretAndPublic);
+        assert_eq!(k.get_public_key().unwrap(), keys.public_key());
+        assert!(k.ncryptsec_input.is_empty());
+        assert_eq!(status.get_last(), "Encrypted secret key imported");
+    }
+
+    #[test]
+    fn test_import_auto_not_a_key() {
+        let cases = [
+            ("satoshi@getalby.com", InputKind::LightningAddress),
+            (
+                "LNURL1DP68GURN8GHJ7UM9WFMXJCM99E3K7MF0V9CXJ0M385EKVCENXC6R2C35XVUKXEFCV5MS47H9ZS",
+                InputKind::Lnurl,
+            ),
+            (
+                "lightning:lnbc2500u1pvjluezpp5qqqsyqcyq5rqwzqfqqqsyqcyq5rqwzqfqqqsyqcyq5rqwzqfqypq",
+                InputKind::LightningInvoice,
+            ),
+            (
+                "nostr:note1tjpa5aa0rhkx6u5fsdye3tt6477eugv389khtmpucfl45aezdumqkkhgwy",
+                InputKind::NoteId,
+            ),
+            (
+                "nevent1qqs9eq76w7h3mmrdw2ycxjvc44a2l0v7yxgnjmt4as7vyl66wu3x7dsrtwlqa",
+                InputKind::Event,
+            ),
+            (
+                "naddr1qqyk67fddehhxarj95pzqxj9n29x4fjyr4yqhfn9lw8myxjvl69u4jmasucqlqzx54v28l8yqvzqqqr4guzn4xpm",
+                InputKind::Address,
+            ),
+            (
+                "nprofile1qqsrhuxx8l9ex335q7he0f09aej04zpazpl0ne2cgukyawd24mayt8gpp4mhxue69uhhytnc9e3k7mgpz4mhxue69uhkg6nzv9ejuumpv34kytnrdaksjlyr9p",
+                InputKind::Profile,
+            ),
+        ];
+        for (input, kind) in cases {
+            let mut k = Keystore::new();
+            match k.import_auto(input) {
+                Err(Error::NotAKey { detected }) => assert_eq!(detected, kind),
+                other => panic!("unexpected result for {input}: {other:?}"),
+            }
+            assert_eq!(k.get_set_state(), KeysSetState::NotSet);
+        }
+
+        assert_eq!(
+            Error::NotAKey {
+                detected: InputKind::LightningAddress
+            }
+            .to_string(),
+            "That looks like a lightning address (or NIP-05 identifier), not a key"
+        );
+    }
+
+    #[test]
+    fn test_import_with_format() {
+        let hex = "1a459a8a6aa6441d480ba665fb8fb21a4cfe8bcacb7d87300f8046a558a3fce4";
+        let npub = "npub1rfze4zn25ezp6jqt5ejlhrajrfx0az72ed7cwvq0spr22k9rlnjq93lmd4";
+        let mut k = Keystore::new();
+
+        // the same hex string is a valid public and secret key, format decides
+        let _res = k.import_with_format(hex, ImportFormat::HexPublic).unwrap();
+        assert_eq!(k.get_set_state(), KeysSetState::PublicOnly);
+        assert_eq!(k.get_npub(), npub);
+        let _res = k.import_with_format(hex, ImportFormat::HexSecret).unwrap();
+        assert_eq!(k.get_set_state(), KeysSetState::SecretAndPublic);
+        assert_ne!(k.get_npub(), npub);
+
+        let _res = k.import_with_format(npub, ImportFormat::Npub).unwrap();
+        assert_eq!(k.get_set_state(), KeysSetState::PublicOnly);
+        assert_eq!(k.get_npub(), npub);
+
+        let _res = k
+            .import_with_format(
+                "nsec1ktekw0hr5evjs0n9nyyquz4sue568snypy2rwk5mpv6hl2hq3vtsk0kpae",
+                ImportFormat::Nsec,
+            )
+            .unwrap();
+        assert_eq!(k.get_set_state(), KeysSetState::SecretAndPublic);
+        assert_eq!(k.get_npub(), npub);
+
+        let _res = k
+            .import_with_format(
+                "oil oil oil oil oil oil oil oil oil oil oil oil",
+                ImportFormat::Mnemonic,
+            )
+            .unwrap();
+        assert_eq!(
+            k.get_npub(),
+            "npub1tczgvlwvcdxp5f4mp8rqehramx6dqemq6v8egf3qdfzazn8cs7dqlhmwux"
+        );
+    }
+
+    #[test]
+    fn test_convert_key_action() {
+        let mut k = Keystore::new();
+        let mut status = StatusMessages::new();
+        k.convert_input =
+            " nsec1ktekw0hr5evjs0n9nyyquz4sue568snypy2rwk5mpv6hl2hq3vtsk0kpae ".to_string();
+        k.convert_key_action(nsec_to_hex, &mut status);
+        assert_eq!(
+            k.convert_input,
+            "b2f3673ee3a659283e6599080e0ab0e669a3c2640914375a9b0b357faae08b17"
+        );
+        k.convert_key_action(hex_to_nsec, &mut status);
+        assert_eq!(
+            k.convert_input,
+            "nsec1ktekw0hr5evjs0n9nyyquz4sue568snypy2rwk5mpv6hl2hq3vtsk0kpae"
+        );
+        // not imported
+        assert!(!k.keys_is_set());
+
+        k.convert_key_action(npub_to_hex, &mut status);
+        assert!(status.get_last().starts_with("Error"));
+        assert_eq!(
+            k.convert_input,
+            "nsec1ktekw0hr5evjs0n9nyyquz4sue568snypy2rwk5mpv6hl2hq3vtsk0kpae"
+        );
+    }
+
+    #[test]
+    fn test_import_with_format_base64() {
+        let npub = "npub1rfze4zn25ezp6jqt5ejlhrajrfx0az72ed7cwvq0spr22k9rlnjq93lmd4";
+        let mut k = Keystore::new();
+        let _res = k
+            .import_with_format(
+                " svNnPuOmWSg+ZZkIDgqw5mmjwmQJFDdamws1f6rgixc=\n",
+                ImportFormat::Base64Secret,
+            )
+            .unwrap();
+        assert_eq!(k.get_set_state(), KeysSetState::SecretAndPublic);
+        assert_eq!(k.get_npub(), npub);
+
+        // without padding
+        let mut k = Keystore::new();
+        let _res = k
+            .import_with_format(
+                "svNnPuOmWSg+ZZkIDgqw5mmjwmQJFDdamws1f6rgixc",
+                ImportFormat::Base64Secret,
+            )
+            .unwrap();
+        assert_eq!(k.get_npub(), npub);
+
+        // not 32 bytes
+        for input in [
+            "svNnPuOmWSg+ZZkIDgqw5mmjwmQJFDdamws1f6rgiw==",
+            "svNnPuOmWSg+ZZkIDgqw5mmjwmQJFDdamws1f6rgixcA",
+            "svNnPuOmWSg+ZZkIDgqw5mmjwmQJFDdamws1f6rgixc=A",
+            "!!!!PuOmWSg+ZZkIDgqw5mmjwmQJFDdamws1f6rgixc=",
+            "",
+        ] {
+            let mut k = Keystore::new();
+            assert!(matches!(
+                k.import_with_format(input, ImportFormat::Base64Secret),
+                Err(Error::KeyInvalidBase64Secret)
+            ));
+            assert_eq!(k.get_set_state(), KeysSetState::NotSet);
+        }
+    }
+
+    #[test]
+    fn test_with_keys() {
+        let mut k = Keystore::new();
+        assert!(matches!(
+            k.with_keys(|keys| keys.public_key()),
+            Err(Error::KeyNotSet)
+        ));
+        let _res = k
+            .import_secret_key(
+                "nsec1ktekw0hr5evjs0n9nyyquz4sue568snypy2rwk5mpv6hl2hq3vtsk0kpae",
+                true,
+            )
+            .unwrap();
+        let npub = k
+            .with_keys(|keys| keys.public_key().to_bech32().unwrap())
+            .unwrap();
+        assert_eq!(
+            npub,
+            "npub1rfze4zn25ezp6jqt5ejlhrajrfx0az72ed7cwvq0spr22k9rlnjq93lmd4"
+        );
+    }
+
+    #[test]
+    fn test_create_http_auth() {
+        let mut k = Keystore::new();
+        let _res = k
+            .import_secret_key(
+                "nsec1ktekw0hr5evjs0n9nyyquz4sue568snypy2rwk5mpv6hl2hq3vtsk0kpae",
+                true,
+            )
+            .unwrap();
+        let url = "https://files.example.com/upload";
+        let hash = "1a459a8a6aa6441d480ba665fb8fb21a4cfe8bcacb7d87300f8046a558a3fce4";
+        let auth = k.create_http_auth("post", url, Some(hash)).unwrap();
+        assert!(auth.event.verify().is_ok());
+        assert_eq!(auth.event.kind.as_u64(), 27235);
+        assert_eq!(auth.event.content, "");
+        let tags: Vec<Vec<String>> = auth.event.tags.iter().map(|t| t.as_vec()).collect();
+        assert_eq!(
+            tags,
+            vec![
+                vec!["u".to_string(), url.to_string()],
+                vec!["method".to_string(), "POST".to_string()],
+                vec!["payload".to_string(), hash.to_string()],
+            ]
+        );
+
+        // the header contains the event, base64-encoded
+        let encoded = auth.authorization_header.strip_prefix("Nostr ").unwrap();
+        let decoded = serde_json::from_slice::<Event>(&STANDARD.decode(encoded).unwrap()).unwrap();
+        assert_eq!(decoded.id, auth.event.id);
+
+        let auth = k.create_http_auth("GET", url, None).unwrap();
+        assert_eq!(auth.event.tags.len(), 2);
+    }
+
+    #[test]
+    fn test_create_http_auth_invalid() {
+        let mut k = Keystore::new();
+        let url = "https://files.example.com/upload";
+        assert!(matches!(
+            k.create_http_auth("GET", url, None),
+            Err(Error::KeyNotSet)
+        ));
+        let _res = k.generate().unwrap();
+        assert!(matches!(
+            k.create_http_auth("FETCH", url, None),
+            Err(Error::HttpAuthInvalidMethod(_))
+        ));
+        assert!(matches!(
+            k.create_http_auth("GET", "__NOT_A_URL__", None),
+            Err(Error::HttpAuthInvalidUrl)
+        ));
+        assert!(matches!(
+            k.create_http_auth("GET", "wss://relay.example.com", None),
+            Err(Error::HttpAuthInvalidUrl)
+        ));
+        assert!(matches!(
+            k.create_http_auth("POST", url, Some("abcd")),
+            Err(Error::HttpAuthInvalidPayloadHash)
+        ));
+    }
+
+    #[test]
+    fn test_sign_event_at() {
+        let mut k = Keystore::new();
+        let _res = k
+            .import_secret_key(
+                "nsec1ktekw0hr5evjs0n9nyyquz4sue568snypy2rwk5mpv6hl2hq3vtsk0kpae",
+                true,
+            )
+            .unwrap();
+        let created_at = 1_600_000_000;
+        let res = k
+            .sign_event_at(EventBuilder::new_text_note("Backdated", &[]), created_at)
+            .unwrap();
+        assert_eq!(res.event.created_at.as_u64(), created_at);
+        assert!(res.event.verify().is_ok());
+        assert_eq!(res.event.pubkey, k.get_public_key().unwrap());
+        assert!(!res.far_future);
+
+        let now = Keystore::current_time();
+        let res = k
+            .sign_event_at(EventBuilder::new_text_note("Soon", &[]), now + 60)
+            .unwrap();
+        assert!(!res.far_future);
+        let res = k
+            .sign_event_at(EventBuilder::new_text_note("Future", &[]), now + 3600)
+            .unwrap();
+        assert!(res.event.verify().is_ok());
+        assert!(res.far_future);
+    }
+
+    #[test]
+    fn test_sign_event_at_no_secret() {
+        let mut k = Keystore::new();
+        let _res = k
+            .import_public_key("npub1rfze4zn25ezp6jqt5ejlhrajrfx0az72ed7cwvq0spr22k9rlnjq93lmd4")
+            .unwrap();
+        assert!(k
+            .sign_event_at(EventBuilder::new_text_note("Backdated", &[]), 1_600_000_000)
+            .is_err());
+    }
+
+    #[test]
+    fn test_self_test() {
+        let k = keystore_with_state(Keys::generate(), KeysSetState::SecretAndPublic);
+        assert!(k.self_test().is_ok());
+        let mut k = Keystore::new();
+        let _res = k
+            .import_mnemonic(
+                "oil oil oil oil oil oil oil oil oil oil oil oil",
+                None,
+                true,
+            )
+            .unwrap();
+        assert!(k.self_test().is_ok());
+
+        // secret key is needed
+        assert!(Keystore::new().self_test().is_err());
+        let k = keystore_with_state(Keys::generate(), KeysSetState::PublicOnly);
+        assert!(k.self_test().is_err());
+        let k = keystore_with_state(Keys::generate(), KeysSetState::Locked);
+        assert!(k.self_test().is_err());
+    }
+
+    #[test]
+    fn test_sign_challenge() {
+        let mut k = Keystore::new();
+        let _res = k
+            .import_secret_key(
+                "nsec1ktekw0hr5evjs0n9nyyquz4sue568snypy2rwk5mpv6hl2hq3vtsk0kpae",
+                true,
+            )
+            .unwrap();
+        let pubkey = k.get_public_key().unwrap();
+        let challenge = b"login-nonce-4f2a9c";
+
+        let sig = k.sign_challenge(challenge).unwrap();
+        assert_eq!(sig.len(), 128);
+        assert!(verify_challenge(&pubkey, challenge, &sig));
+
+        // tampered challenge, other key, or malformed signature fails
+        assert!(!verify_challenge(&pubkey, b"login-nonce-4f2a9d", &sig));
+        assert!(!verify_challenge(
+            &Keys::generate().public_key(),
+            challenge,
+            &sig
+        ));
+        assert!(!verify_challenge(&pubkey, challenge, "__NOT_A_SIGNATURE__"));
+        assert!(!verify_challenge(&pubkey, challenge, ""));
+    }
+
+    #[test]
+    fn test_verification_qr() {
+        let k = Keystore::from_keys(Keys::generate());
+        let pubkey = k.get_public_key().unwrap();
+        let challenge = "meetup-2023-7d1e";
+
+        let payload = k.verification_payload(challenge).unwrap();
+        assert!(payload.contains(&k.get_npub()));
+        assert_eq!(
+            verify_verification_payload(&payload, challenge),
+            Some(pubkey)
+        );
+        // other expected challenge
+        assert_eq!(
+            verify_verification_payload(&payload, "meetup-2023-7d1f"),
+            None
+        );
+        // tampered challenge in the payload
+        let tampered = payload.replace(challenge, "meetup-2023-7d1f");
+        assert_eq!(
+            verify_verification_payload(&tampered, "meetup-2023-7d1f"),
+            None
+        );
+        // tampered npub
+        let other_npub = Keys::generate().public_key().to_bech32().unwrap();
+        let tampered = payload.replace(&k.get_npub(), &other_npub);
+        assert_eq!(verify_verification_payload(&tampered, challenge), None);
+        assert_eq!(verify_verification_payload("{}", challenge), None);
+
+        let qr = k.verification_qr(challenge).unwrap();
+        assert!(qr.contains("<svg"));
+
+        // checked from the inputs, by the other party
+        let mut other = Keystore::new();
+        let mut status = StatusMessages::new();
+        other.verification_challenge_input = format!("{challenge} ");
+        other.verification_payload_input = payload;
+        other.check_verification_action(&mut status);
+        assert_eq!(
+            status.get_last(),
+            format!("Verified, the presenter controls {}", k.get_npub())
+        );
+        other.verification_payload_input = tampered;
+        other.check_verification_action(&mut status);
+        assert!(status.get_last().starts_with("Error: Verification failed"));
+
+        // secret key is needed
+        let k = keystore_with_state(Keys::generate(), KeysSetState::PublicOnly);
+        assert!(k.verification_qr(challenge).is_err());
+    }
+
+    #[test]
+    fn test_sign_challenge_no_secret() {
+        let mut k = Keystore::new();
+        assert!(k.sign_challenge(b"nonce").is_err());
+
+        let _res = k
+            .import_public_key("npub1rfze4zn25ezp6jqt5ejlhrajrfx0az72ed7cwvq0spr22k9rlnjq93lmd4")
+            .unwrap();
+        assert!(k.sign_challenge(b"nonce").is_err());
+    }
+
+    #[test]
+    fn test_sign_event_deterministic() {
+        let mut k = Keystore::new();
+        let _res = k
+            .import_secret_key(
+                "nsec1ktekw0hr5evjs0n9nyyquz4sue568snypy2rwk5mpv6hl2hq3vtsk0kpae",
+                true,
+            )
+            .unwrap();
+        let unsigned = EventBuilder::new_text_note("Hello", &[])
+            .to_unsigned_event(k.get_public_key().unwrap());
+
+        let event1 = k.sign_event_deterministic(unsigned.clone()).unwrap();
+        let event2 = k.sign_event_deterministic(unsigned.clone()).unwrap();
+        assert_eq!(event1.sig, event2.sig);
+        assert!(event1.verify().is_ok());
+
+        // default signing is randomized
+        let event3 = k.sign(unsigned.clone()).unwrap();
+        assert!(event3.verify().is_ok());
+        assert_ne!(event3.sig, event1.sig);
+
+        // other keys
+        let other = Keystore::from_keys(Keys::generate());
+        assert!(matches!(
+            other.sign_event_deterministic(unsigned),
+            Err(Error::KeyIdentityMismatch)
+        ));
+    }
+
+    #[test]
+    fn test_sign_hash_deterministic_vector() {
+        // BIP340 test vector 0 (auxiliary randomness all zeros, same as none)
+        let keys = Keys::new(
+            SecretKey::from_str("0000000000000000000000000000000000000000000000000000000000000003")
+                .unwrap(),
+        );
+        assert_eq!(
+            keys.public_key().to_string(),
+            "f9308a019258c31049344f85f89d5229b531c845836f99b08601f113bce036f9"
+        );
+        let signature = sign_hash_deterministic(&keys, &[0u8; 32]).unwrap();
+        assert_eq!(
+            signature.to_string(),
+            "e907831f80848d1069a5371b402410364bdf1c5f8307b0084c55f1ce2dca821525f66a4a85ea8b71e482a74f382d2ce5ebeee8fdb2172f477df4900d310536c0"
+        );
+    }
+
+    #[test]
+    fn test_parse_kinds() {
+        assert_eq!(parse_kinds("").unwrap(), None);
+        assert_eq!(parse_kinds(" , ").unwrap(), None);
+        assert_eq!(parse_kinds("1, 7,1").unwrap(), Some(HashSet::from([1, 7])));
+        assert_eq!(
+            parse_kinds("1, x").unwrap_err().to_string(),
+            "Invalid event kind 'x'"
+        );
+    }
+
+    #[test]
+    fn test_allowed_kinds() {
+        let mut k = Keystore::new();
+        let _res = k
+            .import_secret_key(
+                "nsec1ktekw0hr5evjs0n9nyyquz4sue568snypy2rwk5mpv6hl2hq3vtsk0kpae",
+                true,
+            )
+            .unwrap();
+        let pubkey = k.get_public_key().unwrap();
+        let dm = EventBuilder::new(Kind::EncryptedDirectMessage, "secret", &[])
+            .to_unsigned_event(pubkey);
+
+        // all allowed by default
+        assert!(k.sign(dm.clone()).is_ok());
+
+        k.set_allowed_kinds(Some(HashSet::from([1])));
+        let note = k.sign_text_note("Hello").unwrap();
+        assert_eq!(note.kind, Kind::TextNote);
+        assert!(note.verify().is_ok());
+        assert!(matches!(k.sign(dm.clone()), Err(Error::KindNotAllowed(4))));
+        assert!(matches!(
+            k.sign_auth_challenge("wss://relay.damus.io", "challenge"),
+            Err(Error::KindNotAllowed(22242))
+        ));
+        let signer = k.get_signer().unwrap();
+        assert!(matches!(
+            signer.sign_event(&dm),
+            Err(Error::KindNotAllowed(4))
+        ));
+
+        k.set_allowed_kinds(None);
+        assert!(k.sign(dm).is_ok());
+    }
+
+    #[test]
+    fn test_import_case_variants() {
+        let npub = "npub1rfze4zn25ezp6jqt5ejlhrajrfx0az72ed7cwvq0spr22k9rlnjq93lmd4";
+        let nsec = "nsec1ktekw0hr5evjs0n9nyyquz4sue568snypy2rwk5mpv6hl2hq3vtsk0kpae";
+
+        let mut k = Keystore::new();
+        let _res = k.import_public_key(&npub.to_uppercase()).unwrap();
+        assert_eq!(k.get_npub(), npub);
+        let _res = k.import_secret_key(&nsec.to_uppercase(), true).unwrap();
+        assert_eq!(k.get_npub(), npub);
+        let _res = k
+            .import_with_format(&nsec.to_uppercase(), ImportFormat::Nsec)
+            .unwrap();
+        assert_eq!(k.get_npub(), npub);
+
+        let mut k = Keystore::new();
+        assert!(matches!(
+            k.import_public_key("Npub1rfze4zn25ezp6jqt5ejlhrajrfx0az72ed7cwvq0spr22k9rlnjq93lmd4"),
+            Err(Error::MixedCaseBech32)
+        ));
+        assert!(matches!(
+            k.import_secret_key(
+                "nsec1KTEKW0hr5evjs0n9nyyquz4sue568snypy2rwk5mpv6hl2hq3vtsk0kpae",
+                true
+            ),
+            Err(Error::MixedCaseBech32)
+        ));
+        assert!(matches!(
+            k.import_with_format(
+                "nPub1rfze4zn25ezp6jqt5ejlhrajrfx0az72ed7cwvq0spr22k9rlnjq93lmd4",
+                ImportFormat::Npub
+            ),
+            Err(Error::MixedCaseBech32)
+        ));
+        assert_eq!(k.keys_is_set(), false);
+    }
+
+    #[test]
+    fn test_import_with_format_mismatch() {
+        let mut k = Keystore::new();
+        assert!(k
+            .import_with_format(
+                "npub1rfze4zn25ezp6jqt5ejlhrajrfx0az72ed7cwvq0spr22k9rlnjq93lmd4",
+                ImportFormat::Nsec
+            )
+            .is_err());
+        assert!(k
+            .import_with_format(
+                "npub1rfze4zn25ezp6jqt5ejlhrajrfx0az72ed7cwvq0spr22k9rlnjq93lmd4",
+                ImportFormat::HexPublic
+            )
+            .is_err());
+        assert!(k
+            .import_with_format(
+                "1a459a8a6aa6441d480ba665fb8fb21a4cfe8bcacb7d87300f8046a558a3fce4",
+                ImportFormat::Npub
+            )
+            .is_err());
+        assert_eq!(k.get_set_state(), KeysSetState::NotSet);
+    }
+
+    #[test]
+    fn test_xonly_public_key_bytes() {
+        let mut k = Keystore::new();
+        assert!(k.xonly_public_key_bytes().is_none());
+        let _res = k
+            .import_public_key("npub1rfze4zn25ezp6jqt5ejlhrajrfx0az72ed7cwvq0spr22k9rlnjq93lmd4")
+            .unwrap();
+        assert_eq!(
+            hex::encode(k.xonly_public_key_bytes().unwrap()),
+            "1a459a8a6aa6441d480ba665fb8fb21a4cfe8bcacb7d87300f8046a558a3fce4"
+        );
+    }
+
+    #[test]
+    fn test_public_key_checksummed_hex() {
+        let mut k = Keystore::new();
+        assert!(k.public_key_checksummed_hex().is_none());
+        let _res = k
+            .import_public_key("npub1rfze4zn25ezp6jqt5ejlhrajrfx0az72ed7cwvq0spr22k9rlnjq93lmd4")
+            .unwrap();
+        let checksummed = k.public_key_checksummed_hex().unwrap();
+        assert_eq!(
+            checksummed,
+            "1a459a8A6aa6441d480Ba665fB8Fb21A4cFe8BCaCB7d87300f8046A558A3FCE4"
+        );
+        // can be imported back
+        let mut k2 = Keystore::new();
+        k2.import_public_key(&checksummed).unwrap();
+        assert_eq!(k2.get_npub(), k.get_npub());
+    }
+
+    #[test]
+    fn test_import_mnemonic_passphrase() {
+        let mnemonic = "oil oil oil oil oil oil oil oil oil oil oil oil";
+        let mut k = Keystore::new();
+        let _res = k.import_mnemonic(mnemonic, None, true).unwrap();
+        let npub_no_passphrase = k.get_npub();
+        let _res = k
+            .import_mnemonic(mnemonic, Some("passphrase1"), true)
+            .unwrap();
+        let npub_passphrase1 = k.get_npub();
+        let _res = k
+            .import_mnemonic(mnemonic, Some("passphrase2"), true)
+            .unwrap();
+        let npub_passphrase2 = k.get_npub();
+        assert_ne!(npub_passphrase1, npub_no_passphrase);
+        assert_ne!(npub_passphrase2, npub_no_passphrase);
+        assert_ne!(npub_passphrase1, npub_passphrase2);
+        // empty passphrase is the same as no passphrase
+        let _res = k.import_mnemonic(mnemonic, Some(""), true).unwrap();
+        assert_eq!(k.get_npub(), npub_no_passphrase);
+    }
+
+    #[test]
+    fn test_generate_with_mnemonic_and_passphrase() {
+        let mut k = Keystore::new();
+        assert!(k.generate_with_mnemonic_and_passphrase("", "").is_err());
+        assert!(k
+            .generate_with_mnemonic_and_passphrase("pass", "pasx")
+            .is_err());
+        assert_eq!(k.get_set_state(), KeysSetState::NotSet);
+
+        let mnemonic = k
+            .generate_with_mnemonic_and_passphrase("pass", "pass")
+            .unwrap();
+        assert_eq!(mnemonic.split(' ').count(), 12);
+        let npub = k.get_npub();
+
+        let mut k2 = Keystore::new();
+        let _res = k2.import_mnemonic(&mnemonic, Some("pass"), true).unwrap();
+        assert_eq!(k2.get_npub(), npub);
+        let _res = k2.import_mnemonic(&mnemonic, None, true).unwrap();
+        assert_ne!(k2.get_npub(), npub);
+
+        // from the inputs, the mnemonic is put into the mnemonic input
+        let mut k3 = Keystore::new();
+        let mut status = StatusMessages::new();
+        k3.mnemonic_passphrase_input = "pass".to_string();
+        k3.mnemonic_passphrase_repeat_input = "pass".to_string();
+        k3.generate_with_mnemonic_action(&mut status);
+        assert_eq!(k3.get_set_state(), KeysSetState::SecretAndPublic);
+        assert!(k3.mnemonic_passphrase_repeat_input.is_empty());
+        let _res = k2
+            .import_mnemonic(&k3.mnemonic_input, Some("pass"), true)
+            .unwrap();
+        assert_eq!(k2.get_npub(), k3.get_npub());
+    }
+
+    #[test]
+    fn test_age_bucket() {
+        let mut k = Keystore::new();
+        assert_eq!(k.age_bucket(), AgeBucket::Unknown);
+        k.generate().unwrap();
+        assert_eq!(k.age_bucket(), AgeBucket::JustNow);
+        assert_eq!(k.age_bucket().to_string(), "created just now");
+
+        let now = 1680000000;
+        k.created_at = Some(now - 10);
+        assert_eq!(k.age_bucket_at(now), AgeBucket::JustNow);
+        k.created_at = Some(now - 10 * 60);
+        assert_eq!(k.age_bucket_at(now), AgeBucket::Minutes);
+        k.created_at = Some(now - 5 * 3600);
+        assert_eq!(k.age_bucket_at(now), AgeBucket::Hours);
+        k.created_at = Some(now - 3 * 24 * 3600);
+        assert_eq!(k.age_bucket_at(now), AgeBucket::Days);
+        k.created_at = Some(now - 100 * 24 * 3600);
+        assert_eq!(k.age_bucket_at(now), AgeBucket::Older);
+        // creation in the future
+        k.created_at = Some(now + 100);
+        assert_eq!(k.age_bucket_at(now), AgeBucket::JustNow);
+
+        // unknown for imported keys
+        let _res = k
+            .import_public_key("npub1rfze4zn25ezp6jqt5ejlhrajrfx0az72ed7cwvq0spr22k9rlnjq93lmd4")
+            .unwrap();
+        assert_eq!(k.age_bucket_at(now), AgeBucket::Unknown);
+    }
+
+    #[tokio::test]
+    async fn test_revalidate_identity_offline() {
+        let k = Keystore::new();
+        assert_eq!(
+            k.revalidate_identity("_@example.com", &[], &CancellationToken::new())
+                .await,
+            IdentityStatus::KeyNotSet
+        );
+
+        let k = Keystore::from_keys(Keys::generate());
+        let cancel = CancellationToken::new();
+        assert!(matches!(
+            k.revalidate_identity("not-a-nip05-identifier", &[], &cancel)
+                .await,
+            IdentityStatus::Nip05Failed(_)
+        ));
+        cancel.cancel();
+        assert_eq!(
+            k.revalidate_identity("_@example.com", &[], &cancel).await,
+            IdentityStatus::Cancelled
+        );
+        assert_eq!(
+            IdentityStatus::ProfileMismatch(Some("bob@example.com".to_string())).to_string(),
+            "NIP-05 identity verifies, but the published profile has 'bob@example.com'"
+        );
+    }
+
+    #[tokio::test]
+    async fn test_offline_mode() {
+        let mut k = Keystore::from_keys(Keys::generate());
+        assert!(!k.offline);
+        k.set_offline(true);
+        assert!(matches!(
+            k.fetch_recent_notes(&["wss://relay.example.com".to_string()], 10),
+            Err(Error::OfflineMode)
+        ));
+        assert_eq!(
+            k.revalidate_identity("_@example.com", &[], &CancellationToken::new())
+                .await,
+            IdentityStatus::Offline
+        );
+        assert!(matches!(
+            k.import_and_announce(
+                "nsec1ktekw0hr5evjs0n9nyyquz4sue568snypy2rwk5mpv6hl2hq3vtsk0kpae",
+                &Metadata::new(),
+                &["wss://relay.example.com".to_string()]
+            ),
+            Err(Error::OfflineMode)
+        ));
+        // keys are not changed
+        assert_ne!(
+            k.get_npub(),
+            "npub1rfze4zn25ezp6jqt5ejlhrajrfx0az72ed7cwvq0spr22k9rlnjq93lmd4"
+        );
+
+        k.set_offline(false);
+        assert!(k
+            .fetch_recent_notes(&[], 10)
+            .unwrap()
+            .await
+            .unwrap()
+            .is_empty());
+    }
+
+    #[tokio::test]
+    async fn test_publish_text_note() {
+        let mut k = Keystore::from_keys(Keys::generate());
+        let relays = vec![start_mock_relay(Vec::new())];
+        let results = k
+            .publish_text_note("Hello", &relays, Some(Duration::from_secs(2)))
+            .unwrap()
+            .await;
+        assert_eq!(results.len(), 1);
+        assert!(results[0].publish.accepted);
+        assert_eq!(results[0].confirmed, Some(true));
+        assert_eq!(results[0].to_string(), "accepted, stored");
+        let publishes: Vec<PublishResult> = results.into_iter().map(|r| r.publish).collect();
+        k.record_publish(&publishes);
+        assert_eq!(k.recent_relays(10)[0].success_count, 1);
+
+        // secret key is needed, and no network access in offline mode
+        let k2 = keystore_with_state(Keys::generate(), KeysSetState::PublicOnly);
+        assert!(k2.publish_text_note("Hello", &relays, None).is_err());
+        k.set_offline(true);
+        assert!(matches!(
+            k.publish_text_note("Hello", &relays, None),
+            Err(Error::OfflineMode)
+        ));
+    }
+
+    #[tokio::test]
+    async fn test_import_and_announce() {
+        let nsec = "nsec1ktekw0hr5evjs0n9nyyquz4sue568snypy2rwk5mpv6hl2hq3vtsk0kpae";
+        let npub = "npub1rfze4zn25ezp6jqt5ejlhrajrfx0az72ed7cwvq0spr22k9rlnjq93lmd4";
+        let relays = vec!["__NOT_A_RELAY__".to_string()];
+
+        // metadata kind not allowed: rejected before changing the keys
+        let mut k = Keystore::from_keys(Keys::generate());
+        k.set_allowed_kinds(Some(HashSet::from([1])));
+        assert!(matches!(
+            k.import_and_announce(nsec, &Metadata::new(), &relays),
+            Err(Error::KindNotAllowed(0))
+        ));
+        assert_ne!(k.get_npub(), npub);
+        assert!(k
+            .import_and_announce("__NOT_A_VALID_KEY__", &Metadata::new(), &relays)
+            .is_err());
+        assert_ne!(k.get_npub(), npub);
+
+        // keys are imported right away, a failing relay is reported in the results
+        let mut k = Keystore::new();
+        let announce = k
+            .import_and_announce(nsec, &Metadata::new().name("keystr"), &relays)
+            .unwrap();
+        assert_eq!(k.get_npub(), npub);
+        let results = announce.await;
+        assert_eq!(results.len(), 1);
+        assert!(!results[0].accepted);
+
+        // published to a relay
+        let mut k = Keystore::new();
+        let relays = vec![start_mock_relay(Vec::new())];
+        let results = k
+            .import_and_announce(nsec, &Metadata::new().name("keystr"), &relays)
+            .unwrap()
+            .await;
+        assert_eq!(results.len(), 1);
+        assert!(results[0].accepted);
+        k.record_publish(&results);
+        assert_eq!(k.recent_relays(10)[0].success_count, 1);
+    }
+
+    #[tokio::test]
+    async fn test_fetch_recent_notes_offline() {
+        let k = Keystore::new();
+        assert!(matches!(
+            k.fetch_recent_notes(&[], 10),
+            Err(Error::KeyNotSet)
+        ));
+
+        // public key only is enough, no relays give no notes
+        let mut k = keystore_with_state(Keys::generate(), KeysSetState::PublicOnly);
+        assert!(k
+            .fetch_recent_notes(&[], 10)
+            .unwrap()
+            .await
+            .unwrap()
+            .is_empty());
+        let relays = vec!["__NOT_A_RELAY__".to_string()];
+        let res = k.fetch_recent_notes(&relays, 10).unwrap().await;
+        assert!(res.is_err());
+        // the failed fetch is recorded in the relay stats
+        k.record_relay_use(&relays, res.is_ok());
+        let recent = k.recent_relays(10);
+        assert_eq!(recent.len(), 1);
+        assert_eq!(recent[0].url, "__NOT_A_RELAY__");
+        assert_eq!(recent[0].failure_count, 1);
+    }
+
+    #[test]
+    fn test_import_mnemonic_path() {
+        let mnemonic = "oil oil oil oil oil oil oil oil oil oil oil oil";
+        let mut k = Keystore::new();
+        // standard path
+        let _res = k
+            .import_mnemonic_path(mnemonic, None, "m/44'/1237'/0'/0/0")
+            .unwrap();
+        assert_eq!(
+            k.get_npub(),
+            "npub1tczgvlwvcdxp5f4mp8rqehramx6dqemq6v8egf3qdfzazn8cs7dqlhmwux"
+        );
+        // custom path, known key
+        let _res = k
+            .import_mnemonic_path(mnemonic, None, "m/44'/1237'/0'/0/3")
+            .unwrap();
+        assert_eq!(
+            k.get_npub(),
+            "npub1r70mmxj45j9r7vtttz8x3gh90utjmsrk6cwhgckp6ykkmzmcyejs2q4pfr"
+        );
+        // same as preview for account
+        let _res = k
+            .import_mnemonic_path(mnemonic, None, "m/44'/1237'/1'/0/0")
+            .unwrap();
+        assert_eq!(
+            k.get_npub(),
+            Keystore::preview_mnemonic_npub(mnemonic, None, 1).unwrap()
+        );
+    }
+
+    #[test]
+    fn test_import_mnemonic_path_invalid() {
+        let mnemonic = "oil oil oil oil oil oil oil oil oil oil oil oil";
+        let mut k = Keystore::new();
+        for path in [
+            "44'/1237'/0'/0/0",
+            "m/44'/abc/0",
+            "m/44''/0",
+            "m/-1",
+            "m/44'/1237'/0'/0/0/",
+            "m/2147483648",
+            "",
+        ] {
+            assert!(matches!(
+                k.import_mnemonic_path(mnemonic, None, path),
+                Err(Error::KeyInvalidDerivationPath(_))
+            ));
+        }
+        assert_eq!(k.get_set_state(), KeysSetState::NotSet);
+    }
+
+    #[test]
+    fn test_import_xprv() {
+        // BIP32 test vector 1, master key
+        let xprv = "xprv9s21ZrQH143K3QTDL4LXw2F7HEK3wJUD2nW2nRk4stbPy6cq3jPPqjiChkVvvNKmPGJxWUtg6LnF5kejMRNNU3TGtRBeJgk33yuGBxrMPHi";
+        let mut k = Keystore::new();
+        // default NIP-06 path
+        let _res = k.import_xprv(xprv, None).unwrap();
+        assert!(k.is_secret_key_set());
+        assert_eq!(
+            k.get_npub(),
+            "npub1emlla45qgkxa0n6yj243m0uygv6332atrxckg7z5c4226lg3ke2qxdfpgk"
+        );
+        // custom path
+        let _res = k.import_xprv(xprv, Some("m/0'/1")).unwrap();
+        assert_eq!(
+            k.get_npub(),
+            "npub12q0y2jlsqaglyjcmfzd2jffpt4n27g35uwy3cwep5547mv7dwywqm3fm78"
+        );
+        // testnet version of the same key
+        let _res = k.import_xprv("tprv8ZgxMBicQKsPeDgjzdC36fs6bMjGApWDNLR9erAXMs5skhMv36j9MV5ecvfavji5khqjWaWSFhN3YcCUUdiKH6isR4Pwy3U5y5egddBr16m", None).unwrap();
+        assert_eq!(
+            k.get_npub(),
+            "npub1emlla45qgkxa0n6yj243m0uygv6332atrxckg7z5c4226lg3ke2qxdfpgk"
+        );
+    }
+
+    #[test]
+    fn test_import_xprv_invalid() {
+        let mut k = Keystore::new();
+        // invalid checksum
+        assert!(matches!(
+            k.import_xprv("xprv9s21ZrQH143K3QTDL4LXw2F7HEK3wJUD2nW2nRk4stbPy6cq3jPPqjiChkVvvNKmPGJxWUtg6LnF5kejMRNNU3TGtRBeJgk33yuGBxrMPHj", None),
+            Err(Error::KeyInvalidExtendedKey)
+        ));
+        // extended public key
+        assert!(matches!(
+            k.import_xprv("xpub661MyMwAqRbcFtXgS5sYJABqqG9YLmC4Q1Rdap9gSE8NqtwybGhePY2gZ29ESFjqJoCu1Rupje8YtGqsefD265TMg7usUDFdp6W1EGMcet8", None),
+            Err(Error::KeyExtendedKeyUnsupportedPrefix(_))
+        ));
+        // invalid path
+        assert!(matches!(
+            k.import_xprv("xprv9s21ZrQH143K3QTDL4LXw2F7HEK3wJUD2nW2nRk4stbPy6cq3jPPqjiChkVvvNKmPGJxWUtg6LnF5kejMRNNU3TGtRBeJgk33yuGBxrMPHi", Some("x/1")),
+            Err(Error::KeyInvalidDerivationPath(_))
+        ));
+        assert_eq!(k.get_set_state(), KeysSetState::NotSet);
+    }
+
+    #[test]
+    fn test_key_policy() {
+        let mut k = Keystore::new();
+        k.key_policy = KeyPolicy::mnemonic_only();
+
+        let res = k.import_secret_key(
+            "b2f3673ee3a659283e6599080e0ab0e669a3c2640914375a9b0b357faae08b17",
+            true,
+        );
+        assert!(matches!(res, Err(Error::KeyPolicyViolation(_))));
+        assert_eq!(k.get_set_state(), KeysSetState::NotSet);
+        assert!(matches!(k.generate(), Err(Error::KeyPolicyViolation(_))));
+        assert_eq!(k.get_set_state(), KeysSetState::NotSet);
+
+        let _res = k
+            .import_mnemonic(
+                "oil oil oil oil oil oil oil oil oil oil oil oil",
+                None,
+                true,
+            )
+            .unwrap();
+        assert_eq!(k.get_set_state(), KeysSetState::SecretAndPublic);
+        assert_eq!(k.origin, Some(KeyOrigin::Mnemonic));
+
+        // unlocking own stored key is not restricted, even by a policy allowing nothing
+        let mut k = keystore_with_state(Keys::generate(), KeysSetState::Locked);
+        k.key_policy = KeyPolicy {
+            allowed_origins: Some(Vec::new()),
+        };
+        let _res = k.decrypt_secret_key(TEST_LOCK_PASSWORD).unwrap();
+        assert_eq!(k.get_set_state(), KeysSetState::SecretAndPublic);
+        assert_eq!(k.origin, Some(KeyOrigin::Storage));
+
+        // default policy allows everything
+        let mut k = Keystore::new();
+        let _res = k
+            .import_secret_key(
+                "b2f3673ee3a659283e6599080e0ab0e669a3c2640914375a9b0b357faae08b17",
+                true,
+            )
+            .unwrap();
+        assert_eq!(k.origin, Some(KeyOrigin::SecretKey));
+    }
+
+    #[test]
+    fn test_note_save_load() {
+        let mut path = std::env::temp_dir();
+        path.push(format!(
+            "keystr-test-note-{}",
+            Keys::generate().public_key()
+        ));
+
+        let mut k = Keystore::new();
+        let _res = k
+            .import_secret_key(
+                "nsec1ktekw0hr5evjs0n9nyyquz4sue568snypy2rwk5mpv6hl2hq3vtsk0kpae",
+                true,
+            )
+            .unwrap();
+        k.set_note("old key, rotated 2024");
+        let _res = k.save_note_to(&path).unwrap();
+
+        let mut k2 = Keystore::new();
+        let _res = k2.load_note_from(&path).unwrap();
+        assert_eq!(k2.note(), "old key, rotated 2024");
+        // note survives import
+        let _res = k2.generate().unwrap();
+        assert_eq!(k2.note(), "old key, rotated 2024");
+
+        // empty note removes the file
+        k.set_note("");
+        let _res = k.save_note_to(&path).unwrap();
+        assert!(!path.is_file());
+        let _res = k2.load_note_from(&path).unwrap();
+        assert_eq!(k2.note(), "");
+    }
+
+    #[test]
+    fn test_label_persist() {
+        let mut path = std::env::temp_dir();
+        path.push(format!(
+            "keystr-test-label-{}",
+            Keys::generate().public_key()
+        ));
+
+        let mut k = Keystore::new();
+        assert!(k.set_label("  ").is_err());
+        assert!(k.set_label(&"x".repeat(MAX_LABEL_LEN + 1)).is_err());
+        let _res = k.set_label(" Main ").unwrap();
+        assert_eq!(k.label, "Main");
+        assert!(k.has_unsaved_change);
+        let _res = k.save_label_to(&path).unwrap();
+
+        let mut k2 = Keystore::new();
+        let _res = k2.load_label_from(&path).unwrap();
+        assert_eq!(k2.label, "Main");
+
+        // empty label removes the file
+        k.label = String::new();
+        let _res = k.save_label_to(&path).unwrap();
+        assert!(!path.is_file());
+        let _res = k2.load_label_from(&path).unwrap();
+        assert_eq!(k2.label, "");
+    }
+
+    #[test]
+    fn test_load_relay_stats_corrupt() {
+        let mut path = std::env::temp_dir();
+        path.push(format!(
+            "keystr-test-relay-stats-{}",
+            Keys::generate().public_key()
+        ));
+        let mut k = Keystore::new();
+        k.relay_stats
+            .record("wss://relay.example.com", true, 1_700_000_000);
+        let _res = k.relay_stats.save_to(&path).unwrap();
+
+        let mut k2 = Keystore::new();
+        k2.load_relay_stats_from(&path);
+        assert_eq!(k2.recent_relays(10).len(), 1);
+
+        // corrupt file: stats are reset, not an error
+        fs::write(&path, "{not json").unwrap();
+        k2.load_relay_stats_from(&path);
+        assert!(k2.recent_relays(10).is_empty());
+        let _res = fs::remove_file(&path);
+    }
+}
@@ -0,0 +1,336 @@
+use crate::base::error::Error;
+use crate::model::clipboard::ClipboardCopy;
+use crate::model::keystore::{Keystore, REDACTED};
+use crate::model::secret_sink::{ClipboardSink, FileSink, SecretSink};
+
+use nostr::prelude::{SecretKey, ToBech32};
+use zeroize::Zeroize;
+
+use std::fmt;
+use std::path::Path;
+use std::time::{Duration, Instant};
+
+/// A revealed secret key (nsec) for showing it briefly, until the expiry.
+/// After expiry the secret is no longer given out, and it is zeroized (also on drop).
+pub(crate) struct RevealGuard {
+    secret: String,
+    expires_at: Instant,
+}
+
+impl RevealGuard {
+    /// If the reveal has expired, and the secret is no longer given out
+    pub fn expired(&self) -> bool {
+        self.expired_at(Instant::now())
+    }
+
+    /// If the reveal has expired, relative to the given time
+    pub fn expired_at(&self, now: Instant) -> bool {
+        now >= self.expires_at
+    }
+
+    /// Warning: Security-sensitive method!
+    /// The secret, if not yet expired; once expired, the secret is zeroized and `None` is returned
+    pub fn secret(&mut self) -> Option<&str> {
+        self.secret_at(Instant::now())
+    }
+
+    /// Warning: Security-sensitive method!
+    /// The secret, relative to the given time, see `secret`
+    pub fn secret_at(&mut self, now: Instant) -> Option<&str> {
+        if self.expired_at(now) {
+            self.secret.zeroize();
+            return None;
+        }
+        Some(&self.secret)
+    }
+
+    /// Warning: Security-sensitive method!
+    /// The secret, as `secret`, but an expired reveal is an `Error::RevealExpired`
+    pub fn as_str(&mut self) -> Result<&str, Error> {
+        self.secret().ok_or(Error::RevealExpired)
+    }
+}
+
+/// Debug output never includes the secret
+impl fmt::Debug for RevealGuard {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.debug_struct("RevealGuard")
+            .field("secret", &REDACTED)
+            .field("expires_at", &self.expires_at)
+            .finish()
+    }
+}
+
+impl Drop for RevealGuard {
+    fn drop(&mut self) {
+        self.secret.zeroize();
+    }
+}
+
+impl Keystore {
+    /// Warning: Security-sensitive method!
+    /// Reveal the secret key (nsec) into the given sink (file, clipboard, buffer), instead of returning it.
+    /// If `require_unlock_for_reveal` is set and the auto-lock period has expired, the key gets locked
+    /// and `KeyLocked` error is returned. A successful reveal restarts the auto-lock timer.
+    pub fn reveal_secret_into(&mut self, sink: &mut dyn SecretSink) -> Result<(), Error> {
+        self.reveal_secret_into_at(sink, Instant::now())
+    }
+
+    /// Warning: Security-sensitive method!
+    /// Reveal the secret key into the given sink, relative to the given time, see `reveal_secret_into`
+    pub fn reveal_secret_into_at(
+        &mut self,
+        sink: &mut dyn SecretSink,
+        now: Instant,
+    ) -> Result<(), Error> {
+        let mut nsec = self.reveal_secret_key_at(now)?.to_bech32()?;
+        let res = sink.write_secret(&nsec);
+        nsec.zeroize();
+        res
+    }
+
+    /// Warning: Security-sensitive method!
+    /// Reveal the secret key (nsec) for showing it only for the given duration; check `is_reveal_expired`
+    /// to know when to hide it. Same policy as `reveal_secret_into`.
+    pub fn reveal_nsec_timed(&mut self, duration: Duration) -> Result<RevealGuard, Error> {
+        let now = Instant::now();
+        let secret = self.reveal_secret_key_at(now)?.to_bech32()?;
+        let expires_at = now + duration;
+        self.reveal_expires_at = Some(expires_at);
+        Ok(RevealGuard { secret, expires_at })
+    }
+
+    /// Warning: Security-sensitive method!
+    /// Reveal the secret key (nsec) for an on-screen reveal that auto-hides after the duration:
+    /// the guard gives out the nsec (`as_str`) until `expired`, then refuses and zeroizes it.
+    /// See `reveal_nsec_timed`.
+    pub fn reveal_secret_temporarily(&mut self, duration: Duration) -> Result<RevealGuard, Error> {
+        self.reveal_nsec_timed(duration)
+    }
+
+    /// If the last timed reveal has expired (or there was none), so the secret key should be hidden
+    pub fn is_reveal_expired(&self) -> bool {
+        self.is_reveal_expired_at(Instant::now())
+    }
+
+    /// If the last timed reveal has expired, relative to the given time
+    pub fn is_reveal_expired_at(&self, now: Instant) -> bool {
+        match self.reveal_expires_at {
+            Some(expires_at) => now >= expires_at,
+            None => true,
+        }
+    }
+
+    /// Warning: Security-sensitive method!
+    /// Reveal the secret key (nsec) for copying to the clipboard, with information on clearing
+    /// the clipboard after the timeout (if supported), and a warning to show to the user.
+    /// Same policy as `reveal_secret_into`.
+    pub fn copy_nsec_with_timeout(&mut self, timeout: Duration) -> Result<ClipboardCopy, Error> {
+        let mut sink = ClipboardSink::new(timeout);
+        self.reveal_secret_into(&mut sink)?;
+        sink.take().ok_or(Error::KeyNotSet)
+    }
+
+    /// Warning: Security-sensitive method!
+    /// Export the secret key (nsec) in PLAINTEXT to the given file, readable only by the owner (on unix).
+    /// Same policy as `reveal_secret_into`.
+    pub fn export_nsec_to_file(&mut self, path: &Path) -> Result<(), Error> {
+        self.reveal_secret_into(&mut FileSink::new(path.to_path_buf()))
+    }
+
+    /// Warning: Security-sensitive method!
+    /// Check the reveal policy (see `reveal_secret_into`) relative to the given time,
+    /// and return the secret key for revealing it
+    fn reveal_secret_key_at(&mut self, now: Instant) -> Result<SecretKey, Error> {
+        if self.require_unlock_for_reveal && self.is_auto_lock_expired_at(now) {
+            let _res = self.lock();
+            return Err(Error::KeyLocked);
+        }
+        let sk = self.get_secret_key()?;
+        self.last_activity = Some(now);
+        Ok(sk)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::model::clipboard::ClipboardWarning;
+    use nostr::prelude::Keys;
+
+    #[test]
+    fn test_reveal_secret_into() {
+        let mut k = Keystore::new();
+        let mut sink = Vec::new();
+        assert!(k.reveal_secret_into(&mut sink).is_err());
+        assert!(sink.is_empty());
+
+        let _res = k
+            .import_secret_key(
+                "nsec1ktekw0hr5evjs0n9nyyquz4sue568snypy2rwk5mpv6hl2hq3vtsk0kpae",
+                true,
+            )
+            .unwrap();
+        let _res = k.reveal_secret_into(&mut sink).unwrap();
+        assert_eq!(
+            String::from_utf8(sink).unwrap(),
+            "nsec1ktekw0hr5evjs0n9nyyquz4sue568snypy2rwk5mpv6hl2hq3vtsk0kpae"
+        );
+    }
+
+    #[test]
+    fn test_export_nsec_to_file() {
+        let mut path = std::env::temp_dir();
+        path.push(format!(
+            "keystr-test-export-nsec-{}",
+            Keys::generate().public_key()
+        ));
+        let mut k = Keystore::new();
+        assert!(k.export_nsec_to_file(&path).is_err());
+        assert!(!path.exists());
+
+        let nsec = "nsec1ktekw0hr5evjs0n9nyyquz4sue568snypy2rwk5mpv6hl2hq3vtsk0kpae";
+        let _res = k.import_secret_key(nsec, true).unwrap();
+        let _res = k.export_nsec_to_file(&path).unwrap();
+        assert_eq!(std::fs::read_to_string(&path).unwrap(), nsec);
+        let _res = std::fs::remove_file(&path);
+    }
+
+    #[test]
+    fn test_reveal_nsec_timed() {
+        let nsec = "nsec1ktekw0hr5evjs0n9nyyquz4sue568snypy2rwk5mpv6hl2hq3vtsk0kpae";
+        let mut k = Keystore::new();
+        assert!(k.is_reveal_expired());
+        assert!(k.reveal_nsec_timed(Duration::from_secs(5)).is_err());
+
+        let _res = k.import_secret_key(nsec, true).unwrap();
+        let start = Instant::now();
+        let mut guard = k.reveal_nsec_timed(Duration::from_secs(5)).unwrap();
+        assert!(!k.is_reveal_expired_at(start));
+        assert!(!guard.expired_at(start));
+        assert_eq!(guard.secret_at(start), Some(nsec));
+
+        let later = start + Duration::from_secs(6);
+        assert!(k.is_reveal_expired_at(later));
+        assert!(guard.expired_at(later));
+        assert_eq!(guard.secret_at(later), None);
+        // no copy kept after expiry
+        assert!(guard.secret.is_empty());
+
+        let mut guard = k.reveal_nsec_timed(Duration::from_secs(60)).unwrap();
+        assert_eq!(guard.as_str().unwrap(), nsec);
+        let mut guard = k.reveal_nsec_timed(Duration::ZERO).unwrap();
+        assert!(guard.expired());
+        assert!(matches!(guard.as_str(), Err(Error::RevealExpired)));
+
+        k.clear();
+        assert!(k.is_reveal_expired_at(start));
+
+        // locking ends the reveal too
+        let _res = k.import_encrypted_secret_key("010d6a32e0decd8553f02372df251c7f06dd0a54ba09bc0e8b2ea52e816c50f430fd0f051b2f7abcae05017f3c6f8a1ff7f3d694db4e624ef7dece7e3152b1ff536bc954eab1c85b3dbeb8e29140e84f0db5c473822e550d53a66e", false).unwrap();
+        let _res = k.decrypt_secret_key("password").unwrap();
+        let _guard = k.reveal_nsec_timed(Duration::from_secs(60)).unwrap();
+        assert!(!k.is_reveal_expired());
+        let _res = k.lock().unwrap();
+        assert!(k.is_reveal_expired());
+    }
+
+    #[test]
+    fn test_reveal_secret_temporarily() {
+        let nsec = "nsec1ktekw0hr5evjs0n9nyyquz4sue568snypy2rwk5mpv6hl2hq3vtsk0kpae";
+        let mut k = Keystore::new();
+        assert!(k.reveal_secret_temporarily(Duration::from_secs(5)).is_err());
+
+        let _res = k.import_secret_key(nsec, true).unwrap();
+        let mut guard = k
+            .reveal_secret_temporarily(Duration::from_secs(60))
+            .unwrap();
+        assert!(!guard.expired());
+        assert_eq!(guard.as_str().unwrap(), nsec);
+
+        let mut guard = k
+            .reveal_secret_temporarily(Duration::from_millis(10))
+            .unwrap();
+        assert_eq!(guard.as_str().unwrap(), nsec);
+        std::thread::sleep(Duration::from_millis(20));
+        assert!(guard.expired());
+        assert!(matches!(guard.as_str(), Err(Error::RevealExpired)));
+        assert!(guard.secret.is_empty());
+        assert!(k.is_reveal_expired());
+    }
+
+    #[test]
+    fn test_reveal_secret_auto_lock() {
+        let nsec = "nsec1ktekw0hr5evjs0n9nyyquz4sue568snypy2rwk5mpv6hl2hq3vtsk0kpae";
+        let mut k = Keystore::new();
+        let _res = k.import_encrypted_secret_key("010d6a32e0decd8553f02372df251c7f06dd0a54ba09bc0e8b2ea52e816c50f430fd0f051b2f7abcae05017f3c6f8a1ff7f3d694db4e624ef7dece7e3152b1ff536bc954eab1c85b3dbeb8e29140e84f0db5c473822e550d53a66e", false).unwrap();
+        let _res = k.decrypt_secret_key("password").unwrap();
+        k.require_unlock_for_reveal = true;
+        k.set_auto_lock(Some(Duration::from_secs(60)));
+        let start = Instant::now();
+        let reveal_at = |k: &mut Keystore, now: Instant| {
+            let mut sink = Vec::new();
+            k.reveal_secret_into_at(&mut sink, now)
+                .map(|_| String::from_utf8(sink).unwrap())
+        };
+
+        // unlocked
+        assert_eq!(reveal_at(&mut k, start).unwrap(), nsec);
+        // reveal resets the timer
+        assert_eq!(
+            reveal_at(&mut k, start + Duration::from_secs(50)).unwrap(),
+            nsec
+        );
+        assert_eq!(
+            reveal_at(&mut k, start + Duration::from_secs(100)).unwrap(),
+            nsec
+        );
+        // timer elapsed
+        assert!(matches!(
+            reveal_at(&mut k, start + Duration::from_secs(200)),
+            Err(Error::KeyLocked)
+        ));
+        assert!(k.is_locked());
+
+        // unlock again
+        let _res = k.decrypt_secret_key("password").unwrap();
+        let mut sink = Vec::new();
+        let _res = k.reveal_secret_into(&mut sink).unwrap();
+        assert_eq!(String::from_utf8(sink).unwrap(), nsec);
+    }
+
+    #[test]
+    fn test_copy_nsec_with_timeout() {
+        let mut k = Keystore::new();
+        assert!(k.copy_nsec_with_timeout(Duration::from_secs(30)).is_err());
+        let _res = k
+            .import_secret_key(
+                "nsec1ktekw0hr5evjs0n9nyyquz4sue568snypy2rwk5mpv6hl2hq3vtsk0kpae",
+                true,
+            )
+            .unwrap();
+        let copy = k.copy_nsec_with_timeout(Duration::from_secs(30)).unwrap();
+        assert_eq!(
+            copy.content,
+            "nsec1ktekw0hr5evjs0n9nyyquz4sue568snypy2rwk5mpv6hl2hq3vtsk0kpae"
+        );
+        if copy.auto_clear_supported {
+            assert_eq!(copy.clear_after, Some(Duration::from_secs(30)));
+            assert_eq!(copy.warning, ClipboardWarning::HistoryMayRetain);
+        } else {
+            assert_eq!(copy.clear_after, None);
+            assert_eq!(copy.warning, ClipboardWarning::CannotAutoClear);
+        }
+        assert!(!copy.warning.message().is_empty());
+    }
+
+    #[test]
+    fn test_reveal_secret_no_policy() {
+        let mut k = Keystore::from_keys(Keys::generate());
+        k.set_auto_lock(Some(Duration::from_secs(60)));
+        let later = Instant::now() + Duration::from_secs(200);
+        assert!(k.is_auto_lock_expired_at(later));
+        // policy flag not set, reveal is allowed
+        assert!(k.reveal_secret_into_at(&mut Vec::new(), later).is_ok());
+    }
+}
@@ -0,0 +1,96 @@
+use crate::model::keystore::Keystore;
+
+use std::sync::Mutex;
+
+/// Signing activity in the current session, e.g. for noticing unexpected signing
+#[derive(Clone, Copy, Debug, Default, Eq, PartialEq)]
+pub(crate) struct SigningStats {
+    /// Number of events signed
+    pub count: u64,
+    /// Time of the last signature (unix seconds)
+    pub last_signed_at: Option<u64>,
+}
+
+impl Keystore {
+    /// Signing activity since the keys were set or unlocked (count, time of last signature)
+    pub fn signing_stats(&self) -> SigningStats {
+        self.signing_stats
+            .lock()
+            .map(|stats| *stats)
+            .unwrap_or_default()
+    }
+}
+
+/// Count a signature made now in the signing stats
+pub(super) fn record_signature(signing_stats: &Mutex<SigningStats>) {
+    if let Ok(mut stats) = signing_stats.lock() {
+        stats.count += 1;
+        stats.last_signed_at = Some(Keystore::current_time());
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::base::encrypt::Encrypt;
+    use nostr::prelude::{EventBuilder, Keys};
+
+    #[test]
+    fn test_signing_stats() {
+        let keys = Keys::generate();
+        let mut k = Keystore::from_keys(keys.clone());
+        assert_eq!(k.signing_stats(), SigningStats::default());
+
+        let unsigned =
+            EventBuilder::new_text_note("note", &[]).to_unsigned_event(keys.public_key());
+        let _event = k.sign(unsigned.clone()).unwrap();
+        assert_eq!(k.signing_stats().count, 1);
+        let _event = k.sign(unsigned.clone()).unwrap();
+        let stats = k.signing_stats();
+        assert_eq!(stats.count, 2);
+        assert!(stats.last_signed_at.unwrap() >= Keystore::current_time() - 5);
+
+        // failed signing is not counted
+        let other = EventBuilder::new_text_note("note", &[])
+            .to_unsigned_event(Keys::generate().public_key());
+        assert!(k.sign(other).is_err());
+        assert_eq!(k.signing_stats().count, 2);
+
+        // reset on clear
+        k.clear();
+        assert_eq!(k.signing_stats(), SigningStats::default());
+
+        // reset on lock
+        let encrypted = Encrypt::encrypt_key(
+            &keys.secret_key().unwrap(),
+            "password",
+            Encrypt::default_log2_rounds(),
+        )
+        .unwrap();
+        let mut k = Keystore::from_locked(keys.public_key(), encrypted);
+        let _res = k.decrypt_secret_key("password").unwrap();
+        let _event = k.sign(unsigned).unwrap();
+        assert_eq!(k.signing_stats().count, 1);
+        let _res = k.lock().unwrap();
+        assert_eq!(k.signing_stats(), SigningStats::default());
+    }
+
+    #[test]
+    fn test_signing_stats_key_signer() {
+        let keys = Keys::generate();
+        let mut k = Keystore::from_keys(keys.clone());
+        let signer = k.get_signer().unwrap();
+        let unsigned =
+            EventBuilder::new_text_note("note", &[]).to_unsigned_event(keys.public_key());
+        let _signature = signer.sign_event(&unsigned).unwrap();
+        assert_eq!(k.signing_stats().count, 1);
+        let _event = k.sign(unsigned.clone()).unwrap();
+        let _signature = signer.clone().sign(vec![1; 32]).unwrap();
+        assert_eq!(k.signing_stats().count, 3);
+
+        // a signer of previous keys does not count for the new keys
+        let _res = k.generate().unwrap();
+        let _signature = signer.sign_event(&unsigned).unwrap();
+        assert_eq!(k.signing_stats(), SigningStats::default());
+    }
+}
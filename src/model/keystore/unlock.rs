@@ -0,0 +1,232 @@
+use crate::base::cancellation::CancellationToken;
+use crate::base::encrypt::Encrypt;
+use crate::base::error::Error;
+use crate::model::keystore::Keystore;
+use crate::model::security_settings::{SecurityLevel, SecuritySettings};
+use crate::model::status_messages::StatusMessages;
+
+use nostr::prelude::SecretKey;
+use zeroize::Zeroizing;
+
+use std::sync::{Arc, Mutex};
+use std::thread;
+
+/// Unlocking (decryption) of the secret key, running in a background thread, so that
+/// the slow key derivation does not block the UI, and it can be cancelled.
+/// Finish it with `finish_unlock`.
+pub(crate) struct BackgroundUnlock {
+    cancel: CancellationToken,
+    progress: Arc<Mutex<f32>>,
+    /// The encrypted key being decrypted
+    encrypted: Vec<u8>,
+    password_empty: bool,
+    handle: thread::JoinHandle<Result<SecretKey, Error>>,
+}
+
+impl BackgroundUnlock {
+    /// Cancel the unlock; `finish_unlock` then returns `Cancelled`
+    pub fn cancel(&self) {
+        self.cancel.cancel();
+    }
+
+    /// Progress of the unlock, from 0.0 to 1.0 (coarse)
+    pub fn progress(&self) -> f32 {
+        self.progress.lock().map(|p| *p).unwrap_or_default()
+    }
+}
+
+/// Warning: Security-sensitive method!
+/// Start unlocking the secret key with the password, in a background thread.
+/// `on_progress` is called on progress, `on_done` when finished (also on failure or cancellation),
+/// both from the background thread.
+pub(crate) fn start_unlock(
+    keystore: &Keystore,
+    password: &str,
+    on_progress: impl Fn(f32) + Send + 'static,
+    on_done: impl FnOnce() + Send + 'static,
+) -> Result<BackgroundUnlock, Error> {
+    let encrypted = keystore
+        .encrypted_secret_key
+        .clone()
+        .ok_or(Error::KeyNotSet)?;
+    let cancel = CancellationToken::new();
+    let progress = Arc::new(Mutex::new(0.0));
+
+    let worker_encrypted = encrypted.clone();
+    let worker_password = Zeroizing::new(password.to_string());
+    let worker_cancel = cancel.clone();
+    let worker_progress = progress.clone();
+    let handle = thread::spawn(move || {
+        let report = |p: f32| {
+            if let Ok(mut progress) = worker_progress.lock() {
+                *progress = p;
+            }
+            on_progress(p);
+        };
+        let res = Encrypt::decrypt_key_with(
+            &worker_encrypted,
+            &worker_password,
+            &worker_cancel,
+            Some(&report),
+        );
+        on_done();
+        res
+    });
+
+    Ok(BackgroundUnlock {
+        cancel,
+        progress,
+        encrypted,
+        password_empty: password.is_empty(),
+        handle,
+    })
+}
+
+/// Warning: Security-sensitive method!
+/// Finish the background unlock: wait for it, and set the decrypted secret key.
+/// On failure or cancellation, or if the keys have changed meanwhile, the state is not changed.
+pub(crate) fn finish_unlock(
+    keystore: &mut Keystore,
+    unlock: BackgroundUnlock,
+) -> Result<(), Error> {
+    let sk = unlock.handle.join().map_err(|_e| Error::KeyEncryption)??;
+    if keystore.encrypted_secret_key.as_ref() != Some(&unlock.encrypted) {
+        return Err(Error::KeyNotSet);
+    }
+    keystore.set_decrypted_keys(sk, unlock.encrypted)?;
+    keystore.empty_password.set(unlock.password_empty);
+    Ok(())
+}
+
+/// Action to start unlocking the secret key with the password input, see `start_unlock`
+pub(crate) fn start_unlock_action(
+    keystore: &mut Keystore,
+    security_settings: &SecuritySettings,
+    status: &mut StatusMessages,
+    on_progress: impl Fn(f32) + Send + 'static,
+    on_done: impl FnOnce() + Send + 'static,
+) -> Option<BackgroundUnlock> {
+    // check if password is set if needed
+    let res = if security_settings.security_level == SecurityLevel::PersistMandatoryPassword
+        && keystore.decrypt_password_input.is_empty()
+    {
+        Err(Error::KeyEncryptionPasswordMissing)
+    } else {
+        start_unlock(
+            keystore,
+            &keystore.decrypt_password_input,
+            on_progress,
+            on_done,
+        )
+    };
+    // cleanup
+    keystore.decrypt_password_input = "".to_string();
+    match res {
+        Err(e) => {
+            status.set(&format!(
+                "Could not decrypt secret key, check password! ({})",
+                e
+            ));
+            None
+        }
+        Ok(unlock) => {
+            status.set("Decrypting secret key...");
+            Some(unlock)
+        }
+    }
+}
+
+/// Action to finish the background unlock, see `finish_unlock`
+pub(crate) fn finish_unlock_action(
+    keystore: &mut Keystore,
+    unlock: BackgroundUnlock,
+    status: &mut StatusMessages,
+) {
+    match finish_unlock(keystore, unlock) {
+        Err(Error::Cancelled) => status.set("Decryption of secret key cancelled"),
+        Err(e) => status.set(&format!(
+            "Could not decrypt secret key, check password! ({})",
+            e
+        )),
+        Ok(_) => match keystore.self_test() {
+            Err(e) => status.set_error_err(&e),
+            Ok(_) => status.set("Secret key decrypted"),
+        },
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::model::keystore::KeysSetState;
+    use nostr::prelude::Keys;
+
+    use std::sync::mpsc;
+
+    const PASSWORD: &str = "password";
+
+    /// Keystore with the secret key of the keys locked, encrypted with `PASSWORD`
+    fn locked_keystore(keys: &Keys) -> Keystore {
+        let encrypted = Encrypt::encrypt_key(
+            &keys.secret_key().unwrap(),
+            PASSWORD,
+            Encrypt::default_log2_rounds(),
+        )
+        .unwrap();
+        Keystore::from_locked(keys.public_key(), encrypted)
+    }
+
+    #[test]
+    fn test_background_unlock() {
+        let keys = Keys::generate();
+        let mut k = locked_keystore(&keys);
+        let (sender, receiver) = mpsc::channel();
+        let unlock = start_unlock(
+            &k,
+            PASSWORD,
+            |_p| {},
+            move || {
+                let _res = sender.send(());
+            },
+        )
+        .unwrap();
+        let _res = receiver.recv().unwrap();
+        assert_eq!(unlock.progress(), 1.0);
+        let _res = finish_unlock(&mut k, unlock).unwrap();
+        assert_eq!(k.get_set_state(), KeysSetState::SecretAndPublic);
+        assert_eq!(k.get_keys().unwrap().public_key(), keys.public_key());
+    }
+
+    #[test]
+    fn test_background_unlock_wrong_password() {
+        let keys = Keys::generate();
+        let mut k = locked_keystore(&keys);
+        let unlock = start_unlock(&k, "wrong", |_p| {}, || {}).unwrap();
+        assert!(finish_unlock(&mut k, unlock).is_err());
+        assert_eq!(k.get_set_state(), KeysSetState::Locked);
+    }
+
+    #[test]
+    fn test_background_unlock_cancel() {
+        let keys = Keys::generate();
+        // a slow KDF, cancelled while running
+        let encrypted = Encrypt::encrypt_key(&keys.secret_key().unwrap(), PASSWORD, 15).unwrap();
+        let mut k = Keystore::from_locked(keys.public_key(), encrypted);
+        let unlock = start_unlock(&k, PASSWORD, |_p| {}, || {}).unwrap();
+        unlock.cancel();
+        assert!(matches!(
+            finish_unlock(&mut k, unlock),
+            Err(Error::Cancelled)
+        ));
+        assert_eq!(k.get_set_state(), KeysSetState::Locked);
+    }
+
+    #[test]
+    fn test_background_unlock_not_locked() {
+        let k = Keystore::from_keys(Keys::from_public_key(Keys::generate().public_key()));
+        assert!(matches!(
+            start_unlock(&k, PASSWORD, |_p| {}, || {}),
+            Err(Error::KeyNotSet)
+        ));
+    }
+}
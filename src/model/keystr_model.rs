@@ -1,6 +1,10 @@
 use crate::base::error::Error;
+use crate::base::storage::Storage;
+use crate::model::accounts::AccountManager;
 use crate::model::delegator::Delegator;
+use crate::model::keystore::unlock::{finish_unlock_action, start_unlock_action, BackgroundUnlock};
 use crate::model::keystore::Keystore;
+use crate::model::security_settings::KeyPolicy;
 use crate::model::settings::Settings;
 use crate::model::signer::{ConnectionStatus, Signer};
 use crate::model::status_messages::StatusMessages;
@@ -10,26 +14,89 @@ use nostr::prelude::Keys;
 use crossbeam::channel;
 use once_cell::sync::Lazy;
 
+/// NIPs implemented (or used) by this app, which are always built in, sorted
+const IMPLEMENTED_NIPS: &[u16] = &[1, 4, 5, 6, 11, 19, 20, 21, 26, 42, 44, 47, 49, 59, 65, 98];
+
+/// The NIPs implemented (or used) by this app regardless of the features, sorted; see `supported_nips`
+pub(crate) fn implemented_nips() -> &'static [u16] {
+    IMPLEMENTED_NIPS
+}
+
+/// NIPs behind a feature flag, as (NIP, `cfg!(feature = ...)`).
+/// The other optional features (`clipboard`, `tracing`) add no NIP of their own.
+const FEATURE_GATED_NIPS: &[(u16, bool)] = &[(46, cfg!(feature = "nip46"))];
+
+/// The NIPs supported by this build (sorted), e.g. for an about panel:
+/// the ones always built in, and the ones of the enabled features
+pub(crate) fn supported_nips() -> Vec<u16> {
+    let mut nips = implemented_nips().to_vec();
+    nips.extend(
+        FEATURE_GATED_NIPS
+            .iter()
+            .filter(|(_nip, enabled)| *enabled)
+            .map(|(nip, _enabled)| *nip),
+    );
+    nips.sort_unstable();
+    nips.dedup();
+    nips
+}
+
+/// The optional features enabled in this build, e.g. for an about panel
+pub(crate) fn enabled_features() -> Vec<&'static str> {
+    [
+        ("clipboard", cfg!(feature = "clipboard")),
+        ("nip46", cfg!(feature = "nip46")),
+        ("tracing", cfg!(feature = "tracing")),
+    ]
+    .iter()
+    .filter(|(_name, enabled)| *enabled)
+    .map(|(name, _enabled)| *name)
+    .collect()
+}
+
 /// Actions that can be triggerred from the UI
 #[derive(Clone, Debug)]
 pub(crate) enum Action {
+    AccountsAdd,
+    AccountsExportArchive,
+    AccountsExportBundle,
+    AccountsImportArchive,
+    AccountsImportBundle,
     DelegateDeeGenerate,
     DelegateSign,
+    DelegateVerifyEvent,
+    KeysCheckVerification,
     KeysClearNoConfirm,
     KeysClear,
+    KeysConfirmBackup,
     KeysGenerate,
+    KeysGenerateMnemonic,
     KeysImportPubkey,
+    KeysImportPublicFromSecret,
     KeysImportSecretkey,
     KeysImportMnemonic,
+    KeysImportNcryptsec,
+    KeysImportNcryptsecCancel,
+    KeysImportQrPayload,
+    KeysImportSubmit,
+    KeysImportWithFormat,
     KeysLoad,
+    KeysLoadFromKeychain,
+    KeysPanicWipe,
+    KeysReencryptNcryptsec,
     KeysSave,
+    KeysSaveToKeychain,
+    KeysSetLabel,
     KeysUnlock,
+    KeysUnlockCancel,
+    KeysUnlockFinish,
     ConfirmationYes,
     ConfirmationNo,
     SignerConnect,
     SignerDisconnect,
     SignerPendingIgnoreFirst,
     SignerPendingProcessFirst,
+    SignerSetAllowedKinds,
 }
 
 /// Events that can affect the UI
@@ -38,6 +105,8 @@ pub enum Event {
     SignerConnected,
     SignerNewRequest,
     StatusUpdate,
+    /// Background unlock of the secret key has finished (see `Action::KeysUnlockFinish`)
+    UnlockFinished,
 }
 
 /// Modal dialogs
@@ -51,17 +120,23 @@ pub(crate) enum Modal {
 #[derive(Clone)]
 pub(crate) enum Confirmation {
     KeysClearBeforeAction(Option<Action>),
+    KeysPanicWipe,
 }
 
 #[readonly::make]
 pub(crate) struct KeystrModel {
     pub own_keys: Keystore,
+    /// Further accounts, e.g. for backing up all of them together
+    pub accounts: AccountManager,
     pub delegator: Delegator,
     pub signer: Signer,
     pub status: StatusMessages,
     pub settings: Settings,
     #[readonly]
     confirmation: Option<Confirmation>,
+    /// Unlock of the secret key in progress, if any
+    #[readonly]
+    unlock: Option<BackgroundUnlock>,
 }
 
 pub(crate) struct EventQueue {
@@ -84,11 +159,13 @@ impl KeystrModel {
         let status = StatusMessages::new();
         Self {
             own_keys: Keystore::new(),
+            accounts: AccountManager::new(),
             delegator: Delegator::new(),
             signer: Signer::new(&app_id, status.clone()),
             status,
             settings: Settings::default(),
             confirmation: None,
+            unlock: None,
         }
     }
 
@@ -101,15 +178,113 @@ impl KeystrModel {
         if let Ok(sett) = Settings::load() {
             model.settings = sett;
         }
+        model.own_keys.network_config = model.settings.network_config();
+        model.apply_auto_lock();
+        model.own_keys.key_policy = model.settings.security.key_policy.clone();
+        let scrypt_log_n = model.settings.security.scrypt_log_n;
+        if let Err(e) = model
+            .own_keys
+            .set_scrypt_log_n(scrypt_log_n)
+            .and_then(|_| model.accounts.set_scrypt_log_n(scrypt_log_n))
+        {
+            model.status.set_error_err(&e);
+        }
         //. Try load keys
         if model.settings.security.allows_persist() {
             model.action(Action::KeysLoad);
         }
+        //. Integrity check of loaded secret key
+        if model.own_keys.is_secret_key_set() {
+            if let Err(e) = model.own_keys.self_test() {
+                model.status.set_error_err(&e);
+            }
+        }
         model
     }
 
+    /// Set the scrypt cost for encrypting the secret key (and account exports), and keep it in the settings
+    pub fn set_scrypt_log_n(&mut self, log_n: u8) {
+        match self
+            .own_keys
+            .set_scrypt_log_n(log_n)
+            .and_then(|_| self.accounts.set_scrypt_log_n(log_n))
+        {
+            Err(e) => self.status.set_error_err(&e),
+            Ok(_) => self.settings.set_scrypt_log_n(log_n),
+        }
+    }
+
+    /// Set the timeout of network operations, and keep it in the settings
+    pub fn set_network_timeout_secs(&mut self, secs: u64) {
+        self.settings.set_network_timeout_secs(secs);
+        self.own_keys.network_config = self.settings.network_config();
+    }
+
+    /// Allow importing only keys derived from mnemonic (or any keys), and keep it in the settings
+    pub fn set_mnemonic_only(&mut self, mnemonic_only: bool) {
+        let key_policy = if mnemonic_only {
+            KeyPolicy::mnemonic_only()
+        } else {
+            KeyPolicy::default()
+        };
+        self.own_keys.key_policy = key_policy.clone();
+        self.settings.set_key_policy(key_policy);
+    }
+
+    /// Set the auto-lock period (0 for none), and keep it in the settings
+    pub fn set_auto_lock_mins(&mut self, mins: u64) {
+        self.settings.set_auto_lock_mins(mins);
+        self.apply_auto_lock();
+    }
+
+    /// With auto-lock set, revealing the secret key is refused after the period of inactivity
+    fn apply_auto_lock(&mut self) {
+        let auto_lock = self.settings.auto_lock();
+        self.own_keys.set_auto_lock(auto_lock);
+        self.own_keys.require_unlock_for_reveal = auto_lock.is_some();
+    }
+
+    /// Emergency wipe of the keys: in memory (also of the accounts), and the secret key file;
+    /// the signer is disconnected, as it holds the keys too
+    fn panic_wipe(&mut self) {
+        if let Some(unlock) = self.unlock.take() {
+            unlock.cancel();
+        }
+        self.accounts.panic_wipe();
+        if !matches!(
+            self.signer.get_connection_status(),
+            ConnectionStatus::NotConnected
+        ) {
+            self.signer.disconnect_action(&mut self.status);
+        }
+        match self
+            .own_keys
+            .panic_wipe(Some(&Storage::encrypted_secret_key_file()))
+        {
+            Err(e) => self.status.set_error_err(&e),
+            Ok(_) => self
+                .status
+                .set("Keys wiped, the secret key file is deleted"),
+        }
+    }
+
     pub fn action(&mut self, action: Action) {
         match action {
+            Action::AccountsAdd => {
+                self.accounts.add_action(&self.own_keys, &mut self.status);
+            }
+            Action::AccountsExportArchive => {
+                self.accounts.export_archive_action(&mut self.status);
+            }
+            Action::AccountsExportBundle => {
+                self.accounts.export_bundle_action(&mut self.status);
+            }
+            Action::AccountsImportArchive => {
+                self.accounts.import_archive_action(&mut self.status);
+            }
+            Action::AccountsImportBundle => {
+                self.accounts.import_bundle_action(&mut self.status);
+            }
             Action::DelegateDeeGenerate => self.delegator.generate_random_delegatee(),
             Action::DelegateSign => {
                 match self.own_keys.get_keys() {
@@ -120,6 +295,9 @@ impl KeystrModel {
                     },
                 };
             }
+            Action::DelegateVerifyEvent => {
+                self.delegator.verify_event_action(&mut self.status);
+            }
             Action::KeysClearNoConfirm => {
                 self.own_keys.clear();
                 self.status.set("Keys cleared");
@@ -138,8 +316,21 @@ impl KeystrModel {
                     )));
                 } else {
                     self.confirmation = None;
-                    self.own_keys.generate();
-                    self.status.set("New keypair generated");
+                    match self.own_keys.generate() {
+                        Err(e) => self.status.set_error(&e.to_string()),
+                        Ok(_) => self.status.set("New keypair generated"),
+                    }
+                }
+            }
+            Action::KeysGenerateMnemonic => {
+                if self.own_keys.keys_is_set() {
+                    self.confirmation = Some(Confirmation::KeysClearBeforeAction(Some(
+                        Action::KeysGenerateMnemonic,
+                    )));
+                } else {
+                    self.confirmation = None;
+                    self.own_keys
+                        .generate_with_mnemonic_action(&mut self.status);
                 }
             }
             Action::KeysImportPubkey => {
@@ -153,12 +344,32 @@ impl KeystrModel {
                 // cleanup
                 self.own_keys.public_key_input = String::new();
             }
+            Action::KeysImportPublicFromSecret => {
+                self.own_keys
+                    .import_public_from_secret_action(&mut self.status);
+            }
             Action::KeysImportSecretkey => {
                 self.own_keys.import_secret_key_action(&mut self.status);
             }
+            Action::KeysImportWithFormat => {
+                self.own_keys.import_with_format_action(&mut self.status);
+            }
+            Action::KeysImportSubmit => {
+                self.own_keys.import_submit_action(&mut self.status);
+            }
             Action::KeysImportMnemonic => {
                 self.own_keys.import_mnemonic_action(&mut self.status);
             }
+            Action::KeysImportQrPayload => {
+                self.own_keys
+                    .import_from_qr_payload_action(&mut self.status);
+            }
+            Action::KeysConfirmBackup => {
+                self.own_keys.confirm_backup_action(&mut self.status);
+            }
+            Action::KeysSetLabel => {
+                self.own_keys.set_label_action(&mut self.status);
+            }
             Action::KeysLoad => {
                 if self.own_keys.keys_is_set() {
                     self.confirmation =
@@ -168,13 +379,66 @@ impl KeystrModel {
                         .load_action(&self.settings.security, &mut self.status);
                 }
             }
+            Action::KeysLoadFromKeychain => {
+                if self.own_keys.keys_is_set() {
+                    self.confirmation = Some(Confirmation::KeysClearBeforeAction(Some(
+                        Action::KeysLoadFromKeychain,
+                    )));
+                } else {
+                    self.own_keys
+                        .load_from_keychain_action(&self.settings.security, &mut self.status);
+                }
+            }
+            Action::KeysReencryptNcryptsec => {
+                self.own_keys.reencrypt_ncryptsec_action(&mut self.status);
+            }
+            Action::KeysPanicWipe => {
+                self.confirmation = Some(Confirmation::KeysPanicWipe);
+            }
             Action::KeysSave => {
                 self.own_keys
                     .save_action(&self.settings.security, &mut self.status);
             }
-            Action::KeysUnlock => self
-                .own_keys
-                .unlock_secret_key_action(&self.settings.security, &mut self.status),
+            Action::KeysSaveToKeychain => {
+                self.own_keys
+                    .save_to_keychain_action(&self.settings.security, &mut self.status);
+            }
+            Action::KeysCheckVerification => {
+                self.own_keys.check_verification_action(&mut self.status);
+            }
+            Action::KeysImportNcryptsec => {
+                self.own_keys.import_ncryptsec_action(&mut self.status);
+            }
+            Action::KeysImportNcryptsecCancel => {
+                self.own_keys.ncryptsec_input = String::new();
+                self.own_keys.decrypt_password_input = String::new();
+            }
+            Action::KeysUnlock => {
+                if self.unlock.is_none() {
+                    self.unlock = start_unlock_action(
+                        &mut self.own_keys,
+                        &self.settings.security,
+                        &mut self.status,
+                        |_progress| {
+                            // refresh the UI
+                            let _ = EVENT_QUEUE.push(Event::StatusUpdate);
+                        },
+                        || {
+                            let _ = EVENT_QUEUE.push(Event::UnlockFinished);
+                        },
+                    );
+                }
+            }
+            Action::KeysUnlockCancel => {
+                if let Some(unlock) = &self.unlock {
+                    unlock.cancel();
+                }
+            }
+            Action::KeysUnlockFinish => {
+                if let Some(unlock) = self.unlock.take() {
+                    finish_unlock_action(&mut self.own_keys, unlock, &mut self.status);
+                }
+            }
             Action::ConfirmationYes => {
                 if let Some(conf) = &self.confirmation {
                     match conf {
@@ -186,6 +450,10 @@ impl KeystrModel {
                                 self.action(next_action);
                             }
                         }
+                        Confirmation::KeysPanicWipe => {
+                            self.confirmation = None;
+                            self.panic_wipe();
+                        }
                     }
                 }
             }
@@ -196,6 +464,10 @@ impl KeystrModel {
             }
             Action::SignerConnect => match self.own_keys.get_signer() {
                 Err(_) => self.status.set("Key pair is not loaded or unlocked!"),
+                Ok(_) if !cfg!(feature = "nip46") => self
+                    .status
+                    .set("Remote signing (NIP-46) is not enabled in this build"),
+                Ok(_) if self.own_keys.offline => self.status.set_error_err(&Error::OfflineMode),
                 Ok(signer) => {
                     self.signer.connect_action(signer, &mut self.status);
                 }
@@ -209,6 +481,9 @@ impl KeystrModel {
             Action::SignerPendingProcessFirst => {
                 self.signer.pending_process_first_action(&mut self.status);
             }
+            Action::SignerSetAllowedKinds => {
+                self.own_keys.set_allowed_kinds_action(&mut self.status);
+            }
         }
     }
 
@@ -257,6 +532,36 @@ impl EventQueue {
 mod test {
     use super::*;
 
+    #[test]
+    fn test_implemented_nips() {
+        let nips = implemented_nips();
+        assert!(nips.windows(2).all(|w| w[0] < w[1]));
+        for nip in [6, 19, 26, 49] {
+            assert!(nips.contains(&nip));
+        }
+    }
+
+    #[test]
+    fn test_supported_nips() {
+        let nips = supported_nips();
+        assert!(nips.windows(2).all(|w| w[0] < w[1]));
+        assert!(implemented_nips().iter().all(|nip| nips.contains(nip)));
+        // feature-gated NIPs only when built in
+        assert_eq!(nips.contains(&46), cfg!(feature = "nip46"));
+        assert_eq!(
+            enabled_features().contains(&"nip46"),
+            cfg!(feature = "nip46")
+        );
+        assert_eq!(
+            enabled_features().contains(&"clipboard"),
+            cfg!(feature = "clipboard")
+        );
+        assert_eq!(
+            enabled_features().contains(&"tracing"),
+            cfg!(feature = "tracing")
+        );
+    }
+
     #[test]
     fn test_clear_generate_confirmation() {
         let mut m = KeystrModel::new();
@@ -1,6 +1,12 @@
+pub mod accounts;
+pub mod airgap;
+pub mod clipboard;
 pub mod delegator;
 pub mod keystore;
 pub mod keystr_model;
+pub mod nip47;
+pub mod relays;
+pub mod secret_sink;
 pub mod security_settings;
 pub mod settings;
 pub mod signer;
@@ -0,0 +1,313 @@
+use crate::base::error::Error;
+use crate::model::relays::NetworkConfig;
+
+use nostr::prelude::{
+    Event, EventBuilder, EventId, Filter, Keys, Kind, SecretKey, Tag, Timestamp, Url,
+    XOnlyPublicKey,
+};
+use nostr_sdk::prelude::{decrypt, encrypt, Client, RelayPoolNotification};
+use serde_json::{json, Value};
+
+use std::fmt;
+use std::str::FromStr;
+use std::time::Duration;
+
+/// URI scheme of Nostr Wallet Connect connection strings (NIP-47)
+const NWC_URI_SCHEME: &str = "nostr+walletconnect";
+/// Event kind of wallet requests (NIP-47)
+const NWC_REQUEST_KIND: u64 = 23194;
+/// Event kind of wallet responses (NIP-47)
+const NWC_RESPONSE_KIND: u64 = 23195;
+/// Response wait starts this much before sending the request, to tolerate clock skew
+const NWC_RESPONSE_SINCE_MARGIN: Duration = Duration::from_secs(10);
+
+/// A Nostr Wallet Connect (NIP-47) connection: the wallet service, its relay,
+/// and the secret of the app-side keys (distinct from the identity keys of the keystore)
+#[derive(Clone)]
+pub(crate) struct NwcConnection {
+    pub wallet_pubkey: XOnlyPublicKey,
+    pub relay: Url,
+    secret: SecretKey,
+    /// Lightning address of the wallet, optional
+    pub lud16: Option<String>,
+}
+
+/// Debug output never includes the secret
+impl fmt::Debug for NwcConnection {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.debug_struct("NwcConnection")
+            .field("wallet_pubkey", &self.wallet_pubkey)
+            .field("relay", &self.relay)
+            .field("secret", &"[redacted]")
+            .field("lud16", &self.lud16)
+            .finish()
+    }
+}
+
+impl NwcConnection {
+    /// Keys for communicating with the wallet service
+    pub fn keys(&self) -> Keys {
+        Keys::new(self.secret)
+    }
+}
+
+/// Parse a Nostr Wallet Connect URI, of the form
+/// `nostr+walletconnect://<wallet pubkey hex>?relay=<relay url>&secret=<secret hex>[&lud16=<address>]`
+pub(crate) fn parse_nwc_uri(uri: &str) -> Result<NwcConnection, Error> {
+    let url = Url::parse(uri.trim()).map_err(|_e| Error::NwcInvalidUri("malformed".into()))?;
+    if url.scheme() != NWC_URI_SCHEME {
+        return Err(Error::NwcInvalidUri(format!(
+            "scheme must be '{NWC_URI_SCHEME}'"
+        )));
+    }
+    let wallet_pubkey = url
+        .host_str()
+        .and_then(|h| XOnlyPublicKey::from_str(h).ok())
+        .ok_or_else(|| Error::NwcInvalidUri("invalid wallet public key".into()))?;
+
+    let mut relay = None;
+    let mut secret = None;
+    let mut lud16 = None;
+    for (name, value) in url.query_pairs() {
+        match name.as_ref() {
+            "relay" => {
+                relay = Some(
+                    Url::parse(&value)
+                        .map_err(|_e| Error::NwcInvalidUri("invalid relay URL".into()))?,
+                )
+            }
+            "secret" => {
+                secret = Some(
+                    SecretKey::from_str(&value)
+                        .map_err(|_e| Error::NwcInvalidUri("invalid secret".into()))?,
+                )
+            }
+            "lud16" => lud16 = Some(value.to_string()),
+            _ => {}
+        }
+    }
+    Ok(NwcConnection {
+        wallet_pubkey,
+        relay: relay.ok_or_else(|| Error::NwcInvalidUri("missing relay".into()))?,
+        secret: secret.ok_or_else(|| Error::NwcInvalidUri("missing secret".into()))?,
+        lud16,
+    })
+}
+
+/// Create the encrypted `pay_invoice` request event, signed by the connection keys
+pub(crate) fn pay_invoice_request(conn: &NwcConnection, bolt11: &str) -> Result<Event, Error> {
+    let keys = conn.keys();
+    let request = json!({
+        "method": "pay_invoice",
+        "params": { "invoice": bolt11 },
+    })
+    .to_string();
+    let content = encrypt(&keys.secret_key()?, &conn.wallet_pubkey, &request)?;
+    Ok(EventBuilder::new(
+        Kind::Custom(NWC_REQUEST_KIND),
+        content,
+        &[Tag::PubKey(conn.wallet_pubkey, None)],
+    )
+    .to_event(&keys)?)
+}
+
+/// Decrypt and interpret a `pay_invoice` response event from the wallet.
+/// Returns the payment preimage, or the error reported by the wallet.
+pub(crate) fn parse_pay_invoice_response(
+    conn: &NwcConnection,
+    event: &Event,
+) -> Result<String, Error> {
+    if event.kind.as_u64() != NWC_RESPONSE_KIND || event.pubkey != conn.wallet_pubkey {
+        return Err(Error::NwcInvalidResponse);
+    }
+    event.verify()?;
+    let content = decrypt(&conn.secret, &conn.wallet_pubkey, &event.content)?;
+    let response: Value = serde_json::from_str(&content)?;
+    if let Some(error) = response.get("error").filter(|e| !e.is_null()) {
+        let code = error["code"].as_str().unwrap_or("UNKNOWN");
+        let message = error["message"].as_str().unwrap_or_default();
+        return Err(Error::NwcWalletError(format!("{code} {message}")));
+    }
+    response["result"]["preimage"]
+        .as_str()
+        .map(|s| s.to_string())
+        .ok_or(Error::NwcInvalidResponse)
+}
+
+/// Check if an event references the request (`e` tag)
+fn is_response_to(event: &Event, request_id: &EventId) -> bool {
+    let id = request_id.to_hex();
+    event.tags.iter().any(|t| {
+        let v = t.as_vec();
+        v.len() >= 2 && v[0] == "e" && v[1] == id
+    })
+}
+
+/// Pay a lightning invoice through the wallet of the connection:
+/// send the encrypted request to the wallet relay, and wait for the response, up to the timeout.
+/// Returns the payment preimage.
+pub(crate) async fn pay_invoice(
+    conn: &NwcConnection,
+    bolt11: &str,
+    config: &NetworkConfig,
+) -> Result<String, Error> {
+    let request = pay_invoice_request(conn, bolt11)?;
+    let keys = conn.keys();
+    let relay_client = Client::new(&keys);
+    relay_client.add_relay(conn.relay.as_str(), None).await?;
+    relay_client.connect().await;
+    relay_client
+        .subscribe(vec![Filter::new()
+            .author(conn.wallet_pubkey.to_string())
+            .pubkey(keys.public_key())
+            .kind(Kind::Custom(NWC_RESPONSE_KIND))
+            .since(Timestamp::now() - NWC_RESPONSE_SINCE_MARGIN)])
+        .await;
+    let mut notifications = relay_client.notifications();
+
+    let res = tokio::time::timeout(config.timeout, async {
+        relay_client.send_event(request.clone()).await?;
+        while let Ok(notification) = notifications.recv().await {
+            if let RelayPoolNotification::Event(_url, event) = notification {
+                if event.kind.as_u64() == NWC_RESPONSE_KIND && is_response_to(&event, &request.id) {
+                    return parse_pay_invoice_response(conn, &event);
+                }
+            }
+        }
+        Err(Error::NwcInvalidResponse)
+    })
+    .await;
+    let _ = relay_client.disconnect().await;
+    res.map_err(|_e| Error::NwcTimeout)?
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::model::relays::mock_relay::start_mock_relay;
+
+    const WALLET_SECRET: &str = "b2f3673ee3a659283e6599080e0ab0e669a3c2640914375a9b0b357faae08b17";
+    const WALLET_PUBKEY: &str = "1a459a8a6aa6441d480ba665fb8fb21a4cfe8bcacb7d87300f8046a558a3fce4";
+    const APP_SECRET: &str = "71a8c14c1407c113601079c4302dab36460f0ccd0ad506f1f2dc73b5100e4f3c";
+
+    fn test_uri() -> String {
+        format!("nostr+walletconnect://{WALLET_PUBKEY}?relay=wss%3A%2F%2Frelay.damus.io&secret={APP_SECRET}&lud16=satoshi%40getalby.com")
+    }
+
+    /// Response event as the wallet would send it
+    fn wallet_response(conn: &NwcConnection, request: &Event, result: Value) -> Event {
+        let wallet_keys = Keys::new(SecretKey::from_str(WALLET_SECRET).unwrap());
+        let content = encrypt(
+            &wallet_keys.secret_key().unwrap(),
+            &conn.keys().public_key(),
+            &result.to_string(),
+        )
+        .unwrap();
+        let e_tag: Tag =
+            serde_json::from_str(&format!("[\"e\",\"{}\"]", request.id.to_hex())).unwrap();
+        EventBuilder::new(
+            Kind::Custom(NWC_RESPONSE_KIND),
+            content,
+            &[Tag::PubKey(conn.keys().public_key(), None), e_tag],
+        )
+        .to_event(&wallet_keys)
+        .unwrap()
+    }
+
+    #[test]
+    fn test_parse_nwc_uri() {
+        let conn = parse_nwc_uri(&test_uri()).unwrap();
+        assert_eq!(conn.wallet_pubkey.to_string(), WALLET_PUBKEY);
+        assert_eq!(conn.relay.as_str(), "wss://relay.damus.io/");
+        assert_eq!(conn.lud16, Some("satoshi@getalby.com".to_string()));
+        assert_eq!(
+            conn.keys().public_key(),
+            Keys::new(SecretKey::from_str(APP_SECRET).unwrap()).public_key()
+        );
+        assert!(!format!("{:?}", conn).contains(APP_SECRET));
+    }
+
+    #[test]
+    fn test_parse_nwc_uri_invalid() {
+        let relay = "relay=wss%3A%2F%2Frelay.damus.io";
+        let secret = format!("secret={APP_SECRET}");
+        for uri in [
+            "".to_string(),
+            "__NOT_A_URI__".to_string(),
+            format!("nostrconnect://{WALLET_PUBKEY}?{relay}&{secret}"),
+            format!("nostr+walletconnect://abcd?{relay}&{secret}"),
+            format!("nostr+walletconnect://{WALLET_PUBKEY}?{secret}"),
+            format!("nostr+walletconnect://{WALLET_PUBKEY}?{relay}"),
+            format!("nostr+walletconnect://{WALLET_PUBKEY}?{relay}&secret=xyz"),
+        ] {
+            assert!(
+                matches!(parse_nwc_uri(&uri), Err(Error::NwcInvalidUri(_))),
+                "{uri}"
+            );
+        }
+    }
+
+    #[test]
+    fn test_pay_invoice_round_trip() {
+        let conn = parse_nwc_uri(&test_uri()).unwrap();
+        let request = pay_invoice_request(&conn, "lnbc50n1_invoice").unwrap();
+        assert_eq!(request.kind.as_u64(), NWC_REQUEST_KIND);
+        assert_eq!(request.pubkey, conn.keys().public_key());
+        assert!(!request.content.contains("lnbc50n1_invoice"));
+
+        // wallet side: decrypt the request
+        let wallet_sk = SecretKey::from_str(WALLET_SECRET).unwrap();
+        let decrypted: Value =
+            serde_json::from_str(&decrypt(&wallet_sk, &request.pubkey, &request.content).unwrap())
+                .unwrap();
+        assert_eq!(decrypted["method"], "pay_invoice");
+        assert_eq!(decrypted["params"]["invoice"], "lnbc50n1_invoice");
+
+        let response = wallet_response(
+            &conn,
+            &request,
+            json!({"result_type": "pay_invoice", "error": null, "result": {"preimage": "0123abcd"}}),
+        );
+        assert!(is_response_to(&response, &request.id));
+        assert_eq!(
+            parse_pay_invoice_response(&conn, &response).unwrap(),
+            "0123abcd"
+        );
+    }
+
+    #[test]
+    fn test_pay_invoice_wallet_error() {
+        let conn = parse_nwc_uri(&test_uri()).unwrap();
+        let request = pay_invoice_request(&conn, "lnbc50n1_invoice").unwrap();
+        let response = wallet_response(
+            &conn,
+            &request,
+            json!({"result_type": "pay_invoice", "error": {"code": "INSUFFICIENT_BALANCE", "message": "Not enough funds"}}),
+        );
+        match parse_pay_invoice_response(&conn, &response) {
+            Err(Error::NwcWalletError(e)) => assert_eq!(e, "INSUFFICIENT_BALANCE Not enough funds"),
+            other => panic!("unexpected result {other:?}"),
+        }
+        // a response from someone else than the wallet is rejected
+        assert!(matches!(
+            parse_pay_invoice_response(&conn, &request),
+            Err(Error::NwcInvalidResponse)
+        ));
+    }
+
+    #[tokio::test]
+    async fn test_pay_invoice_timeout() {
+        // relay which accepts the connection, but never sends a response from the wallet
+        let relay = start_mock_relay(Vec::new());
+        let uri =
+            format!("nostr+walletconnect://{WALLET_PUBKEY}?relay={relay}&secret={APP_SECRET}");
+        let conn = parse_nwc_uri(&uri).unwrap();
+        let res = pay_invoice(
+            &conn,
+            "lnbc50n1_invoice",
+            &NetworkConfig::with_timeout(Duration::from_millis(500)),
+        )
+        .await;
+        assert!(matches!(res, Err(Error::NwcTimeout)));
+    }
+}
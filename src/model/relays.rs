@@ -0,0 +1,1480 @@
+use crate::base::error::Error;
+
+use nostr::nips::nip05;
+use nostr::prelude::{Event, Filter, Keys, Kind, Metadata, Url, XOnlyPublicKey};
+use nostr_sdk::prelude::{Client, Options, RelayMessage, RelayPoolNotification, RelayStatus};
+
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use std::fmt;
+use std::fs;
+use std::path::Path;
+use std::str::FromStr;
+use std::time::Duration;
+use tokio::sync::broadcast;
+
+/// Interval for polling relay connection status
+const RELAY_STATUS_POLL_INTERVAL: Duration = Duration::from_millis(100);
+/// Event kind of relay list metadata (NIP-65)
+const RELAY_LIST_KIND: u64 = 10002;
+/// Default timeout of network operations
+pub(crate) const DEFAULT_NETWORK_TIMEOUT: Duration = Duration::from_secs(10);
+/// Number of recent notes fetched in watch mode
+pub(crate) const WATCH_NOTES_LIMIT: usize = 20;
+/// Number of recently used relays suggested
+pub(crate) const RECENT_RELAYS_LIMIT: usize = 5;
+
+/// Settings shared by the network operations (NIP-05 verification, relay check, fetching from
+/// and publishing to relays, wallet requests)
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub(crate) struct NetworkConfig {
+    /// Timeout of a network operation, can be raised for slow connections
+    pub timeout: Duration,
+    /// User agent of HTTP requests (NIP-05 verification, NIP-11 relay information)
+    pub user_agent: String,
+}
+
+impl Default for NetworkConfig {
+    fn default() -> Self {
+        Self {
+            timeout: DEFAULT_NETWORK_TIMEOUT,
+            user_agent: format!("keystr/{}", env!("CARGO_PKG_VERSION")),
+        }
+    }
+}
+
+impl NetworkConfig {
+    /// Default config with the given timeout
+    pub fn with_timeout(timeout: Duration) -> Self {
+        Self {
+            timeout,
+            ..Default::default()
+        }
+    }
+
+    /// HTTP client with the user agent; the timeout is applied by the callers
+    fn http_client(&self) -> Result<reqwest::Client, reqwest::Error> {
+        reqwest::Client::builder()
+            .user_agent(self.user_agent.as_str())
+            .build()
+    }
+}
+
+/// Outcome of publishing an event to a single relay
+#[derive(Clone, Debug)]
+pub(crate) struct PublishResult {
+    pub relay: String,
+    pub accepted: bool,
+    /// Reason given by the relay, e.g. for a rejection
+    pub message: Option<String>,
+}
+
+impl PublishResult {
+    /// Parse the OK response of a relay to a published event (NIP-20): `["OK", <event id>, <accepted>, <message>]`.
+    /// An empty message is treated as none; a rejection message may start with a prefix like `blocked:`.
+    pub(crate) fn from_ok_response(relay: &str, response: &str) -> Result<Self, Error> {
+        let v: serde_json::Value =
+            serde_json::from_str(response).map_err(|_e| Error::RelayInvalidOkResponse)?;
+        let arr = v.as_array().ok_or(Error::RelayInvalidOkResponse)?;
+        if arr.len() < 3 || arr[0].as_str() != Some("OK") || !arr[1].is_string() {
+            return Err(Error::RelayInvalidOkResponse);
+        }
+        let accepted = arr[2].as_bool().ok_or(Error::RelayInvalidOkResponse)?;
+        let message = arr
+            .get(3)
+            .and_then(|m| m.as_str())
+            .filter(|m| !m.is_empty())
+            .map(|m| m.to_string());
+        Ok(PublishResult {
+            relay: relay.to_string(),
+            accepted,
+            message,
+        })
+    }
+
+    fn from_result(relay: &str, res: Result<Self, Error>) -> Self {
+        match res {
+            Ok(result) => result,
+            Err(e) => PublishResult {
+                relay: relay.to_string(),
+                accepted: false,
+                message: Some(e.to_string()),
+            },
+        }
+    }
+}
+
+/// Outcome of publishing an event to a single relay, with optional read-back confirmation
+#[derive(Clone, Debug)]
+pub(crate) struct ConfirmedPublish {
+    pub publish: PublishResult,
+    /// If the relay returned the event when queried for its ID after publishing;
+    /// None if not checked (confirmation not requested, or publish failed)
+    pub confirmed: Option<bool>,
+}
+
+/// Short summary, for status messages
+impl fmt::Display for ConfirmedPublish {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        if self.publish.accepted {
+            write!(f, "accepted")?;
+        } else {
+            write!(f, "rejected")?;
+        }
+        match self.confirmed {
+            Some(true) => write!(f, ", stored")?,
+            Some(false) => write!(f, ", but not returned when queried")?,
+            None => {}
+        }
+        if let Some(message) = &self.publish.message {
+            write!(f, ", '{message}'")?;
+        }
+        Ok(())
+    }
+}
+
+/// Usage statistics of a relay
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub(crate) struct RelayStat {
+    pub url: String,
+    /// Number of successful publishes/fetches
+    pub success_count: u32,
+    /// Number of failed publishes/fetches
+    pub failure_count: u32,
+    /// Time of last use (unix seconds)
+    pub last_used: u64,
+}
+
+/// Usage statistics of relays, for suggesting recently used ones. Updated on each publish/fetch.
+#[derive(Clone, Debug, Default, Serialize, Deserialize)]
+pub(crate) struct RelayUsageStats {
+    stats: HashMap<String, RelayStat>,
+}
+
+impl RelayUsageStats {
+    /// Record a use of the relay, at the given time (unix seconds)
+    pub fn record(&mut self, url: &str, success: bool, time: u64) {
+        let stat = self
+            .stats
+            .entry(url.to_string())
+            .or_insert_with(|| RelayStat {
+                url: url.to_string(),
+                success_count: 0,
+                failure_count: 0,
+                last_used: 0,
+            });
+        if success {
+            stat.success_count += 1;
+        } else {
+            stat.failure_count += 1;
+        }
+        stat.last_used = stat.last_used.max(time);
+    }
+
+    /// Record the results of a publish
+    pub fn record_publish(&mut self, results: &[PublishResult], time: u64) {
+        for r in results {
+            self.record(&r.relay, r.accepted, time);
+        }
+    }
+
+    /// The most recently used relays, at most `limit`; by recency, then by success count
+    pub fn recent_relays(&self, limit: usize) -> Vec<RelayStat> {
+        let mut stats: Vec<RelayStat> = self.stats.values().cloned().collect();
+        stats.sort_by(|a, b| {
+            b.last_used
+                .cmp(&a.last_used)
+                .then(b.success_count.cmp(&a.success_count))
+                .then(a.url.cmp(&b.url))
+        });
+        stats.truncate(limit);
+        stats
+    }
+
+    /// Save to file, readable only by the owner (the relays used can identify the user)
+    pub fn save_to(&self, path: &Path) -> Result<(), Error> {
+        // create empty file
+        fs::write(path, "")?;
+        #[cfg(unix)]
+        {
+            use std::os::unix::fs::PermissionsExt;
+            fs::set_permissions(path, fs::Permissions::from_mode(0o600))?;
+        }
+        fs::write(path, serde_json::to_string(&self)?)?;
+        Ok(())
+    }
+
+    /// Load from file; a missing file gives empty statistics
+    pub fn load_from(path: &Path) -> Result<Self, Error> {
+        if !path.is_file() {
+            return Ok(Self::default());
+        }
+        Ok(serde_json::from_str::<Self>(&fs::read_to_string(path)?)?)
+    }
+}
+
+/// Relay reachability and information, as returned by `check_relay`
+#[derive(Clone, Debug)]
+pub(crate) struct RelayInfo {
+    /// Websocket connection could be opened
+    pub reachable: bool,
+    /// The NIP-11 relay information document (empty if not available)
+    pub information: RelayInformation,
+}
+
+/// Short summary, for status messages
+impl fmt::Display for RelayInfo {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        if self.reachable {
+            write!(f, "reachable")?;
+        } else {
+            write!(f, "not reachable")?;
+        }
+        if let Some(name) = &self.information.name {
+            write!(f, ", '{name}'")?;
+        }
+        let supported_nips = self
+            .information
+            .supported_nips
+            .as_deref()
+            .unwrap_or_default();
+        if !supported_nips.is_empty() {
+            let nips: Vec<String> = supported_nips.iter().map(|n| n.to_string()).collect();
+            write!(f, ", NIPs {}", nips.join(", "))?;
+        }
+        if let Some(max) = self.information.max_content_length() {
+            write!(f, ", max content length {max}")?;
+        }
+        Ok(())
+    }
+}
+
+/// A relay of a relay list, with its read/write markers
+#[derive(Clone, Debug, PartialEq)]
+pub(crate) struct RelayListEntry {
+    pub url: String,
+    pub read: bool,
+    pub write: bool,
+}
+
+/// The parts of a NIP-11 relay information document used here. Parsed here, as the document type
+/// of the nostr crate lacks the `limitation` object.
+#[derive(Clone, Debug, Default, Deserialize)]
+pub(crate) struct RelayInformation {
+    pub name: Option<String>,
+    pub supported_nips: Option<Vec<u16>>,
+    pub limitation: Option<RelayLimitation>,
+}
+
+impl RelayInformation {
+    /// Maximum length of event content, in characters (`limitation.max_content_length`);
+    /// no limit if not advertised
+    pub fn max_content_length(&self) -> Option<usize> {
+        self.limitation
+            .as_ref()
+            .and_then(|limitation| limitation.max_content_length)
+    }
+}
+
+/// Limitations of a relay, from its NIP-11 document
+#[derive(Clone, Debug, Default, Deserialize)]
+pub(crate) struct RelayLimitation {
+    pub max_content_length: Option<usize>,
+}
+
+/// Relay list of a user (NIP-65)
+#[derive(Clone, Debug, Default)]
+pub(crate) struct RelayList {
+    pub relays: Vec<RelayListEntry>,
+    /// Number of malformed `r` tags that were skipped
+    pub skipped: usize,
+}
+
+impl RelayList {
+    /// Merge relays from another source (e.g. a config file) into the list, without duplicates:
+    /// relays are matched by normalized URL (see `normalize_relay_url`), and the read/write markers
+    /// of a relay in both are combined, the more permissive wins. New relays are appended.
+    pub fn merge_relays(&mut self, incoming: &[RelayListEntry]) {
+        for entry in incoming {
+            let url = normalize_relay_url(&entry.url);
+            match self
+                .relays
+                .iter_mut()
+                .find(|r| normalize_relay_url(&r.url) == url)
+            {
+                Some(existing) => {
+                    existing.read |= entry.read;
+                    existing.write |= entry.write;
+                }
+                None => self.relays.push(entry.clone()),
+            }
+        }
+    }
+}
+
+/// Normalize a relay URL for comparison: lowercase scheme and host, no default port, no trailing slash
+pub(crate) fn normalize_relay_url(url: &str) -> String {
+    match Url::parse(url.trim()) {
+        Ok(parsed) => parsed.to_string().trim_end_matches('/').to_string(),
+        Err(_) => url.trim().to_lowercase(),
+    }
+}
+
+/// Relay URLs from a user input, separated by commas or whitespace. Not validated here.
+pub(crate) fn parse_relay_urls(input: &str) -> Vec<String> {
+    input
+        .split(|c: char| c == ',' || c.is_whitespace())
+        .filter(|url| !url.is_empty())
+        .map(|url| url.to_string())
+        .collect()
+}
+
+/// Parse the relays from a relay list event (NIP-65, kind 10002), from its `r` tags.
+/// A relay without marker is for both read and write. Malformed tags (invalid URL,
+/// unknown marker) are skipped, and counted in `skipped`.
+pub(crate) fn parse_relay_list(event: &Event) -> Result<RelayList, Error> {
+    if event.kind.as_u64() != RELAY_LIST_KIND {
+        return Err(Error::RelayListWrongKind(event.kind.as_u64()));
+    }
+    let mut list = RelayList::default();
+    for tag in &event.tags {
+        let v = tag.as_vec();
+        if v.first().map(|s| s.as_str()) != Some("r") {
+            continue;
+        }
+        let url = match v.get(1) {
+            Some(url) if Url::parse(url).is_ok() => url.clone(),
+            _ => {
+                list.skipped += 1;
+                continue;
+            }
+        };
+        let (read, write) = match v.get(2).map(|s| s.as_str()) {
+            None | Some("") => (true, true),
+            Some("read") => (true, false),
+            Some("write") => (false, true),
+            Some(_) => {
+                list.skipped += 1;
+                continue;
+            }
+        };
+        list.relays.push(RelayListEntry { url, read, write });
+    }
+    Ok(list)
+}
+
+/// Try to open a websocket connection to the relay, wait until it is connected, up to the timeout
+async fn relay_try_connect(relay: &str, config: &NetworkConfig) -> Result<bool, Error> {
+    let relay_client = Client::new(&Keys::generate());
+    relay_client.add_relay(relay, None).await?;
+    relay_client.connect().await;
+    let res = tokio::time::timeout(config.timeout, wait_until_connected(&relay_client)).await;
+    let _ = relay_client.disconnect().await;
+    Ok(res.is_ok())
+}
+
+async fn wait_until_connected(relay_client: &Client) {
+    loop {
+        let mut connected = false;
+        for (_url, r) in relay_client.relays().await {
+            if r.status().await == RelayStatus::Connected {
+                connected = true;
+            }
+        }
+        if connected {
+            return;
+        }
+        tokio::time::sleep(RELAY_STATUS_POLL_INTERVAL).await;
+    }
+}
+
+/// Check if a relay is reachable, without publishing anything.
+/// Opens a websocket connection, and also tries to obtain the NIP-11 relay information document.
+/// Timeout or handshake failure results in `reachable: false`, only an invalid URL is an error.
+pub(crate) async fn check_relay(url: &str, config: &NetworkConfig) -> Result<RelayInfo, Error> {
+    let relay_url = Url::parse(url).map_err(|_e| Error::RelayInvalidUrl)?;
+
+    let reachable = matches!(relay_try_connect(url, config).await, Ok(true));
+
+    let information = fetch_relay_information(relay_url, config)
+        .await
+        .unwrap_or_default();
+
+    Ok(RelayInfo {
+        reachable,
+        information,
+    })
+}
+
+/// Fetch the NIP-11 relay information document, over HTTP(S) from the relay URL, up to the timeout
+async fn fetch_relay_information(
+    mut relay_url: Url,
+    config: &NetworkConfig,
+) -> Result<RelayInformation, Error> {
+    let scheme = match relay_url.scheme() {
+        "wss" => "https",
+        "ws" => "http",
+        other => other,
+    }
+    .to_string();
+    // switching between these special schemes cannot fail
+    let _res = relay_url.set_scheme(&scheme);
+    let request = async {
+        config
+            .http_client()?
+            .get(relay_url)
+            .header("Accept", "application/nostr+json")
+            .send()
+            .await?
+            .json::<RelayInformation>()
+            .await
+    };
+    match tokio::time::timeout(config.timeout, request).await {
+        Err(_elapsed) => Err(Error::Timeout),
+        Ok(res) => res.map_err(|_e| Error::RelayInformationUnavailable),
+    }
+}
+
+/// Check event content against the limits of a relay, before publishing (so the user can be warned).
+/// No limit is enforced if the relay does not advertise one in its NIP-11 document.
+pub(crate) fn check_content_limits(
+    content: &str,
+    information: &RelayInformation,
+) -> Result<(), Error> {
+    match information.max_content_length() {
+        None => Ok(()),
+        Some(max) => {
+            let length = content.chars().count();
+            if length > max {
+                Err(Error::RelayContentTooLong { length, max })
+            } else {
+                Ok(())
+            }
+        }
+    }
+}
+
+/// Verify a NIP-05 identifier (`name@domain`) against the public key: the `names` in
+/// the `/.well-known/nostr.json` of the domain have to map the name to the public key.
+/// Not getting a response within the timeout is an `Error::Timeout`.
+pub(crate) async fn verify_nip05(
+    pubkey: XOnlyPublicKey,
+    nip05: &str,
+    config: &NetworkConfig,
+) -> Result<(), Error> {
+    let (name, domain) = nip05
+        .split_once('@')
+        .filter(|(_name, domain)| !domain.contains('@'))
+        .ok_or(nip05::Error::InvalidFormat)?;
+    let url = format!("https://{domain}/.well-known/nostr.json?name={name}");
+    let request = async {
+        config
+            .http_client()?
+            .get(url)
+            .send()
+            .await?
+            .json::<serde_json::Value>()
+            .await
+    };
+    let json = match tokio::time::timeout(config.timeout, request).await {
+        Err(_elapsed) => return Err(Error::Timeout),
+        Ok(res) => res.map_err(nip05::Error::from)?,
+    };
+    let verified = json
+        .get("names")
+        .and_then(|names| names.get(name))
+        .and_then(|value| value.as_str())
+        .and_then(|value| XOnlyPublicKey::from_str(value).ok())
+        == Some(pubkey);
+    if !verified {
+        return Err(nip05::Error::ImpossibleToVerify.into());
+    }
+    Ok(())
+}
+
+/// Fetch the events matching the filters from the relays, with a throwaway client.
+/// Returns the events received within the timeout (possibly duplicates, from several relays).
+async fn fetch_events(
+    filters: Vec<Filter>,
+    relays: &[String],
+    config: &NetworkConfig,
+) -> Result<Vec<Event>, Error> {
+    let relay_client = Client::new(&Keys::generate());
+    for relay in relays {
+        relay_client.add_relay(relay.as_str(), None).await?;
+    }
+    relay_client.connect().await;
+    let res = relay_client
+        .get_events_of(filters, Some(config.timeout))
+        .await;
+    let _ = relay_client.disconnect().await;
+    Ok(res?)
+}
+
+/// Fetch the latest profile metadata (kind 0 event) of a public key from the relays.
+/// Returns None if no metadata is found within the timeout.
+pub(crate) async fn fetch_metadata(
+    pubkey: XOnlyPublicKey,
+    relays: &[String],
+    config: &NetworkConfig,
+) -> Result<Option<Metadata>, Error> {
+    let filter = Filter::new()
+        .author(pubkey.to_string())
+        .kind(Kind::Metadata)
+        .limit(1);
+    let events = fetch_events(vec![filter], relays, config).await?;
+    let latest = events.into_iter().max_by_key(|e| e.created_at);
+    match latest {
+        None => Ok(None),
+        Some(event) => Ok(Some(serde_json::from_str::<Metadata>(&event.content)?)),
+    }
+}
+
+/// Fetch the latest relay list (NIP-65, kind 10002) of a public key from the relays.
+/// Returns None if no relay list is found within the timeout.
+pub(crate) async fn fetch_relay_list(
+    pubkey: XOnlyPublicKey,
+    relays: &[String],
+    config: &NetworkConfig,
+) -> Result<Option<RelayList>, Error> {
+    let filter = Filter::new()
+        .author(pubkey.to_string())
+        .kind(Kind::from(RELAY_LIST_KIND))
+        .limit(1);
+    let events = fetch_events(vec![filter], relays, config).await?;
+    let latest = events.into_iter().max_by_key(|e| e.created_at);
+    latest.map(|event| parse_relay_list(&event)).transpose()
+}
+
+/// Fetch the most recent text notes (kind 1) of a public key from the relays, newest first, at most `limit`.
+/// Returns the notes received within the timeout.
+pub(crate) async fn fetch_notes(
+    pubkey: XOnlyPublicKey,
+    relays: &[String],
+    limit: usize,
+    config: &NetworkConfig,
+) -> Result<Vec<Event>, Error> {
+    let filter = Filter::new()
+        .author(pubkey.to_string())
+        .kind(Kind::TextNote)
+        .limit(limit);
+    let mut notes = fetch_events(vec![filter], relays, config).await?;
+    // same note may come from several relays
+    notes.sort_by(|a, b| b.created_at.cmp(&a.created_at).then(a.id.cmp(&b.id)));
+    notes.dedup_by(|a, b| a.id == b.id);
+    notes.truncate(limit);
+    Ok(notes)
+}
+
+/// Check if a public key has published any events on the relays, of the given kinds (of any kind if empty).
+/// Only events received within the timeout count.
+pub(crate) async fn has_published_events(
+    pubkey: XOnlyPublicKey,
+    kinds: &[Kind],
+    relays: &[String],
+    config: &NetworkConfig,
+) -> Result<bool, Error> {
+    let mut filter = Filter::new().author(pubkey.to_string()).limit(1);
+    if !kinds.is_empty() {
+        filter = filter.kinds(kinds.to_vec());
+    }
+    let events = fetch_events(vec![filter], relays, config).await?;
+    Ok(!events.is_empty())
+}
+
+/// Send the event to the relay, and wait for its OK response (NIP-20)
+async fn publish_event_to(keys: &Keys, event: &Event, relay: &str) -> Result<PublishResult, Error> {
+    let opts = Options::new().wait_for_send(true);
+    let relay_client = Client::with_opts(keys, opts);
+    relay_client.add_relay(relay, None).await?;
+    relay_client.connect().await;
+    // subscribe before sending, not to miss the response
+    let mut notifications = relay_client.notifications();
+    let res = match relay_client.send_event(event.clone()).await {
+        Err(e) => Err(e.into()),
+        Ok(_event_id) => wait_for_ok_response(&mut notifications, event, relay).await,
+    };
+    let _ = relay_client.disconnect().await;
+    res
+}
+
+async fn wait_for_ok_response(
+    notifications: &mut broadcast::Receiver<RelayPoolNotification>,
+    event: &Event,
+    relay: &str,
+) -> Result<PublishResult, Error> {
+    loop {
+        match notifications.recv().await {
+            Ok(RelayPoolNotification::Message(_url, msg)) => {
+                if let RelayMessage::Ok { event_id, .. } = &msg {
+                    if *event_id == event.id {
+                        return PublishResult::from_ok_response(relay, &msg.as_json());
+                    }
+                }
+            }
+            Ok(RelayPoolNotification::Shutdown) | Err(broadcast::error::RecvError::Closed) => {
+                return Err(Error::RelayNoOkResponse)
+            }
+            Ok(RelayPoolNotification::Event(..)) | Err(broadcast::error::RecvError::Lagged(_)) => {}
+        }
+    }
+}
+
+/// Publish an event to each of the relays, separately, each up to the timeout.
+/// A failure on one relay does not abort publishing to the others.
+pub(crate) async fn publish_event(
+    keys: &Keys,
+    event: &Event,
+    relays: &[String],
+    config: &NetworkConfig,
+) -> Vec<PublishResult> {
+    let mut results = Vec::new();
+    for relay in relays {
+        let res = match tokio::time::timeout(config.timeout, publish_event_to(keys, event, relay))
+            .await
+        {
+            Err(_elapsed) => Err(Error::Timeout),
+            Ok(res) => res,
+        };
+        results.push(PublishResult::from_result(relay, res));
+    }
+    results
+}
+
+/// Check if the relay returns the event with the given ID, within the timeout
+async fn relay_has_event(relay: &str, event: &Event, timeout: Duration) -> Result<bool, Error> {
+    let relay_client = Client::new(&Keys::generate());
+    relay_client.add_relay(relay, None).await?;
+    relay_client.connect().await;
+    let filter = Filter::new().id(event.id.to_hex()).limit(1);
+    let res = relay_client
+        .get_events_of(vec![filter], Some(timeout))
+        .await;
+    let _ = relay_client.disconnect().await;
+    Ok(res?.iter().any(|e| e.id == event.id))
+}
+
+/// Publish an event to each of the relays, separately, and if `confirm_timeout` is given, confirm
+/// that each accepting relay actually stored the event, by querying it for the event ID.
+/// This catches relays that acknowledge, but silently drop the event.
+pub(crate) async fn publish_and_confirm(
+    keys: &Keys,
+    event: &Event,
+    relays: &[String],
+    confirm_timeout: Option<Duration>,
+    config: &NetworkConfig,
+) -> Vec<ConfirmedPublish> {
+    let mut results = Vec::new();
+    for publish in publish_event(keys, event, relays, config).await {
+        let confirmed = match confirm_timeout {
+            Some(timeout) if publish.accepted => Some(
+                relay_has_event(&publish.relay, event, timeout)
+                    .await
+                    .unwrap_or(false),
+            ),
+            _ => None,
+        };
+        results.push(ConfirmedPublish { publish, confirmed });
+    }
+    results
+}
+
+/// Minimal in-process mock relay for tests: a websocket server which answers subscriptions
+/// from its events (matching on ids, authors and kinds), followed by EOSE, and stores published
+/// events, answering with OK.
+#[cfg(test)]
+pub(crate) mod mock_relay {
+    use base64::engine::general_purpose::STANDARD;
+    use base64::Engine;
+    use nostr::hashes::sha1::Hash as Sha1Hash;
+    use nostr::prelude::Event;
+    use serde_json::{json, Value};
+    use std::io::{Read, Write};
+    use std::net::{TcpListener, TcpStream};
+    use std::sync::{Arc, Mutex};
+    use std::time::Duration;
+
+    /// GUID for the websocket handshake (RFC 6455)
+    const WEBSOCKET_GUID: &str = "258EAFA5-E914-47DA-95CA-C5AB0DC85B11";
+
+    /// Behavior of the mock relay
+    #[derive(Clone, Default)]
+    pub(crate) struct MockRelayConfig {
+        /// Reject published events with this message
+        pub reject: Option<String>,
+        /// Acknowledge published events, but don't store them
+        pub drop_accepted: bool,
+        /// NIP-11 document, served on plain HTTP requests (without websocket upgrade)
+        pub nip11_document: Option<String>,
+        /// Delay before answering the websocket handshake, to simulate a slow relay
+        pub handshake_delay: Duration,
+    }
+
+    /// Start the mock relay serving the events, returns its URL
+    pub(crate) fn start_mock_relay(events: Vec<Event>) -> String {
+        start_mock_relay_with(events, MockRelayConfig::default())
+    }
+
+    /// Start the mock relay serving the events, with the given behavior, returns its URL
+    pub(crate) fn start_mock_relay_with(events: Vec<Event>, config: MockRelayConfig) -> String {
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let port = listener.local_addr().unwrap().port();
+        let events = Arc::new(Mutex::new(events));
+        std::thread::spawn(move || {
+            for stream in listener.incoming().flatten() {
+                let events = events.clone();
+                let config = config.clone();
+                std::thread::spawn(move || {
+                    let _res = serve_connection(stream, &events, &config);
+                });
+            }
+        });
+        format!("ws://127.0.0.1:{port}")
+    }
+
+    fn serve_connection(
+        mut stream: TcpStream,
+        events: &Mutex<Vec<Event>>,
+        config: &MockRelayConfig,
+    ) -> std::io::Result<()> {
+        let mut request = Vec::new();
+        let mut byte = [0u8; 1];
+        while !request.ends_with(b"\r\n\r\n") {
+            stream.read_exact(&mut byte)?;
+            request.push(byte[0]);
+        }
+        let request = String::from_utf8_lossy(&request);
+        let header = |header: &str| {
+            request.lines().find_map(|l| {
+                let (name, value) = l.split_once(':')?;
+                name.eq_ignore_ascii_case(header)
+                    .then(|| value.trim().to_string())
+            })
+        };
+        if header("upgrade").is_none() {
+            return serve_nip11(stream, config);
+        }
+        std::thread::sleep(config.handshake_delay);
+        let key = header("sec-websocket-key").unwrap_or_default();
+        let accept =
+            <Sha1Hash as nostr::hashes::Hash>::hash(format!("{key}{WEBSOCKET_GUID}").as_bytes());
+        write!(
+            stream,
+            "HTTP/1.1 101 Switching Protocols\r\nUpgrade: websocket\r\nConnection: Upgrade\r\nSec-WebSocket-Accept: {}\r\n\r\n",
+            STANDARD.encode(&accept[..])
+        )?;
+
+        loop {
+            let (opcode, payload) = read_frame(&mut stream)?;
+            match opcode {
+                // text
+                0x1 => {
+                    let message = String::from_utf8_lossy(&payload);
+                    for message in respond(&message, events, config) {
+                        write_frame(&mut stream, 0x1, message.as_bytes())?;
+                    }
+                }
+                // close
+                0x8 => {
+                    write_frame(&mut stream, 0x8, &[])?;
+                    return Ok(());
+                }
+                // ping
+                0x9 => write_frame(&mut stream, 0xA, &payload)?,
+                _ => {}
+            }
+        }
+    }
+
+    fn serve_nip11(mut stream: TcpStream, config: &MockRelayConfig) -> std::io::Result<()> {
+        match &config.nip11_document {
+            None => write!(
+                stream,
+                "HTTP/1.1 404 Not Found\r\nContent-Length: 0\r\nConnection: close\r\n\r\n"
+            ),
+            Some(document) => write!(
+                stream,
+                "HTTP/1.1 200 OK\r\nContent-Type: application/nostr+json\r\nContent-Length: {}\r\nConnection: close\r\n\r\n{}",
+                document.len(),
+                document
+            ),
+        }
+    }
+
+    /// Responses to a client message: stored events and EOSE for a REQ, OK for an EVENT,
+    /// nothing for others
+    fn respond(message: &str, events: &Mutex<Vec<Event>>, config: &MockRelayConfig) -> Vec<String> {
+        let message: Vec<Value> = serde_json::from_str(message).unwrap_or_default();
+        if message.len() < 2 {
+            return Vec::new();
+        }
+        match message[0].as_str() {
+            Some("REQ") => respond_req(&message, &events.lock().unwrap()),
+            Some("EVENT") => match serde_json::from_value::<Event>(message[1].clone()) {
+                Err(_e) => Vec::new(),
+                Ok(event) => {
+                    let id = event.id.to_hex();
+                    match &config.reject {
+                        Some(reason) => vec![json!(["OK", id, false, reason]).to_string()],
+                        None => {
+                            if !config.drop_accepted {
+                                events.lock().unwrap().push(event);
+                            }
+                            vec![json!(["OK", id, true, ""]).to_string()]
+                        }
+                    }
+                }
+            },
+            _ => Vec::new(),
+        }
+    }
+
+    fn respond_req(message: &[Value], events: &[Event]) -> Vec<String> {
+        let subscription_id = &message[1];
+        let mut responses: Vec<String> = events
+            .iter()
+            .filter(|event| {
+                message[2..]
+                    .iter()
+                    .any(|filter| matches_filter(event, filter))
+            })
+            .map(|event| json!(["EVENT", subscription_id, event]).to_string())
+            .collect();
+        responses.push(json!(["EOSE", subscription_id]).to_string());
+        responses
+    }
+
+    fn matches_filter(event: &Event, filter: &Value) -> bool {
+        let id = event.id.to_hex();
+        let id_matches = match filter.get("ids").and_then(|i| i.as_array()) {
+            None => true,
+            Some(ids) => ids
+                .iter()
+                .filter_map(|i| i.as_str())
+                .any(|i| id.starts_with(i)),
+        };
+        let pubkey = event.pubkey.to_string();
+        let author_matches = match filter.get("authors").and_then(|a| a.as_array()) {
+            None => true,
+            Some(authors) => authors
+                .iter()
+                .filter_map(|a| a.as_str())
+                .any(|a| pubkey.starts_with(a)),
+        };
+        let kind_matches = match filter.get("kinds").and_then(|k| k.as_array()) {
+            None => true,
+            Some(kinds) => kinds
+                .iter()
+                .any(|k| k.as_u64() == Some(event.kind.as_u64())),
+        };
+        id_matches && author_matches && kind_matches
+    }
+
+    fn read_frame(stream: &mut TcpStream) -> std::io::Result<(u8, Vec<u8>)> {
+        let mut header = [0u8; 2];
+        stream.read_exact(&mut header)?;
+        let opcode = header[0] & 0x0f;
+        let masked = header[1] & 0x80 != 0;
+        let len = match header[1] & 0x7f {
+            126 => {
+                let mut len = [0u8; 2];
+                stream.read_exact(&mut len)?;
+                u16::from_be_bytes(len) as usize
+            }
+            127 => {
+                let mut len = [0u8; 8];
+                stream.read_exact(&mut len)?;
+                u64::from_be_bytes(len) as usize
+            }
+            len => len as usize,
+        };
+        let mut mask = [0u8; 4];
+        if masked {
+            stream.read_exact(&mut mask)?;
+        }
+        let mut payload = vec![0u8; len];
+        stream.read_exact(&mut payload)?;
+        if masked {
+            for (i, b) in payload.iter_mut().enumerate() {
+                *b ^= mask[i % 4];
+            }
+        }
+        Ok((opcode, payload))
+    }
+
+    fn write_frame(stream: &mut TcpStream, opcode: u8, payload: &[u8]) -> std::io::Result<()> {
+        let mut frame = vec![0x80 | opcode];
+        match payload.len() {
+            len if len < 126 => frame.push(len as u8),
+            len if len <= u16::MAX as usize => {
+                frame.push(126);
+                frame.extend_from_slice(&(len as u16).to_be_bytes());
+            }
+            len => {
+                frame.push(127);
+                frame.extend_from_slice(&(len as u64).to_be_bytes());
+            }
+        }
+        frame.extend_from_slice(payload);
+        stream.write_all(&frame)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::mock_relay::{start_mock_relay, start_mock_relay_with, MockRelayConfig};
+    use super::*;
+    use nostr::prelude::{EventBuilder, Tag};
+    use std::io::{Read, Write};
+    use std::net::TcpListener;
+
+    #[tokio::test]
+    async fn test_check_relay_mock_nip11() {
+        let url = start_mock_relay_with(
+            Vec::new(),
+            MockRelayConfig {
+                nip11_document: Some("{\"name\":\"Mock Relay\",\"supported_nips\":[1,11,42],\"software\":\"mock\",\"limitation\":{\"max_content_length\":8196,\"auth_required\":false}}".to_string()),
+                ..Default::default()
+            },
+        );
+        let info = check_relay(&url, &NetworkConfig::with_timeout(Duration::from_secs(3)))
+            .await
+            .unwrap();
+        assert_eq!(info.reachable, true);
+        assert_eq!(info.information.name, Some("Mock Relay".to_string()));
+        assert_eq!(info.information.supported_nips, Some(vec![1, 11, 42]));
+        assert_eq!(info.information.max_content_length(), Some(8196));
+        assert!(check_content_limits(&"x".repeat(8196), &info.information).is_ok());
+        assert!(check_content_limits(&"x".repeat(8197), &info.information).is_err());
+
+        // no limitation advertised
+        let url = start_mock_relay_with(
+            Vec::new(),
+            MockRelayConfig {
+                nip11_document: Some("{\"name\":\"Mock Relay\"}".to_string()),
+                ..Default::default()
+            },
+        );
+        let info = check_relay(&url, &NetworkConfig::with_timeout(Duration::from_secs(3)))
+            .await
+            .unwrap();
+        assert_eq!(info.information.name, Some("Mock Relay".to_string()));
+        assert_eq!(info.information.max_content_length(), None);
+    }
+
+    #[tokio::test]
+    async fn test_check_relay_reachable() {
+        let url = start_mock_relay_with(
+            Vec::new(),
+            MockRelayConfig {
+                nip11_document: Some(
+                    "{\"name\":\"Mock Relay\",\"supported_nips\":[1,11,20]}".to_string(),
+                ),
+                handshake_delay: Duration::from_millis(200),
+                ..Default::default()
+            },
+        );
+        let info = check_relay(&url, &NetworkConfig::with_timeout(Duration::from_secs(3)))
+            .await
+            .unwrap();
+        assert_eq!(info.reachable, true);
+        assert_eq!(info.information.name, Some("Mock Relay".to_string()));
+        assert_eq!(info.information.supported_nips, Some(vec![1, 11, 20]));
+
+        // handshake slower than the timeout
+        let url = start_mock_relay_with(
+            Vec::new(),
+            MockRelayConfig {
+                handshake_delay: Duration::from_secs(2),
+                ..Default::default()
+            },
+        );
+        let info = check_relay(
+            &url,
+            &NetworkConfig::with_timeout(Duration::from_millis(500)),
+        )
+        .await
+        .unwrap();
+        assert_eq!(info.reachable, false);
+        assert!(info.information.name.is_none());
+    }
+
+    #[tokio::test]
+    async fn test_fetch_relay_information_user_agent() {
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let port = listener.local_addr().unwrap().port();
+        let (sender, receiver) = std::sync::mpsc::channel();
+        std::thread::spawn(move || {
+            let (mut stream, _addr) = listener.accept().unwrap();
+            let mut buf = [0u8; 4096];
+            let len = stream.read(&mut buf).unwrap();
+            let _ = sender.send(String::from_utf8_lossy(&buf[..len]).to_string());
+            let _ = stream
+                .write_all(b"HTTP/1.1 200 OK\r\nContent-Length: 2\r\nConnection: close\r\n\r\n{}");
+        });
+        let config = NetworkConfig {
+            timeout: Duration::from_secs(3),
+            user_agent: "keystr-test/1.0".to_string(),
+        };
+        let url = Url::parse(&format!("ws://127.0.0.1:{port}")).unwrap();
+        let info = fetch_relay_information(url, &config).await.unwrap();
+        assert!(info.information.name.is_none());
+        let request = receiver.recv().unwrap().to_lowercase();
+        assert!(request.contains("user-agent: keystr-test/1.0"));
+    }
+
+    #[tokio::test]
+    async fn test_check_relay_unreachable() {
+        // bind and drop, to obtain a port with no listener
+        let port = TcpListener::bind("127.0.0.1:0")
+            .unwrap()
+            .local_addr()
+            .unwrap()
+            .port();
+        let info = check_relay(
+            &format!("ws://127.0.0.1:{port}"),
+            &NetworkConfig::with_timeout(Duration::from_millis(500)),
+        )
+        .await
+        .unwrap();
+        assert_eq!(info.reachable, false);
+        assert!(info.information.supported_nips.is_none());
+        assert!(info.information.name.is_none());
+    }
+
+    #[test]
+    fn test_publish_result_from_ok_response() {
+        let relay = "wss://relay.example.com";
+        let id = "b1a649ebe8b435ec71d3784793f3bbf4b93e64e17568a741aecd4c7ddeafce30";
+
+        let res = PublishResult::from_ok_response(relay, &format!("[\"OK\",\"{id}\",true,\"\"]"))
+            .unwrap();
+        assert_eq!(res.relay, relay);
+        assert!(res.accepted);
+        assert_eq!(res.message, None);
+
+        let res = PublishResult::from_ok_response(
+            relay,
+            &format!("[\"OK\",\"{id}\",false,\"blocked: you are banned from posting here\"]"),
+        )
+        .unwrap();
+        assert!(!res.accepted);
+        assert_eq!(
+            res.message,
+            Some("blocked: you are banned from posting here".to_string())
+        );
+
+        // message is optional
+        let res =
+            PublishResult::from_ok_response(relay, &format!("[\"OK\",\"{id}\",true]")).unwrap();
+        assert!(res.accepted);
+
+        for invalid in [
+            "",
+            "{}",
+            "[\"NOTICE\",\"hello\"]",
+            "[\"OK\",\"id\"]",
+            "[\"OK\",\"id\",\"true\",\"\"]",
+            "[\"OK\",1,true,\"\"]",
+        ] {
+            assert!(matches!(
+                PublishResult::from_ok_response(relay, invalid),
+                Err(Error::RelayInvalidOkResponse)
+            ));
+        }
+    }
+
+    #[test]
+    fn test_check_content_limits() {
+        let mut info = RelayInformation {
+            name: Some("Small Relay".to_string()),
+            supported_nips: Some(vec![1, 11]),
+            limitation: None,
+        };
+        let long_content = "x".repeat(100_000);
+        assert!(check_content_limits(&long_content, &info).is_ok());
+        info.limitation = Some(RelayLimitation {
+            max_content_length: None,
+        });
+        assert!(check_content_limits(&long_content, &info).is_ok());
+
+        info.limitation = Some(RelayLimitation {
+            max_content_length: Some(10),
+        });
+        assert!(check_content_limits("Hello", &info).is_ok());
+        assert!(check_content_limits("0123456789", &info).is_ok());
+        // characters are counted, not bytes (10 characters, 12 bytes)
+        assert!(check_content_limits("héllo wörl", &info).is_ok());
+        assert!(matches!(
+            check_content_limits("Hello, nostr!", &info),
+            Err(Error::RelayContentTooLong {
+                length: 13,
+                max: 10
+            })
+        ));
+    }
+
+    #[test]
+    fn test_relay_info_display() {
+        let mut info = RelayInfo {
+            reachable: false,
+            information: RelayInformation::default(),
+        };
+        assert_eq!(info.to_string(), "not reachable");
+        info.reachable = true;
+        info.information = RelayInformation {
+            name: Some("Small Relay".to_string()),
+            supported_nips: Some(vec![1, 11]),
+            limitation: Some(RelayLimitation {
+                max_content_length: Some(8196),
+            }),
+        };
+        assert_eq!(
+            info.to_string(),
+            "reachable, 'Small Relay', NIPs 1, 11, max content length 8196"
+        );
+    }
+
+    #[test]
+    fn test_parse_relay_urls() {
+        assert_eq!(
+            parse_relay_urls(" wss://relay.damus.io, wss://nos.lol\nwss://relay.example.com,,"),
+            vec![
+                "wss://relay.damus.io".to_string(),
+                "wss://nos.lol".to_string(),
+                "wss://relay.example.com".to_string(),
+            ]
+        );
+        assert!(parse_relay_urls(" , ").is_empty());
+    }
+
+    #[tokio::test]
+    async fn test_check_relay_invalid_url() {
+        assert!(check_relay(
+            "__NOT_A_RELAY__",
+            &NetworkConfig::with_timeout(Duration::from_millis(500))
+        )
+        .await
+        .is_err());
+    }
+
+    #[tokio::test]
+    async fn test_verify_nip05_timeout() {
+        let pubkey = Keys::generate().public_key();
+        // no response can arrive in no time
+        let config = NetworkConfig::with_timeout(Duration::ZERO);
+        assert!(matches!(
+            verify_nip05(pubkey, "_@example.com", &config).await,
+            Err(Error::Timeout)
+        ));
+    }
+
+    #[test]
+    fn test_network_config_default() {
+        let config = NetworkConfig::default();
+        assert_eq!(config.timeout, DEFAULT_NETWORK_TIMEOUT);
+        assert!(config.user_agent.starts_with("keystr/"));
+        let config = NetworkConfig::with_timeout(Duration::from_secs(60));
+        assert_eq!(config.timeout, Duration::from_secs(60));
+        assert_eq!(config.user_agent, NetworkConfig::default().user_agent);
+    }
+
+    #[test]
+    fn test_parse_relay_list() {
+        let tags: Vec<Tag> = [
+            r#"["r","wss://relay.damus.io"]"#,
+            r#"["r","wss://nos.lol","read"]"#,
+            r#"["r","wss://relay.snort.social","write"]"#,
+            r#"["r","__NOT_A_RELAY__"]"#,
+            r#"["r","wss://relay.example.com","sometimes"]"#,
+            r#"["r"]"#,
+            r#"["p","1a459a8a6aa6441d480ba665fb8fb21a4cfe8bcacb7d87300f8046a558a3fce4"]"#,
+        ]
+        .iter()
+        .map(|t| serde_json::from_str::<Tag>(t).unwrap())
+        .collect();
+        let event = EventBuilder::new(Kind::from(10002), "", &tags)
+            .to_event(&Keys::generate())
+            .unwrap();
+
+        let list = parse_relay_list(&event).unwrap();
+        assert_eq!(
+            list.relays,
+            vec![
+                RelayListEntry {
+                    url: "wss://relay.damus.io".to_string(),
+                    read: true,
+                    write: true
+                },
+                RelayListEntry {
+                    url: "wss://nos.lol".to_string(),
+                    read: true,
+                    write: false
+                },
+                RelayListEntry {
+                    url: "wss://relay.snort.social".to_string(),
+                    read: false,
+                    write: true
+                },
+            ]
+        );
+        assert_eq!(list.skipped, 3);
+    }
+
+    #[tokio::test]
+    async fn test_fetch_relay_list_mock_relay() {
+        let keys = Keys::generate();
+        let tag = serde_json::from_str::<Tag>(r#"["r","wss://nos.lol","read"]"#).unwrap();
+        let event = EventBuilder::new(Kind::from(10002), "", &[tag])
+            .to_event(&keys)
+            .unwrap();
+        let relays = vec![start_mock_relay(vec![event])];
+        let config = NetworkConfig::with_timeout(Duration::from_secs(5));
+
+        let list = fetch_relay_list(keys.public_key(), &relays, &config)
+            .await
+            .unwrap()
+            .unwrap();
+        assert_eq!(list.relays.len(), 1);
+        assert_eq!(list.relays[0].url, "wss://nos.lol");
+        assert_eq!(list.relays[0].write, false);
+
+        let other = Keys::generate().public_key();
+        assert!(fetch_relay_list(other, &relays, &config)
+            .await
+            .unwrap()
+            .is_none());
+    }
+
+    #[test]
+    fn test_merge_relays() {
+        let entry = |url: &str, read, write| RelayListEntry {
+            url: url.to_string(),
+            read,
+            write,
+        };
+        let mut list = RelayList {
+            relays: vec![
+                entry("wss://relay.damus.io", true, true),
+                entry("wss://nos.lol", true, false),
+                entry("wss://relay.snort.social", false, true),
+            ],
+            skipped: 0,
+        };
+        list.merge_relays(&[
+            // same relays, written differently
+            entry("WSS://Relay.Damus.io/", false, true),
+            entry("wss://nos.lol:443", false, true),
+            entry("wss://relay.snort.social/", false, true),
+            // new ones, one of them twice
+            entry("wss://relay.example.com", true, false),
+            entry("wss://relay.example.com/", false, true),
+        ]);
+        assert_eq!(
+            list.relays,
+            vec![
+                entry("wss://relay.damus.io", true, true),
+                entry("wss://nos.lol", true, true),
+                entry("wss://relay.snort.social", false, true),
+                entry("wss://relay.example.com", true, true),
+            ]
+        );
+
+        // merging into an empty list, or merging nothing
+        let mut empty = RelayList::default();
+        empty.merge_relays(&list.relays);
+        assert_eq!(empty.relays, list.relays);
+        empty.merge_relays(&[]);
+        assert_eq!(empty.relays.len(), 4);
+    }
+
+    #[test]
+    fn test_normalize_relay_url() {
+        assert_eq!(
+            normalize_relay_url(" WSS://Relay.Damus.io:443/ "),
+            "wss://relay.damus.io"
+        );
+        assert_eq!(
+            normalize_relay_url("wss://relay.example.com:4848/path/"),
+            "wss://relay.example.com:4848/path"
+        );
+        assert_eq!(normalize_relay_url("__NOT_A_RELAY__"), "__not_a_relay__");
+    }
+
+    #[test]
+    fn test_parse_relay_list_wrong_kind() {
+        let event = EventBuilder::new_text_note("Hello", &[])
+            .to_event(&Keys::generate())
+            .unwrap();
+        assert!(matches!(
+            parse_relay_list(&event),
+            Err(Error::RelayListWrongKind(1))
+        ));
+    }
+
+    #[tokio::test]
+    async fn test_publish_event_invalid_relays() {
+        let keys = Keys::generate();
+        let event = EventBuilder::new_text_note("Hello", &[])
+            .to_event(&keys)
+            .unwrap();
+        let relays = vec!["__NOT_A_RELAY__".to_string(), "wss://".to_string()];
+
+        let results = publish_event(&keys, &event, &relays, &NetworkConfig::default()).await;
+        // failure on the first relay does not abort the second
+        assert_eq!(results.len(), 2);
+        assert_eq!(results[0].relay, "__NOT_A_RELAY__");
+        assert_eq!(results[0].accepted, false);
+        assert!(results[0].message.is_some());
+        assert_eq!(results[1].relay, "wss://");
+        assert_eq!(results[1].accepted, false);
+    }
+
+    #[tokio::test]
+    async fn test_publish_event_mock_relay() {
+        let keys = Keys::generate();
+        let event = EventBuilder::new_text_note("Hello", &[])
+            .to_event(&keys)
+            .unwrap();
+        let accepting = start_mock_relay(Vec::new());
+        let rejecting = start_mock_relay_with(
+            Vec::new(),
+            MockRelayConfig {
+                reject: Some("blocked: not on the allowlist".to_string()),
+                ..Default::default()
+            },
+        );
+        let relays = vec![accepting.clone(), rejecting.clone()];
+
+        let config = NetworkConfig::with_timeout(Duration::from_secs(5));
+        let results = publish_event(&keys, &event, &relays, &config).await;
+        assert_eq!(results.len(), 2);
+        assert_eq!(results[0].relay, accepting);
+        assert_eq!(results[0].accepted, true);
+        assert_eq!(results[0].message, None);
+        assert_eq!(results[1].relay, rejecting);
+        assert_eq!(results[1].accepted, false);
+        assert_eq!(
+            results[1].message,
+            Some("blocked: not on the allowlist".to_string())
+        );
+    }
+
+    #[tokio::test]
+    async fn test_publish_and_confirm_mock_relay() {
+        let keys = Keys::generate();
+        let event = EventBuilder::new_text_note("Hello", &[])
+            .to_event(&keys)
+            .unwrap();
+        let other = EventBuilder::new_text_note("Other", &[])
+            .to_event(&keys)
+            .unwrap();
+        let storing = start_mock_relay(vec![other]);
+        let dropping = start_mock_relay_with(
+            Vec::new(),
+            MockRelayConfig {
+                drop_accepted: true,
+                ..Default::default()
+            },
+        );
+        let relays = vec![storing, dropping];
+
+        let results = publish_and_confirm(
+            &keys,
+            &event,
+            &relays,
+            Some(Duration::from_secs(2)),
+            &NetworkConfig::with_timeout(Duration::from_secs(5)),
+        )
+        .await;
+        assert_eq!(results.len(), 2);
+        // stored, and found by its ID
+        assert_eq!(results[0].publish.accepted, true);
+        assert_eq!(results[0].confirmed, Some(true));
+        // acknowledged, but silently dropped
+        assert_eq!(results[1].publish.accepted, true);
+        assert_eq!(results[1].confirmed, Some(false));
+    }
+
+    #[tokio::test]
+    async fn test_publish_and_confirm_invalid_relays() {
+        let keys = Keys::generate();
+        let event = EventBuilder::new_text_note("Hello", &[])
+            .to_event(&keys)
+            .unwrap();
+        let relays = vec!["__NOT_A_RELAY__".to_string()];
+
+        // failed publish is not confirmed, also without confirmation requested
+        let results = publish_and_confirm(
+            &keys,
+            &event,
+            &relays,
+            Some(Duration::from_secs(1)),
+            &NetworkConfig::default(),
+        )
+        .await;
+        assert_eq!(results.len(), 1);
+        assert_eq!(results[0].publish.accepted, false);
+        assert_eq!(results[0].confirmed, None);
+        let results =
+            publish_and_confirm(&keys, &event, &relays, None, &NetworkConfig::default()).await;
+        assert_eq!(results[0].confirmed, None);
+    }
+
+    #[test]
+    fn test_relay_usage_stats() {
+        let mut stats = RelayUsageStats::default();
+        assert!(stats.recent_relays(10).is_empty());
+        let publish = |relays: &[(&str, bool)]| -> Vec<PublishResult> {
+            relays
+                .iter()
+                .map(|(relay, accepted)| PublishResult {
+                    relay: relay.to_string(),
+                    accepted: *accepted,
+                    message: None,
+                })
+                .collect()
+        };
+        stats.record_publish(&publish(&[("wss://a", true), ("wss://b", true)]), 100);
+        stats.record_publish(&publish(&[("wss://b", true), ("wss://c", false)]), 200);
+        stats.record("wss://d", true, 150);
+        stats.record_publish(&publish(&[("wss://a", true)]), 200);
+
+        let recent = stats.recent_relays(10);
+        let urls: Vec<&str> = recent.iter().map(|s| s.url.as_str()).collect();
+        // by recency, then success count
+        assert_eq!(urls, vec!["wss://a", "wss://b", "wss://c", "wss://d"]);
+        assert_eq!(recent[0].success_count, 2);
+        assert_eq!(recent[0].last_used, 200);
+        assert_eq!(recent[2].success_count, 0);
+        assert_eq!(recent[2].failure_count, 1);
+        assert_eq!(stats.recent_relays(2).len(), 2);
+
+        // an older record does not move back the last use
+        stats.record("wss://a", true, 50);
+        assert_eq!(stats.recent_relays(1)[0].last_used, 200);
+
+        // persistence
+        let mut path = std::env::temp_dir();
+        path.push(format!(
+            "keystr-test-relay-stats-{}.json",
+            Keys::generate().public_key()
+        ));
+        assert!(RelayUsageStats::load_from(&path)
+            .unwrap()
+            .recent_relays(10)
+            .is_empty());
+        let _res = stats.save_to(&path).unwrap();
+        #[cfg(unix)]
+        {
+            use std::os::unix::fs::PermissionsExt;
+            assert_eq!(
+                fs::metadata(&path).unwrap().permissions().mode() & 0o777,
+                0o600
+            );
+        }
+        let loaded = RelayUsageStats::load_from(&path).unwrap();
+        assert_eq!(loaded.recent_relays(10), stats.recent_relays(10));
+        let _res = fs::remove_file(&path);
+    }
+}
@@ -0,0 +1,118 @@
+use crate::base::error::Error;
+use crate::model::clipboard::ClipboardCopy;
+
+use std::fs;
+use std::path::PathBuf;
+use std::time::Duration;
+
+/// Destination for a revealed secret (e.g. nsec). Secret-revealing methods write into a sink
+/// provided by the caller, instead of returning a plain `String` that could easily end up
+/// printed to a terminal or a log.
+pub(crate) trait SecretSink {
+    /// Warning: Security-sensitive method!
+    /// Receive the secret. Implementations should not keep more copies than needed.
+    fn write_secret(&mut self, secret: &str) -> Result<(), Error>;
+}
+
+/// In-memory buffer, e.g. for passing on to encryption
+impl SecretSink for Vec<u8> {
+    fn write_secret(&mut self, secret: &str) -> Result<(), Error> {
+        self.extend_from_slice(secret.as_bytes());
+        Ok(())
+    }
+}
+
+/// Write the secret to a file, readable only by the owner (on unix)
+pub(crate) struct FileSink {
+    path: PathBuf,
+}
+
+impl FileSink {
+    pub fn new(path: PathBuf) -> Self {
+        Self { path }
+    }
+}
+
+impl SecretSink for FileSink {
+    fn write_secret(&mut self, secret: &str) -> Result<(), Error> {
+        // create empty file
+        fs::write(&self.path, "")?;
+        #[cfg(unix)]
+        {
+            use std::os::unix::fs::PermissionsExt;
+            fs::set_permissions(&self.path, fs::Permissions::from_mode(0o600))?;
+        }
+        fs::write(&self.path, secret)?;
+        Ok(())
+    }
+}
+
+/// Prepare the secret for copying to the clipboard, see `ClipboardCopy`
+pub(crate) struct ClipboardSink {
+    timeout: Duration,
+    copy: Option<ClipboardCopy>,
+}
+
+impl ClipboardSink {
+    pub fn new(timeout: Duration) -> Self {
+        Self {
+            timeout,
+            copy: None,
+        }
+    }
+
+    /// Take the clipboard copy (with the secret), if written
+    pub fn take(&mut self) -> Option<ClipboardCopy> {
+        self.copy.take()
+    }
+}
+
+impl SecretSink for ClipboardSink {
+    fn write_secret(&mut self, secret: &str) -> Result<(), Error> {
+        self.copy = Some(ClipboardCopy::new(secret.to_string(), self.timeout));
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn test_vec_sink() {
+        let mut sink = Vec::new();
+        let _res = sink.write_secret("secret1").unwrap();
+        assert_eq!(sink, b"secret1");
+    }
+
+    #[test]
+    fn test_file_sink() {
+        let mut path = std::env::temp_dir();
+        path.push(format!(
+            "keystr-test-secret-sink-{}",
+            nostr::prelude::Keys::generate().public_key()
+        ));
+        let mut sink = FileSink::new(path.clone());
+        let _res = sink.write_secret("secret1").unwrap();
+        assert_eq!(fs::read_to_string(&path).unwrap(), "secret1");
+        #[cfg(unix)]
+        {
+            use std::os::unix::fs::PermissionsExt;
+            assert_eq!(
+                fs::metadata(&path).unwrap().permissions().mode() & 0o777,
+                0o600
+            );
+        }
+        let _res = fs::remove_file(&path);
+    }
+
+    #[test]
+    fn test_clipboard_sink() {
+        let mut sink = ClipboardSink::new(Duration::from_secs(5));
+        assert!(sink.take().is_none());
+        let _res = sink.write_secret("secret1").unwrap();
+        let copy = sink.take().unwrap();
+        assert_eq!(copy.content, "secret1");
+        assert!(sink.take().is_none());
+    }
+}
@@ -1,11 +1,30 @@
+use crate::base::encrypt::Encrypt;
+use crate::base::error::Error;
+
 use serde::{Deserialize, Serialize};
 
 use std::fmt;
 
 /// Security-related settings
-#[derive(Default, Serialize, Deserialize)]
+#[derive(Serialize, Deserialize)]
 pub struct SecuritySettings {
     pub security_level: SecurityLevel,
+    /// Scrypt cost (log2 of N) for encrypting the secret key
+    #[serde(default = "Encrypt::default_log2_rounds")]
+    pub scrypt_log_n: u8,
+    /// Policy on allowed key origins, checked on import
+    #[serde(default)]
+    pub key_policy: KeyPolicy,
+}
+
+impl Default for SecuritySettings {
+    fn default() -> Self {
+        Self {
+            security_level: SecurityLevel::default(),
+            scrypt_log_n: Encrypt::default_log2_rounds(),
+            key_policy: KeyPolicy::default(),
+        }
+    }
 }
 
 /// Security level regarding secret key handling/persistence; chosen by the user
@@ -27,12 +46,69 @@ impl fmt::Display for SecurityLevel {
     }
 }
 
+/// Origin of keys: how they were created or imported
+#[derive(Clone, Copy, Debug, Eq, PartialEq, Serialize, Deserialize)]
+pub enum KeyOrigin {
+    /// Generated randomly
+    Generated,
+    /// Derived from a BIP39 mnemonic (NIP-06)
+    Mnemonic,
+    /// Derived from a BIP32 extended private key
+    ExtendedKey,
+    /// Imported secret key (nsec or hex)
+    SecretKey,
+    /// Imported public key (npub or hex)
+    PublicKey,
+    /// Loaded from own storage
+    Storage,
+}
+
+/// Policy restricting the allowed key origins, for managed deployments that want to enforce provenance.
+/// Default policy allows everything.
+#[derive(Clone, Debug, Default, Serialize, Deserialize)]
+pub struct KeyPolicy {
+    /// Allowed origins, all are allowed if not set
+    pub allowed_origins: Option<Vec<KeyOrigin>>,
+}
+
+impl KeyPolicy {
+    /// Policy allowing only keys derived from mnemonic (and public keys)
+    pub fn mnemonic_only() -> Self {
+        Self {
+            allowed_origins: Some(vec![KeyOrigin::Mnemonic, KeyOrigin::PublicKey]),
+        }
+    }
+
+    pub fn is_mnemonic_only(&self) -> bool {
+        self.allowed_origins == Self::mnemonic_only().allowed_origins
+    }
+
+    /// Check if keys of the given origin are allowed.
+    /// Keys loaded from own storage are always allowed, they were checked on import.
+    pub fn check(&self, origin: KeyOrigin) -> Result<(), Error> {
+        match &self.allowed_origins {
+            None => Ok(()),
+            Some(_) if origin == KeyOrigin::Storage => Ok(()),
+            Some(allowed) => {
+                if allowed.contains(&origin) {
+                    Ok(())
+                } else {
+                    Err(Error::KeyPolicyViolation(format!("{:?}", origin)))
+                }
+            }
+        }
+    }
+}
+
 pub(crate) static SECURITY_LEVELS: &[SecurityLevel] = &[
     SecurityLevel::Never,
     SecurityLevel::PersistMandatoryPassword,
     SecurityLevel::PersistOptionalPassword,
 ];
 
+/// Choices for the scrypt cost (log2 of N): lower is faster (for weak devices), higher is stronger
+pub(crate) static SCRYPT_LOG_N_CHOICES: &[u8] = &[10, 13, 16, 18, 20, 22];
+
 impl SecuritySettings {
     pub fn get_security_warning_secret(&self) -> String {
         "I understand that if the secret key leaks to the wrong hands, the entire identity is COMPROMISED irreversibly.\n\
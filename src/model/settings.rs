@@ -1,18 +1,106 @@
 use crate::base::error::Error;
 use crate::base::storage::Storage;
-use crate::model::security_settings::{SecurityLevel, SecuritySettings};
+use crate::model::relays::{NetworkConfig, DEFAULT_NETWORK_TIMEOUT};
+use crate::model::security_settings::{KeyPolicy, SecurityLevel, SecuritySettings};
 use serde::{Deserialize, Serialize};
 use std::fs;
+use std::time::Duration;
+
+/// Default time after which a revealed secret key is hidden again (seconds)
+const DEFAULT_REVEAL_TIMEOUT_SECS: u64 = 30;
+
+/// Choices for the secret key reveal timeout (seconds)
+pub(crate) static REVEAL_TIMEOUTS_SECS: &[u64] = &[10, 30, 60, 300];
+
+/// Choices for the network timeout (seconds)
+pub(crate) static NETWORK_TIMEOUTS_SECS: &[u64] = &[5, 10, 30, 60];
+
+/// Choices for the auto-lock period (minutes), 0 for none
+pub(crate) static AUTO_LOCK_MINS: &[u64] = &[0, 5, 15, 60];
 
 /// Settings
 #[readonly::make]
-#[derive(Default, Serialize, Deserialize)]
+#[derive(Serialize, Deserialize)]
 pub struct Settings {
     #[readonly]
     pub security: SecuritySettings,
+    /// Time after which a revealed secret key is hidden again (seconds)
+    #[readonly]
+    #[serde(default = "default_reveal_timeout_secs")]
+    pub reveal_timeout_secs: u64,
+    /// Timeout of network operations (seconds), can be raised for slow connections
+    #[readonly]
+    #[serde(default = "default_network_timeout_secs")]
+    pub network_timeout_secs: u64,
+    /// Auto-lock period (minutes): after this much inactivity, revealing the secret key
+    /// needs unlocking again; 0 for no auto-lock
+    #[readonly]
+    #[serde(default)]
+    pub auto_lock_mins: u64,
+}
+
+fn default_reveal_timeout_secs() -> u64 {
+    DEFAULT_REVEAL_TIMEOUT_SECS
+}
+
+fn default_network_timeout_secs() -> u64 {
+    DEFAULT_NETWORK_TIMEOUT.as_secs()
+}
+
+impl Default for Settings {
+    fn default() -> Self {
+        Self {
+            security: SecuritySettings::default(),
+            reveal_timeout_secs: DEFAULT_REVEAL_TIMEOUT_SECS,
+            network_timeout_secs: default_network_timeout_secs(),
+            auto_lock_mins: 0,
+        }
+    }
 }
 
 impl Settings {
+    pub fn set_scrypt_log_n(&mut self, log_n: u8) {
+        self.security.scrypt_log_n = log_n;
+        let _res = self.save();
+    }
+
+    pub fn set_key_policy(&mut self, key_policy: KeyPolicy) {
+        self.security.key_policy = key_policy;
+        let _res = self.save();
+    }
+
+    pub fn set_network_timeout_secs(&mut self, secs: u64) {
+        self.network_timeout_secs = secs;
+        let _res = self.save();
+    }
+
+    /// Config of network operations, with the timeout from the settings
+    pub fn network_config(&self) -> NetworkConfig {
+        NetworkConfig::with_timeout(Duration::from_secs(self.network_timeout_secs))
+    }
+
+    pub fn set_auto_lock_mins(&mut self, mins: u64) {
+        self.auto_lock_mins = mins;
+        let _res = self.save();
+    }
+
+    /// The auto-lock period, if set
+    pub fn auto_lock(&self) -> Option<Duration> {
+        match self.auto_lock_mins {
+            0 => None,
+            mins => Some(Duration::from_secs(mins * 60)),
+        }
+    }
+
+    pub fn set_reveal_timeout_secs(&mut self, secs: u64) {
+        self.reveal_timeout_secs = secs;
+        let _res = self.save();
+    }
+
+    pub fn reveal_timeout(&self) -> Duration {
+        Duration::from_secs(self.reveal_timeout_secs)
+    }
+
     pub fn set_security_level(&mut self, level: SecurityLevel) {
         self.security.security_level = level;
         let _res = self.save();
@@ -205,10 +205,7 @@ impl SignerConnection {
                 if let Ok(request) = &req.req.to_request() {
                     match request {
                         Request::SignEvent(unsigned_event) => {
-                            let unsigned_id = unsigned_event.id;
-                            if let Ok(signature) =
-                                self.key_signer.sign(unsigned_id.as_bytes().to_vec())
-                            {
+                            if let Ok(signature) = self.key_signer.sign_event(unsigned_event) {
                                 let response_msg =
                                     Message::response(id.clone(), Response::SignEvent(signature));
                                 let _ = send_message_blocking(
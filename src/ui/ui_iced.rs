@@ -1,17 +1,68 @@
-use crate::model::keystr_model::{Action, Confirmation, Event, KeystrModel, Modal, EVENT_QUEUE};
-use crate::model::security_settings::{SecurityLevel, SECURITY_LEVELS};
+use crate::base::cancellation::CancellationToken;
+use crate::base::error::Error;
+use crate::base::event_utils::verify_events;
+use crate::base::key_utils::{hex_to_npub, hex_to_nsec, is_valid_npub, npub_to_hex, nsec_to_hex};
+use crate::base::nip19_tlv::nprofile;
+use crate::base::storage::Storage;
+use crate::model::airgap::mnemonic_qr_svg;
+use crate::model::clipboard::CLIPBOARD_CLEAR_TIMEOUT;
+use crate::model::keystore::gift_wrap::{create_gift_wrap, unwrap_gift};
+use crate::model::keystore::reveal::RevealGuard;
+use crate::model::keystore::{
+    ExportFormat, IdentityStatus, ImportFormat, Keystore, WarningLevel, DISCOVERY_GAP_LIMIT,
+    IMPORT_FORMATS, MAX_QR_EXPORT_SIZE,
+};
+use crate::model::keystr_model::{
+    enabled_features, supported_nips, Action, Confirmation, Event, KeystrModel, Modal, EVENT_QUEUE,
+};
+use crate::model::nip47::{parse_nwc_uri, pay_invoice};
+use crate::model::relays::{
+    check_content_limits, check_relay, fetch_relay_list, parse_relay_urls, publish_and_confirm,
+    ConfirmedPublish, PublishResult, RelayInfo, RelayList, RelayListEntry, RECENT_RELAYS_LIMIT,
+    WATCH_NOTES_LIMIT,
+};
+use crate::model::security_settings::{SecurityLevel, SCRYPT_LOG_N_CHOICES, SECURITY_LEVELS};
+use crate::model::settings::{AUTO_LOCK_MINS, NETWORK_TIMEOUTS_SECS, REVEAL_TIMEOUTS_SECS};
 use crate::model::signer::ConnectionStatus;
 use crate::ui::dialog::Dialog;
 
-use iced::widget::{button, column, container, pick_list, row, text, text_input};
+use iced::futures::FutureExt;
+use iced::widget::{
+    button, checkbox, column, container, pick_list, progress_bar, row, svg, text, text_input,
+    TextInput,
+};
 use iced::{executor, subscription};
-use iced::{Alignment, Application, Command, Element, Length, Subscription, Theme};
+use iced::{Alignment, Application, Color, Command, Element, Length, Subscription, Theme};
+use nostr::prelude::{Event as NostrEvent, EventBuilder, Keys, Metadata, Timestamp, ToBech32};
+use zeroize::Zeroizing;
+
+use std::cell::RefCell;
+use std::collections::HashMap;
+use std::fs;
+use std::io;
+
+/// Text input for a secret (secret key, password, mnemonic): masked, unless `reveal` is set
+fn masked_secret_input<'a>(
+    placeholder: &str,
+    value: &str,
+    reveal: bool,
+    on_change: impl Fn(String) -> Message + 'a,
+) -> TextInput<'a, Message> {
+    let input = text_input(placeholder, value, on_change).size(15);
+    if reveal {
+        input
+    } else {
+        input.password()
+    }
+}
 
 #[derive(Debug, Clone, PartialEq)]
 pub enum Tab {
     Keys,
     Delegate,
     Signer,
+    Relays,
+    Accounts,
 }
 
 #[derive(Debug, Clone)]
@@ -22,15 +73,80 @@ pub(crate) enum Message {
     NoOp,
     Refresh,
     SecurityLevelChange(SecurityLevel),
+    RevealTimeoutChange(u64),
+    ScryptLogNChange(u8),
+    AutoLockChange(u64),
+    MnemonicOnlyToggle(bool),
+    NetworkTimeoutChange(u64),
+    OfflineToggle(bool),
     TabSelect(Tab),
 
     KeysPubkeyInput(String),
     KeysToggleHideSecretKey,
+    /// Hide the revealed secret key, if its reveal has expired
+    KeysRevealTimeout,
+    KeysCopySecretKey,
+    /// Export the secret key in plaintext to a file, readable only by the owner
+    KeysExportSecretKey,
+    /// Copy diagnostics for bug reports, with no identifying data
+    CopyDiagnostics,
+    /// Copy the public key in hex, with case checksum, for non-Nostr systems
+    KeysCopyPublicKeyHex,
+    /// Copy the public key in PEM format, for non-Nostr tools
+    KeysCopyPublicKeyPem,
+    /// Copy the public key as 'nprofile', with the relays as relay hints
+    KeysCopyNprofile,
+    /// Copy the public config as a TOML fragment, for embedding into the config of another app
+    KeysCopyTomlConfig,
+    /// Copy the secret key encrypted with the save password ('ncryptsec'), e.g. for a backup
+    KeysCopyNcryptsec,
+    /// Copy a paper backup (npub, ncryptsec and checksum), for printing
+    KeysCopyPaperBackup,
+    /// Show or hide the enrollment QR codes (for hardware signers)
+    KeysToggleEnrollmentQr,
+    /// Clear the clipboard, if it was not copied to again since (generation of the copy)
+    ClipboardClear(u64),
     KeysSecretkeyInput(String),
+    KeysToggleRevealSecretkeyInput,
+    /// Import the secret key input, and announce its profile (named by the label input) on the relays
+    KeysImportAndAnnounce,
+    /// Results of announcing the profile, per relay
+    KeysAnnounced(Vec<PublishResult>),
+    /// Read the clipboard, for importing a key from it
+    KeysPasteImport,
+    KeysPasteImportContent(Option<String>),
+    KeysFormatImportInput(String),
+    KeysConvertInput(String),
+    /// Convert the key in the convert input ('npub' or 'nsec') to hex
+    KeysConvertToHex,
+    KeysConvertToNpub,
+    KeysConvertToNsec,
+    KeysImportFormatChange(ImportFormat),
     KeysMnemonicInput(String),
+    KeysMnemonicPassphraseInput(String),
+    KeysMnemonicPassphraseRepeatInput(String),
+    /// Show or hide the mnemonic input as a QR code
+    KeysToggleMnemonicQr,
+    KeysQrPayloadInput(String),
+    KeysDerivationPathInput(String),
+    /// Discover the used accounts of the mnemonic on the relays
+    KeysDiscoverAccounts,
+    /// Result of the account discovery: used accounts (derivation path, npub), or error
+    KeysAccountsDiscovered(Result<Vec<(String, String)>, String>),
+    KeysVerificationChallengeInput(String),
+    KeysToggleVerificationQr,
+    KeysVerificationPayloadInput(String),
     KeysDecryptPasswordInput(String),
     KeysSavePasswordInput(String),
     KeysSaveRepeatPasswordInput(String),
+    KeysReencryptInput(String),
+    KeysReencryptOldPasswordInput(String),
+    KeysReencryptNewPasswordInput(String),
+    KeysReencryptRepeatPasswordInput(String),
+    KeysNoteInput(String),
+    KeysLabelInput(String),
+    KeysVanityPrefixInput(String),
+    KeysBackupConfirmInput(String),
 
     DelegateDeeChanged(String),
     DelegateKindChanged(String),
@@ -38,13 +154,95 @@ pub(crate) enum Message {
     DelegateTimeEndChanged(String),
     DelegateTimeDaysChanged(String),
     DelegateTimeDaysChangedNoUpdate(String),
+    DelegateVerifyEventInput(String),
 
     SignerUriInput(String),
+    SignerAllowedKindsInput(String),
+    SignerOfflineInput(String),
+    /// Sign the air-gapped signing request in the input, and copy the response
+    SignerOfflineSign,
+    /// Check the air-gapped signing response in the input, and copy the signed event
+    SignerOfflineCheck,
+    SignerHttpAuthMethodInput(String),
+    SignerHttpAuthUrlInput(String),
+    SignerHttpAuthPayloadInput(String),
+    /// Sign an HTTP auth event (NIP-98) for the request, and copy the `Authorization` header
+    SignerHttpAuthSign,
+    SignerConversationPeersInput(String),
+    SignerConversationKeysCopy,
+
+    RelaysInput(String),
+    RelaysCheck,
+    /// Use the recently used relays as the relays
+    RelaysUseRecent,
+    /// Result of a relay check (relay URL, info or error)
+    RelayChecked(String, Result<RelayInfo, String>),
+    RelaysFetchList,
+    /// Result of fetching the relay list (NIP-65) of the keys
+    RelayListFetched(Result<Option<RelayList>, String>),
+    /// Fetch the recent notes of the public key (watch mode)
+    NotesFetch,
+    /// Result of fetching the recent notes (relay URLs, notes or error)
+    NotesFetched(Vec<String>, Result<Vec<NostrEvent>, String>),
+    /// Save the fetched notes to files, one per note
+    NotesSave,
+    Nip05Input(String),
+    IdentityRevalidate,
+    IdentityRevalidateCancel,
+    IdentityRevalidated(IdentityStatus),
+    PublishNoteInput(String),
+    AuthChallengeInput(String),
+    GiftWrapRecipientInput(String),
+    GiftWrapInput(String),
+    GiftWrapCreate,
+    GiftWrapOpen,
+    /// Sign the authentication challenge of the relay (NIP-42), and copy the auth event
+    AuthSign,
+    PublishConfirmToggle(bool),
+    NotePublish,
+    NoteGenerateWithIntro,
+    /// Copy a signing request for the note, to be signed on an offline machine
+    NoteOfflineRequest,
+    /// Results of publishing a note, per relay
+    NotePublished(Vec<ConfirmedPublish>),
+    CreatedAtInput(String),
+    /// Sign the note with the entered creation time, and copy the event
+    NoteSignAt,
+    WalletUriInput(String),
+    InvoiceInput(String),
+    /// Pay the invoice through the wallet connection (NIP-47)
+    InvoicePay,
+    /// Result of paying the invoice (payment preimage or error)
+    InvoicePaid(Result<String, String>),
+
+    AccountsPasswordInput(String),
+    AccountsCopyPublicBundle,
 }
 
 pub(crate) struct KeystrApp {
     pub model: KeystrModel,
     current_tab: Tab,
+    /// Incremented on each copy of a secret to the clipboard, so that a pending clear
+    /// of an older copy does not wipe a newer one
+    clipboard_generation: u64,
+    /// The secret key, while revealed; in a cell, as reading it checks the expiry (and zeroizes when expired)
+    revealed_secret_key: RefCell<Option<RevealGuard>>,
+    /// The npub that would be imported from the mnemonic input, if it is valid
+    mnemonic_preview: Option<String>,
+    /// Token of the NIP-05 identity check in progress, for cancelling it
+    identity_check: Option<CancellationToken>,
+    /// If published notes are confirmed by querying the relays for them
+    publish_confirm: bool,
+    /// Information of the checked relays, for checking notes against their limits before publishing
+    relay_infos: HashMap<String, RelayInfo>,
+    /// The recent notes of the public key, as last fetched (watch mode)
+    recent_notes: Vec<NostrEvent>,
+    /// The verification QR of the own keys, while shown
+    verification_qr: Option<svg::Handle>,
+    /// Shown enrollment QR codes, one per frame
+    enrollment_qr: Vec<svg::Handle>,
+    /// Shown QR code of the mnemonic (plaintext!)
+    mnemonic_qr: Option<svg::Handle>,
 }
 
 impl KeystrApp {
@@ -52,14 +250,37 @@ impl KeystrApp {
         Self {
             model: KeystrModel::init(),
             current_tab: Tab::Keys,
+            clipboard_generation: 0,
+            revealed_secret_key: RefCell::new(None),
+            mnemonic_preview: None,
+            identity_check: None,
+            publish_confirm: true,
+            relay_infos: HashMap::new(),
+            recent_notes: Vec::new(),
+            verification_qr: None,
+            enrollment_qr: Vec::new(),
+            mnemonic_qr: None,
         }
     }
 
+    fn update_mnemonic_preview(&mut self) {
+        let keys = &self.model.own_keys;
+        let passphrase = if keys.mnemonic_passphrase_input.is_empty() {
+            None
+        } else {
+            Some(keys.mnemonic_passphrase_input.as_str())
+        };
+        self.mnemonic_preview =
+            Keystore::preview_mnemonic_npub(&keys.mnemonic_input, passphrase, 0).ok();
+    }
+
     fn tab_selector(&self) -> Element<Message> {
         row![
             button("Keys").on_press(Message::TabSelect(Tab::Keys)),
             button("Delegate").on_press(Message::TabSelect(Tab::Delegate)),
             button("Signer").on_press(Message::TabSelect(Tab::Signer)),
+            button("Relays").on_press(Message::TabSelect(Tab::Relays)),
+            button("Accounts").on_press(Message::TabSelect(Tab::Accounts)),
         ]
         .padding(10)
         .spacing(5)
@@ -67,19 +288,59 @@ impl KeystrApp {
         .into()
     }
 
+    /// The revealed secret key (nsec), if revealed and not yet expired.
+    /// The copy for the view is zeroized when dropped, after the view is built.
+    fn revealed_nsec(&self) -> Option<Zeroizing<String>> {
+        if self.model.own_keys.is_reveal_expired() {
+            return None;
+        }
+        self.revealed_secret_key
+            .borrow_mut()
+            .as_mut()
+            .and_then(|guard| {
+                guard
+                    .as_str()
+                    .ok()
+                    .map(|nsec| Zeroizing::new(nsec.to_string()))
+            })
+    }
+
     fn tab_keys(&self) -> Element<Message> {
         let label_width = Length::Fixed(150.0);
 
-        let unlock_ui = if self.model.own_keys.is_encrypted_secret_key_set() {
+        let unlock_ui = if let Some(unlock) = &self.model.unlock {
+            column![row![
+                text("Decrypting secret key:").size(15),
+                progress_bar(0.0..=1.0, unlock.progress()),
+                button("Cancel").on_press(Message::ModelAction(Action::KeysUnlockCancel)),
+            ]
+            .align_items(Alignment::Fill)
+            .spacing(5)
+            .padding(0)]
+        } else if !self.model.own_keys.ncryptsec_input.is_empty() {
+            column![row![
+                text("Password of the pasted encrypted key (ncryptsec):").size(15),
+                masked_secret_input(
+                    "enter password that was used for encrypting the key",
+                    &self.model.own_keys.decrypt_password_input,
+                    self.model.own_keys.reveal_input,
+                    Message::KeysDecryptPasswordInput,
+                ),
+                button("Import").on_press(Message::ModelAction(Action::KeysImportNcryptsec)),
+                button("Cancel").on_press(Message::ModelAction(Action::KeysImportNcryptsecCancel)),
+            ]
+            .align_items(Alignment::Fill)
+            .spacing(5)
+            .padding(0)]
+        } else if self.model.own_keys.is_locked() {
             column![row![
                 text("Password is needed to unlock secret key:").size(15),
-                text_input(
+                masked_secret_input(
                     "enter password that was used for encrypting secret key",
                     &self.model.own_keys.decrypt_password_input,
+                    self.model.own_keys.reveal_input,
                     Message::KeysDecryptPasswordInput,
-                )
-                .password()
-                .size(15),
+                ),
                 button("Unlock").on_press(Message::ModelAction(Action::KeysUnlock)),
             ]
             .align_items(Alignment::Fill)
@@ -92,6 +353,124 @@ impl KeystrApp {
         .spacing(5)
         .padding(0);
 
+        let secret_key_input = masked_secret_input(
+            "nsec or hex for secret key import",
+            &self.model.own_keys.secret_key_input,
+            self.model.own_keys.reveal_input,
+            Message::KeysSecretkeyInput,
+        )
+        .on_submit(Message::ModelAction(Action::KeysImportSubmit));
+        let revealed_nsec = self.revealed_nsec();
+        #[allow(deprecated)]
+        let hidden_nsec = if self.model.own_keys.hide_secret_key {
+            self.model.own_keys.get_nsec()
+        } else {
+            String::new()
+        };
+        let vanity_prefix = &self.model.own_keys.vanity_prefix_input;
+        let vanity_match = match self.model.own_keys.npub_prefix_match(vanity_prefix) {
+            Some(n) if !vanity_prefix.trim().is_empty() => format!("{n} characters match"),
+            _ => String::new(),
+        };
+        let verification_qr: Element<Message> = match &self.verification_qr {
+            Some(handle) => svg(handle.clone())
+                .width(Length::Fixed(256.0))
+                .height(Length::Fixed(256.0))
+                .into(),
+            None => column![].into(),
+        };
+
+        let enrollment_qr =
+            self.enrollment_qr
+                .iter()
+                .fold(row![].spacing(5).padding(0), |qrs, handle| {
+                    qrs.push(
+                        svg(handle.clone())
+                            .width(Length::Fixed(140.0))
+                            .height(Length::Fixed(140.0)),
+                    )
+                });
+
+        let mnemonic_qr: Element<Message> = match &self.mnemonic_qr {
+            Some(handle) => column![
+                text("The QR code contains the mnemonic in plaintext, don't let it be seen or photographed!")
+                    .size(15)
+                    .style(Color::from_rgb(0.8, 0.0, 0.0)),
+                svg(handle.clone())
+                    .width(Length::Fixed(256.0))
+                    .height(Length::Fixed(256.0)),
+            ]
+            .spacing(5)
+            .padding(0)
+            .into(),
+            None => column![].into(),
+        };
+
+        let backup_ui = if self.model.own_keys.is_secret_key_set() {
+            column![row![
+                column![text("Confirm backup:").size(15)]
+                    .align_items(Alignment::Start)
+                    .width(label_width)
+                    .padding(0),
+                masked_secret_input(
+                    "retype the secret key (nsec) from your backup",
+                    &self.model.own_keys.backup_confirm_input,
+                    self.model.own_keys.reveal_input,
+                    Message::KeysBackupConfirmInput,
+                ),
+                button("I've backed up").on_press(Message::ModelAction(Action::KeysConfirmBackup)),
+            ]
+            .align_items(Alignment::Fill)
+            .spacing(5)
+            .padding(0)]
+        } else {
+            column![]
+        };
+
+        let security = self.model.own_keys.security_warning_level();
+        let security_badge = text(if self.model.own_keys.keys_is_set() {
+            format!(
+                "{:?} {}",
+                security.level,
+                security
+                    .reasons()
+                    .iter()
+                    .map(|r| r.message())
+                    .collect::<Vec<&str>>()
+                    .join(" ")
+            )
+        } else {
+            "".to_string()
+        })
+        .size(15)
+        .style(match security.level {
+            WarningLevel::Good => Color::from_rgb(0.0, 0.6, 0.0),
+            WarningLevel::Caution => Color::from_rgb(0.8, 0.5, 0.0),
+            WarningLevel::Danger => Color::from_rgb(0.8, 0.0, 0.0),
+        });
+
+        let snapshot = self.model.own_keys.snapshot();
+        let info = if snapshot.npub.is_some() {
+            let stats = self.model.own_keys.signing_stats();
+            format!(
+                "'{}', {:?}{}, {}, signed {} events in this session{}",
+                self.model.own_keys.petname().unwrap_or_default(),
+                snapshot.set_state,
+                match snapshot.origin {
+                    None => "".to_string(),
+                    Some(origin) => format!(" ({:?})", origin),
+                },
+                self.model.own_keys.age_bucket(),
+                stats.count,
+                match stats.last_signed_at {
+                    None => "".to_string(),
+                    Some(t) => format!(" (last at {})", t),
+                }
+            )
+        } else {
+            "".to_string()
+        };
+
         column![
             text("Own Keys").size(25),
             unlock_ui,
@@ -106,6 +485,77 @@ impl KeystrApp {
                     Message::ChangedReadonly,
                 )
                 .size(15),
+                button("Copy hex").on_press(Message::KeysCopyPublicKeyHex),
+                button("Copy PEM").on_press(Message::KeysCopyPublicKeyPem),
+                button("Copy nprofile").on_press(Message::KeysCopyNprofile),
+                button("Copy TOML").on_press(Message::KeysCopyTomlConfig),
+            ]
+            .align_items(Alignment::Fill)
+            .spacing(5)
+            .padding(0),
+            row![
+                column![text("Info:").size(15)]
+                    .align_items(Alignment::Start)
+                    .width(label_width)
+                    .padding(0),
+                text(info).size(15),
+            ]
+            .align_items(Alignment::Fill)
+            .spacing(5)
+            .padding(0),
+            row![
+                column![text("Vanity prefix:").size(15)]
+                    .align_items(Alignment::Start)
+                    .width(label_width)
+                    .padding(0),
+                text_input(
+                    "wanted start of the npub, e.g. 'npub1abc'",
+                    &self.model.own_keys.vanity_prefix_input,
+                    Message::KeysVanityPrefixInput,
+                )
+                .size(15),
+                text(&vanity_match).size(15),
+            ]
+            .align_items(Alignment::Fill)
+            .spacing(5)
+            .padding(0),
+            row![
+                column![text("Label:").size(15)]
+                    .align_items(Alignment::Start)
+                    .width(label_width)
+                    .padding(0),
+                text_input(
+                    "label of the keys, e.g. 'Main'",
+                    &self.model.own_keys.label_input,
+                    Message::KeysLabelInput,
+                )
+                .size(15),
+                button("Set").on_press(Message::ModelAction(Action::KeysSetLabel)),
+            ]
+            .align_items(Alignment::Fill)
+            .spacing(5)
+            .padding(0),
+            row![
+                column![text("Note:").size(15)]
+                    .align_items(Alignment::Start)
+                    .width(label_width)
+                    .padding(0),
+                text_input(
+                    "optional note about the keys, e.g. 'old key, rotated 2024'",
+                    self.model.own_keys.note(),
+                    Message::KeysNoteInput,
+                )
+                .size(15),
+            ]
+            .align_items(Alignment::Fill)
+            .spacing(5)
+            .padding(0),
+            row![
+                column![text("Security:").size(15)]
+                    .align_items(Alignment::Start)
+                    .width(label_width)
+                    .padding(0),
+                security_badge,
             ]
             .align_items(Alignment::Fill)
             .spacing(5)
@@ -115,28 +565,37 @@ impl KeystrApp {
                     .align_items(Alignment::Start)
                     .width(label_width)
                     .padding(0),
-                button("Copy TODO").on_press(Message::NoOp), // TODO, TODO confirm
-                button(if self.model.own_keys.hide_secret_key {
+                button("Copy").on_press(Message::KeysCopySecretKey),
+                button("Export").on_press(Message::KeysExportSecretKey),
+                button(if revealed_nsec.is_none() {
                     "Show"
                 } else {
                     "Hide"
                 })
                 .on_press(Message::KeysToggleHideSecretKey),
-                if self.model.own_keys.hide_secret_key {
-                    text_input("(hidden)", "(hidden)", Message::ChangedReadonly)
-                } else {
-                    text_input(
+                pick_list(
+                    REVEAL_TIMEOUTS_SECS,
+                    Some(self.model.settings.reveal_timeout_secs),
+                    Message::RevealTimeoutChange
+                )
+                .text_size(15),
+                text("sec").size(15),
+                match &revealed_nsec {
+                    // "(not set)" without secret key, empty (placeholder shown) while hidden
+                    None => text_input("(hidden)", &hidden_nsec, Message::ChangedReadonly),
+                    Some(nsec) => text_input(
                         "", // empty, placeholder also shows up asterisked
-                        &self.model.own_keys.get_nsec(),
+                        nsec,
                         Message::ChangedReadonly,
                     )
-                    .password()
+                    .password(),
                 }
                 .size(15),
             ]
             .align_items(Alignment::Fill)
             .spacing(5)
             .padding(0),
+            backup_ui,
             text(if self.model.own_keys.has_unsaved_change {
                 "There are Unsaved changes!"
             } else {
@@ -147,8 +606,13 @@ impl KeystrApp {
             row![
                 button("Load").on_press(Message::ModelAction(Action::KeysLoad)),
                 button("Save").on_press(Message::ModelAction(Action::KeysSave)),
+                button("Load from keychain")
+                    .on_press(Message::ModelAction(Action::KeysLoadFromKeychain)),
+                button("Save to keychain")
+                    .on_press(Message::ModelAction(Action::KeysSaveToKeychain)),
                 button("Generate new keypair").on_press(Message::ModelAction(Action::KeysGenerate)),
                 button("Clear keys").on_press(Message::ModelAction(Action::KeysClear)),
+                button("Panic wipe").on_press(Message::ModelAction(Action::KeysPanicWipe)),
             ]
             .align_items(Alignment::Fill)
             .spacing(5)
@@ -159,13 +623,12 @@ impl KeystrApp {
                     .align_items(Alignment::Start)
                     .width(label_width)
                     .padding(0),
-                text_input(
+                masked_secret_input(
                     "enter password for encrypting secret key",
                     &self.model.own_keys.save_password_input,
+                    self.model.own_keys.reveal_input,
                     Message::KeysSavePasswordInput,
-                )
-                .password()
-                .size(15),
+                ),
             ]
             .align_items(Alignment::Fill)
             .spacing(5)
@@ -175,17 +638,127 @@ impl KeystrApp {
                     .align_items(Alignment::Start)
                     .width(label_width)
                     .padding(0),
-                text_input(
+                masked_secret_input(
                     "repeat password",
                     &self.model.own_keys.save_repeat_password_input,
+                    self.model.own_keys.reveal_input,
                     Message::KeysSaveRepeatPasswordInput,
+                ),
+            ]
+            .align_items(Alignment::Fill)
+            .spacing(5)
+            .padding(0),
+            row![
+                column![text("Encryption cost:").size(15),]
+                    .align_items(Alignment::Start)
+                    .width(label_width)
+                    .padding(0),
+                pick_list(
+                    SCRYPT_LOG_N_CHOICES,
+                    Some(self.model.settings.security.scrypt_log_n),
+                    Message::ScryptLogNChange
+                )
+                .text_size(15),
+                text("(scrypt log2 N; lower is faster, higher is stronger)").size(15),
+            ]
+            .align_items(Alignment::Fill)
+            .spacing(5)
+            .padding(0),
+            row![
+                column![text("Encrypted backup:").size(15),]
+                    .align_items(Alignment::Start)
+                    .width(label_width)
+                    .padding(0),
+                button("Copy encrypted secret key (ncryptsec)")
+                    .on_press(Message::KeysCopyNcryptsec),
+                button("Copy paper backup").on_press(Message::KeysCopyPaperBackup),
+                button(if self.enrollment_qr.is_empty() {
+                    "Show enrollment QR"
+                } else {
+                    "Hide enrollment QR"
+                })
+                .on_press(Message::KeysToggleEnrollmentQr),
+                text(&format!(
+                    "({} characters; QR about {} KB)",
+                    self.model
+                        .own_keys
+                        .estimated_export_size(ExportFormat::Ncryptsec),
+                    self.model
+                        .own_keys
+                        .estimated_export_size(ExportFormat::EnrollmentQr)
+                        / 1024
+                ))
+                .size(15),
+            ]
+            .align_items(Alignment::Fill)
+            .spacing(5)
+            .padding(0),
+            enrollment_qr,
+            row![
+                column![text("Change backup password:").size(15),]
+                    .align_items(Alignment::Start)
+                    .width(label_width)
+                    .padding(0),
+                text_input(
+                    "ncryptsec1...",
+                    &self.model.own_keys.reencrypt_input,
+                    Message::KeysReencryptInput
                 )
-                .password()
                 .size(15),
             ]
             .align_items(Alignment::Fill)
             .spacing(5)
             .padding(0),
+            row![
+                column![text("").size(15),]
+                    .align_items(Alignment::Start)
+                    .width(label_width)
+                    .padding(0),
+                masked_secret_input(
+                    "current password",
+                    &self.model.own_keys.reencrypt_old_password_input,
+                    self.model.own_keys.reveal_input,
+                    Message::KeysReencryptOldPasswordInput,
+                ),
+                masked_secret_input(
+                    "new password",
+                    &self.model.own_keys.reencrypt_new_password_input,
+                    self.model.own_keys.reveal_input,
+                    Message::KeysReencryptNewPasswordInput,
+                ),
+                masked_secret_input(
+                    "repeat new password",
+                    &self.model.own_keys.reencrypt_repeat_password_input,
+                    self.model.own_keys.reveal_input,
+                    Message::KeysReencryptRepeatPasswordInput,
+                ),
+                button("Re-encrypt").on_press(Message::ModelAction(Action::KeysReencryptNcryptsec)),
+            ]
+            .align_items(Alignment::Fill)
+            .spacing(5)
+            .padding(0),
+            row![
+                column![text("Auto-lock:").size(15),]
+                    .align_items(Alignment::Start)
+                    .width(label_width)
+                    .padding(0),
+                pick_list(
+                    AUTO_LOCK_MINS,
+                    Some(self.model.settings.auto_lock_mins),
+                    Message::AutoLockChange
+                )
+                .text_size(15),
+                text("min (0 for none; after inactivity, reveal needs unlock)").size(15),
+            ]
+            .align_items(Alignment::Fill)
+            .spacing(5)
+            .padding(0),
+            checkbox(
+                "Allow importing only keys derived from mnemonic (and public keys)",
+                self.model.settings.security.key_policy.is_mnemonic_only(),
+                Message::MnemonicOnlyToggle
+            )
+            .text_size(15),
             iced::widget::rule::Rule::horizontal(5),
             row![
                 text_input(
@@ -193,6 +766,7 @@ impl KeystrApp {
                     &self.model.own_keys.public_key_input,
                     Message::KeysPubkeyInput,
                 )
+                .on_submit(Message::ModelAction(Action::KeysImportSubmit))
                 .size(15),
                 button("Import Public key")
                     .on_press(Message::ModelAction(Action::KeysImportPubkey)),
@@ -202,35 +776,165 @@ impl KeystrApp {
             .padding(0),
             iced::widget::rule::Rule::horizontal(5),
             row![
-                text_input(
-                    "npub or hex for secret key import",
-                    &self.model.own_keys.secret_key_input,
-                    Message::KeysSecretkeyInput,
-                )
-                .password()
-                .size(15),
+                secret_key_input,
+                button(if self.model.own_keys.reveal_input {
+                    "Hide"
+                } else {
+                    "Show"
+                })
+                .on_press(Message::KeysToggleRevealSecretkeyInput),
                 button("Import Secret key")
                     .on_press(Message::ModelAction(Action::KeysImportSecretkey)),
+                button("Import & announce").on_press(Message::KeysImportAndAnnounce),
+                button("Import watch-only")
+                    .on_press(Message::ModelAction(Action::KeysImportPublicFromSecret)),
+                button("Paste & import").on_press(Message::KeysPasteImport),
             ]
             .align_items(Alignment::Fill)
             .spacing(5)
             .padding(0),
             iced::widget::rule::Rule::horizontal(5),
             row![
-                text_input(
+                masked_secret_input(
+                    "key to import, in the selected format",
+                    &self.model.own_keys.format_import_input,
+                    self.model.own_keys.reveal_input,
+                    Message::KeysFormatImportInput,
+                ),
+                pick_list(
+                    IMPORT_FORMATS,
+                    Some(self.model.own_keys.import_format),
+                    Message::KeysImportFormatChange
+                )
+                .text_size(15),
+                button("Import").on_press(Message::ModelAction(Action::KeysImportWithFormat)),
+            ]
+            .align_items(Alignment::Fill)
+            .spacing(5)
+            .padding(0),
+            row![
+                masked_secret_input(
+                    "key to convert, without importing it",
+                    &self.model.own_keys.convert_input,
+                    self.model.own_keys.reveal_input,
+                    Message::KeysConvertInput,
+                ),
+                button("To hex").on_press(Message::KeysConvertToHex),
+                button("To npub").on_press(Message::KeysConvertToNpub),
+                button("To nsec").on_press(Message::KeysConvertToNsec),
+            ]
+            .align_items(Alignment::Fill)
+            .spacing(5)
+            .padding(0),
+            iced::widget::rule::Rule::horizontal(5),
+            row![
+                masked_secret_input(
                     "BIP39 mnemonic (passphrase) to generate secret key, NIP-06",
                     &self.model.own_keys.mnemonic_input,
+                    self.model.own_keys.reveal_input,
                     Message::KeysMnemonicInput,
+                ),
+                masked_secret_input(
+                    "optional passphrase",
+                    &self.model.own_keys.mnemonic_passphrase_input,
+                    self.model.own_keys.reveal_input,
+                    Message::KeysMnemonicPassphraseInput,
+                ),
+                text_input(
+                    "optional derivation path",
+                    &self.model.own_keys.derivation_path_input,
+                    Message::KeysDerivationPathInput,
                 )
-                .password()
                 .size(15),
                 button("Import Mnemonic")
                     .on_press(Message::ModelAction(Action::KeysImportMnemonic)),
+                button("Discover accounts").on_press(Message::KeysDiscoverAccounts),
+            ]
+            .align_items(Alignment::Fill)
+            .spacing(5)
+            .padding(0),
+            row![
+                masked_secret_input(
+                    "repeat the passphrase, for generating keys from a new mnemonic",
+                    &self.model.own_keys.mnemonic_passphrase_repeat_input,
+                    self.model.own_keys.reveal_input,
+                    Message::KeysMnemonicPassphraseRepeatInput,
+                ),
+                button("Generate with mnemonic")
+                    .on_press(Message::ModelAction(Action::KeysGenerateMnemonic)),
+                button(if self.mnemonic_qr.is_none() {
+                    "Show mnemonic QR"
+                } else {
+                    "Hide mnemonic QR"
+                })
+                .on_press(Message::KeysToggleMnemonicQr),
+            ]
+            .align_items(Alignment::Fill)
+            .spacing(5)
+            .padding(0),
+            mnemonic_qr,
+            row![
+                masked_secret_input(
+                    "text of a scanned QR code: mnemonic (or SeedQR digits), nsec or npub",
+                    &self.model.own_keys.qr_payload_input,
+                    self.model.own_keys.reveal_input,
+                    Message::KeysQrPayloadInput,
+                ),
+                button("Import QR").on_press(Message::ModelAction(Action::KeysImportQrPayload)),
             ]
             .align_items(Alignment::Fill)
             .spacing(5)
             .padding(0),
+            text(match &self.mnemonic_preview {
+                // the preview is for the NIP-06 path only
+                Some(npub)
+                    if !self.model.own_keys.mnemonic_input.is_empty()
+                        && self.model.own_keys.derivation_path_input.is_empty() =>
+                {
+                    format!("This will import {}", npub)
+                }
+                _ => String::new(),
+            })
+            .size(15),
             iced::widget::rule::Rule::horizontal(5),
+            row![
+                column![text("Verification:").size(15)]
+                    .align_items(Alignment::Start)
+                    .width(label_width)
+                    .padding(0),
+                text_input(
+                    "challenge, agreed for in-person key verification",
+                    &self.model.own_keys.verification_challenge_input,
+                    Message::KeysVerificationChallengeInput,
+                )
+                .size(15),
+                button(if self.verification_qr.is_none() {
+                    "Show QR"
+                } else {
+                    "Hide QR"
+                })
+                .on_press(Message::KeysToggleVerificationQr),
+            ]
+            .align_items(Alignment::Fill)
+            .spacing(5)
+            .padding(0),
+            verification_qr,
+            row![
+                column![text("Scanned payload:").size(15)]
+                    .align_items(Alignment::Start)
+                    .width(label_width)
+                    .padding(0),
+                text_input(
+                    "verification payload scanned from the other party",
+                    &self.model.own_keys.verification_payload_input,
+                    Message::KeysVerificationPayloadInput,
+                )
+                .size(15),
+                button("Check").on_press(Message::ModelAction(Action::KeysCheckVerification)),
+            ]
+            .align_items(Alignment::Fill)
+            .spacing(5)
+            .padding(0),
         ]
         .align_items(Alignment::Fill)
         .spacing(5)
@@ -255,6 +959,17 @@ impl KeystrApp {
             ]
             .align_items(Alignment::Fill)
             .spacing(5),
+            text(
+                if self.model.delegator.delegatee_npub_input.is_empty()
+                    || is_valid_npub(&self.model.delegator.delegatee_npub_input)
+                {
+                    ""
+                } else {
+                    "Not a valid npub"
+                }
+            )
+            .size(15)
+            .style(Color::from_rgb(0.8, 0.0, 0.0)),
             iced::widget::rule::Rule::horizontal(5),
             row![
                 column![text("Event kinds (eg. 'kind=1'):").size(15),]
@@ -376,6 +1091,20 @@ impl KeystrApp {
                 Message::ChangedReadonly,
             )
             .size(15),
+            iced::widget::rule::Rule::horizontal(5),
+            text("Verify event -- possibly signed under a delegation:").size(15),
+            row![
+                text_input(
+                    "event JSON",
+                    &self.model.delegator.verify_event_input,
+                    Message::DelegateVerifyEventInput,
+                )
+                .size(15),
+                button("Verify").on_press(Message::ModelAction(Action::DelegateVerifyEvent)),
+            ]
+            .align_items(Alignment::Center)
+            .spacing(5)
+            .padding(0),
         ]
         .align_items(Alignment::Fill)
         .spacing(5)
@@ -405,6 +1134,19 @@ impl KeystrApp {
                     .align_items(Alignment::Center)
                     .spacing(5)
                     .padding(0),
+                    row![
+                        text("Allowed event kinds:").size(15),
+                        text_input(
+                            "comma-separated, e.g. '1, 7', empty for all",
+                            &self.model.own_keys.allowed_kinds_input,
+                            Message::SignerAllowedKindsInput,
+                        )
+                        .size(15),
+                        button("Set").on_press(Message::ModelAction(Action::SignerSetAllowedKinds)),
+                    ]
+                    .align_items(Alignment::Center)
+                    .spacing(5)
+                    .padding(0),
                     row![
                         button("Connect").on_press(Message::ModelAction(Action::SignerConnect)),
                         button("Refresh").on_press(Message::Refresh),
@@ -485,14 +1227,358 @@ impl KeystrApp {
             }
         };
 
-        column![text("Signer").size(25), connection_content]
-            // .align_items(Alignment::Fill)
-            .spacing(5)
-            .padding(20)
-            .max_width(600)
-            .into()
-    }
-
+        let offline_content = column![
+            text("Offline (air-gapped) signing:").size(15),
+            row![
+                text_input(
+                    "signing request (to sign) or response (to check)",
+                    &self.model.own_keys.offline_signing_input,
+                    Message::SignerOfflineInput,
+                )
+                .size(15),
+                button("Sign & copy").on_press(Message::SignerOfflineSign),
+                button("Check & copy").on_press(Message::SignerOfflineCheck),
+            ]
+            .align_items(Alignment::Center)
+            .spacing(5)
+            .padding(0),
+        ]
+        .spacing(5)
+        .padding(0);
+
+        let http_auth_content = column![
+            text("HTTP auth (NIP-98):").size(15),
+            row![
+                text_input(
+                    "method, e.g. 'GET'",
+                    &self.model.own_keys.http_auth_method_input,
+                    Message::SignerHttpAuthMethodInput,
+                )
+                .size(15)
+                .width(Length::Fixed(100.0)),
+                text_input(
+                    "URL of the request",
+                    &self.model.own_keys.http_auth_url_input,
+                    Message::SignerHttpAuthUrlInput,
+                )
+                .size(15),
+            ]
+            .align_items(Alignment::Center)
+            .spacing(5)
+            .padding(0),
+            row![
+                text_input(
+                    "SHA-256 of the request body (hex), optional",
+                    &self.model.own_keys.http_auth_payload_input,
+                    Message::SignerHttpAuthPayloadInput,
+                )
+                .size(15),
+                button("Sign & copy header").on_press(Message::SignerHttpAuthSign),
+            ]
+            .align_items(Alignment::Center)
+            .spacing(5)
+            .padding(0),
+        ]
+        .spacing(5)
+        .padding(0);
+
+        let conversation_keys_content = column![
+            text("Conversation keys (NIP-44):").size(15),
+            row![
+                text_input(
+                    "peers, comma-separated npub or hex",
+                    &self.model.own_keys.conversation_peers_input,
+                    Message::SignerConversationPeersInput,
+                )
+                .size(15),
+                button("Derive & copy").on_press(Message::SignerConversationKeysCopy),
+            ]
+            .align_items(Alignment::Center)
+            .spacing(5)
+            .padding(0),
+        ]
+        .spacing(5)
+        .padding(0);
+
+        column![
+            text("Signer").size(25),
+            connection_content,
+            iced::widget::rule::Rule::horizontal(5),
+            offline_content,
+            iced::widget::rule::Rule::horizontal(5),
+            http_auth_content,
+            iced::widget::rule::Rule::horizontal(5),
+            conversation_keys_content,
+        ]
+        // .align_items(Alignment::Fill)
+        .spacing(5)
+        .padding(20)
+        .max_width(600)
+        .into()
+    }
+
+    fn tab_relays(&self) -> Element<Message> {
+        let recent_relays: Vec<String> = self
+            .model
+            .own_keys
+            .recent_relays(RECENT_RELAYS_LIMIT)
+            .iter()
+            .map(|stat| {
+                format!(
+                    "{} ({} ok, {} failed)",
+                    stat.url, stat.success_count, stat.failure_count
+                )
+            })
+            .collect();
+        // ID of the note before signing, only stable with a given creation time
+        let note_id_preview = match (
+            self.model.own_keys.get_public_key(),
+            self.model.own_keys.created_at_input.trim().parse::<u64>(),
+        ) {
+            (Ok(pubkey), Ok(created_at)) => {
+                let mut unsigned =
+                    EventBuilder::new_text_note(self.model.own_keys.publish_note_input.trim(), &[])
+                        .to_unsigned_event(pubkey);
+                unsigned.created_at = Timestamp::from(created_at);
+                format!(
+                    "Note ID: {}",
+                    hex::encode(self.model.own_keys.compute_event_id(&unsigned))
+                )
+            }
+            _ => String::new(),
+        };
+        let note_list = self
+            .recent_notes
+            .iter()
+            .fold(column![].spacing(5).padding(0), |list, note| {
+                list.push(text(&note.content).size(15))
+            });
+        column![
+            text("Relays").size(25),
+            row![
+                text("Relays:").size(15),
+                text_input(
+                    "relay URLs, comma-separated, e.g. 'wss://relay.damus.io'",
+                    &self.model.own_keys.relays_input,
+                    Message::RelaysInput,
+                )
+                .size(15),
+                button("Check").on_press(Message::RelaysCheck),
+                button("Fetch list").on_press(Message::RelaysFetchList),
+                button("Fetch notes").on_press(Message::NotesFetch),
+                button("Save notes").on_press(Message::NotesSave),
+            ]
+            .align_items(Alignment::Center)
+            .spacing(5)
+            .padding(0),
+            row![
+                text(&format!("Recently used: {}", recent_relays.join(", "))).size(15),
+                button("Use these").on_press(Message::RelaysUseRecent),
+            ]
+            .align_items(Alignment::Center)
+            .spacing(5)
+            .padding(0),
+            row![
+                text("NIP-05:").size(15),
+                text_input(
+                    "name@domain, checked against the keys and their profile on the relays",
+                    &self.model.own_keys.nip05_input,
+                    Message::Nip05Input,
+                )
+                .size(15),
+                if self.identity_check.is_some() {
+                    button("Cancel").on_press(Message::IdentityRevalidateCancel)
+                } else {
+                    button("Verify").on_press(Message::IdentityRevalidate)
+                },
+            ]
+            .align_items(Alignment::Center)
+            .spacing(5)
+            .padding(0),
+            row![
+                text("Note:").size(15),
+                text_input(
+                    "text note to publish to the relays, signed with the keys",
+                    &self.model.own_keys.publish_note_input,
+                    Message::PublishNoteInput,
+                )
+                .size(15),
+                checkbox(
+                    "Confirm",
+                    self.publish_confirm,
+                    Message::PublishConfirmToggle
+                )
+                .text_size(15),
+                button("Publish").on_press(Message::NotePublish),
+                button("Offline request").on_press(Message::NoteOfflineRequest),
+                button("New keys & intro").on_press(Message::NoteGenerateWithIntro),
+            ]
+            .align_items(Alignment::Center)
+            .spacing(5)
+            .padding(0),
+            row![
+                text("Created at:").size(15),
+                text_input(
+                    "creation time of the note (unix seconds), instead of now",
+                    &self.model.own_keys.created_at_input,
+                    Message::CreatedAtInput,
+                )
+                .size(15),
+                button("Sign & copy").on_press(Message::NoteSignAt),
+            ]
+            .align_items(Alignment::Center)
+            .spacing(5)
+            .padding(0),
+            text(&note_id_preview).size(15),
+            row![
+                text("Auth:").size(15),
+                text_input(
+                    "authentication challenge sent by the relay (NIP-42)",
+                    &self.model.own_keys.auth_challenge_input,
+                    Message::AuthChallengeInput,
+                )
+                .size(15),
+                button("Sign & copy").on_press(Message::AuthSign),
+            ]
+            .align_items(Alignment::Center)
+            .spacing(5)
+            .padding(0),
+            row![
+                text("Gift wrap:").size(15),
+                text_input(
+                    "recipient of the note, npub or hex (NIP-59)",
+                    &self.model.own_keys.gift_wrap_recipient_input,
+                    Message::GiftWrapRecipientInput,
+                )
+                .size(15),
+                button("Wrap & copy").on_press(Message::GiftWrapCreate),
+            ]
+            .align_items(Alignment::Center)
+            .spacing(5)
+            .padding(0),
+            row![
+                text("Unwrap:").size(15),
+                text_input(
+                    "received gift wrap event (JSON)",
+                    &self.model.own_keys.gift_wrap_input,
+                    Message::GiftWrapInput,
+                )
+                .size(15),
+                button("Open").on_press(Message::GiftWrapOpen),
+            ]
+            .align_items(Alignment::Center)
+            .spacing(5)
+            .padding(0),
+            row![
+                text("Wallet:").size(15),
+                masked_secret_input(
+                    "wallet connect URI, 'nostr+walletconnect://...' (NIP-47)",
+                    &self.model.own_keys.nwc_uri_input,
+                    self.model.own_keys.reveal_input,
+                    Message::WalletUriInput,
+                ),
+                text_input(
+                    "lightning invoice to pay, 'lnbc...'",
+                    &self.model.own_keys.invoice_input,
+                    Message::InvoiceInput,
+                )
+                .size(15),
+                button("Pay").on_press(Message::InvoicePay),
+            ]
+            .align_items(Alignment::Center)
+            .spacing(5)
+            .padding(0),
+            iced::widget::rule::Rule::horizontal(5),
+            text("Recent notes of the keys (watch mode):").size(15),
+            note_list,
+        ]
+        .align_items(Alignment::Fill)
+        .spacing(5)
+        .padding(20)
+        .max_width(600)
+        .into()
+    }
+
+    fn tab_accounts(&self) -> Element<Message> {
+        let accounts = &self.model.accounts;
+        let own_keys = &self.model.own_keys;
+        let own_relays = parse_relay_urls(&own_keys.relays_input);
+        let account_list =
+            accounts
+                .accounts()
+                .iter()
+                .fold(column![].spacing(5).padding(0), |list, account| {
+                    list.push(
+                        text(&format!(
+                            "{}  {}  ({})  vs. current keys: {}",
+                            account.label(),
+                            account.keystore.get_npub(),
+                            account.relays.join(", "),
+                            own_keys.diff(&own_relays, &account.keystore, &account.relays)
+                        ))
+                        .size(15),
+                    )
+                });
+        let duplicates = accounts.duplicate_labels();
+        let duplicates_warning = if duplicates.is_empty() {
+            String::new()
+        } else {
+            format!("Labels used by more accounts: {}", duplicates.join(", "))
+        };
+        let lookalikes_warning = accounts
+            .lookalike_accounts()
+            .iter()
+            .map(|(a, b)| format!("Warning: '{a}' and '{b}' have suspiciously similar keys!"))
+            .collect::<Vec<String>>()
+            .join("\n");
+
+        column![
+            text("Accounts").size(25),
+            row![
+                text(&format!("Accounts: {}", accounts.accounts().len())).size(15),
+                button("Add current keys").on_press(Message::ModelAction(Action::AccountsAdd)),
+                button("Copy public bundle").on_press(Message::AccountsCopyPublicBundle),
+            ]
+            .align_items(Alignment::Center)
+            .spacing(5)
+            .padding(0),
+            account_list,
+            text(&duplicates_warning).size(15),
+            text(&lookalikes_warning)
+                .size(15)
+                .style(Color::from_rgb(0.8, 0.0, 0.0)),
+            row![
+                text("Password:").size(15),
+                masked_secret_input(
+                    "password, for the bundle and archive files",
+                    &accounts.password_input,
+                    self.model.own_keys.reveal_input,
+                    Message::AccountsPasswordInput,
+                ),
+            ]
+            .align_items(Alignment::Center)
+            .spacing(5)
+            .padding(0),
+            row![
+                button("Export bundle")
+                    .on_press(Message::ModelAction(Action::AccountsExportBundle)),
+                button("Import bundle")
+                    .on_press(Message::ModelAction(Action::AccountsImportBundle)),
+                button("Export archive")
+                    .on_press(Message::ModelAction(Action::AccountsExportArchive)),
+                button("Import archive")
+                    .on_press(Message::ModelAction(Action::AccountsImportArchive)),
+            ]
+            .spacing(5)
+            .padding(0),
+        ]
+        .align_items(Alignment::Fill)
+        .spacing(5)
+        .padding(20)
+        .max_width(600)
+        .into()
+    }
+
     fn view_dialog(&self, modal: &Modal) -> Element<Message> {
         container(match modal {
             Modal::Confirmation(Confirmation::KeysClearBeforeAction(_)) => column![
@@ -511,6 +1597,23 @@ impl KeystrApp {
             .width(Length::Fill)
             .spacing(5)
             .padding(20),
+            Modal::Confirmation(Confirmation::KeysPanicWipe) => column![
+                text("Wipe the keys?").size(25),
+                text("The keys are wiped from memory, and the secret key file is overwritten and deleted. This cannot be undone!").size(15),
+                iced::widget::rule::Rule::horizontal(5),
+                row![
+                    button("Yes").on_press(Message::ModelAction(Action::ConfirmationYes)),
+                    button("No").on_press(Message::ModelAction(Action::ConfirmationNo)),
+                ]
+                .align_items(Alignment::Fill)
+                .width(Length::Fill)
+                .spacing(5)
+                .padding(0),
+            ]
+            .align_items(Alignment::Fill)
+            .width(Length::Fill)
+            .spacing(5)
+            .padding(20),
             Modal::SignerRequest(desc) => column![
                 text("Sign Request").size(25),
                 text("You have received a request to SIGN an event/post:").size(15),
@@ -550,6 +1653,24 @@ impl KeystrApp {
                     Message::SecurityLevelChange
                 )
                 .text_size(15),
+                row![
+                    text("Network timeout (sec):").size(15),
+                    pick_list(
+                        NETWORK_TIMEOUTS_SECS,
+                        Some(self.model.settings.network_timeout_secs),
+                        Message::NetworkTimeoutChange
+                    )
+                    .text_size(15),
+                    checkbox(
+                        "Offline mode (no network access)",
+                        self.model.own_keys.offline,
+                        Message::OfflineToggle
+                    )
+                    .text_size(15),
+                ]
+                .align_items(Alignment::Center)
+                .spacing(5)
+                .padding(0),
                 iced::widget::rule::Rule::horizontal(5),
                 self.tab_selector(),
                 iced::widget::rule::Rule::horizontal(5),
@@ -561,8 +1682,26 @@ impl KeystrApp {
                     Tab::Keys => self.tab_keys(),
                     Tab::Delegate => self.tab_delegate(),
                     Tab::Signer => self.tab_signer(),
+                    Tab::Relays => self.tab_relays(),
+                    Tab::Accounts => self.tab_accounts(),
                 },
                 iced::widget::rule::Rule::horizontal(5),
+                row![
+                    text(&format!(
+                        "Supported NIPs: {}   Features: {}",
+                        supported_nips()
+                            .iter()
+                            .map(|n| format!("{n:02}"))
+                            .collect::<Vec<String>>()
+                            .join(", "),
+                        enabled_features().join(", ")
+                    ))
+                    .size(12),
+                    button(text("Copy diagnostics").size(12)).on_press(Message::CopyDiagnostics),
+                ]
+                .align_items(Alignment::Center)
+                .spacing(5)
+                .padding(0),
             ]
             .height(Length::Fill)
             .padding(10)
@@ -633,18 +1772,219 @@ impl Application for KeystrApp {
             }
             Message::ModelAction(action) => {
                 self.model.action(action);
+                // the mnemonic input may have been set, e.g. by generating from a new mnemonic
+                self.update_mnemonic_preview();
+                // the shown enrollment and mnemonic QRs may be for the previous keys
+                self.enrollment_qr.clear();
+                self.mnemonic_qr = None;
             }
             Message::KeysPubkeyInput(s) => self.model.own_keys.public_key_input = s,
             Message::KeysToggleHideSecretKey => {
-                self.model.own_keys.hide_secret_key = !self.model.own_keys.hide_secret_key
+                if self.revealed_secret_key.get_mut().is_some() {
+                    // dropping the guard zeroizes the secret
+                    *self.revealed_secret_key.get_mut() = None;
+                } else {
+                    let timeout = self.model.settings.reveal_timeout();
+                    match self.model.own_keys.reveal_secret_temporarily(timeout) {
+                        Err(e) => self.model.status.set_error_err(&e),
+                        Ok(guard) => {
+                            *self.revealed_secret_key.get_mut() = Some(guard);
+                            return Command::perform(tokio::time::sleep(timeout), |_| {
+                                Message::KeysRevealTimeout
+                            });
+                        }
+                    }
+                }
+            }
+            Message::KeysRevealTimeout => {
+                // a newer reveal may still be valid, see its expiry
+                if self
+                    .revealed_secret_key
+                    .get_mut()
+                    .as_ref()
+                    .map_or(false, |guard| guard.expired())
+                {
+                    *self.revealed_secret_key.get_mut() = None;
+                }
+            }
+            Message::KeysCopySecretKey => {
+                match self
+                    .model
+                    .own_keys
+                    .copy_nsec_with_timeout(CLIPBOARD_CLEAR_TIMEOUT)
+                {
+                    Err(e) => self.model.status.set_error(&e.to_string()),
+                    Ok(mut copy) => {
+                        let clearing = if copy.auto_clear_supported {
+                            format!(", clearing in {} secs", CLIPBOARD_CLEAR_TIMEOUT.as_secs())
+                        } else {
+                            String::new()
+                        };
+                        self.model.status.set(&format!(
+                            "Secret key copied to clipboard{}. {}",
+                            clearing,
+                            copy.warning.message()
+                        ));
+                        self.clipboard_generation += 1;
+                        let generation = self.clipboard_generation;
+                        // move the secret out instead of cloning it, so no un-zeroized local copy remains
+                        let write = iced::clipboard::write(std::mem::take(&mut copy.content));
+                        return match copy.clear_after {
+                            None => write,
+                            Some(timeout) => Command::batch(vec![
+                                write,
+                                Command::perform(tokio::time::sleep(timeout), move |_| {
+                                    Message::ClipboardClear(generation)
+                                }),
+                            ]),
+                        };
+                    }
+                }
+            }
+            Message::KeysExportSecretKey => {
+                let path = Storage::secret_key_export_file();
+                match Storage::check_create_folder()
+                    .and_then(|()| self.model.own_keys.export_nsec_to_file(&path))
+                {
+                    Err(e) => self.model.status.set_error_err(&e),
+                    Ok(()) => self.model.status.set(&format!(
+                        "Secret key exported in PLAINTEXT to {}, delete it when no longer needed",
+                        path.display()
+                    )),
+                }
+            }
+            Message::ClipboardClear(generation) => {
+                if generation == self.clipboard_generation {
+                    return iced::clipboard::write(String::new());
+                }
             }
-            Message::KeysSecretkeyInput(s) => self.model.own_keys.secret_key_input = s,
-            Message::KeysMnemonicInput(s) => self.model.own_keys.mnemonic_input = s,
+            Message::KeysSecretkeyInput(s) => self.model.own_keys.set_secret_input(s),
+            Message::KeysToggleRevealSecretkeyInput => {
+                self.model.own_keys.reveal_input = !self.model.own_keys.reveal_input
+            }
+            Message::KeysImportAndAnnounce => {
+                let keys = &mut self.model.own_keys;
+                let relays = parse_relay_urls(&keys.relays_input);
+                if relays.is_empty() {
+                    self.model
+                        .status
+                        .set("Enter relays (in the Relays tab) to announce the profile on");
+                } else {
+                    let name = keys.label_input.trim();
+                    let profile = if name.is_empty() {
+                        Metadata::new()
+                    } else {
+                        Metadata::new().name(name)
+                    };
+                    let input = Zeroizing::new(keys.secret_key_input.clone());
+                    let res = keys.import_and_announce(&input, &profile, &relays);
+                    keys.clear_secret_input();
+                    match res {
+                        Err(e) => self
+                            .model
+                            .status
+                            .set_error(&format!("Error importing, {}", e.to_string())),
+                        Ok(announce) => {
+                            self.model
+                                .status
+                                .set("Secret key imported, announcing the profile...");
+                            return Command::perform(announce, Message::KeysAnnounced);
+                        }
+                    }
+                }
+            }
+            Message::KeysAnnounced(results) => {
+                for result in &results {
+                    self.model.status.set(&format!(
+                        "Relay {}: profile {}{}",
+                        result.relay,
+                        if result.accepted {
+                            "announced"
+                        } else {
+                            "not announced"
+                        },
+                        match &result.message {
+                            None => String::new(),
+                            Some(message) => format!(", '{message}'"),
+                        }
+                    ));
+                }
+                self.model.own_keys.record_publish(&results);
+            }
+            Message::KeysPasteImport => {
+                return iced::clipboard::read(Message::KeysPasteImportContent);
+            }
+            Message::KeysPasteImportContent(content) => self
+                .model
+                .own_keys
+                .import_from_clipboard_action(content, &mut self.model.status),
+            Message::KeysFormatImportInput(s) => self.model.own_keys.format_import_input = s,
+            Message::KeysConvertInput(s) => self.model.own_keys.convert_input = s,
+            Message::KeysConvertToHex => {
+                let keys = &mut self.model.own_keys;
+                let convert: fn(&str) -> Result<String, Error> =
+                    if keys.convert_input.trim().starts_with("nsec1") {
+                        nsec_to_hex
+                    } else {
+                        npub_to_hex
+                    };
+                keys.convert_key_action(convert, &mut self.model.status);
+            }
+            Message::KeysConvertToNpub => self
+                .model
+                .own_keys
+                .convert_key_action(hex_to_npub, &mut self.model.status),
+            Message::KeysConvertToNsec => self
+                .model
+                .own_keys
+                .convert_key_action(hex_to_nsec, &mut self.model.status),
+            Message::KeysImportFormatChange(f) => self.model.own_keys.import_format = f,
+            Message::KeysMnemonicInput(s) => {
+                self.model.own_keys.mnemonic_input = s;
+                // the shown QR is for the previous mnemonic
+                self.mnemonic_qr = None;
+                self.update_mnemonic_preview();
+            }
+            Message::KeysMnemonicPassphraseInput(s) => {
+                self.model.own_keys.mnemonic_passphrase_input = s;
+                self.update_mnemonic_preview();
+            }
+            Message::KeysMnemonicPassphraseRepeatInput(s) => {
+                self.model.own_keys.mnemonic_passphrase_repeat_input = s
+            }
+            Message::KeysToggleMnemonicQr => {
+                if self.mnemonic_qr.is_some() {
+                    self.mnemonic_qr = None;
+                } else {
+                    match mnemonic_qr_svg(&self.model.own_keys.mnemonic_input) {
+                        Err(e) => self.model.status.set_error_err(&e),
+                        Ok(qr) => {
+                            self.mnemonic_qr = Some(svg::Handle::from_memory(qr.into_bytes()))
+                        }
+                    }
+                }
+            }
+            Message::KeysQrPayloadInput(s) => self.model.own_keys.qr_payload_input = s,
+            Message::KeysDerivationPathInput(s) => self.model.own_keys.derivation_path_input = s,
             Message::KeysDecryptPasswordInput(s) => self.model.own_keys.decrypt_password_input = s,
             Message::KeysSavePasswordInput(s) => self.model.own_keys.save_password_input = s,
             Message::KeysSaveRepeatPasswordInput(s) => {
                 self.model.own_keys.save_repeat_password_input = s
             }
+            Message::KeysReencryptInput(s) => self.model.own_keys.reencrypt_input = s,
+            Message::KeysReencryptOldPasswordInput(s) => {
+                self.model.own_keys.reencrypt_old_password_input = s
+            }
+            Message::KeysReencryptNewPasswordInput(s) => {
+                self.model.own_keys.reencrypt_new_password_input = s
+            }
+            Message::KeysReencryptRepeatPasswordInput(s) => {
+                self.model.own_keys.reencrypt_repeat_password_input = s
+            }
+            Message::KeysNoteInput(s) => self.model.own_keys.set_note(&s),
+            Message::KeysVanityPrefixInput(s) => self.model.own_keys.vanity_prefix_input = s,
+            Message::KeysLabelInput(s) => self.model.own_keys.label_input = s,
+            Message::KeysBackupConfirmInput(s) => self.model.own_keys.backup_confirm_input = s,
             Message::DelegateDeeChanged(s) => {
                 self.model.delegator.delegatee_npub_input = s;
                 if let Err(e) = self.model.delegator.validate_and_update() {
@@ -669,9 +2009,745 @@ impl Application for KeystrApp {
             Message::DelegateTimeDaysChangedNoUpdate(s) => {
                 self.model.delegator.time_cond_days = s;
             }
+            Message::DelegateVerifyEventInput(s) => self.model.delegator.verify_event_input = s,
             Message::SecurityLevelChange(l) => self.model.settings.set_security_level(l),
+            Message::RevealTimeoutChange(secs) => self.model.settings.set_reveal_timeout_secs(secs),
+            Message::ScryptLogNChange(log_n) => self.model.set_scrypt_log_n(log_n),
+            Message::AutoLockChange(mins) => self.model.set_auto_lock_mins(mins),
+            Message::MnemonicOnlyToggle(mnemonic_only) => {
+                self.model.set_mnemonic_only(mnemonic_only)
+            }
+            Message::NetworkTimeoutChange(secs) => self.model.set_network_timeout_secs(secs),
+            Message::OfflineToggle(offline) => self.model.own_keys.set_offline(offline),
             Message::SignerUriInput(s) => self.model.signer.connect_uri_input = s,
+            Message::SignerAllowedKindsInput(s) => self.model.own_keys.allowed_kinds_input = s,
+            Message::SignerOfflineInput(s) => self.model.own_keys.offline_signing_input = s,
+            Message::SignerOfflineSign => {
+                let keys = &self.model.own_keys;
+                match keys.sign_offline_request(&keys.offline_signing_input) {
+                    Err(e) => self.model.status.set_error_err(&e),
+                    Ok(response) => {
+                        self.model
+                            .status
+                            .set("Signing response copied to clipboard");
+                        return iced::clipboard::write(response);
+                    }
+                }
+            }
+            Message::SignerOfflineCheck => {
+                let keys = &self.model.own_keys;
+                match keys.check_offline_response(&keys.offline_signing_input) {
+                    Err(e) => self.model.status.set_error_err(&e),
+                    Ok(event) => {
+                        self.model
+                            .status
+                            .set(&format!("Signed event {} copied to clipboard", event.id));
+                        return iced::clipboard::write(event.as_json());
+                    }
+                }
+            }
+            Message::SignerHttpAuthMethodInput(s) => self.model.own_keys.http_auth_method_input = s,
+            Message::SignerHttpAuthUrlInput(s) => self.model.own_keys.http_auth_url_input = s,
+            Message::SignerHttpAuthPayloadInput(s) => {
+                self.model.own_keys.http_auth_payload_input = s
+            }
+            Message::SignerHttpAuthSign => {
+                let keys = &self.model.own_keys;
+                let payload = keys.http_auth_payload_input.trim();
+                match keys.create_http_auth(
+                    &keys.http_auth_method_input,
+                    &keys.http_auth_url_input,
+                    if payload.is_empty() {
+                        None
+                    } else {
+                        Some(payload)
+                    },
+                ) {
+                    Err(e) => self.model.status.set_error_err(&e),
+                    Ok(auth) => {
+                        self.model.status.set(&format!(
+                            "Authorization header (event {}) copied to clipboard",
+                            auth.event.id
+                        ));
+                        return iced::clipboard::write(auth.authorization_header);
+                    }
+                }
+            }
+            Message::SignerConversationPeersInput(s) => {
+                self.model.own_keys.conversation_peers_input = s
+            }
+            Message::SignerConversationKeysCopy => {
+                match self.model.own_keys.conversation_keys_export() {
+                    Err(e) => self.model.status.set_error_err(&e),
+                    Ok(export) => {
+                        self.model.status.set(&format!(
+                            "Conversation keys for {} peer(s) copied to clipboard",
+                            export.lines().count()
+                        ));
+                        return iced::clipboard::write(export.to_string());
+                    }
+                }
+            }
+            Message::RelaysInput(s) => self.model.own_keys.relays_input = s,
+            Message::RelaysCheck => {
+                let relays = parse_relay_urls(&self.model.own_keys.relays_input);
+                if self.model.own_keys.offline {
+                    self.model.status.set_error_err(&Error::OfflineMode);
+                } else if relays.is_empty() {
+                    self.model.status.set("Enter relays to check");
+                } else {
+                    let config = self.model.own_keys.network_config.clone();
+                    return Command::batch(relays.into_iter().map(|relay| {
+                        let config = config.clone();
+                        Command::perform(
+                            async move {
+                                let res = check_relay(&relay, &config).await;
+                                (relay, res.map_err(|e| e.to_string()))
+                            },
+                            |(relay, res)| Message::RelayChecked(relay, res),
+                        )
+                    }));
+                }
+            }
+            Message::RelayChecked(relay, res) => match res {
+                Ok(info) => {
+                    self.model.status.set(&format!("Relay {relay}: {info}"));
+                    self.relay_infos.insert(relay, info);
+                }
+                Err(e) => self.model.status.set_error(&format!("Relay {relay}: {e}")),
+            },
+            Message::RelaysFetchList => {
+                let keys = &self.model.own_keys;
+                let relays = parse_relay_urls(&keys.relays_input);
+                if keys.offline {
+                    self.model.status.set_error_err(&Error::OfflineMode);
+                } else if relays.is_empty() {
+                    self.model
+                        .status
+                        .set("Enter relays to fetch the relay list from");
+                } else {
+                    match keys.get_public_key() {
+                        Err(e) => self.model.status.set_error_err(&e),
+                        Ok(pubkey) => {
+                            let config = keys.network_config.clone();
+                            self.model.status.set("Fetching relay list...");
+                            return Command::perform(
+                                async move {
+                                    fetch_relay_list(pubkey, &relays, &config)
+                                        .await
+                                        .map_err(|e| e.to_string())
+                                },
+                                Message::RelayListFetched,
+                            );
+                        }
+                    }
+                }
+            }
+            Message::RelaysUseRecent => {
+                let recent: Vec<String> = self
+                    .model
+                    .own_keys
+                    .recent_relays(RECENT_RELAYS_LIMIT)
+                    .into_iter()
+                    .map(|stat| stat.url)
+                    .collect();
+                if recent.is_empty() {
+                    self.model.status.set("No recently used relays yet");
+                } else {
+                    self.model.own_keys.relays_input = recent.join(", ");
+                }
+            }
+            Message::NotesFetch => {
+                let keys = &self.model.own_keys;
+                let relays = parse_relay_urls(&keys.relays_input);
+                if relays.is_empty() {
+                    self.model
+                        .status
+                        .set("Enter relays to fetch the notes from");
+                } else {
+                    match keys.fetch_recent_notes(&relays, WATCH_NOTES_LIMIT) {
+                        Err(e) => self.model.status.set_error_err(&e),
+                        Ok(fetch) => {
+                            self.model.status.set("Fetching recent notes...");
+                            return Command::perform(
+                                async move {
+                                    let res = fetch.await.map_err(|e| e.to_string());
+                                    (relays, res)
+                                },
+                                |(relays, res)| Message::NotesFetched(relays, res),
+                            );
+                        }
+                    }
+                }
+            }
+            Message::NotesFetched(relays, res) => {
+                self.model.own_keys.record_relay_use(&relays, res.is_ok());
+                match res {
+                    Err(e) => self.model.status.set_error(&format!("Recent notes: {e}")),
+                    Ok(notes) => {
+                        // relays are not trusted, notes with invalid signature are not shown
+                        let verified = verify_events(&notes);
+                        let invalid = verified.iter().filter(|(_id, valid)| !valid).count();
+                        if invalid > 0 {
+                            self.model.status.set_error(&format!(
+                                "{invalid} of the fetched notes have an invalid signature"
+                            ));
+                        }
+                        self.model
+                            .status
+                            .set(&format!("Fetched {} recent notes", notes.len() - invalid));
+                        self.recent_notes = notes
+                            .into_iter()
+                            .zip(verified)
+                            .filter(|(_note, (_id, valid))| *valid)
+                            .map(|(note, _verified)| note)
+                            .collect();
+                    }
+                }
+            }
+            Message::NotesSave => {
+                if self.recent_notes.is_empty() {
+                    self.model.status.set("Fetch the notes to save first");
+                } else {
+                    let dir = Storage::events_folder();
+                    match fs::create_dir_all(&dir) {
+                        Err(e) => self.model.status.set_error_err(&Error::from(e)),
+                        Ok(()) => {
+                            let mut saved = 0;
+                            let mut existing = 0;
+                            for note in &self.recent_notes {
+                                match Storage::write_event_to_file(note, &dir, false) {
+                                    Ok(_path) => saved += 1,
+                                    // the file name is the event ID, it is the same note
+                                    Err(Error::IoError(e))
+                                        if e.kind() == io::ErrorKind::AlreadyExists =>
+                                    {
+                                        existing += 1
+                                    }
+                                    Err(e) => self.model.status.set_error_err(&e),
+                                }
+                            }
+                            self.model.status.set(&format!(
+                                "Saved {saved} notes to {} ({existing} were saved before)",
+                                dir.display()
+                            ));
+                        }
+                    }
+                }
+            }
+            Message::RelayListFetched(res) => match res {
+                Err(e) => self.model.status.set_error(&format!("Relay list: {e}")),
+                Ok(None) => self.model.status.set("No relay list found"),
+                Ok(Some(fetched)) => {
+                    // keep the relays entered so far, add the new ones from the list
+                    let mut list = RelayList {
+                        relays: parse_relay_urls(&self.model.own_keys.relays_input)
+                            .into_iter()
+                            .map(|url| RelayListEntry {
+                                url,
+                                read: true,
+                                write: true,
+                            })
+                            .collect(),
+                        skipped: 0,
+                    };
+                    list.merge_relays(&fetched.relays);
+                    let urls: Vec<&str> = list.relays.iter().map(|r| r.url.as_str()).collect();
+                    self.model.own_keys.relays_input = urls.join(", ");
+                    self.model.status.set(&format!(
+                        "Relay list: {} relays, {} malformed skipped",
+                        fetched.relays.len(),
+                        fetched.skipped
+                    ));
+                }
+            },
+            Message::Nip05Input(s) => self.model.own_keys.nip05_input = s,
+            Message::IdentityRevalidate => {
+                let cancel = CancellationToken::new();
+                let keys = &self.model.own_keys;
+                let check = keys.revalidate_identity(
+                    keys.nip05_input.trim(),
+                    &parse_relay_urls(&keys.relays_input),
+                    &cancel,
+                );
+                self.identity_check = Some(cancel);
+                self.model.status.set("Checking NIP-05 identity...");
+                return Command::perform(check, Message::IdentityRevalidated);
+            }
+            Message::IdentityRevalidateCancel => {
+                if let Some(cancel) = &self.identity_check {
+                    cancel.cancel();
+                }
+            }
+            Message::IdentityRevalidated(identity) => {
+                self.identity_check = None;
+                match identity {
+                    IdentityStatus::Verified | IdentityStatus::Cancelled => {
+                        self.model.status.set(&identity.to_string())
+                    }
+                    _ => self.model.status.set_error(&identity.to_string()),
+                }
+            }
+            Message::PublishNoteInput(s) => self.model.own_keys.publish_note_input = s,
+            Message::PublishConfirmToggle(confirm) => self.publish_confirm = confirm,
+            Message::AuthChallengeInput(s) => self.model.own_keys.auth_challenge_input = s,
+            Message::NoteOfflineRequest => {
+                let keys = &self.model.own_keys;
+                match keys.offline_sign_request(keys.publish_note_input.trim()) {
+                    Err(e) => self.model.status.set_error_err(&e),
+                    Ok(request) => {
+                        self.model.status.set(
+                            "Signing request copied to clipboard, sign it on the offline machine",
+                        );
+                        return iced::clipboard::write(request);
+                    }
+                }
+            }
+            Message::GiftWrapRecipientInput(s) => self.model.own_keys.gift_wrap_recipient_input = s,
+            Message::GiftWrapInput(s) => self.model.own_keys.gift_wrap_input = s,
+            Message::GiftWrapCreate => {
+                let keys = &self.model.own_keys;
+                let res = keys.get_public_key().and_then(|pubkey| {
+                    create_gift_wrap(
+                        keys,
+                        &keys.gift_wrap_recipient_input,
+                        EventBuilder::new_text_note(keys.publish_note_input.trim(), &[])
+                            .to_unsigned_event(pubkey),
+                    )
+                });
+                match res {
+                    Err(e) => self.model.status.set_error_err(&e),
+                    Ok(wrap) => {
+                        self.model
+                            .status
+                            .set("Gift wrapped note copied to clipboard");
+                        return iced::clipboard::write(wrap.as_json());
+                    }
+                }
+            }
+            Message::GiftWrapOpen => {
+                let keys = &self.model.own_keys;
+                let res = NostrEvent::from_json(keys.gift_wrap_input.trim())
+                    .map_err(Error::from)
+                    .and_then(|wrap| unwrap_gift(keys, &wrap));
+                match res {
+                    Err(e) => self.model.status.set_error_err(&e),
+                    Ok(rumor) => self.model.status.set(&format!(
+                        "Gift wrap from {}: {}",
+                        rumor.pubkey.to_bech32().unwrap_or_default(),
+                        rumor.content
+                    )),
+                }
+            }
+            Message::AuthSign => {
+                let keys = &self.model.own_keys;
+                match parse_relay_urls(&keys.relays_input).as_slice() {
+                    [relay] => {
+                        match keys.sign_auth_challenge(relay, keys.auth_challenge_input.trim()) {
+                            Err(e) => self.model.status.set_error_err(&e),
+                            Ok(event) => {
+                                self.model.status.set(&format!(
+                                    "Authentication event for {relay} copied to clipboard"
+                                ));
+                                return iced::clipboard::write(event.as_json());
+                            }
+                        }
+                    }
+                    _ => self
+                        .model
+                        .status
+                        .set("Enter the one relay that sent the challenge"),
+                }
+            }
+            Message::NotePublish => {
+                let keys = &self.model.own_keys;
+                let content = keys.publish_note_input.trim();
+                // skip the relays known (from a check) not to accept the note
+                let mut relays = parse_relay_urls(&keys.relays_input);
+                relays.retain(|relay| match self.relay_infos.get(relay) {
+                    None => true,
+                    Some(info) => match check_content_limits(content, &info.information) {
+                        Ok(()) => true,
+                        Err(e) => {
+                            self.model
+                                .status
+                                .set_error(&format!("Relay {relay}: {e}, skipped"));
+                            false
+                        }
+                    },
+                });
+                if relays.is_empty() {
+                    self.model.status.set("Enter relays to publish to");
+                } else if content.is_empty() {
+                    self.model.status.set("Enter a note to publish");
+                } else {
+                    let confirm_timeout = if self.publish_confirm {
+                        Some(keys.network_config.timeout)
+                    } else {
+                        None
+                    };
+                    match keys.publish_text_note(content, &relays, confirm_timeout) {
+                        Err(e) => self.model.status.set_error_err(&e),
+                        Ok(publish) => {
+                            self.model.status.set("Publishing note...");
+                            return Command::perform(publish, Message::NotePublished);
+                        }
+                    }
+                }
+            }
+            Message::NoteGenerateWithIntro => {
+                let keys = &mut self.model.own_keys;
+                let content = keys.publish_note_input.trim().to_string();
+                let relays = parse_relay_urls(&keys.relays_input);
+                if keys.keys_is_set() {
+                    self.model.status.set(
+                        "Clear the current keys first, to generate new ones with an intro note",
+                    );
+                } else if keys.offline {
+                    self.model.status.set_error_err(&Error::OfflineMode);
+                } else if relays.is_empty() {
+                    self.model.status.set("Enter relays to publish to");
+                } else if content.is_empty() {
+                    self.model.status.set("Enter an intro note to publish");
+                } else {
+                    match keys.generate_with_intro(&content) {
+                        Err(e) => self.model.status.set_error_err(&e),
+                        Ok(event) => {
+                            // the note is signed, publishing needs no secret
+                            let public_keys = Keys::from_public_key(event.pubkey);
+                            let config = keys.network_config.clone();
+                            self.model
+                                .status
+                                .set("New keypair generated, publishing intro note...");
+                            return Command::perform(
+                                async move {
+                                    publish_and_confirm(
+                                        &public_keys,
+                                        &event,
+                                        &relays,
+                                        None,
+                                        &config,
+                                    )
+                                    .await
+                                },
+                                Message::NotePublished,
+                            );
+                        }
+                    }
+                }
+            }
+            Message::NotePublished(results) => {
+                for result in &results {
+                    self.model
+                        .status
+                        .set(&format!("Relay {}: {}", result.publish.relay, result));
+                }
+                if results.iter().any(|r| r.publish.accepted) {
+                    self.model.own_keys.publish_note_input = String::new();
+                }
+                let publishes: Vec<PublishResult> =
+                    results.into_iter().map(|r| r.publish).collect();
+                self.model.own_keys.record_publish(&publishes);
+            }
+            Message::CreatedAtInput(s) => self.model.own_keys.created_at_input = s,
+            Message::NoteSignAt => {
+                let keys = &self.model.own_keys;
+                match keys.created_at_input.trim().parse::<u64>() {
+                    Err(_e) => self
+                        .model
+                        .status
+                        .set("Enter the creation time in unix seconds"),
+                    Ok(created_at) => match keys.sign_event_at(
+                        EventBuilder::new_text_note(keys.publish_note_input.trim(), &[]),
+                        created_at,
+                    ) {
+                        Err(e) => self.model.status.set_error_err(&e),
+                        Ok(res) => {
+                            if res.far_future {
+                                self.model.status.set_error(
+                                    "Creation time is far in the future, relays will likely reject the note",
+                                );
+                            }
+                            self.model.status.set("Signed note copied to clipboard");
+                            return iced::clipboard::write(res.event.as_json());
+                        }
+                    },
+                }
+            }
+            Message::WalletUriInput(s) => self.model.own_keys.nwc_uri_input = s,
+            Message::InvoiceInput(s) => self.model.own_keys.invoice_input = s,
+            Message::InvoicePay => {
+                let keys = &self.model.own_keys;
+                let invoice = keys.invoice_input.trim().to_string();
+                if invoice.is_empty() {
+                    self.model.status.set("Enter an invoice to pay");
+                } else {
+                    match parse_nwc_uri(&keys.nwc_uri_input) {
+                        Err(e) => self.model.status.set_error_err(&e),
+                        Ok(conn) => {
+                            let config = keys.network_config.clone();
+                            self.model.status.set(&format!(
+                                "Paying invoice through wallet {}...",
+                                conn.lud16.as_deref().unwrap_or(conn.relay.as_str())
+                            ));
+                            return Command::perform(
+                                async move {
+                                    pay_invoice(&conn, &invoice, &config)
+                                        .await
+                                        .map_err(|e| e.to_string())
+                                },
+                                Message::InvoicePaid,
+                            );
+                        }
+                    }
+                }
+            }
+            Message::InvoicePaid(res) => match res {
+                Err(e) => self
+                    .model
+                    .status
+                    .set_error(&format!("Error paying invoice, {e}")),
+                Ok(preimage) => {
+                    self.model.own_keys.invoice_input = String::new();
+                    self.model
+                        .status
+                        .set(&format!("Invoice paid, preimage {preimage}"));
+                }
+            },
+            Message::AccountsPasswordInput(s) => self.model.accounts.password_input = s,
+            Message::KeysVerificationChallengeInput(s) => {
+                self.model.own_keys.verification_challenge_input = s;
+                // the shown QR is for the previous challenge
+                self.verification_qr = None;
+            }
+            Message::KeysToggleVerificationQr => {
+                if self.verification_qr.is_some() {
+                    self.verification_qr = None;
+                } else {
+                    let keys = &self.model.own_keys;
+                    match keys.verification_qr(keys.verification_challenge_input.trim()) {
+                        Err(e) => self.model.status.set_error_err(&e),
+                        Ok(qr) => {
+                            self.verification_qr = Some(svg::Handle::from_memory(qr.into_bytes()))
+                        }
+                    }
+                }
+            }
+            Message::KeysVerificationPayloadInput(s) => {
+                self.model.own_keys.verification_payload_input = s
+            }
+            Message::CopyDiagnostics => {
+                let diagnostics = format!(
+                    "keystr {}, {}",
+                    env!("CARGO_PKG_VERSION"),
+                    self.model.own_keys.redacted_debug()
+                );
+                self.model
+                    .status
+                    .set("Diagnostics (with no identifying data) copied to clipboard");
+                return iced::clipboard::write(diagnostics);
+            }
+            Message::KeysDiscoverAccounts => {
+                let keys = &self.model.own_keys;
+                let relays = parse_relay_urls(&keys.relays_input);
+                let passphrase = if keys.mnemonic_passphrase_input.is_empty() {
+                    None
+                } else {
+                    Some(keys.mnemonic_passphrase_input.as_str())
+                };
+                if relays.is_empty() {
+                    self.model
+                        .status
+                        .set("Enter relays (in the Relays tab) to discover the accounts on");
+                } else {
+                    // used accounts are the ones with published events of any kind
+                    let scan = match passphrase {
+                        None => keys
+                            .scan_used_accounts_from_mnemonic(
+                                &keys.mnemonic_input,
+                                &relays,
+                                DISCOVERY_GAP_LIMIT,
+                            )
+                            .map(|scan| scan.boxed()),
+                        Some(passphrase) => keys
+                            .discover_used_accounts(
+                                &keys.mnemonic_input,
+                                Some(passphrase),
+                                &relays,
+                                DISCOVERY_GAP_LIMIT,
+                                &[],
+                            )
+                            .map(|discover| {
+                                async move {
+                                    discover
+                                        .await?
+                                        .into_iter()
+                                        .map(|(account, pubkey)| Ok((account, pubkey.to_bech32()?)))
+                                        .collect::<Result<Vec<_>, Error>>()
+                                }
+                                .boxed()
+                            }),
+                    };
+                    match scan {
+                        Err(e) => self.model.status.set_error_err(&e),
+                        Ok(scan) => {
+                            self.model.status.set("Discovering used accounts...");
+                            return Command::perform(
+                                async move {
+                                    scan.await
+                                        .map(|used| {
+                                            used.into_iter()
+                                                .map(|(account, npub)| {
+                                                    (Keystore::nip06_derivation_path(account), npub)
+                                                })
+                                                .collect::<Vec<_>>()
+                                        })
+                                        .map_err(|e| e.to_string())
+                                },
+                                Message::KeysAccountsDiscovered,
+                            );
+                        }
+                    }
+                }
+            }
+            Message::KeysAccountsDiscovered(res) => match res {
+                Err(e) => self
+                    .model
+                    .status
+                    .set_error(&format!("Account discovery: {e}")),
+                Ok(used) if used.is_empty() => self.model.status.set("No used accounts found"),
+                Ok(used) => {
+                    for (path, npub) in &used {
+                        self.model
+                            .status
+                            .set(&format!("Used account {path}: {npub}"));
+                    }
+                    self.model.status.set(&format!(
+                        "Found {} used accounts, import one with its derivation path",
+                        used.len()
+                    ));
+                }
+            },
+            Message::KeysCopyPublicKeyHex => {
+                match self.model.own_keys.public_key_checksummed_hex() {
+                    None => self.model.status.set_error_err(&Error::KeyNotSet),
+                    Some(hex) => {
+                        self.model
+                            .status
+                            .set("Public key (checksummed hex) copied to clipboard");
+                        return iced::clipboard::write(hex);
+                    }
+                }
+            }
+            Message::KeysCopyPublicKeyPem => match self.model.own_keys.public_key_pem() {
+                Err(e) => self.model.status.set_error_err(&e),
+                Ok(pem) => {
+                    self.model
+                        .status
+                        .set("Public key (PEM) copied to clipboard");
+                    return iced::clipboard::write(pem);
+                }
+            },
+            Message::KeysCopyTomlConfig => {
+                self.model
+                    .status
+                    .set("Public config (TOML) copied to clipboard");
+                return iced::clipboard::write(self.model.own_keys.to_toml_fragment());
+            }
+            Message::KeysCopyNprofile => {
+                let keys = &self.model.own_keys;
+                let relays = parse_relay_urls(&keys.relays_input);
+                match keys.get_public_key().and_then(|pk| nprofile(&pk, &relays)) {
+                    Err(e) => self.model.status.set_error_err(&e),
+                    Ok(nprofile) => {
+                        self.model.status.set(&format!(
+                            "Public key (nprofile, with {} relay hints) copied to clipboard",
+                            relays.len()
+                        ));
+                        return iced::clipboard::write(nprofile);
+                    }
+                }
+            }
+            Message::KeysToggleEnrollmentQr => {
+                if !self.enrollment_qr.is_empty() {
+                    self.enrollment_qr = Vec::new();
+                } else if self
+                    .model
+                    .own_keys
+                    .estimated_export_size(ExportFormat::EnrollmentQr)
+                    > MAX_QR_EXPORT_SIZE
+                {
+                    self.model.status.set(
+                        "Enrollment QR would be too large, save the secret key to a file instead",
+                    );
+                } else {
+                    let own_keys = &self.model.own_keys;
+                    let res = if own_keys.save_password_input != own_keys.save_repeat_password_input
+                    {
+                        Err(Error::KeyEncryptionPasswordMismatch)
+                    } else {
+                        own_keys.enrollment_qr(&own_keys.save_password_input)
+                    };
+                    match res {
+                        Err(e) => self.model.status.set_error_err(&e),
+                        Ok(qrs) => {
+                            self.enrollment_qr = qrs
+                                .into_iter()
+                                .map(|qr| svg::Handle::from_memory(qr.into_bytes()))
+                                .collect();
+                            self.model.status.set(&format!(
+                                "Enrollment QR: scan the {} frames in order",
+                                self.enrollment_qr.len()
+                            ));
+                        }
+                    }
+                }
+            }
+            Message::KeysCopyNcryptsec => {
+                let own_keys = &self.model.own_keys;
+                let res = if own_keys.save_password_input != own_keys.save_repeat_password_input {
+                    Err(Error::KeyEncryptionPasswordMismatch)
+                } else {
+                    own_keys.encrypted_secret_key_ncryptsec(&own_keys.save_password_input)
+                };
+                match res {
+                    Err(e) => self.model.status.set_error_err(&e),
+                    Ok(ncryptsec) => {
+                        self.model
+                            .status
+                            .set("Encrypted secret key (ncryptsec) copied to clipboard");
+                        return iced::clipboard::write(ncryptsec);
+                    }
+                }
+            }
+            Message::KeysCopyPaperBackup => {
+                let own_keys = &self.model.own_keys;
+                let res = if own_keys.save_password_input != own_keys.save_repeat_password_input {
+                    Err(Error::KeyEncryptionPasswordMismatch)
+                } else {
+                    own_keys.export_paper_backup(&own_keys.save_password_input)
+                };
+                match res {
+                    Err(e) => self.model.status.set_error_err(&e),
+                    Ok(backup) => {
+                        self.model
+                            .status
+                            .set("Paper backup copied to clipboard, print it and keep the password separately");
+                        return iced::clipboard::write(backup);
+                    }
+                }
+            }
+            Message::AccountsCopyPublicBundle => match self.model.accounts.public_bundle() {
+                Err(e) => self.model.status.set_error_err(&e),
+                Ok(json) => {
+                    self.model.status.set("Public bundle copied to clipboard");
+                    return iced::clipboard::write(json);
+                }
+            },
             Message::ChangedReadonly(_s) => {}
+            Message::ModelEvent(Event::UnlockFinished) => {
+                self.model.action(Action::KeysUnlockFinish);
+            }
             Message::ModelEvent(_) => {
                 // just do a refresh, no extra action needed here
             }